@@ -0,0 +1,116 @@
+//! Asynchronous file system functions mirroring `tokio::fs`.
+//!
+//! The asynchronous counterpart of `crate::fs`: the same functions, taking an `HdfsClient`, with the same
+//! semantics and result types. Each call starts at the last known active namenode.
+use futures::StreamExt;
+use crate::error::*;
+use crate::async_client::{HdfsClient, FOR, FOResult};
+use crate::op::{CreateOptions, DeleteOptions, MkdirsOptions, OpenOptions};
+use crate::fs::{not_found, is_a_directory, not_a_directory};
+pub use crate::fs::{Metadata, DirEntry};
+
+fn done<T>(r: FOResult<T>) -> Result<T> { FOR::split(r).0 }
+
+/// Entries of a directory, see `read_dir`
+pub struct ReadDir(std::vec::IntoIter<DirEntry>);
+
+impl ReadDir {
+    /// Returns the next entry, `None` at the end. The directory is read in full upon `read_dir`, so this never fails.
+    pub async fn next_entry(&mut self) -> Result<Option<DirEntry>> { Ok(self.0.next()) }
+}
+
+/// Reads the entire contents of a file
+pub async fn read(cx: &HdfsClient, path: &str) -> Result<Vec<u8>> {
+    let mut s = done(cx.open(cx.active_fostate(), path, OpenOptions::new()).await)?;
+    let mut data = vec![];
+    while let Some(chunk) = s.next().await { data.extend_from_slice(&chunk?) }
+    Ok(data)
+}
+
+/// Reads the entire contents of a file into a string
+pub async fn read_to_string(cx: &HdfsClient, path: &str) -> Result<String> {
+    String::from_utf8(read(cx, path).await?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+/// Writes `contents` to a file, replacing it if it exists
+pub async fn write(cx: &HdfsClient, path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+    let r = cx.create(cx.active_fostate(), path, contents.as_ref().to_vec().into(), CreateOptions::new().overwrite(true)).await;
+    FOR::split(r).0.map_err(|e| e.error)
+}
+
+/// Copies the contents of file `from` to file `to`, replacing it if it exists. Returns the number of bytes copied.
+pub async fn copy(cx: &HdfsClient, from: &str, to: &str) -> Result<u64> {
+    done(cx.copy_file(cx.active_fostate(), from, to, CreateOptions::new().overwrite(true)).await)
+}
+
+/// Renames a file or directory, replacing `to` atomically if it exists (see `crate::fs::rename`, which shares
+/// this implementation)
+pub async fn rename(cx: &HdfsClient, from: &str, to: &str) -> Result<()> {
+    done(cx.rename2(cx.active_fostate(), from, to.to_owned(), true).await)
+}
+
+/// Creates a directory and all of its missing parents
+pub async fn create_dir_all(cx: &HdfsClient, path: &str) -> Result<()> {
+    if done(cx.mkdirs(cx.active_fostate(), path, MkdirsOptions::new()).await)? {
+        Ok(())
+    } else {
+        Err(app_error!(generic "Cannot create directory {}", path))
+    }
+}
+
+/// Removes a file. Fails with `IsADirectory` if `path` is a directory.
+pub async fn remove_file(cx: &HdfsClient, path: &str) -> Result<()> {
+    if metadata(cx, path).await?.is_dir() { return Err(is_a_directory(path)) }
+    if done(cx.delete(cx.active_fostate(), path, DeleteOptions::new().recursive(false)).await)? { Ok(()) } else { Err(not_found(path)) }
+}
+
+/// Removes a directory with all its contents. Fails with `NotADirectory` if `path` is not a directory.
+pub async fn remove_dir_all(cx: &HdfsClient, path: &str) -> Result<()> {
+    if !metadata(cx, path).await?.is_dir() { return Err(not_a_directory(path)) }
+    if done(cx.delete(cx.active_fostate(), path, DeleteOptions::new().recursive(true)).await)? { Ok(()) } else { Err(not_found(path)) }
+}
+
+/// Metadata of a file or directory. Symbolic links are not followed.
+pub async fn metadata(cx: &HdfsClient, path: &str) -> Result<Metadata> {
+    Ok(Metadata::new(done(cx.stat(cx.active_fostate(), path).await)?.file_status))
+}
+
+/// Entries of a directory, in the order returned by the server
+pub async fn read_dir(cx: &HdfsClient, path: &str) -> Result<ReadDir> {
+    let entries = done(cx.dir_abs(cx.active_fostate(), path).await)?;
+    Ok(ReadDir(entries.into_iter().map(|(path, fs)| DirEntry::new(path, fs)).collect::<Vec<_>>().into_iter()))
+}
+
+#[test]
+fn test_afs() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    let c = FakeCluster::start(&f);
+    let cx = c.builder().build();
+    c.rt.block_on(async {
+        create_dir_all(&cx, "/d/sub").await.unwrap();
+        write(&cx, "/d/a", "text").await.unwrap();
+        write(&cx, "/d/a", "text 2").await.unwrap();
+        assert_eq!(read_to_string(&cx, "/d/a").await.unwrap(), "text 2");
+        assert_eq!(copy(&cx, "/d/a", "/d/sub/b").await.unwrap(), 6);
+        assert_eq!(read(&cx, "/d/sub/b").await.unwrap(), b"text 2");
+        assert_eq!(read(&cx, "/d/none").await.unwrap_err().io_kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(metadata(&cx, "/d/a").await.unwrap().len(), 6);
+
+        let mut names = vec![];
+        let mut entries = read_dir(&cx, "/d").await.unwrap();
+        while let Some(e) = entries.next_entry().await.unwrap() { names.push((e.file_name().to_owned(), e.metadata().is_dir())) }
+        names.sort();
+        assert_eq!(names, vec![("a".to_owned(), false), ("sub".to_owned(), true)]);
+
+        write(&cx, "/d/c", "c").await.unwrap();
+        rename(&cx, "/d/c", "/d/a").await.unwrap();
+        assert_eq!(read(&cx, "/d/a").await.unwrap(), b"c");
+        assert!(rename(&cx, "/d/a", "/d/sub").await.is_err());
+        assert!(f.get("/d/a").is_some() && f.get("/d/sub/a").is_none());
+        assert_eq!(remove_file(&cx, "/d/sub").await.unwrap_err().io_kind(), std::io::ErrorKind::IsADirectory);
+        remove_file(&cx, "/d/a").await.unwrap();
+        remove_dir_all(&cx, "/d").await.unwrap();
+        assert!(f.paths().iter().all(|p| !p.starts_with("/d")));
+    });
+}
@@ -1,50 +1,159 @@
 //! Asynchronous WebHDFS client implementation
 use std::time::Duration;
-use http::{Uri, uri::Parts as UriParts, Method};
-use futures::Stream;
-use bytes::Bytes;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use http::{Uri, uri::{Parts as UriParts, Scheme}, Method};
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use log::{info, warn};
 use crate::uri_tools::*;
+use crate::json_stream::ArrayObjects;
 use crate::natmap::{NatMap, NatMapPtr};
 use crate::error::*;
+use crate::path::HdfsPath;
 use crate::https::*;
-use crate::rest_client::{HttpyClient, HttpxEndpoint};
-pub use crate::rest_client::{ErrorD, DResult, Data};
+use crate::rest_client::{HttpyClient, HttpxEndpoint, RestSettings, DEFAULT_USER_AGENT, DEFAULT_CONNECT_TIMEOUT_S};
+pub use crate::connector::{TlsObserver, TlsPeerInfo};
+pub use crate::rest_client::{ErrorD, DResult, Data, Strictness, RequestHook, RedirectPolicy, RecoveryPolicy, Recovered, SlowReadPolicy, SlowReadAction, BinaryStream};
 use crate::datatypes::*;
 use crate::op::*;
 use crate::config::*;
+use crate::metrics::{Metrics, MetricsPtr, ClientStats, metrics_ptr, counter};
+use crate::resolver::{EndpointResolver, EndpointResolverPtr};
+use crate::rate_limit::RateLimiter;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::filter::FileFilter;
+use crate::schema::SchemaObserver;
+use crate::shutdown::Lifecycle;
+use crate::dns_cache::DnsCache;
 
 
+/// How data is submitted by CREATE and APPEND
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteStrategy {
+    /// The namenode redirects the request (307) to a datanode, which is then sent the data (default)
+    Redirect,
+    /// The namenode is asked (`noredirect=true`) to return the datanode location in a JSON response body 
+    /// instead, for proxies and gateways that break redirected writes
+    NoRedirect
+}
+
+/// How `HdfsClient::copy_within_cluster` produced the destination file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyStrategy {
+    /// The source was renamed to the destination on the namenode; no data was transferred
+    Rename,
+    /// The data was read and rewritten through the client
+    ReadWrite
+}
+
+/// Outcome of `HdfsClient::copy_within_cluster`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterCopy {
+    pub strategy: CopyStrategy,
+    /// Length of the destination file
+    pub bytes: u64
+}
+
 /// Asynchronous WebHDFS client
+#[derive(Clone)]
 pub struct HdfsClient {
-    entrypoint: UriParts,
-    alt_entrypoint: Option<UriParts>,
+    entrypoint: Entrypoint,
+    alt_entrypoint: Option<Entrypoint>,
     natmap: NatMapPtr,
-    default_timeout: Duration,
+    /// Set by `HdfsClientBuilder::default_timeout`, limiting requests; `None`: requests are not limited, and calls
+    /// of the sync client are limited to `DEFAULT_TIMEOUT_S`
+    default_timeout: Option<Duration>,
     user_name: Option<String>,
     doas: Option<String>,
     dt: Option<String>,
-    https_settings: Option<HttpsSettingsPtr>
+    https_settings: Option<HttpsSettingsPtr>,
+    metrics: MetricsPtr,
+    failover_on_connect_error: bool,
+    max_failovers: u32,
+    /// Initial and maximum delay before switching back to an entrypoint that has already failed
+    failover_backoff: (Duration, Duration),
+    natmap_entrypoints: bool,
+    rest: RestSettings,
+    dry_run: bool,
+    verify_create_options: bool,
+    upload_chunk_size: usize,
+    recovery_policy: RecoveryPolicy,
+    write_strategy: WriteStrategy,
+    /// Pre-encoded query parameters added to every namenode request
+    extra_query: Vec<(String, String)>,
+    client_id: Option<String>,
+    /// Whether the last known active namenode is `alt_entrypoint` (shared with the views returned by `with_context`)
+    active_alt: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    resolver: Option<EndpointResolverPtr>,
+    /// Entrypoints last returned by `resolver` (empty until the first request)
+    resolved: std::sync::Arc<std::sync::RwLock<Vec<Uri>>>,
+    rate_limiter: Option<RateLimiter>,
+    /// Maximum number of APPEND retries on lease conflict, initial and maximum delay between retries
+    lease_retry: (u32, Duration, Duration),
+    /// Optional operations the server has rejected as unknown
+    unsupported_ops: std::sync::Arc<std::sync::Mutex<std::collections::HashSet<&'static str>>>,
+    /// Handling of remote exceptions, by exception name or Java class name
+    exception_classes: HashMap<String, ExceptionClass>,
+    /// Options of CREATE, APPEND and DELETE used unless overridden by those passed to a call
+    default_create: Vec<OpArg>,
+    default_append: Vec<OpArg>,
+    default_delete: Vec<OpArg>,
+    deny_recursive_delete: bool
 }
 
 /// Builder for `HdfsClient`
 pub struct HdfsClientBuilder {
-    c: HdfsClient
+    c: HdfsClient,
+    default_port: Option<u16>
 }
 
 impl HdfsClientBuilder {
     const DEFAULT_TIMEOUT_S: u64 = 30;
+    const DEFAULT_MAX_FAILOVERS: u32 = 1;
+    const DEFAULT_UPLOAD_CHUNK_SIZE: usize = 8 << 20;
+    /// Namenode HTTP port assumed for entrypoints without explicit port (Hadoop 3.x default; 2.x used 50070)
+    pub const DEFAULT_HTTP_PORT: u16 = 9870;
+    /// Namenode HTTPS port assumed for entrypoints without explicit port (Hadoop 3.x default; 2.x used 50470)
+    pub const DEFAULT_HTTPS_PORT: u16 = 9871;
     /// Creates new builder from entrypoint
     pub fn new(entrypoint: Uri) -> Self { 
         Self { c: HdfsClient {
-                entrypoint: entrypoint.into_parts(),
+                entrypoint: Entrypoint(entrypoint.into_parts()),
                 alt_entrypoint: None,
                 natmap: NatMapPtr::empty(),
-                default_timeout: Duration::from_secs(Self::DEFAULT_TIMEOUT_S),
+                default_timeout: None,
                 user_name: None,
                 doas: None,
                 dt: None,
-                https_settings: None
-        }  } 
+                https_settings: None,
+                metrics: metrics_ptr(Metrics::new()),
+                failover_on_connect_error: true,
+                max_failovers: Self::DEFAULT_MAX_FAILOVERS,
+                failover_backoff: (Duration::from_secs(0), Duration::from_secs(0)),
+                active_alt: Default::default(),
+                natmap_entrypoints: false,
+                rest: RestSettings::new(),
+                dry_run: false,
+                verify_create_options: false,
+                upload_chunk_size: Self::DEFAULT_UPLOAD_CHUNK_SIZE,
+                recovery_policy: RecoveryPolicy::Keep,
+                write_strategy: WriteStrategy::Redirect,
+                extra_query: vec![],
+                client_id: None,
+                resolver: None,
+                resolved: Default::default(),
+                rate_limiter: None,
+                lease_retry: (0, Duration::from_secs(0), Duration::from_secs(0)),
+                unsupported_ops: Default::default(),
+                exception_classes: ExceptionClass::defaults(),
+                default_create: vec![],
+                default_append: vec![],
+                default_delete: vec![],
+                deny_recursive_delete: false
+            }, 
+            default_port: None
+        } 
     }
 
     /// Creates new builder from the specified configuration
@@ -54,13 +163,13 @@ impl HdfsClientBuilder {
         ).unwrap_or_else(|| NatMapPtr::empty());
         Self { c: HdfsClient {
                 entrypoint: 
-                    conf.entrypoint.into_uri().into_parts(),
+                    Entrypoint(conf.entrypoint.into_uri().into_parts()),
                 alt_entrypoint: 
-                    conf.alt_entrypoint.map(|u| u.into_uri().into_parts()),
+                    conf.alt_entrypoint.map(|u| Entrypoint(u.into_uri().into_parts())),
                 natmap: 
                     natmap,
                 default_timeout: 
-                    conf.default_timeout.unwrap_or_else(|| Duration::from_secs(Self::DEFAULT_TIMEOUT_S)),
+                    conf.default_timeout,
                 user_name: 
                     conf.user_name,
                 doas:
@@ -68,8 +177,81 @@ impl HdfsClientBuilder {
                 dt: 
                     conf.dt,
                 https_settings:
-                    conf.https_config.map(|s| https_settings_ptr(s.into()))
-        }  } 
+                    conf.https_config.map(|s| https_settings_ptr(s.into())),
+                metrics:
+                    metrics_ptr(Metrics::new()),
+                failover_on_connect_error:
+                    conf.failover_on_connect_error.unwrap_or(true),
+                max_failovers:
+                    conf.max_failovers.unwrap_or(Self::DEFAULT_MAX_FAILOVERS),
+                active_alt: Default::default(),
+                failover_backoff: {
+                    let initial = conf.failover_backoff.unwrap_or_else(|| Duration::from_secs(0));
+                    (initial, conf.failover_backoff_max.unwrap_or(initial))
+                },
+                natmap_entrypoints:
+                    conf.natmap_entrypoints.unwrap_or(false),
+                rest: RestSettings {
+                    strictness: conf.content_type_strictness.unwrap_or(Strictness::Strict),
+                    request_hook: None,
+                    metrics: None,
+                    timeout: None,
+                    datanode_request_hook: None,
+                    datanode_delegation: conf.datanode_dt,
+                    redirect_policy: RedirectPolicy::new(),
+                    user_agent: match conf.user_agent {
+                        Some(ua) if ua.is_empty() => None,
+                        Some(ua) => Some(ua),
+                        None => Some(DEFAULT_USER_AGENT.to_owned())
+                    },
+                    empty_response_strictness: conf.empty_response_strictness.unwrap_or(Strictness::Lenient),
+                    slow_read: None,
+                    circuit_breaker: None,
+                    max_response_body: None,
+                    max_request_body: None,
+                    tcp_keepalive: {
+                        let interval = conf.tcp_keepalive_interval;
+                        conf.tcp_keepalive.map(|t| (t, interval.unwrap_or(t)))
+                    },
+                    connect_timeout: Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_S)),
+                    dns_cache: {
+                        let max_entries = conf.dns_cache_max_entries.unwrap_or(DEFAULT_DNS_CACHE_MAX_ENTRIES);
+                        conf.dns_cache_ttl.map(|ttl| DnsCache::new(ttl, max_entries))
+                    },
+                    schema_observer: None,
+                    tls_observer: None,
+                    lifecycle: Lifecycle::new(),
+                    #[cfg(feature = "record")]
+                    recorder: None,
+                    #[cfg(feature = "record")]
+                    replayer: None
+                },
+                dry_run:
+                    conf.dry_run.unwrap_or(false),
+                verify_create_options:
+                    conf.verify_create_options.unwrap_or(false),
+                upload_chunk_size: Self::DEFAULT_UPLOAD_CHUNK_SIZE,
+                recovery_policy: RecoveryPolicy::Keep,
+                write_strategy: WriteStrategy::Redirect,
+                extra_query: vec![],
+                client_id: conf.client_id,
+                resolver: None,
+                resolved: Default::default(),
+                rate_limiter: None,
+                lease_retry: (0, Duration::from_secs(0), Duration::from_secs(0)),
+                unsupported_ops: Default::default(),
+                exception_classes: {
+                    let mut classes = ExceptionClass::defaults();
+                    classes.extend(conf.exception_classes.unwrap_or_default());
+                    classes
+                },
+                default_create: vec![],
+                default_append: vec![],
+                default_delete: vec![],
+                deny_recursive_delete: false
+            },
+            default_port: conf.default_port
+        } 
     }
 
     
@@ -83,38 +265,382 @@ impl HdfsClientBuilder {
     /// casuse panic rather than returning `None`.
     pub fn from_config_opt() -> Option<Self> { read_config_opt().map(Self::from_explicit_config) }
 
+    /// Same as `from_config`, without blocking the calling thread: configuration files are read with `tokio::fs`, 
+    /// and TLS certificate and identity files referenced by `https_config`, if any, on the blocking thread pool.
+    pub async fn from_config_async() -> Self { Self::from_explicit_config_async(read_config_async().await).await }
+
+    /// Same as `from_config_opt`, without blocking the calling thread (see `from_config_async`)
+    pub async fn from_config_opt_async() -> Option<Self> { 
+        match read_config_opt_async().await {
+            Some(conf) => Some(Self::from_explicit_config_async(conf).await),
+            None => None
+        }
+    }
+
+    async fn from_explicit_config_async(conf: Config) -> Self {
+        if conf.https_config.is_none() { return Self::from_explicit_config(conf) }
+        match tokio::task::spawn_blocking(move || Self::from_explicit_config(conf)).await {
+            Ok(b) => b,
+            Err(e) => std::panic::resume_unwind(e.into_panic())
+        }
+    }
+
     pub fn alt_entrypoint(self, alt_entrypoint: Uri) -> Self {
-        Self { c: HdfsClient { alt_entrypoint: Some(alt_entrypoint.into_parts()), ..self.c } }
+        Self { c: HdfsClient { alt_entrypoint: Some(Entrypoint(alt_entrypoint.into_parts())), ..self.c }, ..self }
     }
     pub fn https_settings(self, https_settings: HttpsSettings) -> Self {
-        Self { c: HdfsClient { https_settings: Some(https_settings_ptr(https_settings)), ..self.c } }
+        Self { c: HdfsClient { https_settings: Some(https_settings_ptr(https_settings)), ..self.c }, ..self }
     }
     pub fn natmap(self, natmap: NatMap) -> Self {
-        Self { c: HdfsClient { natmap: NatMapPtr::new(natmap), ..self.c } }
+        Self { c: HdfsClient { natmap: NatMapPtr::new(natmap), ..self.c }, ..self }
     }
+    /// Limit on waiting for each response, and for each chunk of a response body. Not set by default: requests
+    /// are not limited, except by the sync client, which limits each call to 30s unless this is set (see
+    /// `SyncHdfsClientBuilder::default_timeout`). Override it for a single call with `HdfsClient::with_timeout`.
     pub fn default_timeout(self, timeout: Duration) -> Self {
-        Self { c: HdfsClient { default_timeout: timeout, ..self.c } }
+        Self { c: HdfsClient { default_timeout: Some(timeout), ..self.c }, ..self }
     }
     pub fn user_name(self, user_name: String) -> Self {
-        Self { c: HdfsClient { user_name: Some(user_name), ..self.c } }
+        Self { c: HdfsClient { user_name: Some(user_name), ..self.c }, ..self }
     }    
     pub fn doas(self, doas: String) -> Self {
-        Self { c: HdfsClient { doas: Some(doas), ..self.c } }
+        Self { c: HdfsClient { doas: Some(doas), ..self.c }, ..self }
     }
     pub fn delegation_token(self, dt: String) -> Self {
-        Self { c: HdfsClient { dt: Some(dt), ..self.c } }
+        Self { c: HdfsClient { dt: Some(dt), ..self.c }, ..self }
+    }
+    /// Sets the metrics instance to be updated by the client
+    pub fn metrics(self, metrics: MetricsPtr) -> Self {
+        Self { c: HdfsClient { metrics, ..self.c }, ..self }
+    }
+    /// Whether to fail over when the active namenode cannot be connected to (default `true`).
+    /// Remote exceptions cause failover as classified by `exception_class`. Errors of the datanode step of
+    /// a two-step request (see `Error::is_datanode_error`) never cause failover.
+    pub fn failover_on_connect_error(self, failover_on_connect_error: bool) -> Self {
+        Self { c: HdfsClient { failover_on_connect_error, ..self.c }, ..self }
+    }
+    /// Sets handling of a remote exception, identified by its name (e.g. `StandbyException`) or Java class name
+    /// (overriding `ExceptionClass::defaults`). Retries and failovers share the `max_failovers` budget and
+    /// `failover_backoff` delays, except that the first retry is delayed too.
+    pub fn exception_class(mut self, exception: impl Into<String>, class: ExceptionClass) -> Self {
+        self.c.exception_classes.insert(exception.into(), class);
+        self
+    }
+    /// Maximum number of failovers (switches between entrypoints) per request (default 1)
+    pub fn max_failovers(self, max_failovers: u32) -> Self {
+        Self { c: HdfsClient { max_failovers, ..self.c }, ..self }
+    }
+    /// Exponential backoff between failovers (default none). The first failover is immediate; each subsequent
+    /// one (which returns to an entrypoint that has already failed) is delayed by `initial`, doubled every time,
+    /// up to `max`. If all attempts fail, the error is `AllNamenodesUnavailable` (see `Error::namenode_failures`).
+    pub fn failover_backoff(self, initial: Duration, max: Duration) -> Self {
+        Self { c: HdfsClient { failover_backoff: (initial, max), ..self.c }, ..self }
+    }
+    /// Whether to verify, by stat'ing the file after CREATE, that the requested replication and block size
+    /// were honored (default `false`). A mismatch fails CREATE with `CreateOptionIgnored` (see 
+    /// `Error::create_option_ignored`); the file is created regardless.
+    pub fn verify_create_options(self, verify_create_options: bool) -> Self {
+        Self { c: HdfsClient { verify_create_options, ..self.c }, ..self }
+    }
+    /// Size of the CREATE and APPEND requests `HdfsClient::put_from` splits its input into (8 MiB by default)
+    pub fn upload_chunk_size(self, upload_chunk_size: usize) -> Self {
+        Self { c: HdfsClient { upload_chunk_size: upload_chunk_size.max(1), ..self.c }, ..self }
+    }
+    /// What to do with data recovered from a failed create or append (see `ErrorD`). By default it is 
+    /// returned in memory; large buffers may be spilled to a file or dropped instead.
+    pub fn recovery_policy(self, recovery_policy: RecoveryPolicy) -> Self {
+        Self { c: HdfsClient { recovery_policy, ..self.c }, ..self }
+    }
+    /// How CREATE and APPEND submit data (default `WriteStrategy::Redirect`)
+    pub fn write_strategy(self, write_strategy: WriteStrategy) -> Self {
+        Self { c: HdfsClient { write_strategy, ..self.c }, ..self }
+    }
+    /// Adds a query parameter to every namenode request, e.g. for gateways requiring extra arguments.
+    /// `value` must already be percent-encoded, and is passed through as is.
+    pub fn raw_query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.c.extra_query.push((name.into(), value.into()));
+        self
+    }
+    /// `User-Agent` header of all requests (by default, `DEFAULT_USER_AGENT`: crate name and version), 
+    /// e.g. to let cluster admins identify the application. An empty value disables the header.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        let ua = user_agent.into();
+        self.c.rest.user_agent = if ua.is_empty() { None } else { Some(ua) };
+        self
+    }
+    /// Adds `client.id` query parameter to every namenode request, identifying the application in namenode logs
+    pub fn client_id(self, client_id: impl Into<String>) -> Self {
+        Self { c: HdfsClient { client_id: Some(client_id.into()), ..self.c }, ..self }
+    }
+    /// Checking of success responses to writes and other operations returning no content 
+    /// (default `Strictness::Lenient`: any body and content type are accepted, as sent by some Hadoop versions)
+    pub fn empty_response_strictness(mut self, strictness: Strictness) -> Self {
+        self.c.rest.empty_response_strictness = strictness;
+        self
+    }
+    /// Resolver of namenode entrypoints (see `crate::resolver`). The resolver is queried on the first request
+    /// and on every failover, and the entrypoints it returns replace the ones set by `new` and `alt_entrypoint`.
+    pub fn endpoint_resolver(self, resolver: impl EndpointResolver + 'static) -> Self {
+        Self { c: HdfsClient { resolver: Some(std::sync::Arc::new(resolver)), ..self.c }, ..self }
+    }
+    /// Limiter of namenode requests (see `crate::rate_limit`). The limiter may be shared with other clients.
+    pub fn rate_limiter(self, rate_limiter: RateLimiter) -> Self {
+        Self { c: HdfsClient { rate_limiter: Some(rate_limiter), ..self.c }, ..self }
+    }
+    /// Circuit breaker failing requests to endpoints found dead fast (see `crate::circuit_breaker`). 
+    /// Pass clones of the same breaker to share endpoint state between clients.
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.c.rest.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+    /// Limit on buffered response bodies: JSON responses, error pages and bodies of responses where none is 
+    /// expected, in bytes. Larger responses fail with `Error::is_response_too_large`, instead of being buffered
+    /// in full. File data and `dir_stream` listings are streamed and are not limited. Unlimited by default.
+    pub fn max_response_body_size(mut self, limit: u64) -> Self {
+        self.c.rest.max_response_body = Some(limit);
+        self
+    }
+    /// Limit on request bodies, i.e. data submitted in a single CREATE or APPEND request, in bytes. Larger requests
+    /// fail with `Error::is_request_too_large` without being sent. Unlimited by default.
+    pub fn max_request_body_size(mut self, limit: u64) -> Self {
+        self.c.rest.max_request_body = Some(limit);
+        self
+    }
+    /// TCP keepalive: once a connection has been idle for `time`, probes are sent every `interval`. This keeps
+    /// connections alive through proxies and firewalls dropping silent connections while the server processes a 
+    /// long operation (e.g. a recursive DELETE or a CONCAT) before responding, and detects dead peers. 
+    /// Disabled by default. Note that the response must also arrive within the timeout (see `default_timeout`).
+    pub fn tcp_keepalive(mut self, time: Duration, interval: Duration) -> Self {
+        self.c.rest.tcp_keepalive = Some((time, interval));
+        self
+    }
+    /// Limit on connecting to each address of a namenode or datanode (10s by default), after which the next
+    /// address of the host, if any, is tried. `None` leaves connecting limited by the OS only.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.c.rest.connect_timeout = timeout;
+        self
+    }
+    /// Caches host name resolutions of namenodes and datanodes for `ttl`, keeping at most `max_entries` host names. 
+    /// Expired entries are refreshed in the background while still being used, so that connections are not 
+    /// delayed by the system resolver, whose caching varies across platforms. Disabled by default.
+    pub fn dns_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.c.rest.dns_cache = Some(DnsCache::new(ttl, max_entries));
+        self
+    }
+    /// Strict schema mode: fields of JSON responses unknown to the library are reported to `observer` 
+    /// (see `crate::schema`). Calls still succeed. By default, unknown fields are silently ignored.
+    pub fn strict_schema(mut self, observer: impl SchemaObserver + 'static) -> Self {
+        self.c.rest.schema_observer = Some(std::sync::Arc::new(observer));
+        self
+    }
+    /// Observer receiving the peer certificate of each TLS connection to a namenode or datanode (see `TlsObserver`), 
+    /// e.g. for compliance logging. Only the leaf certificate is reported: native-tls does not expose the rest of
+    /// the chain presented by the peer.
+    pub fn tls_observer(mut self, observer: impl TlsObserver + 'static) -> Self {
+        self.c.rest.tls_observer = Some(std::sync::Arc::new(observer));
+        self
+    }
+    /// Records all requests and responses to a trace (see `crate::record`)
+    #[cfg(feature = "record")]
+    pub fn record(mut self, recorder: crate::record::Recorder) -> Self {
+        self.c.rest.recorder = Some(recorder);
+        self
+    }
+    /// Serves requests from a trace instead of sending them (see `crate::record`)
+    #[cfg(feature = "record")]
+    pub fn replay(mut self, replayer: crate::record::Replayer) -> Self {
+        self.c.rest.replayer = Some(replayer);
+        self
+    }
+    /// Default CREATE options (e.g. replication, permission), applied unless set in the options passed to a call
+    pub fn default_create_options(self, opts: CreateOptions) -> Self {
+        Self { c: HdfsClient { default_create: opts.into(), ..self.c }, ..self }
+    }
+    /// Default APPEND options, applied unless set in the options passed to a call
+    pub fn default_append_options(self, opts: AppendOptions) -> Self {
+        Self { c: HdfsClient { default_append: opts.into(), ..self.c }, ..self }
+    }
+    /// Default DELETE options, applied unless set in the options passed to a call. Library functions that must
+    /// not delete directories (e.g. `fs::remove_file`) always pass `recursive(false)`.
+    pub fn default_delete_options(self, opts: DeleteOptions) -> Self {
+        Self { c: HdfsClient { default_delete: opts.into(), ..self.c }, ..self }
+    }
+    /// Rejects recursive DELETE requests (failing without sending them), as a safeguard against removing trees
+    /// by mistake. `tree_delete` is not affected, as it deletes entries one by one.
+    pub fn deny_recursive_delete(self, deny: bool) -> Self {
+        Self { c: HdfsClient { deny_recursive_delete: deny, ..self.c }, ..self }
+    }
+    /// Retries of APPEND failed because the lease on the file is held by another client or is being recovered
+    /// (default none). Before each retry the file is stat'ed again, and the retry is delayed by `initial`, doubled 
+    /// every time, up to `max`. Once retries are exhausted, the error is `LeaseConflict` (see `Error::lease_conflict`).
+    /// Note that with retries enabled, owned data is copied before each attempt.
+    pub fn lease_retry(self, max_retries: u32, initial: Duration, max: Duration) -> Self {
+        Self { c: HdfsClient { lease_retry: (max_retries, initial, max), ..self.c }, ..self }
+    }
+    /// Whether to apply NAT translation to the entrypoints as well as to redirect locations (default `false`)
+    pub fn natmap_entrypoints(self, natmap_entrypoints: bool) -> Self {
+        Self { c: HdfsClient { natmap_entrypoints, ..self.c }, ..self }
+    }
+    /// Response content-type checking strictness (default `Strictness::Strict`)
+    pub fn content_type_strictness(mut self, strictness: Strictness) -> Self {
+        self.c.rest.strictness = strictness;
+        self
+    }
+    /// Hook invoked with every request just before it is sent (see `RequestHook`), e.g. to add a signature 
+    /// header required by a gateway
+    pub fn request_hook(mut self, hook: impl RequestHook + 'static) -> Self {
+        self.c.rest.request_hook = Some(std::sync::Arc::new(hook));
+        self
+    }
+    /// Which redirects are followed (see `RedirectPolicy`)
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.c.rest.redirect_policy = policy;
+        self
+    }
+    /// Detection of slow datanodes on OPEN (see `SlowReadPolicy`)
+    pub fn slow_read_policy(mut self, policy: SlowReadPolicy) -> Self {
+        self.c.rest.slow_read = Some(policy);
+        self
+    }
+    /// Hook invoked with datanode (redirected) requests instead of the one set by `request_hook`, 
+    /// for setups where datanodes expect different authentication than namenodes
+    pub fn datanode_request_hook(mut self, hook: impl RequestHook + 'static) -> Self {
+        self.c.rest.datanode_request_hook = Some(std::sync::Arc::new(hook));
+        self
+    }
+    /// Delegation token for datanode requests. Replaces the token the namenode puts in redirect locations
+    /// (by default, the token set by `delegation_token`).
+    pub fn datanode_delegation_token(mut self, dt: String) -> Self {
+        self.c.rest.datanode_delegation = Some(dt);
+        self
+    }
+    /// Dry-run mode (default `false`): mutating operations (create, append, concat, mkdirs, rename, create_symlink, 
+    /// delete) are logged and verified by stat'ing the target path, but not performed. See `HdfsClient::is_dry_run`.
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { c: HdfsClient { dry_run, ..self.c }, ..self }
+    }
+    /// Port to assume for entrypoints specified without one, and without scheme or with a `webhdfs` one
+    /// (default is `DEFAULT_HTTP_PORT` or `DEFAULT_HTTPS_PORT`, depending on the scheme)
+    pub fn default_port(self, default_port: u16) -> Self {
+        Self { default_port: Some(default_port), ..self }
+    }
+    /// Validates the entrypoints and builds the client. Entrypoints without scheme default to `http`; `webhdfs://`
+    /// and `swebhdfs://` entrypoints are `http` and `https` ones. Entrypoints without port default to `default_port`
+    /// if they have no scheme or a `webhdfs` one, and to the standard port (80 or 443) otherwise. Entrypoints with
+    /// another scheme than these, `http`, `https` or `unix` (see `crate::unix`), or without host, are rejected.
+    pub fn try_build(self) -> Result<HdfsClient> {
+        if let Some(ua) = &self.c.rest.user_agent {
+            if hyper::header::HeaderValue::from_str(ua).is_err() {
+                return Err(app_error!(generic "Invalid User-Agent '{}'", ua.escape_debug()))
+            }
+        }
+        let entrypoint = Entrypoint(normalize_entrypoint(&self.c.entrypoint, self.default_port)?);
+        let alt_entrypoint = match &self.c.alt_entrypoint {
+            Some(ep) => Some(Entrypoint(normalize_entrypoint(ep, self.default_port)?)),
+            None => None
+        };
+        Ok(HdfsClient { entrypoint, alt_entrypoint, ..self.build() })
+    }
+    /// Builds the client, normalizing the entrypoints as `try_build` does. Invalid settings are not reported here:
+    /// an invalid entrypoint is kept as is, and requests to it fail. Use `try_build` to have them reported upfront.
+    pub fn build(self) -> HdfsClient { 
+        let default_port = self.default_port;
+        let normalize = |ep: Entrypoint| Entrypoint(normalize_entrypoint(&ep, default_port).unwrap_or(ep.0));
+        let c = self.c;
+        let entrypoint = normalize(c.entrypoint);
+        let alt_entrypoint = c.alt_entrypoint.map(normalize);
+        let rest = RestSettings { metrics: Some(c.metrics.clone()), timeout: c.default_timeout, ..c.rest };
+        HdfsClient { entrypoint, alt_entrypoint, rest, ..c }
     }
-    pub fn build(self) -> HdfsClient { self.c }
+}
+
+fn normalize_entrypoint(ep: &UriParts, default_port: Option<u16>) -> Result<UriParts> {
+    let uri_s = || format!("{}{}{}",
+        ep.scheme.as_ref().map(|s| format!("{}://", s)).unwrap_or_default(),
+        ep.authority.as_ref().map(|a| a.as_str()).unwrap_or_default(),
+        ep.path_and_query.as_ref().map(|pq| pq.as_str()).unwrap_or_default()
+    );
+    let hdfs_port = |https: bool| default_port.unwrap_or(
+        if https { HdfsClientBuilder::DEFAULT_HTTPS_PORT } else { HdfsClientBuilder::DEFAULT_HTTP_PORT }
+    );
+    //scheme, and port to assume if none is given (`None`: the standard port of the scheme)
+    let (scheme, port) = match &ep.scheme {
+        None => (Scheme::HTTP, Some(hdfs_port(false))),
+        Some(s) if *s == Scheme::HTTP || *s == Scheme::HTTPS => (s.clone(), None),
+        Some(s) if s.as_str() == "webhdfs" => (Scheme::HTTP, Some(hdfs_port(false))),
+        Some(s) if s.as_str() == "swebhdfs" => (Scheme::HTTPS, Some(hdfs_port(true))),
+        #[cfg(unix)]
+        Some(s) if s.as_str() == crate::unix::SCHEME => {
+            let mut r = UriParts::default();
+            r.scheme = ep.scheme.clone();
+            r.authority = ep.authority.clone();
+            r.path_and_query = ep.path_and_query.clone();
+            return Ok(r)
+        }
+        Some(s) => return Err(app_error!(generic "Invalid entrypoint '{}': unsupported scheme '{}'", uri_s(), s))
+    };
+    let authority = match &ep.authority {
+        Some(a) if !a.host().is_empty() => a,
+        _ => return Err(app_error!(generic "Invalid entrypoint '{}': no host", uri_s()))
+    };
+    let authority = match port {
+        Some(port) if authority.port().is_none() => 
+            format!("{}:{}", authority, port).parse().aerr_f(|| format!("Invalid entrypoint '{}'", uri_s()))?,
+        _ => authority.clone()
+    };
+    let mut r = UriParts::default();
+    r.scheme = Some(scheme);
+    r.authority = Some(authority);
+    r.path_and_query = ep.path_and_query.clone();
+    Ok(r)
 }
 
 
 enum FOAction<T,D> {
     Proceed(Result<T>),
-    FailOver(D)
+    FailOver(D),
+    Retry
+}
+
+/// Handling of a remote exception returned by the namenode
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExceptionClass {
+    /// Fail over to the other namenode (e.g. `StandbyException`)
+    Failover,
+    /// Retry on the same namenode after a delay (e.g. `RetriableException`)
+    Retry,
+    /// Return the error to the caller
+    Fail
+}
+
+impl ExceptionClass {
+    /// Classification used unless overridden: `StandbyException` causes failover; other exceptions fail
+    pub fn defaults() -> HashMap<String, ExceptionClass> {
+        vec![("StandbyException".to_owned(), ExceptionClass::Failover)].into_iter().collect()
+    }
+}
+
+/// Authentication fields overriding those of the client for individual calls, see `HdfsClient::with_context`.
+/// A context replaces all the authentication fields of the client, including its `datanode_delegation_token`:
+/// fields not set are not sent. An empty context keeps those of the client.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    user_name: Option<String>,
+    doas: Option<String>,
+    delegation: Option<String>
+}
+
+impl RequestContext {
+    pub fn new() -> Self { Self::default() }
+    pub fn user_name(self, user_name: impl Into<String>) -> Self { Self { user_name: Some(user_name.into()), ..self } }
+    pub fn doas(self, doas: impl Into<String>) -> Self { Self { doas: Some(doas.into()), ..self } }
+    /// Delegation token, sent to datanodes as well (replacing `datanode_delegation_token` of the client)
+    pub fn delegation_token(self, dt: impl Into<String>) -> Self { Self { delegation: Some(dt.into()), ..self } }
 }
 
 /// Failover state. PRIMARY === entrypoint is active. ALT === alt_entrypoint is active
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum FOState { PRIMARY, ALT }
 
 impl FOState {
@@ -150,36 +676,35 @@ impl FOR {
 
 
 macro_rules! with_failover {
-    ([$f1:expr, $f2:expr], $s:expr, $fostate:expr, $pq:expr) => { 
-        with_failover!([$f1, $f2, |v| v], $s, $fostate, $pq)
-    };
-
-    ([$f1:expr, $f2:expr, $cvt:expr], $s:expr, $fostate:expr, $pq:expr) => { {
-        let pq = $pq;
-        let (r, fostate) = $s.httpc($fostate, &pq)?;
-        let r = $cvt($f1(r).await);
-        let (r, fostate) = $s.failover_fsm(fostate, r);
-        match r {
-            FOAction::Proceed(r) => FOR::bind(r, fostate),
-            FOAction::FailOver(_) => {
-                let (r, fostate) = $s.httpc(fostate, &pq)?;
-                let r = $cvt($f2(r).await);
-                FOR::bind(r, fostate)
-            }
-        } }
+    ([$f:expr], $s:expr, $fostate:expr, $op:expr, $pq:expr) => { 
+        with_failover!([$f, |v| v], $s, $fostate, $op, $pq)
     };
 
-    ([$f1:expr, $f2:expr, $ecvt1:expr, $ecvt2:expr], $s:expr, $fostate:expr, $pq:expr, $data:expr) => { {
+    ([$f:expr, $cvt:expr], $s:expr, $fostate:expr, $op:expr, $pq:expr) => { {
         let pq = $pq;
-        let (r, fostate) = $ecvt1($s.httpc($fostate, &pq))?;
-        let r = $f1(r, $data).await;
-        let (r, fostate) = $s.failover_fsm_d(fostate, r);
-        match r {
-            FOAction::Proceed(r) => FOR::bind($ecvt2(r), fostate),
-            FOAction::FailOver(data) => {
-                let (r, fostate) = $ecvt1($s.httpc(fostate, &pq))?;
-                let r = $f2(r, data).await;
-                FOR::bind(r, fostate)
+        let mut fostate = $fostate;
+        let mut attempt = 0;
+        let mut failures = vec![];
+        loop {
+            $s.resolve_entrypoints(fostate, attempt > 0).await?;
+            if let Some(l) = &$s.rate_limiter { l.acquire().await }
+            let (r, fostate1) = $s.httpc(fostate, &pq)?;
+            let r = $cvt($f(r).await);
+            match $s.failover_fsm(fostate1, $op, attempt, r) {
+                (FOAction::Proceed(r), fostate1) => break FOR::bind($s.failover_done(fostate1, failures, r), fostate1),
+                (FOAction::FailOver(e), fostate1) => { 
+                    failures.push(($s.endpoint_name(fostate), e));
+                    fostate = fostate1; 
+                    attempt += 1; 
+                    let delay = $s.failover_delay(attempt);
+                    if delay > Duration::from_secs(0) { tokio::time::sleep(delay).await }
+                }
+                (FOAction::Retry, fostate1) => {
+                    fostate = fostate1; 
+                    attempt += 1; 
+                    let delay = $s.failover_delay(attempt + 1);
+                    if delay > Duration::from_secs(0) { tokio::time::sleep(delay).await }
+                }
             }
         } }
     };
@@ -188,36 +713,107 @@ macro_rules! with_failover {
 
 impl HdfsClient {
     const SVC_MOUNT_POINT: &'static str = "/webhdfs/v1";
+    /// Maximum number of symbolic links followed by `resolve_path` (as `FsConstants.MAX_PATH_LINKS`)
+    pub const MAX_PATH_LINKS: usize = 32;
+
+    /// Returns a view of the client making requests with the authentication fields of `ctx`, e.g. for a proxy
+    /// serving multiple users. The view shares connection settings, metrics and failover state with the client, 
+    /// and is cheap to create per call.
+    pub fn with_context(&self, ctx: &RequestContext) -> HdfsClient {
+        let c = self.clone();
+        if ctx.user_name.is_none() && ctx.doas.is_none() && ctx.delegation.is_none() { return c }
+        HdfsClient {
+            user_name: ctx.user_name.clone(),
+            doas: ctx.doas.clone(),
+            dt: ctx.delegation.clone(),
+            rest: RestSettings { datanode_delegation: None, ..c.rest },
+            ..c
+        }
+    }
+
+    /// Shorthand for `with_context` authenticating as `user_name` (with neither `doas` nor delegation token)
+    pub fn as_user(&self, user_name: impl Into<String>) -> HdfsClient {
+        self.with_context(&RequestContext::new().user_name(user_name))
+    }
 
     fn natmap(&self) -> NatMapPtr { self.natmap.clone() }
     fn https_settings(&self) -> Option<HttpsSettingsPtr> { self.https_settings.clone() }
 
-    fn path_and_query(&self, file_path: &str, op: Op, args: Vec<OpArg>) -> Vec<u8> {
+    fn path_and_query(&self, file_path: &str, op: &Op, args: Vec<OpArg>) -> Vec<u8> {
         let q = PathEncoder::new(Self::SVC_MOUNT_POINT).extend(file_path).query();
         let q = if let Some(user) = &self.user_name { q.add_pv("user.name", user) } else { q };
         let q = if let Some(doas) = &self.doas { q.add_pv("doas", doas) } else { q };
         let q = if let Some(dt) = &self.dt { q.add_pv("delegation", dt) } else { q };
+        let q = if let Some(id) = &self.client_id { q.add_pv("client.id", id) } else { q };
         let q = q.add_pv("op", op.op_string());
         let q = args.iter().fold(q, |q, s| s.add_to_url(q));
+        let q = self.extra_query.iter().fold(q, |q, (p, v)| q.add_raw(p, v));
         q.result()
     }
     
-    fn uri(&self, fostate: FOState, pq: &[u8]) -> FOResult<Uri> {
-        let mut b = Uri::builder();
-        
-        let ep = if fostate.is_alt() { 
+    fn entrypoint(&self, fostate: FOState) -> &Entrypoint {
+        if fostate.is_alt() { 
             if let Some(ep) = &self.alt_entrypoint { ep } else { &self.entrypoint }
         } else { 
             &self.entrypoint 
+        }
+    }
+
+    /// Entrypoint returned by the resolver, if one is set
+    fn resolved_entrypoint(&self, fostate: FOState) -> Option<Uri> {
+        let resolved = self.resolved.read().unwrap();
+        resolved.get(if fostate.is_alt() && resolved.len() > 1 { 1 } else { 0 }).cloned()
+    }
+
+    /// Queries the resolver, if one is set, unless it has already been queried and `refresh` is false.
+    /// A failed refresh keeps the entrypoints resolved previously.
+    async fn resolve_entrypoints(&self, fostate: FOState, refresh: bool) -> FOResult<()> {
+        let resolver = match &self.resolver {
+            Some(resolver) if refresh || self.resolved.read().unwrap().is_empty() => resolver,
+            _ => return Ok(((), fostate))
         };
-        
-        if let Some(scheme) = &ep.scheme { b = b.scheme(scheme.clone()); }
-        if let Some(authority) = &ep.authority { b = b.authority(authority.clone()); }
+        let r = resolver.resolve().await.and_then(|uris| uris.into_iter().take(2)
+            .map(|uri| normalize_entrypoint(&uri.into_parts(), None).and_then(|p| Uri::from_parts(p).aerr("Invalid entrypoint")))
+            .collect::<Result<Vec<_>>>()
+        ).and_then(|uris| if uris.is_empty() { Err(app_error!(generic "Endpoint resolver returned no entrypoints")) } else { Ok(uris) });
+        match r {
+            Ok(uris) => {
+                info!(target: "webhdfs::failover", "resolved entrypoints: {:?}", uris);
+                *self.resolved.write().unwrap() = uris;
+                Ok(((), fostate))
+            }
+            Err(e) if !self.resolved.read().unwrap().is_empty() => {
+                warn!(target: "webhdfs::failover", "entrypoint resolution failed, using previous entrypoints: {}", e);
+                Ok(((), fostate))
+            }
+            Err(e) => Err((e, fostate))
+        }
+    }
+
+    /// Entrypoint authority, for logging and metrics
+    fn endpoint_name(&self, fostate: FOState) -> String {
+        match self.resolved_entrypoint(fostate) {
+            Some(uri) => uri.authority().map(|a| a.to_string()).unwrap_or_default(),
+            None => self.entrypoint(fostate).authority.as_ref().map(|a| a.to_string()).unwrap_or_default()
+        }
+    }
+
+    fn uri(&self, fostate: FOState, pq: &[u8]) -> FOResult<Uri> {
+        let mut b = Uri::builder();
+        if let Some(uri) = self.resolved_entrypoint(fostate) {
+            if let Some(scheme) = uri.scheme() { b = b.scheme(scheme.clone()); }
+            if let Some(authority) = uri.authority() { b = b.authority(authority.clone()); }
+        } else {
+            let ep = self.entrypoint(fostate);
+            if let Some(scheme) = &ep.scheme { b = b.scheme(scheme.clone()); }
+            if let Some(authority) = &ep.authority { b = b.authority(authority.clone()); }
+        }
 
         let r = b
         .path_and_query(pq)
         .build()
         .aerr_f(|| format!("Could not build URI: path_and_query={}", String::from_utf8_lossy(pq)));
+        let r = if self.natmap_entrypoints { r.and_then(|uri| self.natmap.translate(uri)) } else { r };
 
         FOR::bind(r, fostate)
     }
@@ -226,39 +822,102 @@ impl HdfsClient {
     fn httpc(&self, fostate: FOState, pq: &[u8]) -> FOResult<HttpyClient> {
         let natmap = self.natmap();
         let https_settings = self.https_settings();
+        if let Err(e) = self.rest.lifecycle.check_open() { return Err((e, fostate)) }
         let (uri, fostate) = self.uri(fostate, pq)?;
-        Ok((HttpyClient::new(HttpxEndpoint::new(uri, https_settings), natmap), fostate))
+        Ok((HttpyClient::new(HttpxEndpoint::new(uri, https_settings), natmap, self.rest.clone()), fostate))
     }
 
     #[inline]
-    fn is_standby_error(error: &Error) -> bool { 
+    fn is_not_found_error(error: &Error) -> bool { 
+        matches!(error.remote_exception(), Some(RemoteException { exception, ..}) if exception == "FileNotFoundException")
+    }
+
+    /// Classification of a remote exception, by its name or Java class name
+    fn exception_class(&self, error: &Error) -> Option<ExceptionClass> { 
         //Error { msg: None, cause: RemoteException(RemoteException { 
         //    exception: "StandbyException", 
         //    java_class_name: "org.apache.hadoop.ipc.StandbyException", 
         //    message: "Operation category WRITE is not supported in state standby. Visit https://s.apache.org/sbnn-error" }) }', 
         match error.cause() {
-            Cause::RemoteException(RemoteException { exception, ..}) if exception == "StandbyException" => true,
+            Cause::RemoteException(e) => self.exception_classes.get(&e.exception)
+                .or_else(|| self.exception_classes.get(&e.java_class_name))
+                .copied(),
+            _ => None
+        }
+    }
+
+    /// Namenode is unreachable: connection refused, connect timeout, etc. Errors of datanode steps do not count.
+    #[inline]
+    fn is_connect_error(error: &Error) -> bool {
+        if error.is_datanode_error() { return false }
+        match error.cause() {
+            Cause::Hyper(e) => e.is_connect() || e.is_timeout(),
+            Cause::Timeout | Cause::CircuitOpen(_) => true,
             _ => false
         }
     }
 
-    fn failover_fsm<T>(&self, fostate: FOState, result: Result<T>) -> (FOAction<T, ()>, FOState) {
+    /// Namenode is either standby or unreachable
+    fn is_unavailable_error(&self, error: &Error) -> bool {
+        !error.is_datanode_error() && self.exception_class(error) == Some(ExceptionClass::Failover) || (self.failover_on_connect_error && Self::is_connect_error(error))
+    }
+
+    fn is_failover_error(&self, error: &Error, attempt: u32) -> bool {
+        (self.alt_entrypoint.is_some() || self.resolver.is_some()) && attempt < self.max_failovers && self.is_unavailable_error(error)
+    }
+
+    /// Delay before failover number `attempt` (1-based)
+    fn failover_delay(&self, attempt: u32) -> Duration {
+        let (initial, max) = self.failover_backoff;
+        if attempt < 2 { return Duration::from_secs(0) }
+        let factor = 1u32.checked_shl(attempt - 2).unwrap_or(u32::MAX);
+        initial.checked_mul(factor).unwrap_or(max).min(max)
+    }
+
+    /// Replaces the final error with `AllNamenodesUnavailable` if all failover attempts have failed.
+    /// Otherwise, the namenode has responded, and is recorded as the last known active one.
+    fn failover_done<T>(&self, fostate: FOState, mut failures: Vec<(String, Error)>, result: Result<T>) -> Result<T> {
         match result {
-            Err(e) if self.alt_entrypoint.is_some() && Self::is_standby_error(&e) => (FOAction::FailOver(()), fostate.next()),
-            //TODO: Err(e) => provide more details in 'error' for the situation
-            other => (FOAction::Proceed(other), fostate),
+            Err(e) if !failures.is_empty() && self.is_unavailable_error(&e) => {
+                failures.push((self.endpoint_name(fostate), e));
+                Err(Error::anon(Cause::AllNamenodesUnavailable(failures)))
+            }
+            other => {
+                if !matches!(&other, Err(e) if self.is_unavailable_error(e)) {
+                    self.active_alt.store(fostate.is_alt(), std::sync::atomic::Ordering::Relaxed)
+                }
+                other
+            }
         }
     }
 
-    fn failover_fsm_d<T>(&self, fostate: FOState, result: DResult<T>) -> (FOAction<T, Data>, FOState) {
+    /// Logs failover event (target `webhdfs::failover`) and updates metrics
+    fn note_failover(&self, fostate: FOState, op: &Op, attempt: u32, error: &Error) {
+        let from = self.endpoint_name(fostate);
+        let to = self.endpoint_name(fostate.next());
+        info!(target: "webhdfs::failover", "failover from={} to={} op={} attempt={} cause='{}'", 
+            from, to, op.op_string(), attempt, error
+        );
+        self.metrics.incr(counter::FAILOVER, &from, 1);
+        self.metrics.incr(counter::RETRY, &to, 1);
+    }
+
+    fn failover_fsm<T>(&self, fostate: FOState, op: &Op, attempt: u32, result: Result<T>) -> (FOAction<T, Error>, FOState) {
         match result {
-            Err(ErrorD { error, data_opt: Some(data) }) if self.alt_entrypoint.is_some() && Self::is_standby_error(&error) => 
-                (FOAction::FailOver(data), fostate.next()),
-            Err(ErrorD { error, data_opt: _ }) => 
-                //TODO: provide more details describing the situation in 'error' 
-                (FOAction::Proceed(Err(error)), fostate),
-            Ok(v) => 
-                (FOAction::Proceed(Ok(v)), fostate),
+            Err(e) if self.is_failover_error(&e, attempt) => {
+                self.note_failover(fostate, op, attempt + 1, &e);
+                (FOAction::FailOver(e), fostate.next())
+            }
+            Err(e) if attempt < self.max_failovers && self.exception_class(&e) == Some(ExceptionClass::Retry) => {
+                let endpoint = self.endpoint_name(fostate);
+                info!(target: "webhdfs::failover", "retry at={} op={} attempt={} cause='{}'", 
+                    endpoint, op.op_string(), attempt + 1, e
+                );
+                self.metrics.incr(counter::RETRY, &endpoint, 1);
+                (FOAction::Retry, fostate)
+            }
+            //TODO: Err(e) => provide more details in 'error' for the situation
+            other => (FOAction::Proceed(other), fostate),
         }
     }
 
@@ -267,31 +926,76 @@ impl HdfsClient {
     {
         with_failover!(
             [
-                |r: HttpyClient| r.get_json(),
                 |r: HttpyClient| r.get_json()
             ],
             self,
             fostate,
-            self.path_and_query(path, op, args)
+            &op,
+            self.path_and_query(path, &op, args)
+        )
+    }
+
+    async fn get_json_stream(&self, fostate: FOState, path: &str, op: Op, args: Vec<OpArg>) -> FOResult<BinaryStream> {
+        with_failover!(
+            [
+                |r: HttpyClient| r.get_json_stream()
+            ],
+            self,
+            fostate,
+            &op,
+            self.path_and_query(path, &op, args)
         )
     }
 
-   async fn data_op<'t>(&'t self, fostate: FOState, method: Method, path: &'t str, op: Op, args: Vec<OpArg>, data: Data) 
-    -> FODResult<()> {
+    #[inline]
+    fn recovered(&self, error: Error, data: Data) -> ErrorD { ErrorD::recovered(error, data, &self.recovery_policy) }
 
-        fn nod((error, fostate): (Error, FOState)) -> (ErrorD, FOState) { (ErrorD { error, data_opt: None }, fostate) }
+    /// Two-step data submission. The namenode step is subject to failover; the data is returned back 
+    /// in `ErrorD` if the namenode step fails, or if it exceeds `max_request_body_size` (in which case nothing 
+    /// is sent). Returns the `Location` reported by the datanode, if any
+    async fn data_op<'t>(&'t self, fostate: FOState, method: Method, path: &'t str, op: Op, mut args: Vec<OpArg>, data: Data) 
+    -> FODResult<Option<String>> {
+        if let Some(limit) = self.rest.max_request_body {
+            if data.len() as u64 > limit {
+                let e = Error::new(Some(format!("Request body of {} bytes exceeds {} bytes", data.len(), limit).into()), Cause::RequestTooLarge(limit));
+                return Err((self.recovered(e, data), fostate))
+            }
+        }
+        if self.write_strategy == WriteStrategy::NoRedirect { args.push(OpArg::NoRedirect(true)) }
+        match self.redirect(fostate, method.clone(), path, op, args).await {
+            Ok((dn, fostate)) => FOR::bind(
+                dn.post_binary_redirected(method, data).await.map_err(ErrorD::lift), 
+                fostate
+            ),
+            Err((error, fostate)) => Err((self.recovered(error, data), fostate))
+        }
+    }
 
+    /// Namenode step of a two-step request
+    async fn redirect(&self, fostate: FOState, method: Method, path: &str, op: Op, args: Vec<OpArg>) 
+    -> FOResult<HttpyClient> {
         with_failover!(
             [
-                |r: HttpyClient, data| r.post_binary(method.clone(), data),
-                |r: HttpyClient, data| r.post_binary(method, data),
-                |r: FOResult<HttpyClient>| r.map_err(nod),
-                |r: Result<()>| r.map_err(ErrorD::lift)
+                |r: HttpyClient| r.redirect(method.clone())
             ],
             self,
             fostate,
-            self.path_and_query(path, op, args),
-            data
+            &op,
+            self.path_and_query(path, &op, args)
+        )
+    }
+
+    /// Namenode step of a two-step request, returning redirect location
+    async fn location(&self, fostate: FOState, method: Method, path: &str, op: Op, args: Vec<OpArg>, translate: bool) 
+    -> FOResult<Uri> {
+        with_failover!(
+            [
+                |r: HttpyClient| r.location(method.clone(), translate)
+            ],
+            self,
+            fostate,
+            &op,
+            self.path_and_query(path, &op, args)
         )
     }
 
@@ -300,12 +1004,12 @@ impl HdfsClient {
         with_failover!(
             [
                 |r: HttpyClient| r.op_json(method.clone()),
-                |r: HttpyClient| r.op_json(method),
                 |r: Result<Boolean>| r.map(|b: Boolean| b.boolean)
             ],
             self,
             fostate,
-            self.path_and_query(path, op, args)
+            &op,
+            self.path_and_query(path, &op, args)
             )
     }    
 
@@ -313,12 +1017,12 @@ impl HdfsClient {
     -> FOResult<()> {
         with_failover!(
             [
-                |r: HttpyClient| r.op_empty(method.clone()),
-                |r: HttpyClient| r.op_empty(method)
+                |r: HttpyClient| r.op_empty(method.clone())
             ],
             self,
             fostate,
-            self.path_and_query(path, op, args)
+            &op,
+            self.path_and_query(path, &op, args)
             
         )
     }
@@ -352,78 +1056,1901 @@ impl HdfsClient {
     }
     */   
 
+    /// Limit on calls of the sync client
     #[inline]
-    pub(crate) fn default_timeout(&self) -> &Duration { &self.default_timeout }
+    pub(crate) fn default_timeout(&self) -> Duration {
+        self.default_timeout.unwrap_or(Duration::from_secs(HdfsClientBuilder::DEFAULT_TIMEOUT_S))
+    }
+
+    /// Runs `f`, typically a call of this client, with `timeout` instead of the default timeout. The timeout 
+    /// limits waiting for each response, and for each chunk of a response body (including streams returned by `open`).
+    pub async fn with_timeout<F: std::future::Future>(&self, timeout: Duration, f: F) -> F::Output {
+        crate::rest_client::with_call_timeout(timeout, f).await
+    }
+
+    /// Metrics updated by this client
+    pub fn metrics(&self) -> &MetricsPtr { &self.metrics }
+
+    /// Shuts the client down gracefully, e.g. on SIGTERM. The client (and all clients derived from it, e.g. by
+    /// `with_context`) stops accepting calls: those made afterwards fail with `Error::is_shut_down`. Requests in 
+    /// flight are given `grace` to complete, then aborted. A request is in flight until its response has been 
+    /// fully received or dropped, so an `open` stream still alive after `grace` fails on its next read. 
+    /// The datanode step of a two-step operation already started is still sent during `grace`.
+    /// Returns the number of requests aborted.
+    pub async fn shutdown(&self, grace: Duration) -> usize { self.rest.lifecycle.shutdown(grace).await }
+
+    /// Whether `shutdown` has been called
+    pub fn is_shut_down(&self) -> bool { self.rest.lifecycle.is_closed() }
+
+    /// Failover state pointing to the last known active namenode
+    pub fn active_fostate(&self) -> FOState {
+        if self.active_alt.load(std::sync::atomic::Ordering::Relaxed) { FOState::ALT } else { FOState::PRIMARY }
+    }
+
+    /// Configuration reconstructed from the settings of this client, e.g. for logging and diagnostics.
+    /// Secrets (delegation tokens, TLS identity password) are replaced with `config::REDACTED`, which is not a
+    /// usable secret: to save a configuration without secrets, use `Config::without_secrets` instead.
+    /// Settings that cannot be expressed in `Config` (hooks, resolver, metrics instance, etc.) are omitted.
+    pub fn effective_config(&self) -> Config { self.config(true) }
+
+    /// Same as `effective_config`, but with secrets included, e.g. for saving the configuration to a file
+    pub fn effective_config_with_secrets(&self) -> Config { self.config(false) }
+
+    fn config(&self, redact: bool) -> Config {
+        fn uri(ep: &UriParts) -> UriW {
+            let mut b = Uri::builder();
+            if let Some(scheme) = &ep.scheme { b = b.scheme(scheme.clone()); }
+            if let Some(authority) = &ep.authority { b = b.authority(authority.clone()); }
+            UriW::new(b.path_and_query(ep.path_and_query.as_ref().map(|pq| pq.as_str()).unwrap_or("/")).build()
+                .expect("entrypoint has been validated"))
+        }
+        let secret = |s: &Option<String>| s.as_ref().map(|s| if redact { REDACTED.to_owned() } else { s.clone() });
+        Config {
+            entrypoint: uri(&self.entrypoint),
+            alt_entrypoint: self.alt_entrypoint.as_ref().map(|ep| uri(ep)),
+            default_timeout: self.default_timeout,
+            user_name: self.user_name.clone(),
+            doas: self.doas.clone(),
+            dt: secret(&self.dt),
+            datanode_dt: secret(&self.rest.datanode_delegation),
+            natmap: self.natmap.to_map(),
+            https_config: self.https_settings.as_ref().map(|s| HttpsConfig { 
+                identity_password: secret(&s.config().identity_password), 
+                ..s.config().clone() 
+            }),
+            failover_on_connect_error: Some(self.failover_on_connect_error),
+            max_failovers: Some(self.max_failovers),
+            failover_backoff: Some(self.failover_backoff.0),
+            failover_backoff_max: Some(self.failover_backoff.1),
+            natmap_entrypoints: Some(self.natmap_entrypoints),
+            content_type_strictness: Some(self.rest.strictness),
+            default_port: None,
+            dry_run: Some(self.dry_run),
+            verify_create_options: Some(self.verify_create_options),
+            user_agent: Some(self.rest.user_agent.clone().unwrap_or_default()),
+            client_id: self.client_id.clone(),
+            empty_response_strictness: Some(self.rest.empty_response_strictness),
+            exception_classes: Some(self.exception_classes.clone()),
+            tcp_keepalive: self.rest.tcp_keepalive.map(|k| k.0),
+            tcp_keepalive_interval: self.rest.tcp_keepalive.map(|k| k.1),
+            dns_cache_ttl: self.rest.dns_cache.as_ref().map(|c| c.ttl()),
+            dns_cache_max_entries: self.rest.dns_cache.as_ref().map(|c| c.max_entries())
+        }
+    }
+
+    /// Snapshot of client state and statistics, e.g. for a debug endpoint. Counters are totals over all 
+    /// endpoints of the metrics instance, which may be shared with other clients (see `HdfsClientBuilder::metrics`).
+    pub fn stats(&self) -> ClientStats {
+        let m = &self.metrics;
+        ClientStats {
+            active_endpoint: self.endpoint_name(self.active_fostate()),
+            requests: m.total(counter::REQUESTS),
+            failovers: m.total(counter::FAILOVER),
+            retries: m.total(counter::RETRY),
+            bytes_in: m.total(counter::BYTES_IN),
+            bytes_out: m.total(counter::BYTES_OUT),
+            mean_latency: m.total_mean(counter::TTFB)
+        }
+    }
+
+    /// Whether mutating operations are only logged and verified, not performed. In dry-run mode,
+    /// - `create` fails if the file exists and overwrite is not requested;
+    /// - `append` and `concat` fail if the file does not exist;
+    /// - `rename` and `delete` return whether the path exists;
+    /// - `mkdirs` returns `true`.
+    pub fn is_dry_run(&self) -> bool { self.dry_run }
+
+    /// Logs a mutating operation instead of performing it, and stats its path. Returns whether the path exists.
+    async fn dry_run_op(&self, fostate: FOState, op: Op, path: &str, detail: String) -> FOResult<bool> {
+        info!(target: "webhdfs::dry_run", "dry-run op={} path={} {}", op.op_string(), path, detail);
+        match self.stat(fostate, path).await {
+            Ok((_, fostate)) => Ok((true, fostate)),
+            Err((e, fostate)) if Self::is_not_found_error(&e) => Ok((false, fostate)),
+            Err(e) => Err(e)
+        }
+    }
+
+    async fn dry_run_data_op(&self, fostate: FOState, op: Op, path: &str, args: &[OpArg], data: Data) -> FODResult<()> {
+        let overwrite = args.iter().any(|a| matches!(a, OpArg::Overwrite(true)));
+        let expect_exists = if let Op::CREATE = op { None } else { Some(true) };
+        match self.dry_run_op(fostate, op, path, format!("len={} args={:?}", data.len(), args)).await {
+            Ok((exists, fostate)) => match expect_exists {
+                None if exists && !overwrite => 
+                    Err((self.recovered(app_error!(generic "dry-run: {} already exists", path), data), fostate)),
+                Some(true) if !exists => 
+                    Err((self.recovered(app_error!(generic "dry-run: {} does not exist", path), data), fostate)),
+                _ => Ok(((), fostate))
+            }
+            Err((e, fostate)) => Err((self.recovered(e, data), fostate))
+        }
+    }
 
     /// Get directory listing
-    pub async fn dir(&self, fostate: FOState, path: &str) -> FOResult<ListStatusResponse> {
+    pub async fn dir(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<ListStatusResponse> {
+        let path = path.as_ref().as_str();
         self.get_json(fostate, path, Op::LISTSTATUS, vec![]).await
     }
 
+    /// Get directory listing as a stream of entries, parsed as the response is received, so that processing 
+    /// can start before a large listing is complete. Only the request is subject to failover: an error while 
+    /// receiving the response ends the stream.
+    pub async fn dir_stream(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) 
+    -> FOResult<impl Stream<Item=Result<FileStatus>> + Unpin + Send> {
+        let path = path.as_ref().as_str();
+        let (body, fostate) = self.get_json_stream(fostate, path, Op::LISTSTATUS, vec![]).await?;
+        let entries = futures::stream::unfold(Some((body, ArrayObjects::new())), |state| async move {
+            let (mut body, mut parser) = state?;
+            let r = match body.next().await {
+                Some(Ok(chunk)) => parser.push(&chunk),
+                Some(Err(e)) => Err(e),
+                None => return parser.finish().err().map(|e| (vec![Err(e)], None))
+            };
+            match r {
+                Ok(entries) => Some((entries.into_iter().map(Ok).collect(), Some((body, parser)))),
+                Err(e) => Some((vec![Err(e)], None))
+            }
+        });
+        Ok((Box::pin(entries.flat_map(futures::stream::iter)), fostate))
+    }
+
+    /// Get directory entries matching `filter`, as a stream. The filter is applied as the listing is received
+    /// (see `dir_stream`), so non-matching entries are dropped without ever being collected.
+    pub async fn dir_filtered(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), filter: FileFilter)
+    -> FOResult<impl Stream<Item=Result<FileStatus>> + Unpin + Send> {
+        let (entries, fostate) = self.dir_stream(fostate, path).await?;
+        let entries = entries.filter(move |e| futures::future::ready(e.as_ref().map_or(true, |fs| filter.matches(fs))));
+        Ok((entries, fostate))
+    }
+
+    /// Get directory listing, starting after entry `start_after` (a child name, not a path), e.g. to resume an 
+    /// interrupted listing of a large directory. `startAfter` is passed to the server; since not all servers 
+    /// honor it for LISTSTATUS, entries up to and including `start_after` are also dropped on the client.
+    pub async fn dir_from(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), start_after: &str) -> FOResult<ListStatusResponse> {
+        let path = path.as_ref().as_str();
+        let args = vec![OpArg::StartAfter(start_after.to_owned())];
+        let (mut r, fostate): (ListStatusResponse, _) = self.get_json(fostate, path, Op::LISTSTATUS, args).await?;
+        r.file_statuses.file_status.retain(|fs| fs.path_suffix.as_str() > start_after);
+        Ok((r, fostate))
+    }
+
+    /// Get directory listing, with entries paired with their absolute paths. WebHDFS returns entry names
+    /// (`pathSuffix`) as they are, not percent-encoded, so they are not decoded: a name such as `a%20b` is kept
+    /// verbatim, and the returned paths can be passed to client methods directly.
+    pub async fn dir_abs(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<Vec<(String, FileStatus)>> {
+        let path = path.as_ref().as_str();
+        let (r, fostate) = self.dir(fostate, path).await?;
+        let r = r.file_statuses.file_status.into_iter()
+            .map(|fs| (crate::path::join(path, &fs.path_suffix), fs))
+            .collect();
+        Ok((r, fostate))
+    }
+
     /// Get status
-    pub async fn stat(&self, fostate: FOState, path: &str) -> FOResult<FileStatusResponse> {
+    pub async fn stat(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<FileStatusResponse> {
+        let path = path.as_ref().as_str();
         self.get_json(fostate, path, Op::GETFILESTATUS, vec![]).await
     }
 
+    /// Resolves symbolic links: stats `path`, and while it is a symbolic link, its target (relative targets are 
+    /// resolved against the link's parent directory), like `FileSystem#resolvePath`. Fails after `MAX_PATH_LINKS` 
+    /// links, or if a link loop is detected. Only the last path component is resolved. 
+    /// Returns the resolved path and its status.
+    pub async fn resolve_path(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<(String, FileStatus)> {
+        let mut path = path.as_ref().as_str().to_owned();
+        let mut fostate = fostate;
+        let mut seen = std::collections::HashSet::new();
+        loop {
+            let (r, fostate1) = FOR::split(self.stat(fostate, &path).await);
+            fostate = fostate1;
+            let st = match r { Ok(r) => r.file_status, Err(e) => return Err((e, fostate)) };
+            let target = match (st.type_.as_str(), &st.symlink) {
+                (dirent_type::SYMLINK, Some(t)) => t,
+                (dirent_type::SYMLINK, None) => 
+                    return Err((app_error!(generic "Symbolic link {} has no target in its status", path), fostate)),
+                _ => return Ok(((path, st), fostate))
+            };
+            let target = if HdfsPath::new(target).is_absolute() { 
+                target.clone() 
+            } else {
+                crate::path::join(HdfsPath::new(&path).parent().map(|p| p.as_str()).unwrap_or("/"), target)
+            };
+            let target = match HdfsPath::new(&target).normalize() { Ok(t) => t.into_string(), Err(e) => return Err((e, fostate)) };
+            seen.insert(path);
+            if seen.contains(&target) {
+                return Err((app_error!(generic "Symbolic link loop detected at {}", target), fostate))
+            }
+            if seen.len() >= Self::MAX_PATH_LINKS {
+                return Err((app_error!(generic "Too many symbolic links (more than {}) resolving {}", Self::MAX_PATH_LINKS, target), fostate))
+            }
+            path = target;
+        }
+    }
+
+    /// Get status of the file or directory a symbolic link points to, see `resolve_path`
+    pub async fn stat_resolved(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<FileStatus> {
+        let ((_, st), fostate) = self.resolve_path(fostate, path).await?;
+        Ok((st, fostate))
+    }
+
+    /// Read data of the file a symbolic link points to, see `resolve_path`
+    pub async fn open_resolved(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: OpenOptions) 
+    -> FOResult<BinaryStream> {
+        let ((path, _), fostate) = self.resolve_path(fostate, path).await?;
+        self.open(fostate, &path, opts).await
+    }
+
+    /// Get encryption info of a file, `None` if the file is not in an encryption zone. WebHDFS has no counterpart 
+    /// of GETEZFORPATH or LISTENCRYPTIONZONES, so the info is taken from GETFILESTATUS (`feInfo`, Hadoop 3.1+). 
+    /// Fails if the server reports the file as encrypted without giving its encryption info.
+    pub async fn encryption_info(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<Option<FileEncryptionInfo>> {
+        let path = path.as_ref().as_str();
+        let (r, fostate) = FOR::split(self.stat(fostate, path).await);
+        let r = r.and_then(|r| match r.file_status {
+            FileStatus { fe_info: Some(i), .. } => Ok(Some(i)),
+            FileStatus { enc_bit: true, .. } => 
+                Err(app_error!(generic "{} is encrypted, but the server does not report encryption info", path)),
+            _ => Ok(None)
+        });
+        FOR::bind(r, fostate)
+    }
+
+    /// Get content summary (sizes and counts) of a directory tree
+    pub async fn content_summary(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<ContentSummaryResponse> {
+        let path = path.as_ref().as_str();
+        self.get_json(fostate, path, Op::GETCONTENTSUMMARY, vec![]).await
+    }
+
+    /// Pre-flight check for writing `length` bytes, stored with `replication` replicas, in `files` new files 
+    /// or directories under directory `path`. Fails with `QuotaExceeded` (see `Error::quota_exceeded`) if the 
+    /// write would exceed the namespace or space quota of `path`. Quotas of ancestor directories are not checked.
+    pub async fn check_quota(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), length: u64, files: u64, replication: u16) 
+    -> FOResult<()> {
+        let path = path.as_ref().as_str();
+        let (r, fostate) = FOR::split(self.content_summary(fostate, path).await);
+        let r = r.and_then(|s| Ok(s.content_summary.check_quota(path, length, files, replication)?));
+        FOR::bind(r, fostate)
+    }
+
+    /// Get the difference between snapshots `from` and `to` of snapshottable directory `path`.
+    /// An empty snapshot name denotes the current state of the directory.
+    pub async fn snapshot_diff(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), from: &str, to: &str) 
+    -> FOResult<SnapshotDiffReportResponse> {
+        let path = path.as_ref().as_str();
+        let args = vec![OpArg::OldSnapshotName(from.to_owned()), OpArg::SnapshotName(to.to_owned())];
+        self.get_json(fostate, path, Op::GETSNAPSHOTDIFF, args).await
+    }
+
+    /// List snapshottable directories visible to the user
+    pub async fn snapshottable_dirs(&self, fostate: FOState) -> FOResult<SnapshottableDirectoryListResponse> {
+        self.get_json(fostate, "/", Op::GETSNAPSHOTTABLEDIRECTORYLIST, vec![]).await
+    }
+
+    /// Get defaults the server applies to new files (GETSERVERDEFAULTS; Hadoop 3.x). If the server does not support 
+    /// GETSERVERDEFAULTS, block size and replication are derived from the status of an empty probe file, which is 
+    /// created in directory `probe_dir` and deleted afterwards.
+    pub async fn server_defaults(&self, fostate: FOState, probe_dir: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<ServerDefaults> {
+        let probe_dir = probe_dir.as_ref().as_str();
+        let op = Op::GETSERVERDEFAULTS;
+        let fostate = if self.is_supported(op.op_string()) {
+            match self.get_json::<ServerDefaultsResponse>(fostate, "/", op.clone(), vec![]).await {
+                Ok((r, fostate)) => return Ok((r.server_defaults, fostate)),
+                Err((e, fostate)) if Self::is_unknown_op_error(&e) => {
+                    warn!("{} is not supported by the server: {}", op.op_string(), e);
+                    self.unsupported_ops.lock().unwrap().insert(op.op_string());
+                    fostate
+                }
+                Err(e) => return Err(e)
+            }
+        } else {
+            fostate
+        };
+        if self.dry_run {
+            return Err((app_error!(generic "dry-run: cannot create a probe file in {}", probe_dir), fostate))
+        }
+        let probe = crate::path::join(probe_dir, &format!(".webhdfs-probe-{}-{}", std::process::id(), 
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()));
+        let (r, fostate) = FOR::split(self.create(fostate, &probe, crate::rest_client::data_empty(), CreateOptions::new()).await);
+        if let Err(e) = r { return Err((e.error, fostate)) }
+        let (r, fostate) = FOR::split(self.stat(fostate, &probe).await);
+        let (d, fostate) = FOR::split(self.delete(fostate, &probe, DeleteOptions::new()).await);
+        if let Err(e) = d { warn!("Cannot delete probe file {}: {}", probe, e) }
+        let r = r.map(|r| ServerDefaults { 
+            block_size: r.file_status.block_size, replication: r.file_status.replication, 
+            bytes_per_checksum: None, checksum_type: None, file_buffer_size: None, write_packet_size: None
+        });
+        FOR::bind(r, fostate)
+    }
+
+    /// Get home directory of the user
+    pub async fn home_dir(&self, fostate: FOState) -> FOResult<String> {
+        let (r, fostate) = FOR::split(self.get_json::<PathResponse>(fostate, "/", Op::GETHOMEDIRECTORY, vec![]).await);
+        FOR::bind(r.map(|r| r.path), fostate)
+    }
+
+    /// Deletes checkpoints in the user's trash (`<home>/.Trash`) that are older than `older_than`, as `hdfs dfs -expunge`
+    /// does with the trash interval. Unlike `hdfs dfs -expunge`, `Current` is not checkpointed. Checkpoint times are 
+    /// parsed from checkpoint names (see `crate::trash`). Returns paths of deleted checkpoints.
+    pub async fn expunge_trash(&self, fostate: FOState, older_than: Duration) -> FOResult<Vec<String>> {
+        let (home, fostate) = self.home_dir(fostate).await?;
+        let trash = crate::path::join(&home, crate::trash::TRASH_DIR);
+        let (entries, mut fostate) = match self.dir(fostate, &trash).await {
+            Ok((r, fostate)) => (r.file_statuses.file_status, fostate),
+            Err((e, fostate)) if Self::is_not_found_error(&e) => return Ok((vec![], fostate)),
+            Err(e) => return Err(e)
+        };
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        let cutoff = now.saturating_sub(older_than.as_millis() as i64);
+        let mut deleted = vec![];
+        for e in entries.into_iter().filter(|e| e.type_ == dirent_type::DIRECTORY) {
+            match crate::trash::checkpoint_time(&e.path_suffix) {
+                Some(t) if t < cutoff => {
+                    let path = crate::path::join(&trash, &e.path_suffix);
+                    let (r, fo) = FOR::split(self.delete(fostate, &path, DeleteOptions::new().recursive(true)).await);
+                    fostate = fo;
+                    if r.map_err(|e| (e, fostate))? {
+                        info!("Deleted trash checkpoint {}", path);
+                        deleted.push(path)
+                    }
+                }
+                _ => ()
+            }
+        }
+        Ok((deleted, fostate))
+    }
+
+    /// Get file checksum (computed by a datanode)
+    pub async fn checksum(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized)) -> FOResult<FileChecksumResponse> {
+        let path = path.as_ref().as_str();
+        with_failover!(
+            [
+                |r: HttpyClient| r.get_json_redirected()
+            ],
+            self,
+            fostate,
+            &Op::GETFILECHECKSUM,
+            self.path_and_query(path, &Op::GETFILECHECKSUM, vec![])
+        )
+    }
+
     /// Read file data
-    pub async fn open(&self, fostate: FOState, path: &str, opts: OpenOptions) -> FOResult<Box<dyn Stream<Item=Result<Bytes>>+Unpin>> {
+    pub async fn open(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: OpenOptions) -> FOResult<BinaryStream> {
+        let path = path.as_ref().as_str();
         with_failover!(
             [
-                |r: HttpyClient| r.get_binary(),
                 |r: HttpyClient| r.get_binary()
             ],
             self,
             fostate,
-            self.path_and_query(path, Op::OPEN, opts.into())
+            &Op::OPEN,
+            self.path_and_query(path, &Op::OPEN, opts.into())
         )
     }
 
+    /// Read file data into `output`, e.g. a socket or a compression encoder. Each chunk is written out before 
+    /// the next one is read, so a slow writer slows down the download instead of having data buffered.
+    /// Returns the number of bytes written.
+    pub async fn get_to(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), mut output: impl AsyncWrite + Unpin) -> FOResult<u64> {
+        let (mut s, fostate) = self.open(fostate, path, OpenOptions::new()).await?;
+        let r = async {
+            let mut n = 0;
+            while let Some(chunk) = s.next().await {
+                let chunk = chunk?;
+                output.write_all(&chunk).await?;
+                n += chunk.len() as u64;
+            }
+            output.flush().await?;
+            Ok(n)
+        }.await;
+        FOR::bind(r, fostate)
+    }
+
+    /// Resumes an interrupted download: writes file data from `offset` on into `output`. `expected` is the status
+    /// of the file at the start of the download; the file is checked against it (length and modification time)
+    /// before and after reading. If the file has changed, fails with `SourceChanged` (see `Error::source_changed`);
+    /// if it has changed while the data was being read, the data written to `output` must be discarded.
+    /// Returns the number of bytes written.
+    pub async fn resume_to(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), offset: i64, expected: &FileStatus,
+        mut output: impl AsyncWrite + Unpin) -> FOResult<u64> {
+        let path = path.as_ref().as_str();
+        let (r, fostate) = FOR::split(self.stat(fostate, path).await);
+        if let Err(e) = r.and_then(|r| Ok(expected.check_unchanged(path, &r.file_status)?)) {
+            return Err((e, fostate))
+        }
+        let (mut s, fostate) = self.open(fostate, path, OpenOptions::new().offset(offset)).await?;
+        let r = async {
+            let mut n = 0;
+            while let Some(chunk) = s.next().await {
+                let chunk = chunk?;
+                output.write_all(&chunk).await?;
+                n += chunk.len() as u64;
+            }
+            output.flush().await?;
+            Ok(n)
+        }.await;
+        let n = match r { Ok(n) => n, Err(e) => return Err((e, fostate)) };
+        let (r, fostate) = FOR::split(self.stat(fostate, path).await);
+        let r = r.and_then(|r| Ok(expected.check_unchanged(path, &r.file_status)?));
+        FOR::bind(r.map(|()| n), fostate)
+    }
+
+    /// Creates file `path` with data read from `input`, without buffering the whole input. The data is sent
+    /// in chunks (see `HdfsClientBuilder::upload_chunk_size`): the first one by CREATE, the rest by APPENDs. 
+    /// Returns the number of bytes written. If a request fails, the file holds the chunks sent before.
+    pub async fn put_from(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), mut input: impl AsyncRead + Unpin, opts: CreateOptions) 
+    -> FOResult<u64> {
+        let path = path.as_ref().as_str();
+        let mut fostate = fostate;
+        let mut total = 0;
+        let mut first = true;
+        loop {
+            let mut chunk = Vec::with_capacity(self.upload_chunk_size);
+            if let Err(e) = (&mut input).take(self.upload_chunk_size as u64).read_to_end(&mut chunk).await {
+                return Err((e.into(), fostate))
+            }
+            if chunk.is_empty() && !first { break }
+            let len = chunk.len();
+            let r = if first {
+                self.create(fostate, path, Data::Owned(chunk), opts.clone()).await
+            } else {
+                self.append(fostate, path, Data::Owned(chunk), AppendOptions::new()).await
+            };
+            fostate = match r {
+                Ok(((), fostate)) => fostate,
+                Err((e, fostate)) => return Err((e.error, fostate))
+            };
+            total += len as u64;
+            if len < self.upload_chunk_size { break }
+            first = false;
+        }
+        Ok((total, fostate))
+    }
+
+    /// Copies file `from` to new file `to` by reading and rewriting its data, as WebHDFS has no server-side copy. 
+    /// The data is written in chunks, as by `put_from`. Returns the number of bytes copied.
+    pub async fn copy_file(&self, fostate: FOState, from: &(impl AsRef<HdfsPath> + ?Sized), to: &(impl AsRef<HdfsPath> + ?Sized), opts: CreateOptions) 
+    -> FOResult<u64> {
+        let (from, to) = (from.as_ref().as_str(), to.as_ref().as_str());
+        let (mut s, mut fostate) = self.open(fostate, from, OpenOptions::new()).await?;
+        let mut total = 0;
+        let mut first = true;
+        let mut chunk = Vec::with_capacity(self.upload_chunk_size);
+        loop {
+            let next = match s.next().await {
+                Some(Ok(b)) => Some(b),
+                Some(Err(e)) => return Err((e, fostate)),
+                None => None
+            };
+            let end = next.is_none();
+            if let Some(b) = next { chunk.extend_from_slice(&b) }
+            if chunk.len() >= self.upload_chunk_size || (end && (first || !chunk.is_empty())) {
+                let data = std::mem::replace(&mut chunk, Vec::with_capacity(self.upload_chunk_size));
+                let len = data.len();
+                let r = if first {
+                    self.create(fostate, to, Data::Owned(data), opts.clone()).await
+                } else {
+                    self.append(fostate, to, Data::Owned(data), AppendOptions::new()).await
+                };
+                fostate = match r {
+                    Ok(((), fostate)) => fostate,
+                    Err((e, fostate)) => return Err((e.error, fostate))
+                };
+                total += len as u64;
+                first = false;
+            }
+            if end { break }
+        }
+        Ok((total, fostate))
+    }
+
+    /// Copies file `from` to `to` within the cluster, avoiding data transfer through the client where possible.
+    /// WebHDFS has no server-side copy, and snapshots are read-only, so data can only stay on the cluster if the 
+    /// source is not needed afterwards: with `move_source`, `from` is renamed to `to` (`CopyStrategy::Rename`, 
+    /// see `rename2`; `from` is never moved into an existing directory `to`). If that is not allowed, or the rename 
+    /// fails (e.g. `to` exists, or the paths are in different encryption zones), the data is copied by `copy_file` 
+    /// (`CopyStrategy::ReadWrite`) and `from` is left in place.
+    pub async fn copy_within_cluster(&self, fostate: FOState, from: &(impl AsRef<HdfsPath> + ?Sized), to: &(impl AsRef<HdfsPath> + ?Sized), 
+        opts: CreateOptions, move_source: bool) -> FOResult<ClusterCopy> {
+        let (from, to) = (from.as_ref().as_str(), to.as_ref().as_str());
+        let mut fostate = fostate;
+        if move_source {
+            let (s, fo) = self.stat(fostate, from).await?;
+            if s.file_status.type_ != dirent_type::FILE {
+                return Err((app_error!(generic "{} is not a file", from), fo))
+            }
+            fostate = match self.rename2(fo, from, to.to_owned(), false).await {
+                Ok(((), fo)) => return Ok((ClusterCopy { strategy: CopyStrategy::Rename, bytes: s.file_status.length as u64 }, fo)),
+                Err((e, fo)) if e.remote_exception().is_some() => {
+                    info!("Cannot rename {} to {}, copying instead: {}", from, to, e);
+                    fo
+                }
+                Err(e) => return Err(e)
+            };
+        }
+        let (bytes, fostate) = self.copy_file(fostate, from, to, opts).await?;
+        Ok((ClusterCopy { strategy: CopyStrategy::ReadWrite, bytes }, fostate))
+    }
+
+    /// Positional read: reads up to `buf.len()` bytes starting at `offset` with a single OPEN request.
+    /// Returns the number of bytes read, which is less than requested only if EOF is reached, 
+    /// or if the server sends a short response. Several positional reads may run concurrently.
+    pub async fn read_at(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), offset: i64, buf: &mut [u8]) -> FOResult<usize> {
+        let path = path.as_ref().as_str();
+        let (mut s, fostate) = self.open(fostate, path, OpenOptions::new().offset(offset).length(buf.len() as i64)).await?;
+        let mut pos = 0;
+        while let Some(chunk) = s.next().await {
+            match chunk {
+                Ok(chunk) => {
+                    let n = std::cmp::min(chunk.len(), buf.len() - pos);
+                    buf[pos..pos + n].copy_from_slice(&chunk[..n]);
+                    pos += n;
+                }
+                Err(e) => return Err((e, fostate))
+            }
+        }
+        Ok((pos, fostate))
+    }
+
+    /// Open the last `n` bytes of a file (the whole file, if shorter) with a single OPEN request, e.g. the footer 
+    /// of a columnar file. Returns the file length and the data stream.
+    pub async fn open_tail(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), n: u64) -> FOResult<(u64, BinaryStream)> {
+        let path = path.as_ref().as_str();
+        let (stat, fostate) = self.stat(fostate, path).await?;
+        let len = stat.file_status.length.max(0) as u64;
+        let offset = len.saturating_sub(n);
+        let (s, fostate) = self.open(fostate, path, OpenOptions::new().offset(offset as i64).length((len - offset) as i64)).await?;
+        Ok(((len, s), fostate))
+    }
+
+    /// Read the last `n` bytes of a file (the whole file, if shorter), see `open_tail`. 
+    /// Returns the file length and the data. Fails if the file is truncated meanwhile.
+    pub async fn read_tail(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), n: u64) -> FOResult<(u64, Vec<u8>)> {
+        let path = path.as_ref().as_str();
+        let ((len, mut s), fostate) = self.open_tail(fostate, path, n).await?;
+        let offset = len.saturating_sub(n);
+        let mut data = vec![0u8; (len - offset) as usize];
+        let mut pos = 0;
+        while let Some(chunk) = s.next().await {
+            match chunk {
+                Ok(chunk) => {
+                    let k = std::cmp::min(chunk.len(), data.len() - pos);
+                    data[pos..pos + k].copy_from_slice(&chunk[..k]);
+                    pos += k;
+                }
+                Err(e) => return Err((e, fostate))
+            }
+        }
+        //the server has sent a short response
+        let mut fostate = fostate;
+        while pos < data.len() {
+            let (k, f) = self.read_at(fostate, path, (offset + pos as u64) as i64, &mut data[pos..]).await?;
+            fostate = f;
+            if k == 0 { return Err((app_error!(generic "{} has been truncated while reading its tail", path), fostate)) }
+            pos += k;
+        }
+        Ok(((len, data), fostate))
+    }
+
     /// Create a HDFS file and write some data
-    pub async fn create<'t>(&'t self, fostate: FOState, path: &'t str, data: Data, opts: CreateOptions) -> FODResult<()> {
+    pub async fn create<'t>(&'t self, fostate: FOState, path: &'t (impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) -> FODResult<()> {
+        self.create_with_location(fostate, path, data, opts).await.map(|(_, fostate)| ((), fostate))
+    }
+
+    /// Same as `create`, also returning the `Location` of the created file if the datanode reports it 
+    /// (some versions respond with `201 Created` and e.g. `hdfs://namenode:8020/path`)
+    pub async fn create_with_location<'t>(&'t self, fostate: FOState, path: &'t (impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) 
+    -> FODResult<Option<String>> {
+        let path = path.as_ref().as_str();
         //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=CREATE
         //           [&overwrite=<true |false>][&blocksize=<LONG>][&replication=<SHORT>]
-        //           [&permission=<OCTAL>][&buffersize=<INT>]"
-        self.data_op(fostate, Method::PUT, path, Op::CREATE, opts.into(), data).await
+        //           [&permission=<OCTAL>][&buffersize=<INT>][&createflag=<FLAGS>]"
+        let args = with_defaults(opts.into(), &self.default_create);
+        if self.dry_run { return self.dry_run_data_op(fostate, Op::CREATE, path, &args, data).await.map(|(_, fostate)| (None, fostate)) }
+        let expected: Vec<(&'static str, i64)> = args.iter().filter_map(|a| match a {
+            OpArg::Replication(v) => Some(("replication", *v as i64)),
+            OpArg::Blocksize(v) => Some(("blocksize", *v)),
+            _ => None
+        }).collect();
+        let r = self.data_op(fostate, Method::PUT, path, Op::CREATE, args, data).await;
+        if !self.verify_create_options || expected.is_empty() { return r }
+        let (location, fostate) = r?;
+        let (r, fostate) = FOR::split(self.verify_created(fostate, path, expected).await);
+        FOR::bind(r.map(|_| location).map_err(ErrorD::lift), fostate)
+    }
+
+    /// Checks that options requested by CREATE were honored
+    async fn verify_created(&self, fostate: FOState, path: &str, expected: Vec<(&'static str, i64)>) -> FOResult<()> {
+        let (r, fostate) = FOR::split(self.stat(fostate, path).await);
+        let r = r.and_then(|r| {
+            let fs = r.file_status;
+            for (option, requested) in expected {
+                let actual = if option == "replication" { fs.replication as i64 } else { fs.block_size };
+                if actual != requested {
+                    warn!("CREATE {}: {} not honored, requested={}, actual={}", path, option, requested, actual);
+                    return Err(CreateOptionIgnored { path: path.to_owned(), option, requested, actual }.into())
+                }
+            }
+            Ok(())
+        });
+        FOR::bind(r, fostate)
+    }
+
+    /// Performs the namenode step of OPEN only, returning the datanode location the data can be read from 
+    /// (e.g. by a browser). The location is NAT-translated if `translate` is set.
+    pub async fn open_location(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: OpenOptions, translate: bool) -> FOResult<Uri> {
+        let path = path.as_ref().as_str();
+        self.location(fostate, Method::GET, path, Op::OPEN, opts.into(), translate).await
+    }
+
+    /// Performs the namenode step of CREATE only, returning the datanode location the data is to be `PUT` to. 
+    /// The location is NAT-translated if `translate` is set.
+    pub async fn create_location(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: CreateOptions, translate: bool) -> FOResult<Uri> {
+        let path = path.as_ref().as_str();
+        self.location(fostate, Method::PUT, path, Op::CREATE, with_defaults(opts.into(), &self.default_create), translate).await
+    }
+
+    /// Diagnoses the most common setup problem of dockerized dev clusters: datanodes advertising addresses that are
+    /// only valid inside the container network. Gets the datanode location of `path` (an existing file) and tries to
+    /// connect to it within `timeout`. Returns `None` if it is reachable (NAT-translated, if a mapping exists). 
+    /// Otherwise returns the NAT mapping `(advertised datanode host:port, namenode host:datanode port)` if the latter 
+    /// is reachable, e.g. to be added with `with_natmap_entry`, and fails if it is not.
+    pub async fn detect_nat(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), timeout: Duration) 
+    -> FOResult<Option<(String, String)>> {
+        let (location, fostate) = self.open_location(fostate, path, OpenOptions::new(), false).await?;
+        let (nn, fostate) = self.uri(fostate, b"/")?;
+        let r = async {
+            let advertised = location.authority().ok_or_else(|| app_error!(generic "No authority in datanode location {}", crate::redact::Redacted(&location)))?;
+            if is_reachable(&self.natmap.translate(location.clone())?, timeout).await { return Ok(None) }
+            let candidate = format!("{}:{}", nn.host().unwrap_or_default(), location.port_u16().unwrap_or_else(|| default_port(&location)));
+            let mut parts = location.clone().into_parts();
+            parts.authority = Some(candidate.parse().aerr("Cannot build NAT candidate authority")?);
+            let candidate_uri = Uri::from_parts(parts).aerr("Cannot build NAT candidate URI")?;
+            if candidate != advertised.as_str() && is_reachable(&candidate_uri, timeout).await {
+                info!("Datanode {} is unreachable, {} is reachable: suggesting NAT mapping", advertised, candidate);
+                Ok(Some((advertised.to_string(), candidate)))
+            } else {
+                Err(app_error!(generic "Datanode {} is unreachable, and so is {}", advertised, candidate))
+            }
+        }.await;
+        FOR::bind(r, fostate)
+    }
+
+    /// Returns the client with NAT mapping `from` -> `to` added, e.g. as suggested by `detect_nat`
+    pub fn with_natmap_entry(self, from: String, to: String) -> Result<Self> {
+        Ok(Self { natmap: self.natmap.with_entry(from, to)?, ..self })
+    }
+
+    /// Performs the namenode step of CREATE with `noredirect=true`, returning the NAT-translated datanode URL that 
+    /// browsers or other services can `PUT` the file body to directly (with `Content-Type: application/octet-stream`). 
+    /// The URL carries the datanode delegation token, if any, and is only valid for a single upload.
+    pub async fn create_upload_url(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: CreateOptions) -> FOResult<Uri> {
+        let path = path.as_ref().as_str();
+        let mut args = with_defaults(opts.into(), &self.default_create);
+        args.push(OpArg::NoRedirect(true));
+        self.location(fostate, Method::PUT, path, Op::CREATE, args, true).await
     }
 
     /// Append to a HDFS file
-    pub async fn append<'t>(&'t self, fostate: FOState, path: &'t str, data: Data, opts: AppendOptions) -> FODResult<()> {
+    pub async fn append<'t>(&'t self, fostate: FOState, path: &'t (impl AsRef<HdfsPath> + ?Sized), data: Data, opts: AppendOptions) -> FODResult<()> {
+        let path = path.as_ref().as_str();
         //curl -i -X POST "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=APPEND[&buffersize=<INT>]"
-        self.data_op(fostate, Method::POST, path, Op::APPEND, opts.into(), data).await
+        let args = with_defaults(opts.into(), &self.default_append);
+        if self.dry_run { return self.dry_run_data_op(fostate, Op::APPEND, path, &args, data).await }
+        let (max_retries, initial, max) = self.lease_retry;
+        let (mut fostate, mut data, mut attempt) = (fostate, data, 0);
+        loop {
+            let retry_data = if attempt < max_retries { Some(data.clone()) } else { None };
+            let (e, fo) = match self.data_op(fostate, Method::POST, path, Op::APPEND, args.clone(), data).await {
+                Err((e, fo)) if e.error.is_lease_conflict() => (e, fo),
+                other => return other.map(|(_, fo)| ((), fo))
+            };
+            attempt += 1;
+            let d = match retry_data {
+                Some(d) => d,
+                None => return Err((ErrorD { error: e.error.into_lease_conflict(path, attempt), ..e }, fo))
+            };
+            let delay = initial.checked_mul(1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX)).unwrap_or(max).min(max);
+            warn!("APPEND {}: lease conflict, retry {}/{} in {:?}: {}", path, attempt, max_retries, delay, e.error);
+            tokio::time::sleep(delay).await;
+            match FOR::split(self.stat(fo, path).await) {
+                (Ok(_), fo) => fostate = fo,
+                (Err(e), fo) => return Err((self.recovered(e, d), fo))
+            }
+            data = d;
+        }
     }
 
     /// Concatenate files
-    pub async fn concat(&self, fostate: FOState, path: &str, paths: Vec<String>) -> FOResult<()> {
+    pub async fn concat(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), paths: Vec<String>) -> FOResult<()> {
+        let path = path.as_ref().as_str();
+        Self::validate_paths(fostate, std::iter::once(path).chain(paths.iter().map(|p| p.as_str())))?;
         //curl -i -X POST "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=CONCAT&sources=<PATHS>"
+        if self.dry_run {
+            let (exists, fostate) = self.dry_run_op(fostate, Op::CONCAT, path, format!("sources={:?}", paths)).await?;
+            return if exists { Ok(((), fostate)) } else { Err((app_error!(generic "dry-run: {} does not exist", path), fostate)) }
+        }
         self.data_op_e(fostate, Method::POST, path, Op::CONCAT, vec![OpArg::Sources(paths)]).await
     }
 
     /// Make a directory
-    pub async fn mkdirs(&self, fostate: FOState, path: &str, opts: MkdirsOptions) -> FOResult<bool> {
+    pub async fn mkdirs(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: MkdirsOptions) -> FOResult<bool> {
+        let path = path.as_ref().as_str();
         //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=MKDIRS[&permission=<OCTAL>]"
-        self.data_op_b(fostate, Method::PUT, path, Op::MKDIRS, opts.into()).await
+        let args = opts.into();
+        if self.dry_run {
+            return self.dry_run_op(fostate, Op::MKDIRS, path, format!("args={:?}", args)).await.map(|(_, fostate)| (true, fostate))
+        }
+        self.data_op_b(fostate, Method::PUT, path, Op::MKDIRS, args).await
     }
 
     /// Rename a file/directory
-    pub async fn rename(&self, fostate: FOState, path: &str, destination: String) -> FOResult<bool> {
+    pub async fn rename(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String) -> FOResult<bool> {
+        let path = path.as_ref().as_str();
+        Self::validate_paths(fostate, [path, &destination])?;
         //curl -i -X PUT "<HOST>:<PORT>/webhdfs/v1/<PATH>?op=RENAME&destination=<PATH>"
+        if self.dry_run { return self.dry_run_op(fostate, Op::RENAME, path, format!("destination={}", destination)).await }
         self.data_op_b(fostate, Method::PUT, path, Op::RENAME, vec![OpArg::Destination(destination)]).await
     }
 
+    /// Rename a file/directory with rename options (as `FileSystem.rename(src, dst, options)`). Unlike `rename`,
+    /// the source is never moved into an existing directory, and failures are reported as errors, not as `false`:
+    /// if `destination` exists, the call fails, unless `overwrite` is set and both are files, or both are directories
+    /// and `destination` is empty, in which case it is replaced atomically. The parent of `destination` must exist.
+    pub async fn rename2(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String, overwrite: bool) 
+    -> FOResult<()> {
+        let path = path.as_ref().as_str();
+        Self::validate_paths(fostate, [path, &destination])?;
+        //curl -i -X PUT "<HOST>:<PORT>/webhdfs/v1/<PATH>?op=RENAME&destination=<PATH>&renameoptions=<OPTIONS>"
+        let args = vec![OpArg::Destination(destination), OpArg::RenameOverwrite(overwrite)];
+        if self.dry_run {
+            return self.dry_run_op(fostate, Op::RENAME, path, format!("args={:?}", args)).await.map(|(_, fostate)| ((), fostate))
+        }
+        self.data_op_e(fostate, Method::PUT, path, Op::RENAME, args).await
+    }
+
+    /// Validates the path arguments of a call (see `HdfsPath::validate`)
+    fn validate_paths<'a>(fostate: FOState, paths: impl IntoIterator<Item=&'a str>) -> FOResult<()> {
+        FOR::bind(paths.into_iter().try_for_each(|p| HdfsPath::new(p).validate()), fostate)
+    }
+
     /// Create a Symbolic Link
-    pub async fn create_symlink(&self, fostate: FOState, path: &str, destination: String, opts: CreateSymlinkOptions) -> FOResult<()> {
+    pub async fn create_symlink(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String, opts: CreateSymlinkOptions) -> FOResult<()> {
+        let path = path.as_ref().as_str();
         //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=CREATESYMLINK
         //                      &destination=<PATH>[&createParent=<true|false>]"
         let mut o = vec![OpArg::Destination(destination)];
         o.append(&mut opts.into());
+        if self.dry_run {
+            return self.dry_run_op(fostate, Op::CREATESYMLINK, path, format!("args={:?}", o)).await.map(|(_, fostate)| ((), fostate))
+        }
         self.data_op_e(fostate, Method::PUT, path, Op::CREATESYMLINK, o).await
     }
 
     /// Delete a File/Directory
-    pub async fn delete(&self, fostate: FOState, path: &str, opts: DeleteOptions) -> FOResult<bool> {
+    pub async fn delete(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), opts: DeleteOptions) -> FOResult<bool> {
+        let path = path.as_ref().as_str();
         //curl -i -X DELETE "http://<host>:<port>/webhdfs/v1/<path>?op=DELETE
         //                      [&recursive=<true|false>]"
-        self.data_op_b(fostate, Method::DELETE, path, Op::DELETE, opts.into()).await
+        let args = with_defaults(opts.into(), &self.default_delete);
+        if self.deny_recursive_delete && args.iter().any(|a| matches!(a, OpArg::Recursive(true))) {
+            return Err((app_error!(generic "Recursive delete of {} denied", path), fostate))
+        }
+        if self.dry_run { return self.dry_run_op(fostate, Op::DELETE, path, format!("args={:?}", args)).await }
+        self.data_op_b(fostate, Method::DELETE, path, Op::DELETE, args).await
     }
 
+    /// Set namespace and storage space quotas of a directory (admin only; Hadoop 3.3+). Pass `QUOTA_DONT_SET` 
+    /// to leave a quota unchanged, or `QUOTA_RESET` to clear it.
+    pub async fn set_quota(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), namespace_quota: i64, storagespace_quota: i64) 
+    -> FOResult<()> {
+        let path = path.as_ref().as_str();
+        //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=SETQUOTA
+        //                      &namespacequota=<QUOTA>&storagespacequota=<QUOTA>"
+        let args = vec![OpArg::NamespaceQuota(namespace_quota), OpArg::StoragespaceQuota(storagespace_quota)];
+        self.optional_op_e(fostate, Method::PUT, path, Op::SETQUOTA, args).await
+    }
+
+    /// Set storage space quota of a directory for storage type `storage_type` (e.g. `SSD`, `DISK`, `ARCHIVE`)
+    /// (admin only; Hadoop 3.3+). Pass `QUOTA_RESET` to clear the quota.
+    pub async fn set_quota_by_storage_type(&self, fostate: FOState, path: &(impl AsRef<HdfsPath> + ?Sized), storage_type: &str, 
+        storagespace_quota: i64) -> FOResult<()> {
+        let path = path.as_ref().as_str();
+        //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=SETQUOTABYSTORAGETYPE
+        //                      &storagetype=<STORAGETYPE>&storagespacequota=<QUOTA>"
+        let args = vec![OpArg::StorageType(storage_type.to_owned()), OpArg::StoragespaceQuota(storagespace_quota)];
+        self.optional_op_e(fostate, Method::PUT, path, Op::SETQUOTABYSTORAGETYPE, args).await
+    }
+
+    /// Whether optional operation `op` (e.g. `"SETQUOTA"`) may be supported by the server: `false` once the server
+    /// has rejected it as unknown
+    pub fn is_supported(&self, op: &str) -> bool { !self.unsupported_ops.lock().unwrap().contains(op) }
+
+    /// Runs an operation that older servers may not know. An operation rejected as unknown is remembered, and 
+    /// subsequent calls fail without contacting the server.
+    async fn optional_op_e(&self, fostate: FOState, method: Method, path: &str, op: Op, args: Vec<OpArg>) -> FOResult<()> {
+        let name = op.op_string();
+        if !self.is_supported(name) {
+            return Err((app_error!(generic "{} is not supported by the server", name), fostate))
+        }
+        if self.dry_run { 
+            return self.dry_run_op(fostate, op, path, format!("args={:?}", args)).await.map(|(_, fostate)| ((), fostate)) 
+        }
+        match self.data_op_e(fostate, method, path, op, args).await {
+            Err((e, fostate)) if Self::is_unknown_op_error(&e) => {
+                warn!("{} is not supported by the server: {}", name, e);
+                self.unsupported_ops.lock().unwrap().insert(name);
+                Err((e.with_msg_prepended(format!("{} is not supported by the server", name).into()), fostate))
+            }
+            r => r
+        }
+    }
+
+    /// Whether the server has rejected the `op` parameter value
+    fn is_unknown_op_error(error: &Error) -> bool {
+        matches!(error.remote_exception(), Some(RemoteException { exception, message, .. }) 
+            if exception == "IllegalArgumentException" && message.contains("parameter \"op\""))
+    }
+}
+
+/// Namenode entrypoint (`UriParts`, which is not `Clone`)
+struct Entrypoint(UriParts);
+
+impl Clone for Entrypoint {
+    fn clone(&self) -> Self {
+        let mut r = UriParts::default();
+        r.scheme = self.0.scheme.clone();
+        r.authority = self.0.authority.clone();
+        r.path_and_query = self.0.path_and_query.clone();
+        Entrypoint(r)
+    }
+}
+
+impl std::ops::Deref for Entrypoint {
+    type Target = UriParts;
+    fn deref(&self) -> &UriParts { &self.0 }
+}
+
+fn default_port(uri: &Uri) -> u16 { if uri.scheme() == Some(&Scheme::HTTPS) { 443 } else { 80 } }
+
+/// Whether a TCP connection to the host and port of `uri` can be established within `timeout`
+async fn is_reachable(uri: &Uri, timeout: Duration) -> bool {
+    let addr = (uri.host().unwrap_or_default().to_owned(), uri.port_u16().unwrap_or_else(|| default_port(uri)));
+    matches!(tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await, Ok(Ok(_)))
+}
+
+#[test]
+fn test_failover_on_connect_error() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let cx = HdfsClientBuilder::new("http://127.0.0.1:1".parse().unwrap())
+        .alt_entrypoint("http://127.0.0.1:2".parse().unwrap())
+        .max_failovers(3)
+        .build();
+    let r = rt.block_on(cx.stat(FOState::PRIMARY, "/"));
+    let (_, fostate) = FOR::split(r);
+    assert!(fostate.is_alt());
+    assert_eq!(cx.metrics().counter(counter::FAILOVER, "127.0.0.1:1"), 2);
+    assert_eq!(cx.metrics().counter(counter::FAILOVER, "127.0.0.1:2"), 1);
+
+    let cx = HdfsClientBuilder::new("http://127.0.0.1:1".parse().unwrap())
+        .alt_entrypoint("http://127.0.0.1:2".parse().unwrap())
+        .failover_on_connect_error(false)
+        .build();
+    let _ = rt.block_on(cx.stat(FOState::PRIMARY, "/"));
+    assert_eq!(cx.metrics().total(counter::FAILOVER), 0);
+}
+
+#[cfg(test)]
+fn standby_and_active() -> (crate::mock_server::MockServer, crate::mock_server::MockServer) {
+    use crate::mock_server::*;
+    let standby = MockServer::start(|_| MockResponse::standby());
+    let active = MockServer::start(|r| if r.param("datanode").is_some() {
+        MockResponse::new(201)
+    } else {
+        MockResponse::redirect(&format!("http://{}{}&datanode=true", r.host, r.path_and_query))
+    });
+    (standby, active)
+}
+
+#[test]
+fn test_write_failover_from_standby() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (standby, active) = standby_and_active();
+    let cx = HdfsClientBuilder::new(standby.uri().parse().unwrap())
+        .alt_entrypoint(active.uri().parse().unwrap())
+        .build();
+
+    let r = rt.block_on(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"0123"), CreateOptions::new()));
+    let (r, fostate) = FOR::split(r);
+    assert!(r.is_ok());
+    assert!(fostate.is_alt());
+    let r = rt.block_on(cx.append(fostate, "/f", Data::Borrowed(b"4567"), AppendOptions::new()));
+    assert!(r.is_ok());
+
+    assert_eq!(standby.requests().len(), 1);
+    let dn: Vec<_> = active.requests().into_iter().filter(|r| r.param("datanode").is_some()).collect();
+    assert_eq!(dn.len(), 2);
+    assert_eq!((dn[0].op(), &dn[0].body[..]), ("CREATE", &b"0123"[..]));
+    assert_eq!((dn[1].op(), &dn[1].body[..]), ("APPEND", &b"4567"[..]));
+}
+
+#[test]
+fn test_write_data_recovered_on_namenode_failure() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (standby, _) = standby_and_active();
+    let cx = HdfsClientBuilder::new(standby.uri().parse().unwrap()).build();
+    let r = rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"4567"), AppendOptions::new()));
+    match FOR::split(r).0 {
+        Err(ErrorD { data_opt: Some(Recovered::Data(d)), .. }) => assert_eq!(&d[..], b"4567"),
+        _ => panic!("data not recovered")
+    }
+
+    let cx = HdfsClientBuilder::new(standby.uri().parse().unwrap())
+        .recovery_policy(RecoveryPolicy::Spill { threshold: 2, dir: std::env::temp_dir() })
+        .build();
+    let r = rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"4567"), AppendOptions::new()));
+    let mut e = FOR::split(r).0.err().unwrap();
+    assert!(matches!(e.data_opt, Some(Recovered::Spilled(_))));
+    assert_eq!(&e.take_data().unwrap().unwrap()[..], b"4567");
+}
+
+#[test]
+fn test_natmap_entrypoints() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|_| MockResponse::json(200, &file_status_json(dirent_type::DIRECTORY, 0)));
+    let natmap = || NatMap::new(vec![("namenode.invalid:50070".to_owned(), nn.authority())].into_iter()).unwrap();
+    let cx = HdfsClientBuilder::new("http://namenode.invalid:50070".parse().unwrap())
+        .natmap(natmap())
+        .natmap_entrypoints(true)
+        .build();
+    let (r, _) = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/")));
+    assert_eq!(r.unwrap().file_status.type_, dirent_type::DIRECTORY);
+    assert_eq!(nn.requests().len(), 1);
+}
+
+#[test]
+fn test_concurrent_read_at() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123456789");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let (mut b1, mut b2, mut b3) = ([0u8; 4], [0u8; 4], [0u8; 4]);
+    let (r1, r2, r3) = c.rt.block_on(futures::future::join3(
+        cx.read_at(FOState::PRIMARY, "/f", 0, &mut b1),
+        cx.read_at(FOState::PRIMARY, "/f", 4, &mut b2),
+        cx.read_at(FOState::PRIMARY, "/f", 8, &mut b3)
+    ));
+    assert_eq!((r1.unwrap().0, r2.unwrap().0, r3.unwrap().0), (4, 4, 2));
+    assert_eq!((&b1, &b2, &b3[..2]), (b"0123", b"4567", &b"89"[..]));
+}
+
+#[test]
+fn test_entrypoint_validation() {
+    fn ep(uri: &str) -> Result<String> {
+        let c = HdfsClientBuilder::new(uri.parse().unwrap()).try_build()?;
+        Ok(c.endpoint_name(FOState::PRIMARY) + " " + c.entrypoint.scheme.as_ref().unwrap().as_str())
+    }
+    assert_eq!(ep("namenode:50070").unwrap(), "namenode:50070 http");
+    assert_eq!(ep("namenode").unwrap(), "namenode:9870 http");
+    assert_eq!(ep("webhdfs://namenode").unwrap(), "namenode:9870 http");
+    assert_eq!(ep("swebhdfs://namenode/").unwrap(), "namenode:9871 https");
+    // explicit http(s) entrypoints keep the standard port
+    assert_eq!(ep("http://namenode").unwrap(), "namenode http");
+    assert_eq!(ep("https://namenode/").unwrap(), "namenode https");
+    assert_eq!(ep("https://namenode:8443").unwrap(), "namenode:8443 https");
+    assert!(ep("ftp://namenode:21").is_err());
+    assert!(ep("/webhdfs").is_err());
+
+    let c = HdfsClientBuilder::new("webhdfs://nn1".parse().unwrap())
+        .alt_entrypoint("nn2".parse().unwrap())
+        .default_port(50070)
+        .try_build()
+        .unwrap();
+    assert_eq!(c.endpoint_name(FOState::PRIMARY), "nn1:50070");
+    assert_eq!(c.endpoint_name(FOState::PRIMARY.next()), "nn2:50070");
+
+    // build() does not panic on invalid entrypoints; requests fail instead
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let c = HdfsClientBuilder::new("ftp://namenode:21".parse().unwrap()).build();
+    assert!(FOR::split(rt.block_on(c.stat(FOState::PRIMARY, "/"))).0.is_err());
+    assert_eq!(HdfsClientBuilder::new("nn".parse().unwrap()).build().endpoint_name(FOState::PRIMARY), "nn:9870");
+}
+
+#[test]
+fn test_locations() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let natmap = NatMap::new(vec![(c.nn.authority(), "datanode.public:1022".to_owned())].into_iter()).unwrap();
+    let cx = c.builder().natmap(natmap).build();
+
+    let r = c.run(cx.open_location(FOState::PRIMARY, "/f", OpenOptions::new().offset(1), false));
+    let uri = r.unwrap();
+    assert_eq!(uri.authority().unwrap().as_str(), c.nn.authority());
+    assert_eq!(uri.path(), "/webhdfs/v1/f");
+    assert!(uri.query().unwrap().contains("offset=1"));
+
+    let r = c.run(cx.create_location(FOState::PRIMARY, "/g", CreateOptions::new(), true));
+    assert_eq!(r.unwrap().authority().unwrap().as_str(), "datanode.public:1022");
+    assert!(fs.get("/g").is_none());
+    assert_eq!(c.nn.requests().len(), 2);
+
+    let r = c.run(cx.create_upload_url(FOState::PRIMARY, "/g", CreateOptions::new().overwrite(true)));
+    let uri = r.unwrap();
+    assert_eq!(uri.authority().unwrap().as_str(), "datanode.public:1022");
+    assert!(uri.query().unwrap().contains("overwrite=true"));
+    assert_eq!(c.nn.requests()[2].param("noredirect"), Some("true"));
+    assert!(fs.get("/g").is_none());
+}
+
+#[test]
+fn test_dry_run() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/d/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().dry_run(true).build();
+    let before = fs.paths();
+
+    assert!(c.run(cx.delete(FOState::PRIMARY, "/d", DeleteOptions::new().recursive(true))).unwrap());
+    assert!(!c.run(cx.delete(FOState::PRIMARY, "/missing", DeleteOptions::new())).unwrap());
+    assert!(c.run(cx.rename(FOState::PRIMARY, "/d/f", "/d/g".to_owned())).unwrap());
+    assert!(c.run(cx.mkdirs(FOState::PRIMARY, "/e/f", MkdirsOptions::new())).unwrap());
+    assert!(c.run(cx.create(FOState::PRIMARY, "/d/new", Data::Borrowed(b"x"), CreateOptions::new())).is_ok());
+    assert!(c.run(cx.create(FOState::PRIMARY, "/d/f", Data::Borrowed(b"x"), CreateOptions::new())).is_err());
+    assert!(c.run(cx.create(FOState::PRIMARY, "/d/f", Data::Borrowed(b"x"), CreateOptions::new().overwrite(true))).is_ok());
+    let a = c.run(cx.append(FOState::PRIMARY, "/missing", Data::Borrowed(b"x"), AppendOptions::new()));
+    assert_eq!(&a.unwrap_err().take_data().unwrap().unwrap()[..], b"x");
+
+    assert_eq!(fs.paths(), before);
+    assert_eq!(fs.get("/d/f").unwrap().data, b"0123");
+    assert!(c.nn.requests().iter().all(|q| q.op() == "GETFILESTATUS"));
+}
+
+#[test]
+fn test_request_hook() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/d/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let sign = |method: &Method, uri: &Uri, headers: &mut http::HeaderMap| -> Result<()> {
+        let pq = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("");
+        headers.insert("X-Signature", format!("{}:{}", method, pq).parse().map_err(http::Error::from)?);
+        Ok(())
+    };
+    let cx = c.builder().request_hook(sign).build();
+
+    assert!(c.run(cx.create(FOState::PRIMARY, "/d/g", Data::Borrowed(b"x"), CreateOptions::new())).is_ok());
+    let mut input = c.run(cx.open(FOState::PRIMARY, "/d/f", OpenOptions::new())).unwrap();
+    assert_eq!(c.rt.block_on(input.next()).unwrap().unwrap(), &b"0123"[..]);
+    let requests = c.nn.requests();
+    assert_eq!(requests.len(), 4);
+    for q in &requests {
+        assert_eq!(q.header("X-Signature").unwrap(), format!("{}:{}", q.method, q.path_and_query));
+    }
+
+    let cx = c.builder()
+        .request_hook(|_: &Method, _: &Uri, _: &mut http::HeaderMap| Err(app_error!(generic "no signing key")))
+        .build();
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/d/f")).is_err());
+    assert_eq!(c.nn.requests().len(), 4);
+}
+
+#[test]
+fn test_shutdown() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/d/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+
+    let mut input = c.run(cx.open(FOState::PRIMARY, "/d/f", OpenOptions::new())).unwrap();
+    let derived = cx.with_context(&RequestContext::new());
+    assert_eq!(c.rt.block_on(cx.shutdown(Duration::from_millis(50))), 1);
+    assert!(cx.is_shut_down() && derived.is_shut_down());
+    assert!(c.rt.block_on(input.next()).unwrap().unwrap_err().is_shut_down());
+    drop(input);
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/d/f")).unwrap_err().is_shut_down());
+    assert!(c.run(derived.stat(FOState::PRIMARY, "/d/f")).unwrap_err().is_shut_down());
+    assert_eq!(c.nn.requests().len(), 2);
+
+    let cx = c.builder().build();
+    let input = c.run(cx.open(FOState::PRIMARY, "/d/f", OpenOptions::new())).unwrap();
+    let read = async move { input.map(|c| c.unwrap().len()).fold(0, |a, n| async move { a + n }).await };
+    let (aborted, len) = c.rt.block_on(futures::future::join(cx.shutdown(Duration::from_secs(5)), read));
+    assert_eq!((aborted, len), (0, 4));
+}
+
+#[test]
+fn test_strict_schema() {
+    use crate::mock_server::*;
+    use std::sync::{Arc, Mutex};
+    let nn = MockServer::start(|_| MockResponse::json(200,
+        r#"{"FileStatus":{"length":4,"type":"FILE","fileId":16386,"ecPolicy":"RS-6-3-1024k"}}"#
+    ));
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    let fs = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/f"))).0.unwrap().file_status;
+    assert_eq!((fs.length, fs.owner.as_str(), fs.modification_time), (4, "", 0));
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let s = seen.clone();
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .strict_schema(move |t: &str, f: &[String]| s.lock().unwrap().push((t.to_owned(), f.to_vec())))
+        .build();
+    assert!(FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/f"))).0.is_ok());
+    assert_eq!(*seen.lock().unwrap(), vec![("FileStatus".to_owned(), vec!["ecPolicy".to_owned(), "fileId".to_owned()])]);
+}
+
+#[test]
+fn test_connection_timers() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/f")).is_ok());
+    let m = cx.metrics();
+    for timer in &[counter::DNS, counter::CONNECT, counter::TTFB] {
+        assert_eq!(m.counter(timer, &c.nn.authority()), 1);
+        assert!(m.mean(timer, &c.nn.authority()).is_some());
+    }
+    assert_eq!(m.total(counter::TLS_HANDSHAKE), 0);
+}
+
+#[test]
+fn test_exception_classes() {
+    use crate::mock_server::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let calls = std::sync::Arc::new(AtomicUsize::new(0));
+    let c = calls.clone();
+    let nn = MockServer::start(move |_| if c.fetch_add(1, Ordering::SeqCst) < 2 {
+        MockResponse::remote_exception(403, "RetriableException", "org.apache.hadoop.ipc.RetriableException", "Namenode is starting")
+    } else {
+        MockResponse::json(200, &file_status_json(dirent_type::DIRECTORY, 0))
+    });
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    let e = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/"))).0.unwrap_err();
+    assert_eq!(e.remote_exception().unwrap().exception, "RetriableException");
+
+    calls.store(0, Ordering::SeqCst);
+    let mut conf = cx.effective_config_with_secrets();
+    conf.max_failovers = Some(2);
+    conf.exception_classes.as_mut().unwrap().insert("org.apache.hadoop.ipc.RetriableException".to_owned(), ExceptionClass::Retry);
+    let cx = HdfsClientBuilder::from_explicit_config(conf).build();
+    assert!(FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/"))).0.is_ok());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(cx.metrics().counter(counter::RETRY, &nn.authority()), 2);
+
+    let (a, b) = (
+        MockServer::start(|_| MockResponse::standby()), 
+        MockServer::start(|_| MockResponse::json(200, &file_status_json(dirent_type::DIRECTORY, 0)))
+    );
+    let cx = HdfsClientBuilder::new(a.uri().parse().unwrap()).alt_entrypoint(b.uri().parse().unwrap())
+        .exception_class("StandbyException", ExceptionClass::Fail)
+        .build();
+    assert!(FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/"))).0.is_err());
+    assert!(b.requests().is_empty());
+}
+
+#[test]
+fn test_failover_backoff() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let cx = HdfsClientBuilder::new("http://127.0.0.1:1".parse().unwrap())
+        .alt_entrypoint("http://127.0.0.1:2".parse().unwrap())
+        .max_failovers(3)
+        .failover_backoff(Duration::from_millis(40), Duration::from_millis(60))
+        .build();
+    assert_eq!(
+        (1..=4).map(|a| cx.failover_delay(a).as_millis()).collect::<Vec<_>>(),
+        vec![0, 40, 60, 60]
+    );
+    let t = std::time::Instant::now();
+    let (r, _) = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/")));
+    assert!(t.elapsed() >= Duration::from_millis(100));
+    let e = r.unwrap_err();
+    let failures = e.namenode_failures().unwrap();
+    let endpoints: Vec<_> = failures.iter().map(|(ep, _)| ep.as_str()).collect();
+    assert_eq!(endpoints, vec!["127.0.0.1:1", "127.0.0.1:2", "127.0.0.1:1", "127.0.0.1:2"]);
+    assert!(failures.iter().all(|(_, e)| HdfsClient::is_connect_error(e)));
+
+    let cx = HdfsClientBuilder::new("http://127.0.0.1:1".parse().unwrap()).build();
+    let (r, _) = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/")));
+    assert!(r.unwrap_err().namenode_failures().is_none());
+}
+
+#[test]
+fn test_no_failover_on_datanode_error() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (a, b) = (
+        MockServer::start(|_| MockResponse::redirect("http://127.0.0.1:1/webhdfs/v1/f?op=OPEN")),
+        MockServer::start(|_| MockResponse::binary(b"data"))
+    );
+    let cx = HdfsClientBuilder::new(a.uri().parse().unwrap()).alt_entrypoint(b.uri().parse().unwrap()).build();
+    let e = FOR::split(rt.block_on(cx.open(FOState::PRIMARY, "/f", OpenOptions::new()))).0.err().unwrap();
+    assert!(e.is_datanode_error());
+    assert!(e.namenode_failures().is_none());
+    assert!(b.requests().is_empty());
+    assert!(!cx.is_failover_error(&e, 0));
+}
+
+#[test]
+fn test_stats() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (standby, active) = standby_and_active();
+    let cx = HdfsClientBuilder::new(standby.uri().parse().unwrap())
+        .alt_entrypoint(active.uri().parse().unwrap())
+        .build();
+    assert_eq!(cx.stats().active_endpoint, standby.authority());
+    assert_eq!(cx.stats().mean_latency, None);
+
+    let (r, _) = FOR::split(rt.block_on(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"0123"), CreateOptions::new())));
+    assert!(r.is_ok());
+    let s = cx.stats();
+    assert_eq!(s.active_endpoint, active.authority());
+    assert_eq!((s.requests, s.failovers, s.retries, s.bytes_out), (3, 1, 1, 4));
+    assert!(s.bytes_in > 0);
+    assert!(s.mean_latency.is_some());
+}
+
+#[test]
+fn test_raw_query_param() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|_| MockResponse::json(200, &file_status_json(dirent_type::DIRECTORY, 0)));
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .raw_query_param("gw.token", "a%2Fb")
+        .build();
+    assert!(FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/"))).0.is_ok());
+    assert!(nn.requests()[0].path_and_query.ends_with("?op=GETFILESTATUS&gw.token=a%2Fb"));
+}
+
+#[test]
+fn test_user_agent() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().client_id("etl-job").build();
+    assert!(c.run(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"x"), CreateOptions::new())).is_ok());
+    let rs = c.nn.requests();
+    assert!(rs.len() >= 2);
+    assert!(rs.iter().all(|r| r.header("User-Agent") == Some(DEFAULT_USER_AGENT)));
+    assert_eq!(rs[0].param("client.id"), Some("etl-job"));
+
+    let cx = c.builder().user_agent("my-app/1.0").build();
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/f")).is_ok());
+    let r = c.nn.requests().pop().unwrap();
+    assert_eq!(r.header("User-Agent"), Some("my-app/1.0"));
+    assert_eq!(r.param("client.id"), None);
+
+    let cx = c.builder().user_agent("").build();
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/f")).is_ok());
+    assert_eq!(c.nn.requests().pop().unwrap().header("User-Agent"), None);
+    assert!(c.builder().user_agent("a\nb").try_build().is_err());
+}
+
+#[test]
+fn test_default_timeout() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|_| {
+        std::thread::sleep(Duration::from_millis(300));
+        MockResponse::json(200, &file_status_json(dirent_type::DIRECTORY, 0))
+    });
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .default_timeout(Duration::from_millis(50))
+        .build();
+    let e = FOR::split(rt.block_on(cx.stat(FOState::PRIMARY, "/"))).0.unwrap_err();
+    assert_eq!(e.io_kind(), std::io::ErrorKind::TimedOut);
+    let r = rt.block_on(cx.with_timeout(Duration::from_secs(5), cx.stat(FOState::PRIMARY, "/")));
+    assert!(FOR::split(r).0.is_ok());
+
+    //async requests are not limited by default
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    assert_eq!(cx.rest.timeout, None);
+    assert_eq!(cx.effective_config().default_timeout, None);
+    assert_eq!(cx.default_timeout(), Duration::from_secs(HdfsClientBuilder::DEFAULT_TIMEOUT_S));
+}
+
+#[test]
+fn test_get_to() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123456789");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let mut out = vec![];
+    let n = c.run(cx.get_to(FOState::PRIMARY, "/f", &mut out));
+    assert_eq!(n.unwrap(), 10);
+    assert_eq!(out, b"0123456789");
+    assert!(c.run(cx.get_to(FOState::PRIMARY, "/missing", &mut out)).is_err());
+}
+
+#[test]
+fn test_put_from() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().upload_chunk_size(4).build();
+    let put = |path, data: &'static [u8]| c.run(cx.put_from(FOState::PRIMARY, path, data, CreateOptions::new()));
+    assert_eq!(put("/f", b"0123456789").unwrap(), 10);
+    assert_eq!(fs.get("/f").unwrap().data, b"0123456789");
+    let ops = c.nn.requests().iter().filter(|r| r.param("datanode").is_none()).map(|r| r.op().to_owned()).collect::<Vec<_>>();
+    assert_eq!(ops, vec!["CREATE", "APPEND", "APPEND"]);
+    assert_eq!(put("/g", b"01234567").unwrap(), 8);
+    assert_eq!(fs.get("/g").unwrap().data, b"01234567");
+    assert_eq!(put("/empty", b"").unwrap(), 0);
+    assert_eq!(fs.get("/empty").unwrap().data, b"");
+}
+
+#[test]
+fn test_datanode_auth() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let header = |name: &'static str| move |_: &Method, _: &Uri, headers: &mut http::HeaderMap| -> Result<()> {
+        headers.insert(name, http::HeaderValue::from_static("1"));
+        Ok(())
+    };
+    let cx = c.builder()
+        .delegation_token("nn-token".to_owned())
+        .datanode_delegation_token("dn token".to_owned())
+        .request_hook(header("X-NN"))
+        .datanode_request_hook(header("X-DN"))
+        .build();
+    assert!(c.run(cx.create(FOState::PRIMARY, "/g", Data::Borrowed(b"x"), CreateOptions::new())).is_ok());
+    let mut input = c.run(cx.open(FOState::PRIMARY, "/f", OpenOptions::new())).unwrap();
+    assert_eq!(c.rt.block_on(input.next()).unwrap().unwrap(), &b"0123"[..]);
+    let requests = c.nn.requests();
+    assert_eq!(requests.len(), 4);
+    for q in &requests {
+        let dn = q.param("datanode").is_some();
+        assert_eq!(q.param("delegation"), Some(if dn { "dn%20token" } else { "nn-token" }));
+        assert_eq!(q.path_and_query.matches("delegation=").count(), 1);
+        assert_eq!((q.header("X-NN").is_some(), q.header("X-DN").is_some()), (!dn, dn));
+    }
+}
+
+#[test]
+fn test_redirect_policy() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let dn = fs.start();
+    let dn_uri = dn.uri();
+    let gw = MockServer::start(move |r| MockResponse::redirect(&format!("{}{}&datanode=true", dn_uri, r.path_and_query)));
+    let gw_uri = gw.uri();
+    let nn = MockServer::start(move |r| MockResponse::redirect(&format!("{}{}", gw_uri, r.path_and_query)));
+    let read = |policy: RedirectPolicy| {
+        let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).redirect_policy(policy).build();
+        rt.block_on(async {
+            let (s, _) = FOR::split(cx.open(FOState::PRIMARY, "/f", OpenOptions::new()).await);
+            let mut s = s?;
+            let mut data: Vec<u8> = vec![];
+            while let Some(chunk) = s.next().await { data.extend(&chunk?[..]) }
+            Ok::<_, Error>(data)
+        })
+    };
+    assert!(read(RedirectPolicy::new()).unwrap_err().to_string().contains("Too many redirects"));
+    assert_eq!(read(RedirectPolicy::new().max_hops(2)).unwrap(), b"0123");
+    assert!(read(RedirectPolicy::new().max_hops(2).allowed_host("*.internal")).unwrap_err().to_string().contains("not allowed"));
+    assert_eq!(read(RedirectPolicy::new().max_hops(2).allowed_host("127.0.0.1")).unwrap(), b"0123");
+}
+
+#[test]
+fn test_slow_read_policy() {
+    use crate::mock_server::*;
+    use crate::metrics::{Metrics, counter};
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let data: &'static [u8] = b"0123456789";
+    let serve = move |r: &MockRequest| MockResponse::binary(&data[r.param("offset").and_then(|o| o.parse().ok()).unwrap_or(0)..]);
+    let slow = MockServer::start(move |r| serve(r).pause(2, Duration::from_millis(500)));
+    let fast = MockServer::start(serve);
+    let (slow_uri, fast_uri) = (slow.uri(), fast.uri());
+    let nn = MockServer::start(move |r| {
+        let dn = if r.param("excludedatanodes").is_some() { &fast_uri } else { &slow_uri };
+        MockResponse::redirect(&format!("{}{}", dn, r.path_and_query))
+    });
+    let read = |action: SlowReadAction| {
+        let metrics = std::sync::Arc::new(Metrics::new());
+        let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+            .metrics(metrics.clone())
+            .slow_read_policy(SlowReadPolicy::new(1000, Duration::from_millis(100)).action(action))
+            .build();
+        let data = rt.block_on(async {
+            let (s, _) = FOR::split(cx.open(FOState::PRIMARY, "/f", OpenOptions::new()).await);
+            let mut s = s.unwrap();
+            let mut data: Vec<u8> = vec![];
+            while let Some(chunk) = s.next().await { data.extend(&chunk.unwrap()[..]) }
+            data
+        });
+        (data, metrics.counter(counter::SLOW_READ, &slow.authority()))
+    };
+    let (d, n) = read(SlowReadAction::Warn);
+    assert_eq!(d, data);
+    assert!(n >= 1);
+    assert_eq!(nn.requests().len(), 1);
+
+    let (d, n) = read(SlowReadAction::Reconnect);
+    assert_eq!(d, data);
+    assert_eq!(n, 1);
+    let r = nn.requests();
+    assert_eq!(r.len(), 3);
+    assert_eq!((r[2].param("offset"), r[2].param("excludedatanodes")), (Some("2"), Some("127.0.0.1")));
+
+    //a slow reader does not make the datanode slow
+    let fast_uri = fast.uri();
+    let nn = MockServer::start(move |r| MockResponse::redirect(&format!("{}{}", fast_uri, r.path_and_query)));
+    let metrics = std::sync::Arc::new(Metrics::new());
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .metrics(metrics.clone())
+        .slow_read_policy(SlowReadPolicy::new(1000, Duration::from_millis(100)))
+        .build();
+    let d = rt.block_on(async {
+        let mut s = FOR::split(cx.open(FOState::PRIMARY, "/f", OpenOptions::new()).await).0.unwrap();
+        let mut data: Vec<u8> = vec![];
+        while let Some(chunk) = s.next().await {
+            data.extend(&chunk.unwrap()[..]);
+            tokio::time::sleep(Duration::from_millis(300)).await
+        }
+        data
+    });
+    assert_eq!(d, data);
+    assert_eq!(metrics.counter(counter::SLOW_READ, &fast.authority()), 0);
+}
+
+#[test]
+fn test_endpoint_resolver() {
+    use std::sync::Mutex;
+    use futures::future::BoxFuture;
+    struct TestResolver(Mutex<Vec<Result<Vec<Uri>>>>);
+    impl EndpointResolver for TestResolver {
+        fn resolve(&self) -> BoxFuture<'_, Result<Vec<Uri>>> {
+            let r = self.0.lock().unwrap().remove(0);
+            Box::pin(async move { r })
+        }
+    }
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (standby, active) = standby_and_active();
+    let resolver = TestResolver(Mutex::new(vec![
+        Ok(vec![standby.uri().parse().unwrap()]),
+        Ok(vec![standby.uri().parse().unwrap(), active.uri().parse().unwrap()]),
+        Err(app_error!(generic "discovery unavailable"))
+    ]));
+    let cx = HdfsClientBuilder::new("http://unused:1".parse().unwrap()).endpoint_resolver(resolver).build();
+
+    let (r, fostate) = FOR::split(rt.block_on(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"0123"), CreateOptions::new())));
+    assert!(r.is_ok() && fostate.is_alt());
+    assert_eq!(cx.endpoint_name(fostate), active.authority());
+    //not re-resolved without failover
+    let (r, _) = FOR::split(rt.block_on(cx.append(fostate, "/f", Data::Borrowed(b"4567"), AppendOptions::new())));
+    assert!(r.is_ok());
+    assert_eq!(standby.requests().len(), 1);
+    //failover re-resolves; resolution failure keeps the previous entrypoints
+    let (r, fostate) = FOR::split(rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"89"), AppendOptions::new())));
+    assert!(r.is_ok() && fostate.is_alt());
+    assert_eq!(standby.requests().len(), 2);
+    assert_eq!(cx.resolved.read().unwrap().len(), 2);
+}
+
+#[test]
+fn test_effective_config() {
+    let mut https = HttpsConfig::new();
+    https.danger_accept_invalid_certs = Some(true);
+    https.identity_password = Some("pw".to_owned());
+    let cx = HdfsClientBuilder::new("nn1".parse().unwrap())
+        .alt_entrypoint("https://nn2:9000".parse().unwrap())
+        .user_name("u".to_owned())
+        .delegation_token("token".to_owned())
+        .natmap(NatMap::new(vec![("nn1:9870".to_owned(), "localhost:19870".to_owned())].into_iter()).unwrap())
+        .https_settings(https.into())
+        .max_failovers(3)
+        .default_timeout(Duration::from_secs(5))
+        .tcp_keepalive(Duration::from_secs(30), Duration::from_secs(10))
+        .dns_cache(Duration::from_secs(60), 100)
+        .build();
+
+    let c = cx.effective_config();
+    assert_eq!(c.entrypoint.into_uri(), "http://nn1:9870/");
+    assert_eq!(c.alt_entrypoint.unwrap().into_uri(), "https://nn2:9000/");
+    assert_eq!(c.user_name.as_deref(), Some("u"));
+    assert_eq!(c.dt.as_deref(), Some(REDACTED));
+    assert_eq!(c.default_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(c.max_failovers, Some(3));
+    assert_eq!(c.natmap.unwrap()["nn1:9870"], "localhost:19870");
+    let h = c.https_config.unwrap();
+    assert_eq!((h.danger_accept_invalid_certs, h.identity_password.as_deref()), (Some(true), Some(REDACTED)));
+
+    let c = cx.effective_config_with_secrets();
+    assert_eq!(c.dt.as_deref(), Some("token"));
+    let c: Config = toml::from_str(&config_to_toml(&c).unwrap()).unwrap();
+    assert_eq!(c.https_config.as_ref().unwrap().identity_password.as_deref(), Some("pw"));
+    let cx = HdfsClientBuilder::from_explicit_config(c).build();
+    assert_eq!(cx.effective_config().entrypoint.into_uri(), "http://nn1:9870/");
+    assert_eq!(cx.rest.tcp_keepalive, Some((Duration::from_secs(30), Duration::from_secs(10))));
+    let dns_cache = cx.rest.dns_cache.as_ref().unwrap();
+    assert_eq!((dns_cache.ttl(), dns_cache.max_entries()), (Duration::from_secs(60), 100));
+}
+
+#[test]
+fn test_write_strategy_no_redirect() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|r| if r.param("datanode").is_some() {
+        MockResponse::new(if r.op() == "CREATE" { 201 } else { 200 })
+    } else if r.param("noredirect") == Some("true") {
+        MockResponse::json(200, &format!(r#"{{"Location":"http://{}{}&datanode=true"}}"#, r.host, r.path_and_query))
+    } else {
+        MockResponse::new(403)
+    });
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    let r = rt.block_on(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"01"), CreateOptions::new()));
+    assert!(FOR::split(r).0.is_err());
+
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).write_strategy(WriteStrategy::NoRedirect).build();
+    let r = rt.block_on(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"01"), CreateOptions::new()));
+    assert!(FOR::split(r).0.is_ok());
+    let r = rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"23"), AppendOptions::new()));
+    assert!(FOR::split(r).0.is_ok());
+    let dn: Vec<_> = nn.requests().into_iter().filter(|r| r.param("datanode").is_some()).collect();
+    assert_eq!(dn.len(), 2);
+    assert_eq!((dn[0].op(), &dn[0].body[..]), ("CREATE", &b"01"[..]));
+    assert_eq!((dn[1].op(), &dn[1].body[..]), ("APPEND", &b"23"[..]));
+}
+
+#[test]
+fn test_resume_to() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123456789");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let expected = c.run(cx.stat(FOState::PRIMARY, "/f")).unwrap().file_status;
+    let mut out = b"0123".to_vec();
+    let n = c.run(cx.resume_to(FOState::PRIMARY, "/f", 4, &expected, &mut out));
+    assert_eq!(n.unwrap(), 6);
+    assert_eq!(out, b"0123456789");
+
+    fs.update("/f", |e| e.mtime += 1);
+    let mut out = vec![];
+    let e = c.run(cx.resume_to(FOState::PRIMARY, "/f", 4, &expected, &mut out)).unwrap_err();
+    let sc = e.source_changed().unwrap();
+    assert_eq!((sc.expected_length, sc.actual_length), (10, 10));
+    assert_eq!(sc.actual_modification_time, sc.expected_modification_time + 1);
+    assert!(out.is_empty());
+    assert!(c.run(cx.resume_to(FOState::PRIMARY, "/missing", 0, &expected, &mut out)).unwrap_err().source_changed().is_none());
+}
+
+#[test]
+fn test_shared_rate_limiter() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0");
+    let c = FakeCluster::start(&fs);
+    let limiter = RateLimiter::new(20.0, 1);
+    let cx1 = c.builder().rate_limiter(limiter.clone()).build();
+    let cx2 = c.builder().rate_limiter(limiter.clone()).build();
+    let t = std::time::Instant::now();
+    c.rt.block_on(async {
+        for _ in 0..3 {
+            let (r1, r2) = futures::join!(cx1.stat(FOState::PRIMARY, "/f"), cx2.stat(FOState::PRIMARY, "/f"));
+            assert!(r1.is_ok() && r2.is_ok());
+        }
+    });
+    //6 requests, the first one from the initial burst
+    assert!(t.elapsed() >= Duration::from_millis(240), "{:?}", t.elapsed());
+    assert!(!limiter.try_acquire());
+}
+
+#[test]
+fn test_append_lease_retry() {
+    use crate::mock_server::*;
+    use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0");
+    //the first `conflicts` datanode APPENDs fail
+    let conflicts = Arc::new(AtomicU32::new(0));
+    let (f, c) = (fs.clone(), conflicts.clone());
+    let nn = MockServer::start(move |r| {
+        if r.op() == "APPEND" && r.param("datanode").is_some() && c.load(Ordering::SeqCst) > 0 {
+            c.fetch_sub(1, Ordering::SeqCst);
+            MockResponse::remote_exception(403, "AlreadyBeingCreatedException", 
+                "org.apache.hadoop.hdfs.protocol.AlreadyBeingCreatedException",
+                "Failed to APPEND_FILE /f for DFSClient_1 on 127.0.0.1 because this file lease is currently owned by DFSClient_2 on 10.0.0.2")
+        } else {
+            f.handle(r)
+        }
+    });
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+    conflicts.store(1, Ordering::SeqCst);
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    let e = rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"1"), AppendOptions::new())).unwrap_err().0.error;
+    assert!(e.is_lease_conflict());
+    let lc = e.lease_conflict().unwrap();
+    assert_eq!((lc.path.as_str(), lc.holder.as_deref(), lc.attempts), ("/f", Some("DFSClient_2 on 10.0.0.2"), 1));
+    assert_eq!(std::io::Error::from(e).kind(), std::io::ErrorKind::AlreadyExists);
+
+    conflicts.store(2, Ordering::SeqCst);
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .lease_retry(2, Duration::from_millis(10), Duration::from_millis(20))
+        .build();
+    let t = std::time::Instant::now();
+    rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Owned(b"12".to_vec()), AppendOptions::new())).map_err(|(e, _)| ErrorD::drop(e)).unwrap();
+    assert!(t.elapsed() >= Duration::from_millis(30));
+    assert_eq!(fs.get("/f").unwrap().data, b"012");
+    assert_eq!(nn.requests().iter().filter(|r| r.op() == "GETFILESTATUS").count(), 2);
+
+    conflicts.store(3, Ordering::SeqCst);
+    let e = rt.block_on(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"3"), AppendOptions::new())).unwrap_err().0.error;
+    assert_eq!(e.lease_conflict().unwrap().attempts, 3);
+    assert_eq!(fs.get("/f").unwrap().data, b"012");
+}
+
+#[test]
+fn test_dir_stream() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/d/a", b"1");
+    fs.put("/d/b", b"22");
+    fs.mkdirs("/d/c");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let entries = c.run(cx.dir_stream(FOState::PRIMARY, "/d")).unwrap();
+    let mut entries: Vec<_> = c.rt.block_on(entries.map(|e| e.map(|e| (e.path_suffix, e.length))).collect::<Vec<_>>())
+        .into_iter().collect::<Result<_>>().unwrap();
+    entries.sort();
+    assert_eq!(entries, vec![("a".to_owned(), 1), ("b".to_owned(), 2), ("c".to_owned(), 0)]);
+    assert!(c.run(cx.dir_stream(FOState::PRIMARY, "/none")).is_err());
+}
+
+#[test]
+fn test_dir_filtered() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/d/a.csv", b"1");
+    fs.put("/d/b.csv", b"22");
+    fs.put("/d/c.txt", b"333");
+    fs.mkdirs("/d/e.csv");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let names = |filter| {
+        let entries = c.run(cx.dir_filtered(FOState::PRIMARY, "/d", filter)).unwrap();
+        let mut names: Vec<_> = c.rt.block_on(entries.map(|e| e.map(|e| e.path_suffix)).collect::<Vec<_>>())
+            .into_iter().collect::<Result<_>>().unwrap();
+        names.sort();
+        names
+    };
+    assert_eq!(names(FileFilter::new()), vec!["a.csv", "b.csv", "c.txt", "e.csv"]);
+    assert_eq!(names(FileFilter::new().glob("*.csv")), vec!["a.csv", "b.csv", "e.csv"]);
+    assert_eq!(names(FileFilter::new().glob("*.csv").type_(dirent_type::FILE)), vec!["a.csv", "b.csv"]);
+    assert_eq!(names(FileFilter::new().min_size(2)), vec!["b.csv", "c.txt"]);
+    assert!(names(FileFilter::new().modified_before(0)).is_empty());
+    assert_eq!(names(FileFilter::new().modified_after(0).type_(dirent_type::DIRECTORY)), vec!["e.csv"]);
+}
+
+#[test]
+fn test_default_options() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.mkdirs("/d");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder()
+        .default_create_options(CreateOptions::new().replication(2).buffersize(4096))
+        .default_append_options(AppendOptions::new().buffersize(1024))
+        .default_delete_options(DeleteOptions::new().recursive(true))
+        .build();
+    assert!(c.run(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"0"), CreateOptions::new().overwrite(true))).is_ok());
+    assert!(c.run(cx.create(FOState::PRIMARY, "/g", Data::Borrowed(b"0"), CreateOptions::new().replication(3))).is_ok());
+    assert!(c.run(cx.append(FOState::PRIMARY, "/f", Data::Borrowed(b"1"), AppendOptions::new())).is_ok());
+    assert!(c.run(cx.delete(FOState::PRIMARY, "/d", DeleteOptions::new())).unwrap());
+    let requests: Vec<_> = c.nn.requests().into_iter().filter(|r| r.param("datanode").is_none()).collect();
+    let params = |i: usize, names: &[&str]| names.iter().map(|n| requests[i].param(n).map(str::to_owned)).collect::<Vec<_>>();
+    let s = |v: &str| Some(v.to_owned());
+    assert_eq!(params(0, &["overwrite", "replication", "buffersize"]), vec![s("true"), s("2"), s("4096")]);
+    assert_eq!(params(1, &["replication", "buffersize"]), vec![s("3"), s("4096")]);
+    assert_eq!(requests[1].path_and_query.matches("replication=").count(), 1);
+    assert_eq!(params(2, &["buffersize"]), vec![s("1024")]);
+    assert_eq!(params(3, &["recursive"]), vec![s("true")]);
+
+    let cx = c.builder().deny_recursive_delete(true).build();
+    assert!(c.run(cx.delete(FOState::PRIMARY, "/f", DeleteOptions::new().recursive(true))).is_err());
+    assert!(c.run(cx.delete(FOState::PRIMARY, "/f", DeleteOptions::new())).unwrap());
+}
+
+#[test]
+fn test_create_flag() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let opts = CreateOptions::new().create_flag(CreateFlag::CREATE | CreateFlag::OVERWRITE | CreateFlag::SYNC_BLOCK);
+    assert!(c.run(cx.create(FOState::PRIMARY, "/h", Data::Borrowed(b"0"), opts)).is_ok());
+    let r = c.nn.requests().into_iter().find(|r| r.op() == "CREATE").unwrap();
+    assert_eq!(crate::path::decode(r.param("createflag").unwrap()).unwrap(), "create,overwrite,sync_block");
+    assert_eq!(fs.get("/h").unwrap().data, b"0");
+}
+
+#[test]
+fn test_copy_within_cluster() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/a", b"data");
+    fs.put("/x", b"old");
+    fs.mkdirs("/d");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    let copy = |from, to, move_source| c.run(
+        cx.copy_within_cluster(FOState::PRIMARY, from, to, CreateOptions::new().overwrite(true), move_source));
+
+    let r = copy("/a", "/b", false).unwrap();
+    assert_eq!(r, ClusterCopy { strategy: CopyStrategy::ReadWrite, bytes: 4 });
+    assert_eq!(fs.get("/b").unwrap().data, b"data");
+    assert!(fs.get("/a").is_some());
+
+    let r = copy("/a", "/c", true).unwrap();
+    assert_eq!(r, ClusterCopy { strategy: CopyStrategy::Rename, bytes: 4 });
+    assert_eq!(fs.get("/c").unwrap().data, b"data");
+    assert!(fs.get("/a").is_none());
+
+    let r = copy("/c", "/x", true).unwrap();
+    assert_eq!(r.strategy, CopyStrategy::ReadWrite);
+    assert_eq!(fs.get("/x").unwrap().data, b"data");
+    assert!(fs.get("/c").is_some());
+
+    // the source is not moved into an existing directory
+    fs.mkdirs("/target");
+    assert_ne!(copy("/c", "/target", true).ok().map(|c| c.strategy), Some(CopyStrategy::Rename));
+    assert!(fs.get("/c").is_some() && fs.get("/target/c").is_none());
+    assert!(c.nn.requests().iter().filter(|r| r.op() == "RENAME").all(|r| r.param("renameoptions") == Some("NONE")));
+
+    assert!(copy("/d", "/e", true).is_err());
+    assert!(copy("/none", "/e", true).is_err());
+}
+
+#[test]
+fn test_detect_nat() {
+    use crate::mock_server::*;
+    use std::sync::{Arc, atomic::{AtomicU16, Ordering}};
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let port = Arc::new(AtomicU16::new(0));
+    let p = port.clone();
+    let nn = MockServer::start(move |r| if r.param("datanode").is_some() {
+        MockResponse::binary(b"x")
+    } else {
+        MockResponse::redirect(&format!("http://datanode.invalid:{}{}?op=OPEN&datanode=1", p.load(Ordering::SeqCst), r.path()))
+    });
+    port.store(nn.authority().rsplit(':').next().unwrap().parse().unwrap(), Ordering::SeqCst);
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).build();
+    let timeout = Duration::from_secs(5);
+    let (from, to) = FOR::split(rt.block_on(cx.detect_nat(FOState::PRIMARY, "/f", timeout))).0.unwrap().unwrap();
+    assert_eq!((from.as_str(), to.as_str()), (format!("datanode.invalid:{}", port.load(Ordering::SeqCst)).as_str(), nn.authority().as_str()));
+    let cx = cx.with_natmap_entry(from, to).unwrap();
+    assert_eq!(FOR::split(rt.block_on(cx.detect_nat(FOState::PRIMARY, "/f", timeout))).0.unwrap(), None);
+    let mut input = FOR::split(rt.block_on(cx.open(FOState::PRIMARY, "/f", OpenOptions::new()))).0.unwrap();
+    assert_eq!(rt.block_on(input.next()).unwrap().unwrap(), &b"x"[..]);
+}
+
+#[test]
+fn test_request_context() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder()
+        .user_name("svc".to_owned())
+        .doas("alice".to_owned())
+        .datanode_delegation_token("dn-token".to_owned())
+        .build();
+    let auth = |r: &MockRequest| (r.param("user.name").map(str::to_owned), r.param("doas").map(str::to_owned), r.param("delegation").map(str::to_owned));
+    let s = |v: &str| Some(v.to_owned());
+
+    assert!(c.run(cx.as_user("bob").stat(FOState::PRIMARY, "/f")).is_ok());
+    assert_eq!(auth(&c.nn.requests()[0]), (s("bob"), None, None));
+
+    let view = cx.with_context(&RequestContext::new().doas("carol").delegation_token("t"));
+    let mut input = c.run(view.open(FOState::PRIMARY, "/f", OpenOptions::new())).unwrap();
+    assert_eq!(c.rt.block_on(input.next()).unwrap().unwrap(), &b"0123"[..]);
+    let requests = c.nn.requests();
+    assert_eq!(auth(&requests[1]), (None, s("carol"), s("t")));
+    assert_eq!(auth(&requests[2]).2, s("t"));
+
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/f")).is_ok());
+    assert_eq!(auth(&c.nn.requests()[3]), (s("svc"), s("alice"), None));
+}
+
+#[test]
+fn test_circuit_breaker_requests() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let dead = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+    let metrics = metrics_ptr(Metrics::new());
+    let cx = HdfsClientBuilder::new(format!("http://{}", dead).parse().unwrap())
+        .circuit_breaker(breaker.clone())
+        .metrics(metrics.clone())
+        .build();
+    let e = c.run(cx.stat(FOState::PRIMARY, "/")).unwrap_err();
+    assert!(!e.is_circuit_open());
+    assert_eq!(breaker.state(&dead), crate::circuit_breaker::CircuitState::Open);
+    let e = c.run(cx.stat(FOState::PRIMARY, "/")).unwrap_err();
+    assert!(e.is_circuit_open());
+    assert_eq!(metrics.counter(counter::CIRCUIT_OPEN, &dead), 1);
+
+    //an open circuit fails over to the other namenode
+    let cx = HdfsClientBuilder::new(format!("http://{}", dead).parse().unwrap())
+        .alt_entrypoint(c.nn.uri().parse().unwrap())
+        .circuit_breaker(breaker.clone())
+        .build();
+    assert!(c.run(cx.stat(FOState::PRIMARY, "/")).is_ok());
+    assert_eq!(breaker.state(&c.nn.authority()), crate::circuit_breaker::CircuitState::Closed);
+}
+
+#[test]
+fn test_body_size_limits() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let f = fs.clone();
+    let nn = MockServer::start(move |r| match r.path() {
+        "/webhdfs/v1/huge" => MockResponse::json(200, &format!("{{\"FileStatus\":{{\"pathSuffix\":\"{}\"}}}}", "x".repeat(5000))),
+        "/webhdfs/v1/error" => MockResponse::json(500, &"x".repeat(5000)),
+        _ => f.handle(r)
+    });
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap()).max_response_body_size(1000).max_request_body_size(4).build();
+    macro_rules! r { ($f:expr) => { FOR::split(rt.block_on($f)).0 } }
+    assert!(r!(cx.stat(FOState::PRIMARY, "/f")).is_ok());
+    let e = r!(cx.stat(FOState::PRIMARY, "/huge")).unwrap_err();
+    assert!(e.is_response_too_large());
+    assert_eq!(e.io_kind(), std::io::ErrorKind::InvalidData);
+    assert!(r!(cx.stat(FOState::PRIMARY, "/error")).unwrap_err().is_response_too_large());
+
+    assert!(r!(cx.create(FOState::PRIMARY, "/g", Data::Borrowed(b"0123"), CreateOptions::new())).is_ok());
+    let sent = nn.requests().len();
+    let mut e = r!(cx.create(FOState::PRIMARY, "/h", Data::Borrowed(b"01234"), CreateOptions::new())).unwrap_err();
+    assert!(e.error.is_request_too_large());
+    assert_eq!(&e.take_data().unwrap().unwrap()[..], b"01234");
+    assert_eq!(nn.requests().len(), sent);
+    assert!(fs.get("/h").is_none());
+}
+
+#[test]
+fn test_path_arguments() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/a", b"0");
+    let c = FakeCluster::start(&fs);
+    let cx = c.builder().build();
+    assert!(c.run(cx.rename(FOState::PRIMARY, "/a", "b".to_owned())).is_err());
+    assert!(c.run(cx.rename2(FOState::PRIMARY, "/a", "/x/../b".to_owned(), false)).is_err());
+    assert!(c.run(cx.concat(FOState::PRIMARY, "/a", vec!["/b\0".to_owned()])).is_err());
+    assert!(c.nn.requests().is_empty());
+    assert!(c.run(cx.rename(FOState::PRIMARY, "/a", "/b".to_owned())).unwrap());
 }
@@ -6,19 +6,33 @@ fn main() {
     use std::fs::create_dir_all;
     use commandline::*;
     let (mut client, op) = parse_command_line();
+    interrupt::install();
 
     match op {
         Operation::Get(mut fs) => {
+            let mut get = |input: &str, output: &Path| {
+                let mut out = File::create(output).expect2("Could not create output file");
+                let r = interrupt::transfer(Some(output), || client.get_file(input, &mut interrupt::Checked(&mut out)));
+                if let Err(e) = r {
+                    if interrupt::take() {
+                        let _ = std::fs::remove_file(output);
+                        interrupted_exit(&format!("get interrupted, partial file {} removed", output.display()))
+                    }
+                    Err::<(), _>(e).expect2("get error")
+                }
+                if json_output() {
+                    let length = out.metadata().map(|m| m.len()).unwrap_or_default();
+                    emit(serde_json::json!({"type": "get", "path": input, "local_path": output, "length": length}))
+                }
+            };
             match &fs[..] {
                 &[ref input] => {
                     let input_path = Path::new(input);
                     let output = input_path.file_name().expect2("file name must be specified if no output file is given");
-                    let mut out = File::create(&output).expect2("Could not create output file");
-                    client.get_file(&input, &mut out).expect2("get error")
+                    get(input, Path::new(output))
                 }
                 &[ref input, ref output] => {
-                    let mut out = File::create(&output).expect2("Could not create output file");
-                    client.get_file(&input, &mut out).expect2("get error")
+                    get(input, Path::new(output))
                 }
                 _ => {
                     let target_dir_ = fs.pop().unwrap();
@@ -28,23 +42,82 @@ fn main() {
                         let input_path = Path::new(&input);
                         let output_file = input_path.file_name().expect2("file name must be specified if no output file is given");
                         let output = target_dir.join(&Path::new(output_file));
-                        let mut out = File::create(&output).expect2("Could not create output file");
-                        client.get_file(&input, &mut out).expect2("get error")
+                        get(&input, &output)
                     }
                     
                 }
             }
         }
+        Operation::Diff(a, b, other, mode) => {
+            let other = other.as_ref().unwrap_or(&client);
+            let diffs = compare::compare_sync(&client, &a, other, &b, mode, 8).expect2("diff error");
+            for d in &diffs {
+                if json_output() {
+                    let (change, path) = match d {
+                        compare::Difference::Added(p) => ("added", p),
+                        compare::Difference::Removed(p) => ("removed", p),
+                        compare::Difference::Changed(p) => ("changed", p)
+                    };
+                    emit(serde_json::json!({"type": "diff", "change": change, "path": path}))
+                } else {
+                    println!("{}", d)
+                }
+            }
+            if !diffs.is_empty() {
+                std::process::exit(1)
+            }
+        }
+        Operation::Inventory(root, format) => {
+            let stdout = std::io::stdout();
+            inventory::write_inventory(&mut client, &root, format, &mut stdout.lock()).expect2("inventory error");
+        }
+        Operation::Top(dir, n) => {
+            for c in inventory::largest_children(&mut client, &dir, n).expect2("top error") {
+                if json_output() {
+                    emit(serde_json::json!({
+                        "type": "top", "path": c.path, "kind": c.type_, "length": c.length, "space_consumed": c.space_consumed
+                    }))
+                } else {
+                    println!("{:>16} {:>16} {}", c.length, c.space_consumed, c.path)
+                }
+            }
+        }
+        Operation::Remove(path, opts) => {
+            /// Total number of entries, and the number at which progress is printed next
+            struct Progress(u64, u64);
+            impl tree_delete::DeleteObserver for Progress {
+                fn started(&mut self, total: u64) { self.0 = total }
+                fn deleted(&mut self, _path: &str, done: u64) {
+                    if done >= self.1 || done == self.0 {
+                        self.1 = done + 1000;
+                        if json_output() {
+                            emit(serde_json::json!({"type": "rm_progress", "deleted": done, "total": self.0}))
+                        } else {
+                            eprintln!("deleted {}/{}", done, self.0)
+                        }
+                    }
+                }
+            }
+            let n = tree_delete::delete_tree(&mut client, &path, &opts, &mut Progress(0, 1000)).expect2("rm error");
+            if json_output() { emit(serde_json::json!({"type": "rm", "path": path, "deleted": n})) }
+        }
+        Operation::Shell => shell::run(client)
     }
 }
 
 fn version() -> ! {
-    println!(
-        "{} ({}) version {}",
-        env!("CARGO_PKG_DESCRIPTION"),
-        env!("CARGO_PKG_NAME"),
-        env!("CARGO_PKG_VERSION")
-    );
+    if commandline::json_output() {
+        commandline::emit(serde_json::json!({
+            "type": "version", "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION")
+        }))
+    } else {
+        println!(
+            "{} ({}) version {}",
+            env!("CARGO_PKG_DESCRIPTION"),
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
     std::process::exit(0);
 }
 
@@ -57,12 +130,28 @@ webhdfs -v|--version
 options:
 
     -U|--uri|--url <url>        API entrypoint
+    --alt-uri <url>             Alternative API entrypoint (standby namenode)
     -u|--user <string>          User name
     -d|--doas <string>          DoAs username
     -T|--dt <string>            Delegation token
     -t|--timeout <unsigned>     Default timeout in seconds
-    -N|--natmap-file <filepath> Path to NAT mappings file
+    -N|--natmap-file <filepath> Path to NAT mappings file (k=v lines; JSON or TOML if
+                                the file name ends with .json or .toml)
     -n|--natmap-entry <k=v>     NAT mapping (multiple options are Ok)
+    --ca-cert <filepath>        Additional trusted root certificate, DER (multiple options are Ok)
+    --insecure                  Accept invalid TLS certificates and host names
+    --redact                    Leave secrets (delegation token) out of the --save-config file
+    --diff-uri <url>            API entrypoint of the cluster holding the second tree for --diff
+                                (the same cluster by default)
+    --checksum                  Compare files by checksum rather than modification time for --diff
+    --jsonl                     Write --inventory as JSON lines rather than CSV
+    --max-entries <unsigned>    Fail --rm, before deleting anything, if the tree has more entries
+    --rate <number>             Maximum number of DELETE requests per second for --rm
+    --output text|json          Output format (default text). With json, results and errors are printed
+                                to stdout as JSON objects, one per line, each with a \"type\" field
+                                (get, diff, top, rm, rm_progress, version, config_saved, error); errors
+                                carry a \"code\"
+                                (e.g. NotFound, PermissionDenied, usage). Implies --jsonl.
 
 command and files:
     -v|--version                   
@@ -72,19 +161,45 @@ command and files:
         Print this thelp screen and exit
 
     --save-config <filepath>
-        Save the effective configuration to the file
+        Save the effective configuration (all options above) to the file
 
     -g|--get <remote-filepath> <local-path>
     -g|--get <remote-filepath>
     -g|--get <remote-filepath>.. <local-dirpath>
         Get files from HDFS
 
+    --diff <remote-dirpath> <remote-dirpath>
+        Compare two directory trees, print differences ('+' added, '-' removed, '*' changed, 
+        relative to the first tree) and exit with status 1 if there are any
+
+    --inventory <remote-dirpath>
+        Write a report of the directory tree (path, type, size, owner, group, mtime, replication)
+        to stdout
+
+    --top <remote-dirpath> [<n>]
+        Print the n (default 10) largest entries of the directory, largest first: total length,
+        space consumed (including replicas) and path
+
+    --rm <remote-path>
+        Delete a file or a directory tree, one entry per request (rather than by a single recursive
+        request), printing progress to stderr
+
+    --shell
+        Start an interactive shell (cd, pwd, ls, get, put, rm, mkdir); type 'help' for details
+
+Ctrl-C cancels a running get or put and removes its partial output; the exit status is then 130.
+
 ");
     std::process::exit(1);
 }
 
 enum Operation {
-    Get(Vec<String>)
+    Get(Vec<String>),
+    Diff(String, String, Option<SyncHdfsClient>, compare::CompareMode),
+    Inventory(String, inventory::ReportFormat),
+    Top(String, usize),
+    Remove(String, tree_delete::TreeDeleteOptions),
+    Shell
 }
 
 
@@ -94,39 +209,59 @@ fn parse_command_line() -> (SyncHdfsClient, Operation) {
     use commandline::*;
 
     enum Sw {
-        Uri, User, Doas, DToken, Timeout, NMFile, NMEntry, SaveConfig
+        Uri, AltUri, User, Doas, DToken, Timeout, NMFile, NMEntry, CaCert, SaveConfig, DiffUri, Output, MaxEntries, Rate
     }
     enum Op {
-        Get
+        Get, Diff, Inventory, Top, Remove, Shell
     }
     struct S {
         sw: Option<Sw>,
         op: Option<Op>,
         files: Vec<String>,
         uri: Option<String>,
+        alt_uri: Option<String>,
         user: Option<String>,
         doas: Option<String>,
         dtoken: Option<String>,
         timeout: Option<Duration>,
         natmap: Option<HashMap<String, String>>,
+        ca_certs: Vec<String>,
+        insecure: bool,
         save_config: Option<String>,
+        redact: bool,
+        diff_uri: Option<String>,
+        checksum: bool,
+        jsonl: bool,
+        max_entries: Option<u64>,
+        rate: Option<f64>
     }
 
     let s0 = S { 
         sw: None, op: None, files: vec![], 
-        uri: None, user: None, doas:None, timeout: None, dtoken: None, natmap: None,
-        save_config: None 
+        uri: None, alt_uri: None, user: None, doas:None, timeout: None, dtoken: None, natmap: None,
+        ca_certs: vec![], insecure: false,
+        save_config: None, redact: false, diff_uri: None, checksum: false, jsonl: false, max_entries: None, rate: None
     };
 
     let result = commandline::parse_cmdln(s0, |mut s, arg| if let Some(sw) = s.sw.take() {
         match sw {
             Sw::Uri => S { uri: Some(arg.arg()), ..s },
+            Sw::AltUri => S { alt_uri: Some(arg.arg()), ..s },
+            Sw::CaCert => { s.ca_certs.push(arg.arg()); s }
             Sw::User => S { user: Some(arg.arg()), ..s },
             Sw::Doas => S { doas: Some(arg.arg()), ..s },
             Sw::DToken => S { dtoken: Some(arg.arg()), ..s },
             Sw::SaveConfig => S { save_config: Some(arg.arg()), ..s },
+            Sw::DiffUri => S { diff_uri: Some(arg.arg()), ..s },
+            Sw::Output => match arg.arg().as_str() {
+                "text" => { set_json_output(false); s }
+                "json" => { set_json_output(true); s }
+                other => error_exit("invalid --output format (expected text or json)", other)
+            }
+            Sw::MaxEntries => S { max_entries: Some(arg.arg().parse().expect2("Invalid --max-entries")), ..s },
+            Sw::Rate => S { rate: Some(arg.arg().parse().expect2("Invalid --rate")), ..s },
             Sw::Timeout => S { timeout: Some(Duration::from_secs(arg.arg().parse().expect2("Invalid timeout duration"))), ..s },
-            Sw::NMFile => S { natmap: Some(config::read_kv_file(&arg.arg()).expect2("malformed natmap file")), ..s },
+            Sw::NMFile => S { natmap: Some(config::read_natmap_file(&arg.arg()).expect2("malformed natmap file")), ..s },
             Sw::NMEntry =>  { 
                 let mut nm = if let Some(nm) = s.natmap { nm } else { HashMap::new() };
                 let (k, v) = config::split_kv(arg.arg()).expect2("invalid natmap entry");
@@ -139,7 +274,22 @@ fn parse_command_line() -> (SyncHdfsClient, Operation) {
             "-v"|"--version" => version(),
             "-h"|"--help" => usage(),
             "-g"|"--get" => S { op: Some(Op::Get), ..s },
+            "--diff" => S { op: Some(Op::Diff), ..s },
+            "--diff-uri" => S { sw: Some(Sw::DiffUri), ..s },
+            "--checksum" => S { checksum: true, ..s },
+            "--inventory" => S { op: Some(Op::Inventory), ..s },
+            "--top" => S { op: Some(Op::Top), ..s },
+            "--rm" => S { op: Some(Op::Remove), ..s },
+            "--max-entries" => S { sw: Some(Sw::MaxEntries), ..s },
+            "--rate" => S { sw: Some(Sw::Rate), ..s },
+            "--shell" => S { op: Some(Op::Shell), ..s },
+            "--jsonl" => S { jsonl: true, ..s },
+            "--output" => S { sw: Some(Sw::Output), ..s },
             "-U"|"--uri"|"--url" => S { sw: Some(Sw::Uri), ..s },
+            "--alt-uri" => S { sw: Some(Sw::AltUri), ..s },
+            "--ca-cert" => S { sw: Some(Sw::CaCert), ..s },
+            "--insecure" => S { insecure: true, ..s },
+            "--redact" => S { redact: true, ..s },
             "-u"|"--user" => S { sw: Some(Sw::User), ..s },
             "-d"|"--doas" => S { sw: Some(Sw::Doas), ..s },
             "-T"|"--dt" => S { sw: Some(Sw::DToken), ..s },
@@ -155,13 +305,48 @@ fn parse_command_line() -> (SyncHdfsClient, Operation) {
         error_exit("invalid command line at the end", "")
     }
 
-    if let Some(f) = result.save_config {
-        if result.op.is_some() {
-            error_exit("--save-config must be used alone", "")
+    if result.save_config.is_some() && result.op.is_some() {
+        error_exit("--save-config must be used alone", "")
+    }
+
+    //build context
+    let cx = if let Some(uri) = &result.uri { 
+        SyncHdfsClientBuilder::new(uri.parse().expect2("Cannot parse URI")) 
+    } else { 
+        SyncHdfsClientBuilder::from_config_opt().expect2("No configuration files were found, and no mandatory options (--uri) were specified")
+    };
+    let cx = match &result.alt_uri {
+        Some(uri) => cx.alt_entrypoint(uri.parse().expect2("Cannot parse --alt-uri")),
+        None => cx
+    };
+    let https = if result.insecure || !result.ca_certs.is_empty() {
+        let mut https = config::HttpsConfig::new();
+        if result.insecure {
+            https.danger_accept_invalid_certs = Some(true);
+            https.danger_accept_invalid_hostnames = Some(true);
         }
-        let uri = result.uri.expect2("must specify --uri when saving config");
-        let cfg = config::Config::new(uri.parse().expect2("Cannot parse URI"));
+        if !result.ca_certs.is_empty() { https.root_certificates = Some(result.ca_certs) }
+        Some(https)
+    } else {
+        None
+    };
+    let (user, doas, timeout, natmap, dtoken) = (result.user, result.doas, result.timeout, result.natmap, result.dtoken);
+    let configure = |mut cx: SyncHdfsClientBuilder| {
+        if let Some(user) = &user { cx = cx.user_name(user.clone()) }
+        if let Some(doas) = &doas { cx = cx.doas(doas.clone()) }
+        if let Some(timeout) = timeout { cx = cx.default_timeout(timeout) }
+        if let Some(natmap) = &natmap { cx = cx.natmap(NatMap::new(natmap.clone().into_iter()).expect2("Invalid natmap")) }
+        if let Some(dtoken) = &dtoken { cx = cx.delegation_token(dtoken.clone()) }
+        if let Some(https) = &https { cx = cx.https_settings(https.clone().into()) }
+        cx.build().expect2("Cannot build SyncHdfsClient")
+    };
+    let client = configure(cx);
+
+    if let Some(f) = result.save_config {
+        let cfg = client.effective_config_with_secrets();
+        let cfg = if result.redact { cfg.without_secrets() } else { cfg };
         config::write_config(&std::path::Path::new(&f), &cfg, true);
+        if json_output() { emit(serde_json::json!({"type": "config_saved", "path": f})) }
         std::process::exit(0);
     } else {
         let operation = if let Some(op) = result.op {
@@ -170,22 +355,42 @@ fn parse_command_line() -> (SyncHdfsClient, Operation) {
             error_exit("must specify operation", "")
         };
 
-        //build context
-        let mut cx = if let Some(uri) = result.uri { 
-            SyncHdfsClientBuilder::new(uri.parse().expect2("Cannot parse URI")) 
-        } else { 
-            SyncHdfsClientBuilder::from_config_opt().expect2("No configuration files were found, and no mandatory options (--uri) were specified")
-        };
-        if let Some(user) = result.user { cx = cx.user_name(user) }
-        if let Some(doas) = result.doas { cx = cx.doas(doas) }
-        if let Some(timeout) = result.timeout { cx = cx.default_timeout(timeout) }
-        if let Some(natmap) = result.natmap { cx = cx.natmap(NatMap::new(natmap.into_iter()).expect2("Invalid natmap")) }
-        if let Some(dtoken) = result.dtoken { cx = cx.delegation_token(dtoken) }
-        let client = cx.build().expect2("Cannot build SyncHdfsClient");
-
         let operation = match operation {
             Op::Get =>
-                if result.files.len() > 0 { Operation::Get(result.files) } else { error_exit("must specify at least one input file for --get", "") }
+                if result.files.len() > 0 { Operation::Get(result.files) } else { error_exit("must specify at least one input file for --get", "") },
+            Op::Diff => match &result.files[..] {
+                [a, b] => {
+                    let other = result.diff_uri.as_ref().map(|uri| 
+                        configure(SyncHdfsClientBuilder::new(uri.parse().expect2("Cannot parse --diff-uri")))
+                    );
+                    let mode = if result.checksum { compare::CompareMode::Checksum } else { compare::CompareMode::SizeAndMtime };
+                    Operation::Diff(a.clone(), b.clone(), other, mode)
+                }
+                _ => error_exit("must specify exactly two directories for --diff", "")
+            }
+            Op::Inventory => match &result.files[..] {
+                [root] => {
+                    let format = if result.jsonl || json_output() { inventory::ReportFormat::JsonLines } else { inventory::ReportFormat::Csv };
+                    Operation::Inventory(root.clone(), format)
+                }
+                _ => error_exit("must specify exactly one directory for --inventory", "")
+            }
+            Op::Top => match &result.files[..] {
+                [dir] => Operation::Top(dir.clone(), 10),
+                [dir, n] => Operation::Top(dir.clone(), n.parse().expect2("Invalid number of entries for --top")),
+                _ => error_exit("must specify a directory and, optionally, the number of entries for --top", "")
+            }
+            Op::Remove => match &result.files[..] {
+                [path] => {
+                    let opts = tree_delete::TreeDeleteOptions::new();
+                    let opts = if let Some(n) = result.max_entries { opts.max_entries(n) } else { opts };
+                    let opts = if let Some(r) = result.rate { opts.rate_limiter(rate_limit::RateLimiter::new(r, 1)) } else { opts };
+                    Operation::Remove(path.clone(), opts)
+                }
+                _ => error_exit("must specify exactly one path for --rm", "")
+            }
+            Op::Shell =>
+                if result.files.is_empty() { Operation::Shell } else { error_exit("--shell takes no arguments", "") }
         };
 
         (client, operation)
@@ -197,32 +402,335 @@ fn parse_command_line() -> (SyncHdfsClient, Operation) {
 //-------------------------
 
 
+mod shell {
+    use std::io::{BufRead, Write, BufWriter};
+    use std::path::Path;
+    use webhdfs::*;
+    use webhdfs::path::HdfsPath;
+    use webhdfs::sync_client::WriteHdfsFile;
+    use crate::interrupt;
+
+    const HELP: &str = "\
+commands:
+    pwd                         Print the current directory
+    cd [<dir>]                  Change the current directory (to / if none given)
+    ls [-h] [<path>]            List a directory, or show a file: type and permission, owner, group,
+                                size in bytes (-h: humanized, e.g. 1.5K), modification time (UTC)
+    get <remote> [<local>]      Download a file (to the current local directory by default)
+    put [-f] <local> [<remote>] Upload a file (to the current directory by default); -f overwrites
+    rm [-r] <path>              Delete a file, or a directory with -r
+    mkdir <dir>                 Create a directory, with parents
+    help                        Print this help
+    exit|quit                   Leave the shell (also end of input)
+Paths are relative to the current directory unless absolute. Arguments containing spaces may be quoted.";
+
+    /// Splits a command line into words; double quotes group words
+    fn split_words(line: &str) -> Vec<String> {
+        let (mut words, mut cur, mut quoted, mut any) = (vec![], String::new(), false, false);
+        for c in line.chars() {
+            match c {
+                '"' => { quoted = !quoted; any = true }
+                c if c.is_whitespace() && !quoted => if any { words.push(std::mem::take(&mut cur)); any = false },
+                c => { cur.push(c); any = true }
+            }
+        }
+        if any { words.push(cur) }
+        words
+    }
+
+    /// Size with a binary unit suffix (`K`, `M`, ...) and one decimal, exact below 1K
+    fn human_size(n: u64) -> String {
+        const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+        if n < 1024 { return n.to_string() }
+        let mut v = n as f64 / 1024.0;
+        let mut u = 0;
+        while v >= 1024.0 && u < UNITS.len() - 1 { v /= 1024.0; u += 1 }
+        format!("{:.1}{}", v, UNITS[u])
+    }
+
+    struct Shell {
+        client: SyncHdfsClient,
+        cwd: String
+    }
+
+    impl Shell {
+        /// Absolute normalized path of `path`, relative to the current directory
+        fn abs(&self, path: &str) -> Result<String> {
+            let p = if path.starts_with('/') { path.to_owned() } else { webhdfs::path::join(&self.cwd, path) };
+            Ok(HdfsPath::new(&p).normalize()?.into_string())
+        }
+
+        fn ls_line(name: &str, fs: &FileStatus, human: bool) -> String {
+            let t = match fs.type_.as_str() { "DIRECTORY" => 'd', "SYMLINK" => 'l', _ => '-' };
+            let size = if human { human_size(fs.size()) } else { fs.size().to_string() };
+            format!("{}{:>4} {:<10} {:<10} {:>12} {} {}", t, fs.permission, fs.owner, fs.group, size, iso8601(fs.modification_time), name)
+        }
+
+        /// Executes one command. Returns `false` if the shell is to be left
+        fn exec(&mut self, words: &[String]) -> Result<bool> {
+            let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+            match &words[..] {
+                [] => (),
+                ["exit"] | ["quit"] => return Ok(false),
+                ["help"] | ["?"] => println!("{}", HELP),
+                ["pwd"] => println!("{}", self.cwd),
+                ["cd"] => self.cwd = "/".to_owned(),
+                ["cd", dir] => {
+                    let dir = self.abs(dir)?;
+                    let fs = self.client.stat(&dir)?.file_status;
+                    if fs.type_ != "DIRECTORY" { return Err(Error::app_s(format!("{}: not a directory", dir))) }
+                    self.cwd = dir
+                }
+                ["ls"] => self.ls(&self.cwd.clone(), false)?,
+                ["ls", "-h"] => self.ls(&self.cwd.clone(), true)?,
+                ["ls", "-h", path] => { let p = self.abs(path)?; self.ls(&p, true)? }
+                ["ls", path] => { let p = self.abs(path)?; self.ls(&p, false)? }
+                ["get", remote] => {
+                    let remote = self.abs(remote)?;
+                    let name = HdfsPath::new(&remote).file_name().unwrap_or_default().to_owned();
+                    if name.is_empty() { return Err(Error::app_c("get: cannot download /")) }
+                    self.get(&remote, Path::new(&name))?
+                }
+                ["get", remote, local] => { let r = self.abs(remote)?; self.get(&r, Path::new(local))? }
+                ["put", rest @ ..] => {
+                    let (overwrite, rest) = match rest { ["-f", rest @ ..] => (true, rest), rest => (false, rest) };
+                    match rest {
+                        [local] => {
+                            let name = Path::new(local).file_name().and_then(|n| n.to_str())
+                                .ok_or_else(|| Error::app_s(format!("put: invalid local file name '{}'", local)))?;
+                            let r = self.abs(name)?;
+                            self.put(Path::new(local), &r, overwrite)?
+                        }
+                        [local, remote] => { let r = self.abs(remote)?; self.put(Path::new(local), &r, overwrite)? }
+                        _ => return Err(Error::app_c("usage: put [-f] <local> [<remote>]"))
+                    }
+                }
+                ["rm", path] => { let p = self.abs(path)?; self.rm(&p, false)? }
+                ["rm", "-r", path] => { let p = self.abs(path)?; self.rm(&p, true)? }
+                ["mkdir", dir] => { let d = self.abs(dir)?; self.client.mkdirs(&d, MkdirsOptions::new())?; }
+                [cmd, ..] => return Err(Error::app_s(format!("unknown command or wrong arguments: {} (type 'help')", cmd)))
+            }
+            Ok(true)
+        }
+
+        fn ls(&mut self, path: &str, human: bool) -> Result<()> {
+            let fs = self.client.stat(path)?.file_status;
+            if fs.type_ == "DIRECTORY" {
+                for e in self.client.dir(path)?.file_statuses.file_status {
+                    println!("{}", Self::ls_line(&e.path_suffix, &e, human))
+                }
+            } else {
+                println!("{}", Self::ls_line(path, &fs, human))
+            }
+            Ok(())
+        }
+
+        fn get(&mut self, remote: &str, local: &Path) -> Result<()> {
+            let mut out = std::fs::File::create(local)?;
+            let r = interrupt::transfer(Some(local), || self.client.get_file(remote, &mut interrupt::Checked(&mut out)));
+            if let Err(e) = r {
+                if interrupt::take() {
+                    let _ = std::fs::remove_file(local);
+                    return Err(Error::app_s(format!("get interrupted, partial file {} removed", local.display())))
+                }
+                return Err(e)
+            }
+            println!("{} -> {} ({} bytes)", remote, local.display(), out.metadata()?.len());
+            Ok(())
+        }
+
+        fn put(&mut self, local: &Path, remote: &str, overwrite: bool) -> Result<()> {
+            let mut input = interrupt::Checked(std::fs::File::open(local)?);
+            let file = WriteHdfsFile::create(self.client.clone(), remote.to_owned(), 
+                CreateOptions::new().overwrite(overwrite), AppendOptions::new())?;
+            //each write to WriteHdfsFile is an APPEND request
+            let mut out = BufWriter::with_capacity(8 << 20, file);
+            let r = interrupt::transfer(None, || std::io::copy(&mut input, &mut out).and_then(|n| out.flush().map(|()| n)));
+            match r {
+                Ok(n) => println!("{} -> {} ({} bytes)", local.display(), remote, n),
+                Err(_) if interrupt::take() => {
+                    //discard buffered data rather than flush it on drop
+                    let _ = out.into_parts();
+                    return Err(match self.client.delete(remote, DeleteOptions::new()) {
+                        Ok(_) => Error::app_s(format!("put interrupted, partial file {} removed", remote)),
+                        Err(e) => Error::app_s(format!("put interrupted, partial file {} left behind: {}", remote, e))
+                    })
+                }
+                Err(e) => return Err(e.into())
+            }
+            Ok(())
+        }
+
+        fn rm(&mut self, path: &str, recursive: bool) -> Result<()> {
+            if path == "/" { return Err(Error::app_c("rm: refusing to delete /")) }
+            if !self.client.delete(path, DeleteOptions::new().recursive(recursive))? {
+                return Err(Error::app_s(format!("{}: not deleted", path)))
+            }
+            Ok(())
+        }
+    }
+
+    /// Runs the shell on stdin until `exit` or end of input. All commands share `client` and its connections.
+    pub fn run(client: SyncHdfsClient) {
+        let mut sh = Shell { client, cwd: "/".to_owned() };
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("hdfs:{}> ", sh.cwd);
+            let _ = std::io::stdout().flush();
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => { eprintln!("Error: {}", e); break }
+                None => { println!(); break }
+            };
+            match sh.exec(&split_words(&line)) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => eprintln!("Error: {}", e)
+            }
+        }
+    }
+}
+
+
+mod interrupt {
+    //! Ctrl-C handling. Ctrl-C during a transfer (see `transfer`) cancels it: `Checked` readers and writers start
+    //! failing, and the caller cleans up partial output (see `take`). Ctrl-C outside of a transfer, or a second
+    //! Ctrl-C while a transfer is being cancelled, exits immediately, removing the local partial file, if any.
+    use std::io::{Read, Write, Result as IoResult, Error as IoError};
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Exit status after an interruption (128 + SIGINT, as reported by shells)
+    pub const EXIT_CODE: i32 = 130;
+
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+    /// `Some` while a transfer is running, with its local output file, if any
+    static TRANSFER: Mutex<Option<Option<PathBuf>>> = Mutex::new(None);
+
+    /// Starts the Ctrl-C handler thread
+    pub fn install() {
+        let rt = match tokio::runtime::Builder::new_current_thread().enable_io().build() {
+            Ok(rt) => rt,
+            Err(e) => { eprintln!("Warning: cannot install Ctrl-C handler: {}", e); return }
+        };
+        std::thread::spawn(move || rt.block_on(async {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                let transfer = TRANSFER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                match transfer {
+                    Some(_) if !INTERRUPTED.swap(true, Ordering::SeqCst) =>
+                        eprintln!("\nInterrupted, cancelling the transfer (press Ctrl-C again to exit immediately)"),
+                    Some(Some(partial)) => { let _ = std::fs::remove_file(partial); std::process::exit(EXIT_CODE) }
+                    _ => std::process::exit(EXIT_CODE)
+                }
+            }
+        }));
+    }
+
+    /// Runs transfer `f`, which is cancelled on Ctrl-C. `local_output` is the local file the transfer writes to.
+    pub fn transfer<T>(local_output: Option<&Path>, f: impl FnOnce() -> T) -> T {
+        *TRANSFER.lock().unwrap() = Some(local_output.map(Path::to_owned));
+        let r = f();
+        *TRANSFER.lock().unwrap() = None;
+        r
+    }
+
+    /// Whether the last transfer has been interrupted; clears the flag
+    pub fn take() -> bool { INTERRUPTED.swap(false, Ordering::SeqCst) }
+
+    /// Error of `Checked` readers and writers after Ctrl-C. Reported with kind `Other`, as `Interrupted` is
+    /// retried by `std::io::copy` and the like.
+    #[derive(Debug)]
+    pub struct Cancelled;
+
+    impl std::fmt::Display for Cancelled {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str("interrupted by user") }
+    }
+
+    impl std::error::Error for Cancelled {}
+
+    fn check() -> IoResult<()> {
+        if INTERRUPTED.load(Ordering::SeqCst) { Err(IoError::other(Cancelled)) } else { Ok(()) }
+    }
+
+    /// Reader or writer failing once Ctrl-C has been pressed
+    pub struct Checked<T>(pub T);
+
+    impl<T: Read> Read for Checked<T> {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> { check()?; self.0.read(buf) }
+    }
+
+    impl<T: Write> Write for Checked<T> {
+        fn write(&mut self, buf: &[u8]) -> IoResult<usize> { check()?; self.0.write(buf) }
+        fn flush(&mut self) -> IoResult<()> { self.0.flush() }
+    }
+}
+
+
 mod commandline {
-    
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+    /// Switches results and errors to JSON records on stdout (`--output json`)
+    pub fn set_json_output(json: bool) { JSON_OUTPUT.store(json, Ordering::Relaxed) }
+
+    pub fn json_output() -> bool { JSON_OUTPUT.load(Ordering::Relaxed) }
 
+    /// Prints a JSON record as a single line to stdout
+    pub fn emit(record: serde_json::Value) { println!("{}", record) }
 
     /// Prints two-part message to stderr and exits
-    pub fn error_exit(msg: &str, detail: &str) -> ! {
-        eprint!("Error: {}", msg);
-        if detail.is_empty() {
-            eprintln!()
+    pub fn error_exit(msg: &str, detail: &str) -> ! { error_exit_c("usage", msg, detail) }
+
+    /// Same as `error_exit`; in JSON output mode, prints an error record with `code` to stdout instead
+    pub fn error_exit_c(code: &str, msg: &str, detail: &str) -> ! {
+        if json_output() {
+            emit(serde_json::json!({"type": "error", "code": code, "message": msg, "detail": detail}))
         } else {
-            eprintln!(" ({})", detail);
+            eprint!("Error: {}", msg);
+            if detail.is_empty() {
+                eprintln!()
+            } else {
+                eprintln!(" ({})", detail);
+            }
         }
         std::process::exit(1)
     }
 
+    /// Reports an interrupted operation and exits with `interrupt::EXIT_CODE`
+    pub fn interrupted_exit(msg: &str) -> ! {
+        if json_output() {
+            emit(serde_json::json!({"type": "error", "code": "Interrupted", "message": msg, "detail": ""}))
+        } else {
+            eprintln!("Error: {}", msg);
+        }
+        std::process::exit(crate::interrupt::EXIT_CODE)
+    }
+
+    /// Error code for JSON output: the closest `std::io::ErrorKind` for I/O and WebHDFS errors
+    fn error_code(e: &(dyn std::error::Error + 'static)) -> String {
+        if let Some(e) = e.downcast_ref::<webhdfs::Error>() {
+            format!("{:?}", e.io_kind())
+        } else if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            format!("{:?}", e.kind())
+        } else {
+            "error".to_owned()
+        }
+    }
+
     /// Expect2 function
     pub trait Expect2<T> {
         /// Same as Result::expect but the error message is brief and not intimidating
         fn expect2(self, msg: &str) -> T;
     }
 
-    impl<T, E: std::error::Error> Expect2<T> for std::result::Result<T, E> {
+    impl<T, E: std::error::Error + 'static> Expect2<T> for std::result::Result<T, E> {
         fn expect2(self, msg: &str) -> T {
             match self {
                 Ok(v) => v,
-                Err(e) => error_exit(msg, &e.to_string())
+                Err(e) => error_exit_c(&error_code(&e), msg, &e.to_string())
             }
         }
     }
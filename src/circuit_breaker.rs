@@ -0,0 +1,130 @@
+//! Per-endpoint circuit breaking.
+//!
+//! `CircuitBreaker` tracks consecutive transport failures (connection errors and timeouts) of each endpoint (URI
+//! authority), namenodes and datanodes alike. After `failure_threshold` consecutive failures the endpoint's circuit
+//! opens: requests to it fail immediately, without waiting for a connection or a timeout, for `open_duration`. The
+//! circuit then becomes half-open: a single probe request is let through, and the circuit closes if it succeeds, or
+//! opens again if it fails. Any response received, including error responses, counts as a success.
+//!
+//! Like `RateLimiter`, it is a cheap-to-clone handle: clones share the state, so a breaker passed to several clients
+//! (see `HdfsClientBuilder::circuit_breaker`) learns of a dead endpoint from any of them. Requests rejected by an
+//! open circuit fail with an error for which `Error::is_circuit_open` holds, and trigger namenode failover as
+//! connection errors do.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+/// State of the circuit of an endpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    /// Requests pass
+    Closed,
+    /// Requests are rejected
+    Open,
+    /// A probe request is allowed (or is in flight)
+    HalfOpen
+}
+
+#[derive(Default)]
+struct Endpoint {
+    failures: u32,
+    opened_at: Option<Instant>,
+    /// Start of the probe request in flight, if any. A probe that has not completed within `open_duration` 
+    /// (e.g. has been cancelled) no longer blocks another one.
+    probe: Option<Instant>
+}
+
+/// Circuit breaker shared by all its clones
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    endpoints: Arc<Mutex<HashMap<String, Endpoint>>>
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker opening the circuit of an endpoint after `failure_threshold` consecutive failures,
+    /// for `open_duration` before a probe request is let through
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self { failure_threshold: failure_threshold.max(1), open_duration, endpoints: Default::default() }
+    }
+
+    /// Whether a request to `endpoint` may be sent. In the half-open state, lets the first caller through as a probe.
+    pub(crate) fn allow(&self, endpoint: &str) -> bool {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let e = match endpoints.get_mut(endpoint) {
+            Some(e) => e,
+            None => return true
+        };
+        let probe_done = e.probe.is_none_or(|p| p.elapsed() >= self.open_duration);
+        match e.opened_at {
+            None => true,
+            Some(t) if t.elapsed() >= self.open_duration && probe_done => { e.probe = Some(Instant::now()); true }
+            Some(_) => false
+        }
+    }
+
+    /// Records a response received from `endpoint`
+    pub(crate) fn success(&self, endpoint: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        if let Some(e) = endpoints.remove(endpoint) {
+            if e.opened_at.is_some() { info!("Circuit of {} closed", endpoint) }
+        }
+    }
+
+    /// Records a transport failure of a request to `endpoint`
+    pub(crate) fn failure(&self, endpoint: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let e = endpoints.entry(endpoint.to_owned()).or_default();
+        e.failures += 1;
+        if e.probe.is_some() || (e.opened_at.is_none() && e.failures >= self.failure_threshold) {
+            warn!("Circuit of {} opened after {} consecutive failures", endpoint, e.failures);
+            e.opened_at = Some(Instant::now());
+            e.probe = None;
+        }
+    }
+
+    /// Current state of the circuit of `endpoint` (URI authority)
+    pub fn state(&self, endpoint: &str) -> CircuitState {
+        match self.endpoints.lock().unwrap().get(endpoint) {
+            Some(Endpoint { opened_at: Some(t), probe, .. }) if probe.is_some() || t.elapsed() >= self.open_duration => CircuitState::HalfOpen,
+            Some(Endpoint { opened_at: Some(_), .. }) => CircuitState::Open,
+            _ => CircuitState::Closed
+        }
+    }
+}
+
+impl std::fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CircuitBreaker {{ failure_threshold: {}, open_duration: {:?} }}", self.failure_threshold, self.open_duration)
+    }
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let b = CircuitBreaker::new(2, Duration::from_millis(50));
+    let shared = b.clone();
+    assert!(b.allow("dn:1"));
+    b.failure("dn:1");
+    b.success("dn:1");
+    b.failure("dn:1");
+    assert_eq!(b.state("dn:1"), CircuitState::Closed);
+    shared.failure("dn:1");
+    assert_eq!(b.state("dn:1"), CircuitState::Open);
+    assert!(!b.allow("dn:1"));
+    assert!(b.allow("dn:2"));
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(b.state("dn:1"), CircuitState::HalfOpen);
+    assert!(b.allow("dn:1"));
+    assert!(!shared.allow("dn:1"));
+    b.failure("dn:1");
+    assert_eq!(b.state("dn:1"), CircuitState::Open);
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(b.allow("dn:1"));
+    b.success("dn:1");
+    assert_eq!(b.state("dn:1"), CircuitState::Closed);
+    assert!(b.allow("dn:1"));
+}
@@ -0,0 +1,527 @@
+//! Compression and decompression of HDFS files (features `gzip`, `zstd`, `snappy`).
+//!
+//! `DecompressReader` wraps a `Read` (e.g. `ReadHdfsFile`), and `decompress_stream` wraps a byte stream
+//! (e.g. one returned by `HdfsClient::open`); both decompress data on the fly. `Codec::from_path` picks the codec by
+//! file extension, as Hadoop's `CompressionCodecFactory` does. Gzip input may consist of several members
+//! (as written by concatenating files). Snappy input is expected in Hadoop's block format (as written by
+//! `SnappyCodec`), not in the Snappy framing format.
+//!
+//! On the write side, `CompressWriter` wraps a `Write` (e.g. `WriteHdfsFile`), and `compress_stream` wraps a byte
+//! stream. Since each write to `WriteHdfsFile` is an APPEND request, `CompressWriter` passes compressed data on
+//! in large chunks only (see `CompressWriter::chunk_size`), or when flushed. The output is complete only after
+//! `CompressWriter::finish`.
+use std::io::{Read, Write, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use crate::error::*;
+
+/// Compression codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "snappy")]
+    Snappy
+}
+
+impl Codec {
+    /// Codec of a file, by extension (`.gz`, `.zst`, `.snappy`)
+    pub fn from_path(path: &str) -> Option<Codec> {
+        match path.rsplit('.').next()? {
+            #[cfg(feature = "gzip")]
+            "gz" => Some(Codec::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Codec::Zstd),
+            #[cfg(feature = "snappy")]
+            "snappy" => Some(Codec::Snappy),
+            _ => None
+        }
+    }
+
+    /// File extension, including the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => ".gz",
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => ".zst",
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => ".snappy"
+        }
+    }
+
+    fn decompressor(&self) -> IoResult<Box<dyn Decompressor + Send>> {
+        Ok(match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Box::new(flate2::write::MultiGzDecoder::new(vec![])),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Box::new(ZstdDecoder { d: zstd::stream::raw::Decoder::new()?, frame_done: true }),
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => Box::new(SnappyBlockDecoder::default())
+        })
+    }
+
+    fn compressor(&self) -> IoResult<Box<dyn Compressor + Send>> {
+        Ok(match self {
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Box::new(flate2::write::GzEncoder::new(vec![], flate2::Compression::default())),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Box::new(zstd::stream::write::Encoder::new(vec![], 0)?),
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => Box::new(SnappyBlockEncoder::default())
+        })
+    }
+}
+
+/// Push decompressor: takes compressed data in arbitrary pieces, appends decompressed data to `out`
+trait Decompressor {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()>;
+    /// Called at the end of input
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()>;
+}
+
+#[cfg(feature = "gzip")]
+impl Decompressor for flate2::write::MultiGzDecoder<Vec<u8>> {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        self.write_all(input)?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        self.try_finish()?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+}
+
+/// Zstandard decoder; the frame state is tracked to detect truncated input
+#[cfg(feature = "zstd")]
+struct ZstdDecoder {
+    d: zstd::stream::raw::Decoder<'static>,
+    frame_done: bool
+}
+
+#[cfg(feature = "zstd")]
+impl Decompressor for ZstdDecoder {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        use zstd::stream::raw::{InBuffer, OutBuffer, Operation};
+        let mut input = InBuffer::around(input);
+        let mut buf = vec![0u8; zstd::zstd_safe::DCtx::out_size()];
+        loop {
+            let mut output = OutBuffer::around(&mut buf[..]);
+            let hint = self.d.run(&mut input, &mut output)?;
+            let n = output.pos();
+            self.frame_done = hint == 0;
+            out.extend_from_slice(&buf[..n]);
+            if input.pos() == input.src.len() && n < buf.len() { break Ok(()) }
+        }
+    }
+    fn finish(&mut self, _out: &mut Vec<u8>) -> IoResult<()> {
+        if self.frame_done { Ok(()) } else { Err(IoError::new(IoErrorKind::UnexpectedEof, "Truncated zstd frame")) }
+    }
+}
+
+/// Hadoop block format: each block is the uncompressed block length (32-bit big endian), followed by chunks
+/// (compressed length, then raw Snappy data) until the block length is reached
+#[cfg(feature = "snappy")]
+#[derive(Default)]
+struct SnappyBlockDecoder {
+    buf: Vec<u8>,
+    /// Bytes of the current block yet to be decompressed; `None` between blocks
+    block_left: Option<usize>
+}
+
+#[cfg(feature = "snappy")]
+impl Decompressor for SnappyBlockDecoder {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        self.buf.extend_from_slice(input);
+        let mut pos = 0;
+        while let Some(b) = self.buf.get(pos..pos + 4) {
+            let len = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize;
+            match self.block_left {
+                None => { self.block_left = Some(len); pos += 4; }
+                Some(0) => self.block_left = None,
+                Some(left) => {
+                    let chunk = match self.buf.get(pos + 4..pos + 4 + len) { Some(c) => c, None => break };
+                    let data = snap::raw::Decoder::new().decompress_vec(chunk)
+                        .map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
+                    if data.len() > left {
+                        return Err(IoError::new(IoErrorKind::InvalidData, "Snappy chunk exceeds block length"))
+                    }
+                    self.block_left = Some(left - data.len());
+                    out.extend_from_slice(&data);
+                    pos += 4 + len;
+                }
+            }
+        }
+        self.buf.drain(..pos);
+        if self.block_left == Some(0) { self.block_left = None }
+        Ok(())
+    }
+    fn finish(&mut self, _out: &mut Vec<u8>) -> IoResult<()> {
+        if self.buf.is_empty() && self.block_left.is_none() {
+            Ok(())
+        } else {
+            Err(IoError::new(IoErrorKind::UnexpectedEof, "Truncated Snappy block"))
+        }
+    }
+}
+
+/// Push compressor: takes data in arbitrary pieces, appends compressed data to `out`
+trait Compressor {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()>;
+    /// Outputs all data fed so far, in a form decompressible without the rest of the stream
+    fn flush(&mut self, out: &mut Vec<u8>) -> IoResult<()>;
+    /// Outputs the rest of the stream. No data may be fed afterwards.
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()>;
+}
+
+#[cfg(feature = "gzip")]
+impl Compressor for flate2::write::GzEncoder<Vec<u8>> {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        self.write_all(input)?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+    fn flush(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        Write::flush(self)?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        self.try_finish()?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for zstd::stream::write::Encoder<'static, Vec<u8>> {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        self.write_all(input)?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+    fn flush(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        Write::flush(self)?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        self.do_finish()?;
+        out.append(self.get_mut());
+        Ok(())
+    }
+}
+
+/// Hadoop block format (see `SnappyBlockDecoder`), one chunk per block
+#[cfg(feature = "snappy")]
+#[derive(Default)]
+struct SnappyBlockEncoder {
+    block: Vec<u8>
+}
+
+#[cfg(feature = "snappy")]
+impl SnappyBlockEncoder {
+    /// As Hadoop's default `io.compression.codec.snappy.buffersize`
+    const BLOCK_SIZE: usize = 256 * 1024;
+
+    fn emit(&mut self, out: &mut Vec<u8>) -> IoResult<()> {
+        if self.block.is_empty() { return Ok(()) }
+        let c = snap::raw::Encoder::new().compress_vec(&self.block).map_err(|e| IoError::new(IoErrorKind::InvalidInput, e))?;
+        out.extend_from_slice(&(self.block.len() as u32).to_be_bytes());
+        out.extend_from_slice(&(c.len() as u32).to_be_bytes());
+        out.extend_from_slice(&c);
+        self.block.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "snappy")]
+impl Compressor for SnappyBlockEncoder {
+    fn feed(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        while !input.is_empty() {
+            let n = std::cmp::min(input.len(), Self::BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&input[..n]);
+            input = &input[n..];
+            if self.block.len() == Self::BLOCK_SIZE { self.emit(out)? }
+        }
+        Ok(())
+    }
+    fn flush(&mut self, out: &mut Vec<u8>) -> IoResult<()> { self.emit(out) }
+    fn finish(&mut self, out: &mut Vec<u8>) -> IoResult<()> { self.emit(out) }
+}
+
+/// Writer compressing data written to `W`. Call `finish` to complete the output; if dropped, the writer is
+/// finished on a best-effort basis, with errors ignored.
+pub struct CompressWriter<W: Write> {
+    inner: Option<W>,
+    enc: Box<dyn Compressor + Send>,
+    out: Vec<u8>,
+    chunk_size: usize
+}
+
+impl<W: Write> CompressWriter<W> {
+    const DEFAULT_CHUNK_SIZE: usize = 4 << 20;
+
+    pub fn new(codec: Codec, inner: W) -> Result<Self> {
+        Ok(Self { inner: Some(inner), enc: codec.compressor()?, out: vec![], chunk_size: Self::DEFAULT_CHUNK_SIZE })
+    }
+
+    /// Amount of compressed data collected before it is written to the underlying writer (4MB by default)
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self { self.chunk_size = chunk_size; self }
+
+    fn inner(&mut self) -> IoResult<&mut W> {
+        self.inner.as_mut().ok_or_else(|| IoError::other("CompressWriter is finished"))
+    }
+
+    fn write_out(&mut self) -> IoResult<()> {
+        if !self.out.is_empty() {
+            let out = std::mem::take(&mut self.out);
+            self.inner()?.write_all(&out)?;
+        }
+        Ok(())
+    }
+
+    fn do_finish(&mut self) -> IoResult<()> {
+        self.enc.finish(&mut self.out)?;
+        self.write_out()?;
+        self.inner()?.flush()
+    }
+
+    /// Completes the compressed stream and returns the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.do_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.inner()?;
+        self.enc.feed(buf, &mut self.out)?;
+        if self.out.len() >= self.chunk_size { self.write_out()? }
+        Ok(buf.len())
+    }
+    /// Writes out all data written so far (in compressed form), then flushes the underlying writer
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner()?;
+        self.enc.flush(&mut self.out)?;
+        self.write_out()?;
+        self.inner()?.flush()
+    }
+}
+
+impl<W: Write> Drop for CompressWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() { let _ = self.do_finish(); }
+    }
+}
+
+/// Reader decompressing data read from `R`
+pub struct DecompressReader<R> {
+    inner: R,
+    dec: Box<dyn Decompressor + Send>,
+    input: Vec<u8>,
+    out: Vec<u8>,
+    out_pos: usize,
+    eof: bool
+}
+
+impl<R: Read> DecompressReader<R> {
+    const INPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+    pub fn new(codec: Codec, inner: R) -> Result<Self> {
+        Ok(Self { inner, dec: codec.decompressor()?, input: vec![0; Self::INPUT_BUFFER_SIZE], out: vec![], out_pos: 0, eof: false })
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read> Read for DecompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while self.out_pos == self.out.len() && !self.eof {
+            self.out.clear();
+            self.out_pos = 0;
+            let n = self.inner.read(&mut self.input)?;
+            if n == 0 {
+                self.eof = true;
+                self.dec.finish(&mut self.out)?;
+            } else {
+                self.dec.feed(&self.input[..n], &mut self.out)?;
+            }
+        }
+        let n = std::cmp::min(buf.len(), self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+/// Decompresses a byte stream, such as one returned by `HdfsClient::open`
+pub fn decompress_stream(codec: Codec, input: impl Stream<Item=Result<Bytes>> + Unpin + 'static)
+-> Result<Box<dyn Stream<Item=Result<Bytes>> + Unpin>> {
+    let dec = codec.decompressor()?;
+    let s = futures::stream::unfold(Some((input, dec)), |state| async move {
+        let (mut input, mut dec) = state?;
+        let mut out = vec![];
+        loop {
+            let r = match input.next().await {
+                Some(Ok(chunk)) => dec.feed(&chunk, &mut out),
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    let r = dec.finish(&mut out).map(|()| Bytes::from(out)).map_err(Error::from);
+                    return Some((r, None))
+                }
+            };
+            match r {
+                Err(e) => return Some((Err(e.into()), None)),
+                Ok(()) if !out.is_empty() => return Some((Ok(Bytes::from(out)), Some((input, dec)))),
+                Ok(()) => ()
+            }
+        }
+    }).filter(|r| futures::future::ready(!matches!(r, Ok(b) if b.is_empty())));
+    Ok(Box::new(Box::pin(s)))
+}
+
+/// Compresses a byte stream. Compressed data is emitted as soon as the codec produces it.
+pub fn compress_stream(codec: Codec, input: impl Stream<Item=Result<Bytes>> + Unpin + 'static)
+-> Result<Box<dyn Stream<Item=Result<Bytes>> + Unpin>> {
+    let enc = codec.compressor()?;
+    let s = futures::stream::unfold(Some((input, enc)), |state| async move {
+        let (mut input, mut enc) = state?;
+        let mut out = vec![];
+        loop {
+            let r = match input.next().await {
+                Some(Ok(chunk)) => enc.feed(&chunk, &mut out),
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    let r = enc.finish(&mut out).map(|()| Bytes::from(out)).map_err(Error::from);
+                    return Some((r, None))
+                }
+            };
+            match r {
+                Err(e) => return Some((Err(e.into()), None)),
+                Ok(()) if !out.is_empty() => return Some((Ok(Bytes::from(out)), Some((input, enc)))),
+                Ok(()) => ()
+            }
+        }
+    }).filter(|r| futures::future::ready(!matches!(r, Ok(b) if b.is_empty())));
+    Ok(Box::new(Box::pin(s)))
+}
+
+#[cfg(test)]
+fn test_data() -> Vec<u8> { (0..100_000u32).flat_map(|i| format!("line {}\n", i % 1000).into_bytes()).collect() }
+
+#[cfg(test)]
+fn check_codec(codec: Codec, compressed: &[u8]) {
+    let data = test_data();
+    //sync, small reads from a reader returning short chunks
+    struct Chunked<'a>(&'a [u8]);
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+            let n = self.0.len().min(buf.len()).min(777);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let mut out = vec![];
+    DecompressReader::new(codec, Chunked(compressed)).unwrap().read_to_end(&mut out).unwrap();
+    assert!(out == data, "{:?}", codec);
+
+    let chunks: Vec<Result<Bytes>> = compressed.chunks(1000).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let s = decompress_stream(codec, futures::stream::iter(chunks)).unwrap();
+    let out: Vec<u8> = rt.block_on(s.map(|c| c.unwrap().to_vec()).concat());
+    assert!(out == data, "{:?}", codec);
+
+    let truncated = &compressed[..compressed.len() - 3];
+    assert!(DecompressReader::new(codec, truncated).unwrap().read_to_end(&mut vec![]).is_err(), "{:?}", codec);
+}
+
+#[cfg(all(test, feature = "gzip"))]
+#[test]
+fn test_gzip() {
+    let data = test_data();
+    let (a, b) = data.split_at(1234);
+    let mut compressed = vec![];
+    for part in &[a, b] {
+        let mut e = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        e.write_all(part).unwrap();
+        compressed.extend(e.finish().unwrap());
+    }
+    check_codec(Codec::Gzip, &compressed);
+    assert_eq!(Codec::from_path("/logs/a.log.gz"), Some(Codec::Gzip));
+}
+
+#[cfg(all(test, feature = "zstd"))]
+#[test]
+fn test_zstd() {
+    let compressed = zstd::stream::encode_all(&test_data()[..], 3).unwrap();
+    check_codec(Codec::Zstd, &compressed);
+    assert_eq!(Codec::from_path("a.zst"), Some(Codec::Zstd));
+}
+
+#[cfg(all(test, feature = "snappy"))]
+#[test]
+fn test_snappy() {
+    let data = test_data();
+    let mut compressed = vec![];
+    for block in data.chunks(256 * 1024) {
+        compressed.extend(&(block.len() as u32).to_be_bytes());
+        for chunk in block.chunks(100_000) {
+            let c = snap::raw::Encoder::new().compress_vec(chunk).unwrap();
+            compressed.extend(&(c.len() as u32).to_be_bytes());
+            compressed.extend(c);
+        }
+    }
+    check_codec(Codec::Snappy, &compressed);
+    assert_eq!(Codec::from_path("part-0.snappy"), Some(Codec::Snappy));
+    assert_eq!(Codec::from_path("part-0"), None);
+}
+
+#[test]
+fn test_compress_writer() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    use crate::sync_client::{SyncHdfsClientBuilder, ReadHdfsFile, WriteHdfsFile};
+    use crate::op::{CreateOptions, AppendOptions};
+    let data = test_data();
+    let fs = FakeHdfs::new();
+    let cl = FakeCluster::start(&fs);
+    let codecs = vec![
+        #[cfg(feature = "gzip")] Codec::Gzip,
+        #[cfg(feature = "zstd")] Codec::Zstd,
+        #[cfg(feature = "snappy")] Codec::Snappy
+    ];
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    for codec in codecs {
+        let path = format!("/out.{}", codec.extension());
+        let cx = cl.sync_builder().build().unwrap();
+        let file = WriteHdfsFile::create(cx, path.clone(), CreateOptions::new(), AppendOptions::new()).unwrap();
+        let mut w = CompressWriter::new(codec, file).unwrap().chunk_size(10_000);
+        let appends = || cl.nn.requests().iter().filter(|r| r.path_and_query.contains("op=APPEND") && r.path_and_query.contains("datanode")).count();
+        let before = appends();
+        for part in data.chunks(3000) { w.write_all(part).unwrap(); }
+        w.flush().unwrap();
+        let flushed = fs.get(&path).unwrap().data.len();
+        assert!(flushed > 0, "{:?}", codec);
+        w.finish().unwrap();
+        let compressed = fs.get(&path).unwrap().data;
+        assert!(compressed.len() < data.len() / 4 && compressed.len() >= flushed, "{:?}", codec);
+        assert!(appends() - before <= compressed.len() / 10_000 + 2, "{:?}", codec);
+
+        let cx = cl.sync_builder().build().unwrap();
+        let mut out = vec![];
+        DecompressReader::new(codec, ReadHdfsFile::open(cx, path).unwrap()).unwrap().read_to_end(&mut out).unwrap();
+        assert!(out == data, "{:?}", codec);
+
+        let chunks: Vec<Result<Bytes>> = data.chunks(5000).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        let s = compress_stream(codec, futures::stream::iter(chunks)).unwrap();
+        let compressed: Vec<u8> = rt.block_on(s.map(|c| c.unwrap().to_vec()).concat());
+        check_codec(codec, &compressed);
+    }
+}
@@ -0,0 +1,140 @@
+//! Directory tree comparison.
+//!
+//! `compare` walks two directory trees, possibly on different clusters, and reports files and directories
+//! that are present in one tree only, or differ. It is intended to validate copies (e.g. made by distcp).
+//! Both trees are listed concurrently; in `CompareMode::Checksum`, up to `parallelism` checksum requests
+//! are in flight at a time.
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use futures::{future, stream, StreamExt};
+use crate::error::*;
+use crate::datatypes::{FileStatus, dirent_type};
+use crate::async_client::{HdfsClient, FOState, FOR};
+use crate::sync_client::SyncHdfsClient;
+
+/// How files of the same path are compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareMode {
+    /// Files differ if sizes or modification times differ
+    SizeAndMtime,
+    /// Files differ if sizes or checksums (GETFILECHECKSUM) differ. Note that checksums of identical files
+    /// differ if the files have different block sizes.
+    Checksum
+}
+
+/// A difference between the source (`a`) tree and the target (`b`) tree. Paths are relative to tree roots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// Present in the target tree only
+    Added(String),
+    /// Present in the source tree only
+    Removed(String),
+    /// Present in both trees, but differ
+    Changed(String)
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Difference::Added(p) => write!(f, "+ {}", p),
+            Difference::Removed(p) => write!(f, "- {}", p),
+            Difference::Changed(p) => write!(f, "* {}", p)
+        }
+    }
+}
+
+/// Lists a tree recursively; keys are paths relative to `root`
+async fn list_tree(cx: &HdfsClient, fostate: FOState, root: &str) -> Result<BTreeMap<String, FileStatus>> {
+    let mut r = BTreeMap::new();
+    let mut dirs = vec![String::new()];
+    while let Some(rel) = dirs.pop() {
+        let (l, _) = FOR::split(cx.dir(fostate, &crate::path::join(root, &rel)).await);
+        for fs in l?.file_statuses.file_status {
+            let rel = if rel.is_empty() { fs.path_suffix.clone() } else { format!("{}/{}", rel, fs.path_suffix) };
+            if fs.type_ == dirent_type::DIRECTORY { dirs.push(rel.clone()) }
+            r.insert(rel, fs);
+        }
+    }
+    Ok(r)
+}
+
+/// Compares tree `a_root` on client `a` (the source) with tree `b_root` on client `b` (the target).
+/// Differences are sorted by path.
+pub async fn compare(a: &HdfsClient, a_root: &str, b: &HdfsClient, b_root: &str, mode: CompareMode, parallelism: usize)
+-> Result<Vec<Difference>> {
+    let (ta, tb) = future::join(list_tree(a, FOState::PRIMARY, a_root), list_tree(b, FOState::PRIMARY, b_root)).await;
+    let (ta, tb) = (ta?, tb?);
+
+    let mut diffs = vec![];
+    let mut to_checksum = vec![];
+    for (p, fa) in &ta {
+        match tb.get(p) {
+            None => diffs.push(Difference::Removed(p.clone())),
+            Some(fb) if fa.type_ != fb.type_ => diffs.push(Difference::Changed(p.clone())),
+            Some(fb) if fa.type_ == dirent_type::FILE => match mode {
+                _ if fa.length != fb.length => diffs.push(Difference::Changed(p.clone())),
+                CompareMode::SizeAndMtime if fa.modification_time != fb.modification_time => diffs.push(Difference::Changed(p.clone())),
+                CompareMode::SizeAndMtime => (),
+                CompareMode::Checksum => to_checksum.push(p)
+            }
+            Some(_) => ()
+        }
+    }
+    diffs.extend(tb.keys().filter(|p| !ta.contains_key(*p)).map(|p| Difference::Added(p.clone())));
+
+    let checksums = stream::iter(to_checksum).map(|p| async move {
+        let (ca, cb) = future::join(
+            a.checksum(FOState::PRIMARY, &crate::path::join(a_root, p)),
+            b.checksum(FOState::PRIMARY, &crate::path::join(b_root, p))
+        ).await;
+        let (ca, cb) = (FOR::split(ca).0?, FOR::split(cb).0?);
+        Ok((p, ca.file_checksum == cb.file_checksum))
+    }).buffer_unordered(parallelism.max(1)).collect::<Vec<Result<_>>>().await;
+    for c in checksums {
+        let (p, same) = c?;
+        if !same { diffs.push(Difference::Changed(p.clone())) }
+    }
+
+    diffs.sort_by(|x, y| path_of(x).cmp(path_of(y)));
+    Ok(diffs)
+}
+
+fn path_of(d: &Difference) -> &str {
+    match d { Difference::Added(p) | Difference::Removed(p) | Difference::Changed(p) => p }
+}
+
+/// Synchronous version of `compare`. Runs on the runtime of `a`; `default_timeout` does not apply.
+pub fn compare_sync(a: &SyncHdfsClient, a_root: &str, b: &SyncHdfsClient, b_root: &str, mode: CompareMode, parallelism: usize)
+-> Result<Vec<Difference>> {
+    a.block_on(compare(a.async_client(), a_root, b.async_client(), b_root, mode, parallelism))
+}
+
+#[test]
+fn test_compare() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let (src, dst) = (FakeHdfs::new(), FakeHdfs::new());
+    for f in &[&src, &dst] {
+        f.put("/data/same", b"0123");
+        f.put("/data/sub/same", b"4567");
+    }
+    src.put("/data/removed", b"x");
+    dst.put("/data/sub/added", b"y");
+    src.put("/data/size", b"abc");
+    dst.put("/data/size", b"abcd");
+    src.put("/data/content", b"abcd");
+    dst.put("/data/content", b"abce");
+    src.put("/data/touched", b"t");
+    dst.put("/data/touched", b"t");
+    dst.update("/data/touched", |e| e.mtime = 2);
+    src.put("/data/kind", b"k");
+    dst.mkdirs("/data/kind");
+
+    let (sc, dc) = (FakeCluster::start(&src), FakeCluster::start(&dst));
+    let a = sc.sync_builder().build().unwrap();
+    let b = dc.sync_builder().build().unwrap();
+    let d = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let c = |mode| compare_sync(&a, "/data", &b, "/data", mode, 2).unwrap().iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    assert_eq!(c(CompareMode::Checksum), d(&["* content", "* kind", "- removed", "* size", "+ sub/added"]));
+    assert_eq!(c(CompareMode::SizeAndMtime), d(&["* kind", "- removed", "* size", "+ sub/added", "* touched"]));
+    assert!(compare_sync(&a, "/missing", &b, "/data", CompareMode::Checksum, 2).is_err());
+}
@@ -13,6 +13,10 @@
 //! 3. If a file is found but is either unreadable or unparseable, then the library panics.
 //! 4. All the configuration fields are optional, except for the entrypoint.
 //! 5. Use `write_sample_config` to get config sample
+//! 6. NAT mappings may be given inline, as the `[natmap]` table, or read from a separate file with
+//!    `read_natmap_file`, which accepts `k=v` lines, JSON or TOML.
+//! 7. `read_config_async`, `read_config_opt_async` and `read_config_file_async` (used by 
+//!    `HdfsClientBuilder::from_config_async`) are equivalents reading files with `tokio::fs`, for use in async code.
 //! 
 use std::fs::read;
 use std::path::Path;
@@ -37,6 +41,12 @@ impl<'de> Deserialize<'de> for UriW {
     fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
     where D: Deserializer<'de> {
         let s: String = Deserialize::deserialize(deserializer)?;
+        #[cfg(unix)]
+        {
+            if let Some(r) = crate::unix::parse_socket_uri(&s) {
+                return r.map(UriW::new).map_err(serde::de::Error::custom)
+            }
+        }
         let uri: Uri = s.parse().map_err(serde::de::Error::custom)?;
         Ok(UriW { uri })
     }
@@ -49,7 +59,7 @@ impl Serialize for UriW {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HttpsConfig {
     pub danger_accept_invalid_certs: Option<bool>,
     pub danger_accept_invalid_hostnames: Option<bool>,
@@ -76,6 +86,9 @@ impl HttpsConfig {
     }
 }
 
+/// Placeholder for secrets in configurations returned by `HdfsClient::effective_config`
+pub const REDACTED: &str = "<redacted>";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub entrypoint: UriW,
@@ -84,10 +97,39 @@ pub struct Config {
     pub user_name: Option<String>,
     pub doas: Option<String>,
     pub dt: Option<String>,
+    /// Delegation token for datanode requests, if different from `dt`
+    pub datanode_dt: Option<String>,
     pub natmap: Option<HashMap<String, String>>,
-    pub https_config: Option<HttpsConfig>
+    pub https_config: Option<HttpsConfig>,
+    pub failover_on_connect_error: Option<bool>,
+    pub max_failovers: Option<u32>,
+    pub failover_backoff: Option<Duration>,
+    pub failover_backoff_max: Option<Duration>,
+    pub natmap_entrypoints: Option<bool>,
+    pub content_type_strictness: Option<crate::async_client::Strictness>,
+    pub default_port: Option<u16>,
+    pub dry_run: Option<bool>,
+    pub verify_create_options: Option<bool>,
+    /// `User-Agent` header; empty for none
+    pub user_agent: Option<String>,
+    /// `client.id` query parameter of namenode requests
+    pub client_id: Option<String>,
+    pub empty_response_strictness: Option<crate::async_client::Strictness>,
+    /// Handling of remote exceptions by name, in addition to `ExceptionClass::defaults`
+    pub exception_classes: Option<HashMap<String, crate::async_client::ExceptionClass>>,
+    /// Idle time before TCP keepalive probes are sent; keepalive is off if not set
+    pub tcp_keepalive: Option<Duration>,
+    /// Interval between TCP keepalive probes, `tcp_keepalive` if not set
+    pub tcp_keepalive_interval: Option<Duration>,
+    /// Time host name resolutions are cached for; not cached if not set
+    pub dns_cache_ttl: Option<Duration>,
+    /// Maximum number of cached host names, `DEFAULT_DNS_CACHE_MAX_ENTRIES` if not set
+    pub dns_cache_max_entries: Option<usize>
 }
 
+/// Default of `Config::dns_cache_max_entries`
+pub const DEFAULT_DNS_CACHE_MAX_ENTRIES: usize = 1024;
+
 impl Config {
     pub fn new(uri: Uri) -> Self {
         Self { 
@@ -97,12 +139,83 @@ impl Config {
             user_name: None,
             doas: None,
             dt: None,
+            datanode_dt: None,
             natmap: None,
-            https_config: None
+            https_config: None,
+            failover_on_connect_error: None,
+            max_failovers: None,
+            failover_backoff: None,
+            failover_backoff_max: None,
+            natmap_entrypoints: None,
+            content_type_strictness: None,
+            default_port: None,
+            dry_run: None,
+            verify_create_options: None,
+            user_agent: None,
+            client_id: None,
+            empty_response_strictness: None,
+            exception_classes: None,
+            tcp_keepalive: None,
+            tcp_keepalive_interval: None,
+            dns_cache_ttl: None,
+            dns_cache_max_entries: None
+        }
+    }
+
+    /// Same configuration with secrets (delegation tokens, TLS identity password) left out, e.g. for saving
+    /// a profile that must not contain them
+    pub fn without_secrets(self) -> Self {
+        Self {
+            dt: None,
+            datanode_dt: None,
+            https_config: self.https_config.map(|h| HttpsConfig { identity_password: None, ..h }),
+            ..self
         }
     }
 }
 
+#[test]
+fn test_config_without_secrets() {
+    let mut c = Config::new("http://localhost:7000".parse().unwrap());
+    let mut https = HttpsConfig::new();
+    https.identity_file = Some("id.p12".to_owned());
+    https.identity_password = Some("pw".to_owned());
+    c.https_config = Some(https);
+    c.dt = Some("token".to_owned());
+    c.datanode_dt = Some("dn-token".to_owned());
+    c.user_name = Some("u".to_owned());
+    let c: Config = toml::from_str(&config_to_toml(&c.without_secrets()).unwrap()).unwrap();
+    assert_eq!((c.dt, c.datanode_dt, c.user_name.as_deref()), (None, None, Some("u")));
+    let h = c.https_config.unwrap();
+    assert_eq!((h.identity_file.as_deref(), h.identity_password), (Some("id.p12"), None));
+}
+
+#[test]
+fn test_config_inline_natmap() {
+    let cfg_str = br#"
+entrypoint="http://localhost:7000"
+[natmap]
+"nn1.internal:50070"="localhost:51070"
+"#;
+    let c: Config = toml::from_slice(cfg_str).unwrap();
+    assert_eq!(c.natmap.unwrap()["nn1.internal:50070"], "localhost:51070")
+}
+
+#[test]
+fn test_config_exception_classes() {
+    use crate::async_client::ExceptionClass;
+    let cfg_str = br#"
+entrypoint="http://localhost:7000"
+[exception_classes]
+RetriableException="retry"
+"org.apache.hadoop.ipc.ObserverRetryOnActiveException"="failover"
+"#;
+    let c: Config = toml::from_slice(cfg_str).unwrap();
+    let classes = c.exception_classes.unwrap();
+    assert_eq!(classes["RetriableException"], ExceptionClass::Retry);
+    assert_eq!(classes["org.apache.hadoop.ipc.ObserverRetryOnActiveException"], ExceptionClass::Failover);
+}
+
 #[test]
 fn test_config() {
     let cfg_str = br#"
@@ -162,6 +275,30 @@ fn read_env_config() -> Result<Option<Config>> {
     }
 }
 
+/// Reads configuration from the file at `path`, which is JSON if it has the `.json` extension, and TOML otherwise
+pub fn read_config_file(path: &Path) -> Result<Config> {
+    match file_format(path) {
+        FileFormat::Json => Ok(serde_json::from_slice(&read(path)?)?),
+        _ => Ok(toml::from_slice(&read(path)?)?)
+    }
+}
+
+/// Structured file formats, detected by file extension
+#[derive(Debug, PartialEq)]
+enum FileFormat {
+    Json,
+    Toml,
+    Other
+}
+
+fn file_format(path: &Path) -> FileFormat {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("json") => FileFormat::Json,
+        Some("toml") => FileFormat::Toml,
+        _ => FileFormat::Other
+    }
+}
+
 pub fn read_config() -> Config {
     read_config_opt().expect("No valid webhdfs configuration file has been found")
 }
@@ -172,6 +309,52 @@ pub fn read_config_opt() -> Option<Config> {
     .or(read_user_config().expect("Configuration error (.webhdfs.toml in homedir)"))
 }
 
+async fn is_file_async(p: &Path) -> bool {
+    tokio::fs::metadata(p).await.map(|m| m.is_file()).unwrap_or(false)
+}
+
+async fn read_toml_async(p: &Path) -> Result<Config> {
+    Ok(toml::from_slice(&tokio::fs::read(p).await?)?)
+}
+
+/// Async version of `read_config_file`
+pub async fn read_config_file_async(path: &Path) -> Result<Config> {
+    let data = tokio::fs::read(path).await?;
+    match file_format(path) {
+        FileFormat::Json => Ok(serde_json::from_slice(&data)?),
+        _ => Ok(toml::from_slice(&data)?)
+    }
+}
+
+/// Async version of `read_config`
+pub async fn read_config_async() -> Config {
+    read_config_opt_async().await.expect("No valid webhdfs configuration file has been found")
+}
+
+/// Async version of `read_config_opt`
+pub async fn read_config_opt_async() -> Option<Config> {
+    if let Ok(f) = std::env::var("WEBHDFS_CONFIG") {
+        return Some(read_toml_async(Path::new(&f)).await
+            .expect("Configuration error (file specified by WEBHDFS_CONFIG environment var)"))
+    }
+    let local = Path::new("webhdfs.toml");
+    if is_file_async(local).await {
+        return Some(read_toml_async(local).await.expect("Configuration error (webhdfs.toml in CWD)"))
+    }
+    match get_home_dir().map(|h| Path::new(&h).join(".webhdfs.toml")) {
+        Some(p) if is_file_async(&p).await => 
+            Some(read_toml_async(&p).await.expect("Configuration error (.webhdfs.toml in homedir)")),
+        _ => None
+    }
+}
+
+/// Serializes configuration to TOML
+pub fn config_to_toml(c: &Config) -> Result<String> {
+    // via Value, which places tables (natmap, https_config, durations) after plain values, as TOML requires
+    let e = |e: toml::ser::Error| app_error!(generic "Cannot serialize configuration: {}", e);
+    toml::Value::try_from(c).and_then(|v| toml::to_string(&v)).map_err(e)
+}
+
 pub fn write_config(path: &Path, c: &Config, new_file: bool) {
     use std::io::Write;
     let mut f = std::fs::OpenOptions::new()
@@ -180,7 +363,7 @@ pub fn write_config(path: &Path, c: &Config, new_file: bool) {
         .create_new(new_file)
         .open(path)
         .unwrap();
-    f.write_all(&toml::to_vec(c).unwrap()).unwrap();
+    f.write_all(config_to_toml(c).unwrap().as_bytes()).unwrap();
 }
 /*
 pub fn write_sample_config() {
@@ -238,5 +421,45 @@ pub fn read_kv_file(path: &str) -> Result<HashMap<String, String>> {
     read_kv_lines(std::fs::File::open(path).aerr("cannot open natmap")?)
 }
 
+/// Reads NAT mappings from a file. A `.json` file must hold a JSON object, and a `.toml` file a TOML table,
+/// of string values; any other file is read as "key=value" lines (see `read_kv_file`).
+pub fn read_natmap_file(path: &str) -> Result<HashMap<String, String>> {
+    match file_format(Path::new(path)) {
+        FileFormat::Json => Ok(serde_json::from_slice(&read(path).aerr("cannot open natmap")?)?),
+        FileFormat::Toml => Ok(toml::from_slice(&read(path).aerr("cannot open natmap")?)?),
+        FileFormat::Other => read_kv_file(path)
+    }
+}
+
+#[test]
+fn test_read_config_file_async() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let p = std::env::temp_dir().join(format!("webhdfs-test-config-{}.json", std::process::id()));
+    std::fs::write(&p, br#"{"entrypoint": "http://localhost:7000", "user_name": "us"}"#).unwrap();
+    let r = rt.block_on(read_config_file_async(&p));
+    std::fs::remove_file(&p).unwrap();
+    let c = r.unwrap();
+    assert_eq!((c.entrypoint.uri.to_string().as_str(), c.user_name.as_deref()), ("http://localhost:7000/", Some("us")));
+    assert!(rt.block_on(read_config_file_async(&p)).is_err());
+}
+
+#[test]
+fn test_read_natmap_file() {
+    let dir = std::env::temp_dir();
+    let p = |ext: &str| dir.join(format!("webhdfs-test-natmap-{}.{}", std::process::id(), ext));
+    let files = [
+        (p("json"), &br#"{"nn1.internal:50070": "localhost:51070"}"#[..]),
+        (p("toml"), &br#""nn1.internal:50070" = "localhost:51070""#[..]),
+        (p("txt"), &b"nn1.internal:50070=localhost:51070\n"[..])
+    ];
+    for (path, content) in &files {
+        std::fs::write(path, content).unwrap();
+        let r = read_natmap_file(path.to_str().unwrap());
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(r.unwrap()["nn1.internal:50070"], "localhost:51070");
+    }
+    assert_eq!(file_format(Path::new("/etc/NATMAP.JSON")), FileFormat::Json);
+}
+
 
 
@@ -1,4 +1,6 @@
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::collections::{HashMap, BTreeMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize};
 
 /*
@@ -25,17 +27,27 @@ pub struct RemoteExceptionResponse {
 #[derive(Debug, Deserialize)]
 pub struct RemoteException {
     pub exception: String,
-    #[serde(rename="javaClassName")]
+    #[serde(rename="javaClassName", default)]
     pub java_class_name: String,
-    pub message: String
+    #[serde(default)]
+    pub message: String,
+    /// Fields not listed above (e.g. `exceptionId` or nested causes, added by some distributions).
+    /// Boxed to keep `Error` small.
+    #[serde(flatten)]
+    pub extra: Box<HashMap<String, serde_json::Value>>
 }
 
 impl Display for RemoteException {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, 
-            "RemoteException[exception={}, java_class_name={}, msg='{}']", 
+            "RemoteException[exception={}, java_class_name={}, msg='{}'", 
             self.exception, self.java_class_name, self.message
-        )
+        )?;
+        if !self.extra.is_empty() {
+            let extra: BTreeMap<_, _> = self.extra.iter().collect();
+            write!(f, ", extra={}", serde_json::to_string(&extra).unwrap_or_default())?;
+        }
+        write!(f, "]")
     }
 }
 
@@ -43,6 +55,17 @@ impl std::error::Error for RemoteException {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
 }
 
+/*
+{"Location":"http://<DATANODE>:<PORT>/webhdfs/v1/<PATH>?op=CREATE..."}
+*/
+
+/// Namenode response to a two-step operation sent with `noredirect=true`
+#[derive(Debug, Deserialize)]
+pub struct LocationResponse {
+    #[serde(rename="Location")]
+    pub location: String
+}
+
 
 /*
 {
@@ -92,42 +115,138 @@ pub struct FileStatuses {
     pub file_status: Vec<FileStatus>
 }
 
+/// Status of a file or directory. Fields other than `length` and `type` are zero or empty if missing
+/// from the response.
 #[derive(Debug, Deserialize)]
 pub struct FileStatus {
     //"accessTime"      : 1320171722771,
-    #[serde(rename="accessTime")]
+    #[serde(rename="accessTime", default)]
     pub access_time: i64,
 
     //"blockSize"       : 33554432,
-    #[serde(rename="blockSize")]
+    #[serde(rename="blockSize", default)]
     pub block_size: i64,
 
     //"group"           : "supergroup",
+    #[serde(default)]
     pub group: String,
 
     //"length"          : 24930,
     pub length: i64,
 
     //"modificationTime": 1320171722771,
-    #[serde(rename="modificationTime")]
+    #[serde(rename="modificationTime", default)]
     pub modification_time: i64,
 
     //"owner"           : "webuser",
+    #[serde(default)]
     pub owner: String,
 
     //"pathSuffix"      : "a.patch",
-    #[serde(rename="pathSuffix")]
+    #[serde(rename="pathSuffix", default)]
     pub path_suffix: String,
 
     //"permission"      : "644",
+    #[serde(default)]
     pub permission: String,
 
     //"replication"     : 1,
+    #[serde(default)]
     pub replication: i32,
 
     //"type"            : "FILE"
     #[serde(rename="type")]
-    pub type_: String
+    pub type_: String,
+
+    //"encBit"          : true (Hadoop 2.8+, present only if set)
+    #[serde(rename="encBit", default)]
+    pub enc_bit: bool,
+
+    //"feInfo"          : {...} (Hadoop 3.1+, files in encryption zones)
+    #[serde(rename="feInfo", default)]
+    pub fe_info: Option<FileEncryptionInfo>,
+
+    //"symlink"         : "/target" (symbolic links only)
+    #[serde(default)]
+    pub symlink: Option<String>
+}
+
+/// Time of a timestamp in milliseconds since the epoch, as found in `FileStatus`
+pub fn system_time(ms: i64) -> SystemTime { UNIX_EPOCH + Duration::from_millis(ms.max(0) as u64) }
+
+/// Formats a timestamp in milliseconds since the epoch as ISO 8601 UTC time, with second precision
+/// (e.g. `2024-02-29T12:30:05Z`), independently of the locale and time zone
+pub fn iso8601(ms: i64) -> String {
+    let (days, secs) = (ms.div_euclid(86_400_000), ms.rem_euclid(86_400_000) / 1000);
+    // civil date from days since 1970-01-01 (proleptic Gregorian)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl FileStatus {
+    /// `length`, in bytes
+    pub fn size(&self) -> u64 { self.length.max(0) as u64 }
+    /// `modificationTime`
+    pub fn modified(&self) -> SystemTime { system_time(self.modification_time) }
+    /// `accessTime`
+    pub fn accessed(&self) -> SystemTime { system_time(self.access_time) }
+    /// Parsed `permission`
+    pub fn permission_bits(&self) -> crate::error::Result<crate::permission::Permission> { self.permission.parse() }
+    /// Whether the file is encrypted, or the directory is in an encryption zone
+    pub fn is_encrypted(&self) -> bool { self.enc_bit || self.fe_info.is_some() }
+    /// Checks that file `path`, previously described by this status, has the same length and modification time
+    /// as `actual` (a fresh status)
+    pub fn check_unchanged(&self, path: &str, actual: &FileStatus) -> Result<(), SourceChanged> {
+        if self.length == actual.length && self.modification_time == actual.modification_time {
+            Ok(())
+        } else {
+            Err(SourceChanged { 
+                path: path.to_owned(), 
+                expected_length: self.length, expected_modification_time: self.modification_time,
+                actual_length: actual.length, actual_modification_time: actual.modification_time
+            })
+        }
+    }
+}
+
+/*
+"feInfo":
+{
+  "cipherSuite"          : "AES_CTR_NOPADDING",
+  "cryptoProtocolVersion": "ENCRYPTION_ZONES",
+  "edek"                 : "...",
+  "ezKeyVersionName"     : "key1@0",
+  "iv"                   : "...",
+  "keyName"              : "key1"
+}
+*/
+
+/// Encryption info of a file in an encryption zone
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct FileEncryptionInfo {
+    #[serde(rename="cipherSuite", default)]
+    pub cipher_suite: String,
+    #[serde(rename="cryptoProtocolVersion", default)]
+    pub crypto_protocol_version: String,
+    /// Name of the encryption zone key
+    #[serde(rename="keyName", default)]
+    pub key_name: String,
+    #[serde(rename="ezKeyVersionName", default)]
+    pub ez_key_version_name: String,
+    /// Encrypted data encryption key, base64-encoded
+    #[serde(default)]
+    pub edek: String,
+    /// Initialization vector, base64-encoded
+    #[serde(default)]
+    pub iv: String
 }
 
 /*
@@ -173,11 +292,522 @@ HTTP/1.1 200 OK
 Content-Type: application/json
 Transfer-Encoding: chunked
 
+{
+  "ContentSummary":
+  {
+    "directoryCount": 2,
+    "fileCount"     : 1,
+    "length"        : 24930,
+    "quota"         : -1,
+    "spaceConsumed" : 24930,
+    "spaceQuota"    : -1
+  }
+}
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct ContentSummaryResponse {
+    #[serde(rename="ContentSummary")]
+    pub content_summary: ContentSummary
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentSummary {
+    #[serde(rename="directoryCount", default)]
+    pub directory_count: i64,
+    #[serde(rename="fileCount", default)]
+    pub file_count: i64,
+    /// Total length of all files, in bytes
+    pub length: i64,
+    /// Namespace quota, -1 if not set
+    #[serde(default="no_quota")]
+    pub quota: i64,
+    #[serde(rename="spaceConsumed", default)]
+    pub space_consumed: i64,
+    /// Space quota, -1 if not set
+    #[serde(rename="spaceQuota", default="no_quota")]
+    pub space_quota: i64
+}
+
+fn no_quota() -> i64 { -1 }
+
+/// Quota value leaving the quota unchanged (see `HdfsClient::set_quota`)
+pub const QUOTA_DONT_SET: i64 = i64::MAX;
+/// Quota value clearing the quota (see `HdfsClient::set_quota`)
+pub const QUOTA_RESET: i64 = -1;
+
+impl ContentSummary {
+    /// Checks whether adding `files` files or directories, and `length` bytes stored with `replication` 
+    /// replicas, stays within the quotas of directory `path` described by this summary
+    pub fn check_quota(&self, path: &str, length: u64, files: u64, replication: u16) -> Result<(), QuotaExceeded> {
+        let exceeded = |kind, quota, used, requested| QuotaExceeded { path: path.to_owned(), kind, quota, used, requested };
+        let used = self.file_count + self.directory_count;
+        if self.quota >= 0 && used + files as i64 > self.quota {
+            return Err(exceeded(QuotaKind::Namespace, self.quota, used, files as i64))
+        }
+        let requested = length as i64 * replication as i64;
+        if self.space_quota >= 0 && self.space_consumed + requested > self.space_quota {
+            return Err(exceeded(QuotaKind::Space, self.space_quota, self.space_consumed, requested))
+        }
+        Ok(())
+    }
+}
+
+/// Kind of a directory quota
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaKind {
+    /// Number of files and directories
+    Namespace,
+    /// Raw (replicated) bytes
+    Space
+}
+
+/// A write would exceed a directory quota (see `HdfsClient::check_quota`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaExceeded {
+    pub path: String,
+    pub kind: QuotaKind,
+    pub quota: i64,
+    /// Amount of the quota already used
+    pub used: i64,
+    /// Amount the write requires
+    pub requested: i64
+}
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?} quota of {} exceeded: quota={}, used={}, requested={}", 
+            self.kind, self.path, self.quota, self.used, self.requested
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// A CREATE option was not honored by the server (see `HdfsClientBuilder::verify_create_options`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateOptionIgnored {
+    pub path: String,
+    /// `"replication"` or `"blocksize"`
+    pub option: &'static str,
+    pub requested: i64,
+    pub actual: i64
+}
+
+impl Display for CreateOptionIgnored {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} of {} not honored: requested={}, actual={}", self.option, self.path, self.requested, self.actual)
+    }
+}
+
+impl std::error::Error for CreateOptionIgnored {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// A file changed while being downloaded in parts (see `HdfsClient::resume_to`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceChanged {
+    pub path: String,
+    pub expected_length: i64,
+    pub expected_modification_time: i64,
+    pub actual_length: i64,
+    pub actual_modification_time: i64
+}
+
+impl Display for SourceChanged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} changed: expected length={}, mtime={}; actual length={}, mtime={}", self.path, 
+            self.expected_length, self.expected_modification_time, self.actual_length, self.actual_modification_time
+        )
+    }
+}
+
+impl std::error::Error for SourceChanged {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// A write failed because the lease on the file is held by another client, or is being recovered
+/// (see `HdfsClientBuilder::lease_retry`)
+#[derive(Debug)]
+pub struct LeaseConflict {
+    pub path: String,
+    /// Current lease holder, if reported by the server
+    pub holder: Option<String>,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// `AlreadyBeingCreatedException` or `RecoveryInProgressException` reported by the server
+    pub exception: RemoteException
+}
+
+impl LeaseConflict {
+    pub fn new(path: String, attempts: u32, exception: RemoteException) -> Self {
+        //"... because this file lease is currently owned by DFSClient_NONMAPREDUCE_1_1 on 10.0.0.1"
+        let holder = exception.message.split("currently owned by ").nth(1)
+            .map(|h| h.trim_end_matches(|c: char| c == '.' || c.is_whitespace()).to_owned());
+        Self { path, holder, attempts, exception }
+    }
+}
+
+impl Display for LeaseConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "lease on {} ", self.path)?;
+        match &self.holder {
+            Some(h) => write!(f, "is held by {}", h)?,
+            None => write!(f, "is not available ({})", self.exception.exception)?
+        }
+        write!(f, " after {} attempt(s)", self.attempts)
+    }
+}
+
+impl std::error::Error for LeaseConflict {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { Some(&self.exception) }
+}
+
+/*
+HTTP/1.1 200 OK
+Content-Type: application/json
+Transfer-Encoding: chunked
+
+{
+  "FileChecksum":
+  {
+    "algorithm": "MD5-of-1MD5-of-512CRC32",
+    "bytes"    : "eadb10de24aa315748930df6e185c0d ...",
+    "length"   : 28
+  }
+}
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct FileChecksumResponse {
+    #[serde(rename="FileChecksum")]
+    pub file_checksum: FileChecksum
+}
+
+/// File checksum. Checksums are only comparable if they have the same algorithm, which depends on
+/// block size and bytes per checksum of the file.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct FileChecksum {
+    pub algorithm: String,
+    pub bytes: String,
+    pub length: i64
+}
+
+impl FileChecksum {
+    /// Checksum type the algorithm is based on (`CRC32` or `CRC32C`), e.g. to compare with 
+    /// `ServerDefaults::checksum_type`
+    pub fn checksum_type(&self) -> Option<ChecksumType> {
+        if self.algorithm.ends_with("CRC32C") {
+            Some(ChecksumType::Crc32c)
+        } else if self.algorithm.ends_with("CRC32") {
+            Some(ChecksumType::Crc32)
+        } else {
+            None
+        }
+    }
+}
+
+/*
+HTTP/1.1 200 OK
+Content-Type: application/json
+Transfer-Encoding: chunked
+
+{
+  "SnapshotDiffReport":
+  {
+    "diffList":
+    [
+      {"sourcePath": "bar", "type": "CREATE"},
+      {"sourcePath": "foo", "targetPath": "baz", "type": "RENAME"}
+    ],
+    "fromSnapshot": "s3",
+    "snapshotRoot": "/foo",
+    "toSnapshot"  : "s4"
+  }
+}
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotDiffReportResponse {
+    #[serde(rename="SnapshotDiffReport")]
+    pub snapshot_diff_report: SnapshotDiffReport
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotDiffReport {
+    #[serde(rename="diffList")]
+    pub diff_list: Vec<SnapshotDiffEntry>,
+    /// Older snapshot name; empty for the current state of the directory
+    #[serde(rename="fromSnapshot")]
+    pub from_snapshot: String,
+    #[serde(rename="snapshotRoot")]
+    pub snapshot_root: String,
+    /// Newer snapshot name; empty for the current state of the directory
+    #[serde(rename="toSnapshot")]
+    pub to_snapshot: String
+}
+
+/// A changed path. Paths are relative to the snapshot root; the root itself is `""`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SnapshotDiffEntry {
+    #[serde(rename="sourcePath")]
+    pub source_path: String,
+    /// New path of a renamed entry (`RENAME` only)
+    #[serde(rename="targetPath", default)]
+    pub target_path: Option<String>,
+    /// One of `snapshot_diff_type` values
+    #[serde(rename="type")]
+    pub type_: String
+}
+
+/// Values of `SnapshotDiffEntry.type`
+pub mod snapshot_diff_type {
+    pub const CREATE: &str = "CREATE";
+    pub const MODIFY: &str = "MODIFY";
+    pub const DELETE: &str = "DELETE";
+    pub const RENAME: &str = "RENAME";
+}
+
+/*
+HTTP/1.1 200 OK
+Content-Type: application/json
+Transfer-Encoding: chunked
+
+{
+  "SnapshottableDirectoryList":
+  [
+    {
+      "dirStatus": {"accessTime": 0, "blockSize": 0, "childrenNum": 0, "fileId": 16386, "group": "supergroup",
+        "length": 0, "modificationTime": 1520761889225, "owner": "root", "pathSuffix": "bar", "permission": "755",
+        "replication": 0, "storagePolicy": 0, "type": "DIRECTORY"},
+      "parentFullPath": "/",
+      "snapshotNumber": 0,
+      "snapshotQuota": 65536
+    }
+  ]
+}
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshottableDirectoryListResponse {
+    #[serde(rename="SnapshottableDirectoryList")]
+    pub snapshottable_directory_list: Vec<SnapshottableDirectoryStatus>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshottableDirectoryStatus {
+    #[serde(rename="dirStatus")]
+    pub dir_status: FileStatus,
+    #[serde(rename="parentFullPath")]
+    pub parent_full_path: String,
+    #[serde(rename="snapshotNumber", default)]
+    pub snapshot_number: i32,
+    #[serde(rename="snapshotQuota")]
+    pub snapshot_quota: i32
+}
+
+impl SnapshottableDirectoryStatus {
+    /// Absolute path of the snapshottable directory
+    pub fn path(&self) -> String { crate::path::join(&self.parent_full_path, &self.dir_status.path_suffix) }
+}
+
+/*
+HTTP/1.1 200 OK
+Content-Type: application/json
+Transfer-Encoding: chunked
+
+{
+  "FsServerDefaults":
+  {
+    "replication": 3, "encryptDataTransfer": "false", "defaultStoragePolicyId": 7, "writePacketSize": 65536,
+    "fileBufferSize": 4096, "checksumType": 2, "trashInterval": 10080, "keyProviderUri": "", 
+    "blockSize": 134217728, "bytesPerChecksum": 512
+  }
+}
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct ServerDefaultsResponse {
+    #[serde(rename="FsServerDefaults")]
+    pub server_defaults: ServerDefaults
+}
+
+/// Defaults the server applies to new files (see `HdfsClient::server_defaults`). Fields other than `block_size` 
+/// and `replication` are `None` if the defaults have been derived from a probe file.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ServerDefaults {
+    #[serde(rename="blockSize")]
+    pub block_size: i64,
+    pub replication: i32,
+    #[serde(rename="bytesPerChecksum", default)]
+    pub bytes_per_checksum: Option<i32>,
+    /// Hadoop `DataChecksum.Type` id, see `checksum_type_name`
+    #[serde(rename="checksumType", default)]
+    pub checksum_type: Option<i32>,
+    #[serde(rename="fileBufferSize", default)]
+    pub file_buffer_size: Option<i32>,
+    /// Size of packets datanodes write in, in bytes
+    #[serde(rename="writePacketSize", default)]
+    pub write_packet_size: Option<i32>
+}
+
+/// Hadoop `DataChecksum.Type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumType {
+    Null,
+    Crc32,
+    Crc32c,
+    Default,
+    Mixed
+}
+
+impl ChecksumType {
+    /// Type of a `DataChecksum.Type` id, as found in `ServerDefaults.checksum_type`
+    pub fn from_id(id: i32) -> Option<Self> {
+        match id {
+            0 => Some(Self::Null),
+            1 => Some(Self::Crc32),
+            2 => Some(Self::Crc32c),
+            3 => Some(Self::Default),
+            4 => Some(Self::Mixed),
+            _ => None
+        }
+    }
+
+    /// `NULL`, `CRC32`, `CRC32C`, `DEFAULT` or `MIXED`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Null => "NULL",
+            Self::Crc32 => "CRC32",
+            Self::Crc32c => "CRC32C",
+            Self::Default => "DEFAULT",
+            Self::Mixed => "MIXED"
+        }
+    }
+}
+
+impl ServerDefaults {
+    /// Typed `checksum_type`, if known
+    pub fn checksum_type(&self) -> Option<ChecksumType> { self.checksum_type.and_then(ChecksumType::from_id) }
+
+    /// Name of the checksum type (`CRC32`, `CRC32C`, ...), if known
+    pub fn checksum_type_name(&self) -> Option<&'static str> { self.checksum_type().map(ChecksumType::name) }
+
+    /// CREATE options requesting the default block size and replication explicitly, e.g. to be adjusted
+    /// and passed to `HdfsClient::create`
+    pub fn create_options(&self) -> crate::op::CreateOptions {
+        crate::op::CreateOptions::new().blocksize(self.block_size).replication(self.replication as i16)
+    }
+}
+
+/*
+HTTP/1.1 200 OK
+Content-Type: application/json
+Transfer-Encoding: chunked
+
 {"boolean": true}
 */
 
+/// Response to GETHOMEDIRECTORY
+#[derive(Debug, Deserialize)]
+pub struct PathResponse {
+    #[serde(rename="Path")]
+    pub path: String
+}
+
 /// Response to MKDIRS, DELETE, RENAME, TRUNCATE
 #[derive(Debug, Deserialize)]
 pub struct Boolean {
     pub boolean: bool
 }
+
+#[test]
+fn test_remote_exception_extra_fields() {
+    let r: RemoteExceptionResponse = serde_json::from_str(r#"{"RemoteException":{
+        "exception":"AccessControlException",
+        "javaClassName":"org.apache.hadoop.security.AccessControlException",
+        "message":"Permission denied",
+        "exceptionId":"a1b2",
+        "cause":{"exception":"IOException"}
+    }}"#).unwrap();
+    let e = r.remote_exception;
+    assert_eq!(e.message, "Permission denied");
+    assert_eq!(e.extra["exceptionId"], "a1b2");
+    assert_eq!(e.extra["cause"]["exception"], "IOException");
+    assert!(e.to_string().ends_with(r#", extra={"cause":{"exception":"IOException"},"exceptionId":"a1b2"}]"#));
+
+    let r: RemoteExceptionResponse = serde_json::from_str(r#"{"RemoteException":{"exception":"StandbyException"}}"#).unwrap();
+    assert_eq!(r.remote_exception.to_string(), "RemoteException[exception=StandbyException, java_class_name=, msg='']");
+    let err: crate::error::Error = r.remote_exception.into();
+    assert_eq!(err.remote_exception().unwrap().exception, "StandbyException");
+}
+
+#[test]
+fn test_check_quota() {
+    let s = ContentSummary { 
+        directory_count: 1, file_count: 2, length: 100, quota: 10, space_consumed: 300, space_quota: 1000
+    };
+    assert!(s.check_quota("/q", 100, 7, 3).is_ok());
+    let e = s.check_quota("/q", 100, 8, 3).unwrap_err();
+    assert_eq!((e.kind, e.used, e.requested), (QuotaKind::Namespace, 3, 8));
+    let e = s.check_quota("/q", 300, 1, 3).unwrap_err();
+    assert_eq!((e.kind, e.quota, e.used, e.requested), (QuotaKind::Space, 1000, 300, 900));
+    let unlimited = ContentSummary { quota: -1, space_quota: -1, ..s };
+    assert!(unlimited.check_quota("/q", 1 << 40, 1 << 20, 3).is_ok());
+}
+
+#[test]
+fn test_snapshot_responses() {
+    let r: SnapshotDiffReportResponse = serde_json::from_str(r#"{"SnapshotDiffReport":{"diffList":[
+        {"sourcePath":"","type":"MODIFY"},{"sourcePath":"bar","type":"CREATE"},{"sourcePath":"foo","targetPath":"baz","type":"RENAME"}
+    ],"fromSnapshot":"s3","snapshotRoot":"/foo","toSnapshot":"s4"}}"#).unwrap();
+    let r = r.snapshot_diff_report;
+    assert_eq!((r.from_snapshot.as_str(), r.to_snapshot.as_str(), r.snapshot_root.as_str()), ("s3", "s4", "/foo"));
+    assert_eq!(r.diff_list[1].target_path, None);
+    assert_eq!(r.diff_list[2], SnapshotDiffEntry { 
+        source_path: "foo".to_owned(), target_path: Some("baz".to_owned()), type_: snapshot_diff_type::RENAME.to_owned()
+    });
+
+    let r: SnapshottableDirectoryListResponse = serde_json::from_str(r#"{"SnapshottableDirectoryList":[{"dirStatus":
+        {"accessTime":0,"blockSize":0,"childrenNum":0,"fileId":16386,"group":"supergroup","length":0,
+        "modificationTime":1520761889225,"owner":"root","pathSuffix":"bar","permission":"755","replication":0,
+        "storagePolicy":0,"type":"DIRECTORY"},"parentFullPath":"/","snapshotNumber":2,"snapshotQuota":65536}]}"#).unwrap();
+    let d = &r.snapshottable_directory_list[0];
+    assert_eq!(d.path(), "/bar");
+    assert_eq!((d.snapshot_number, d.snapshot_quota), (2, 65536));
+}
+
+#[test]
+fn test_file_encryption_info() {
+    let r: FileStatusResponse = serde_json::from_str(r#"{"FileStatus":{"accessTime":0,"blockSize":134217728,
+        "group":"supergroup","length":4,"modificationTime":1,"owner":"webuser","pathSuffix":"","permission":"644",
+        "replication":3,"type":"FILE","encBit":true,"feInfo":{"cipherSuite":"AES_CTR_NOPADDING",
+        "cryptoProtocolVersion":"ENCRYPTION_ZONES","edek":"ZWRlaw==","ezKeyVersionName":"key1@0","iv":"aXY=","keyName":"key1"}}}"#
+    ).unwrap();
+    let fs = r.file_status;
+    assert!(fs.is_encrypted());
+    assert_eq!(fs.fe_info.unwrap().key_name, "key1");
+
+    let r: FileStatusResponse = serde_json::from_str(&crate::mock_server::file_status_json(dirent_type::FILE, 4)).unwrap();
+    assert!(!r.file_status.is_encrypted());
+}
+
+#[test]
+fn test_iso8601() {
+    assert_eq!(iso8601(0), "1970-01-01T00:00:00Z");
+    assert_eq!(iso8601(1709209805123), "2024-02-29T12:30:05Z");
+    assert_eq!(iso8601(946684799999), "1999-12-31T23:59:59Z");
+    assert_eq!(iso8601(-1000), "1969-12-31T23:59:59Z");
+}
+
+#[test]
+fn test_checksum_type() {
+    let c = |algorithm: &str| FileChecksum { algorithm: algorithm.to_owned(), bytes: String::new(), length: 28 };
+    assert_eq!(c("MD5-of-0MD5-of-512CRC32C").checksum_type(), Some(ChecksumType::Crc32c));
+    assert_eq!(c("MD5-of-1MD5-of-512CRC32").checksum_type(), Some(ChecksumType::Crc32));
+    assert_eq!(c("SHA-256").checksum_type(), None);
+    assert_eq!(ChecksumType::from_id(2).map(ChecksumType::name), Some("CRC32C"));
+    assert_eq!(ChecksumType::from_id(5), None);
+}
@@ -0,0 +1,164 @@
+//! Duplicate detection for incremental ingestion.
+//!
+//! `dedupe_check` tells whether a local file is already present in HDFS, so that uploading it again can be skipped.
+//! Sizes are compared first. If they match, the checksum of the HDFS file (GETFILECHECKSUM, `MD5-of-MD5-of-CRC32`
+//! or `...CRC32C`) is compared to the same checksum computed over the local file with the block size of the HDFS
+//! file; the local file is read in full, the HDFS file is not read at all. If the checksum is not available (e.g.
+//! composite CRC checksums, or a gateway not supporting GETFILECHECKSUM), a few ranges of both files are compared
+//! instead, which is cheap but cannot detect changes outside the sampled ranges.
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use log::debug;
+use md5::{Md5, Digest};
+use crate::error::*;
+use crate::datatypes::dirent_type;
+use crate::sync_client::{SyncHdfsClient, ReadHdfsFile};
+
+/// Size of each range compared by sampling (at the start, in the middle and at the end of the file)
+pub const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Result of `dedupe_check`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupeCheck {
+    /// There is no file at the HDFS path
+    Missing,
+    /// The files differ in type, size, checksum or sampled content
+    Different,
+    /// The files have the same size and checksum (or are both empty)
+    Identical,
+    /// The files have the same size and sampled content (the checksum is not available)
+    SameSample
+}
+
+impl DedupeCheck {
+    /// Whether uploading the file can be skipped
+    pub fn is_duplicate(&self) -> bool { matches!(self, Self::Identical | Self::SameSample) }
+}
+
+/// Parameters of an `MD5-of-<n>MD5-of-<bytes per CRC>CRC32[C]` checksum
+#[derive(Debug, PartialEq)]
+struct Md5Md5Crc {
+    bytes_per_crc: usize,
+    crc32c: bool
+}
+
+fn parse_algorithm(algorithm: &str) -> Option<Md5Md5Crc> {
+    let (n, rest) = algorithm.strip_prefix("MD5-of-")?.split_once("MD5-of-")?;
+    n.parse::<u64>().ok()?;
+    let (bpc, crc32c) = match rest.strip_suffix("CRC32C") {
+        Some(bpc) => (bpc, true),
+        None => (rest.strip_suffix("CRC32")?, false)
+    };
+    Some(Md5Md5Crc { bytes_per_crc: bpc.parse().ok().filter(|n| *n > 0)?, crc32c })
+}
+
+/// Computes the checksum of `len` bytes of `input` as HDFS does for a file of block size `block_size`:
+/// MD5 of the MD5s of blocks, where the MD5 of a block is over CRCs of its `bytes_per_crc` chunks.
+/// Returns hex-encoded bytes, as reported by GETFILECHECKSUM.
+fn local_checksum(input: &mut impl Read, len: u64, block_size: u64, a: &Md5Md5Crc) -> Result<String> {
+    let crc_per_block = if len > block_size { block_size / a.bytes_per_crc as u64 } else { 0 };
+    let mut file_md5 = Md5::new();
+    let mut chunk = vec![0u8; a.bytes_per_crc];
+    let mut remaining = len;
+    while remaining > 0 {
+        let mut block_md5 = Md5::new();
+        let mut in_block = remaining.min(block_size);
+        remaining -= in_block;
+        while in_block > 0 {
+            let n = in_block.min(chunk.len() as u64) as usize;
+            input.read_exact(&mut chunk[..n])?;
+            let crc = if a.crc32c { crc32c::crc32c(&chunk[..n]) } else { crc32fast::hash(&chunk[..n]) };
+            block_md5.update(crc.to_be_bytes());
+            in_block -= n as u64;
+        }
+        file_md5.update(block_md5.finalize());
+    }
+    let mut bytes = (a.bytes_per_crc as i32).to_be_bytes().to_vec();
+    bytes.extend(&(crc_per_block as i64).to_be_bytes());
+    bytes.extend(file_md5.finalize());
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Checks whether local file `local_path` has already been uploaded to `hdfs_path`
+pub fn dedupe_check(cx: &mut SyncHdfsClient, local_path: impl AsRef<Path>, hdfs_path: &str) -> Result<DedupeCheck> {
+    let len = std::fs::metadata(local_path.as_ref())?.len();
+    let status = match cx.stat(hdfs_path) {
+        Ok(s) => s.file_status,
+        Err(e) if e.io_kind() == std::io::ErrorKind::NotFound => return Ok(DedupeCheck::Missing),
+        Err(e) => return Err(e)
+    };
+    if status.type_ != dirent_type::FILE || status.length != len as i64 { return Ok(DedupeCheck::Different) }
+    if len == 0 { return Ok(DedupeCheck::Identical) }
+
+    let mut local = File::open(local_path.as_ref())?;
+    match cx.checksum(hdfs_path) {
+        Ok(c) => match parse_algorithm(&c.file_checksum.algorithm) {
+            Some(a) if status.block_size > 0 => {
+                let l = local_checksum(&mut BufReader::new(local), len, status.block_size as u64, &a)?;
+                return Ok(if l.eq_ignore_ascii_case(&c.file_checksum.bytes) { DedupeCheck::Identical } else { DedupeCheck::Different })
+            }
+            _ => debug!("Unsupported checksum algorithm {} of {}, comparing samples", c.file_checksum.algorithm, hdfs_path)
+        }
+        Err(e) => debug!("No checksum of {} ({}), comparing samples", hdfs_path, e)
+    }
+
+    let remote = ReadHdfsFile::open(cx.clone(), hdfs_path.to_owned())?;
+    let n = SAMPLE_SIZE.min(len as usize);
+    let (mut a, mut b) = (vec![0u8; n], vec![0u8; n]);
+    for offset in [0, (len - n as u64) / 2, len - n as u64] {
+        local.seek(SeekFrom::Start(offset))?;
+        local.read_exact(&mut a)?;
+        remote.read_exact_at(offset, &mut b)?;
+        if a != b { return Ok(DedupeCheck::Different) }
+    }
+    Ok(DedupeCheck::SameSample)
+}
+
+#[test]
+fn test_dedupe_check() {
+    use crate::mock_server::*;
+    assert_eq!(parse_algorithm("MD5-of-0MD5-of-512CRC32C"), Some(Md5Md5Crc { bytes_per_crc: 512, crc32c: true }));
+    assert_eq!(parse_algorithm("MD5-of-262144MD5-of-512CRC32"), Some(Md5Md5Crc { bytes_per_crc: 512, crc32c: false }));
+    assert_eq!(parse_algorithm("COMPOSITE-CRC32C"), None);
+
+    let small = b"hello, world\n".repeat(100);
+    let big: Vec<u8> = (0..2500u32).map(|i| (i * 7 % 251) as u8).collect();
+    let f = FakeHdfs::new();
+    f.put("/small", &small);
+    f.put("/big", &big);
+    f.put("/other", &small);
+    let fs = f.clone();
+    //real checksums of /small and /big (block size 1024), computed independently; FakeHdfs stand-in for others
+    let c = FakeCluster::with_server(MockServer::start(move |r| match (r.op(), r.path()) {
+        ("GETFILESTATUS", "/webhdfs/v1/big") => MockResponse::json(200, &file_status_json(dirent_type::FILE, 2500).replace("134217728", "1024")),
+        ("GETFILECHECKSUM", p) if r.param("datanode").is_some() && p != "/webhdfs/v1/other" => {
+            let (n, bytes) = if p.ends_with("/big") { (2, "0000020000000000000000025cdec83a6137f94ae0a55ebe6dd1cda5") } 
+                else { (0, "000002000000000000000000b39234df5b8fd85437d3a89b2697bbcf") };
+            MockResponse::json(200, &serde_json::json!({"FileChecksum": {
+                "algorithm": format!("MD5-of-{}MD5-of-512CRC32", n), "bytes": bytes, "length": 28
+            }}).to_string())
+        }
+        _ => fs.handle(r)
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let dir = std::env::temp_dir().join(format!("webhdfs-test-dedupe-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut check = |data: &[u8], hdfs_path| {
+        let local = dir.join("f");
+        std::fs::write(&local, data).unwrap();
+        dedupe_check(&mut cx, &local, hdfs_path).unwrap()
+    };
+    assert_eq!(check(&small, "/small"), DedupeCheck::Identical);
+    assert_eq!(check(&big, "/big"), DedupeCheck::Identical);
+    let mut changed = big.clone();
+    changed[1500] ^= 1;
+    assert_eq!(check(&changed, "/big"), DedupeCheck::Different);
+    assert_eq!(check(&small[1..], "/small"), DedupeCheck::Different);
+    assert_eq!(check(&small, "/other"), DedupeCheck::SameSample);
+    let mut changed = small.clone();
+    changed[650] = b'!';
+    assert_eq!(check(&changed, "/other"), DedupeCheck::Different);
+    assert_eq!(check(&small, "/none"), DedupeCheck::Missing);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,154 @@
+//! Cache of host name resolutions of the connector (see `HdfsClientBuilder::dns_cache`).
+//!
+//! An entry is served for `ttl` after the name has been resolved. An expired entry is still served for another `ttl`
+//! while it is refreshed by a background task, so that lookups in tight loops do not wait for the resolver; older
+//! entries are resolved anew before being served. A refresh still running after `ttl` is started again. If the
+//! refresh fails, or if none of the addresses of an entry can be connected to (see `forget`), the entry is dropped,
+//! and the next lookup resolves the name again (and reports the error).
+use std::collections::HashMap;
+use std::io::Result as IoResult;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::lookup_host;
+
+struct Entry {
+    addrs: Vec<IpAddr>,
+    resolved: Instant,
+    /// Start of the background refresh, if one is running
+    refreshing: Option<Instant>
+}
+
+#[derive(Clone)]
+pub(crate) struct DnsCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Arc<Mutex<HashMap<String, Entry>>>
+}
+
+/// Resolves `host` to distinct addresses, in resolver order
+async fn resolve(host: &str) -> IoResult<Vec<IpAddr>> {
+    let mut addrs: Vec<IpAddr> = vec![];
+    for a in lookup_host((host, 0)).await? {
+        if !addrs.contains(&a.ip()) { addrs.push(a.ip()) }
+    }
+    Ok(addrs)
+}
+
+impl DnsCache {
+    pub(crate) fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self { ttl, max_entries, entries: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration { self.ttl }
+    pub(crate) fn max_entries(&self) -> usize { self.max_entries }
+
+    /// Addresses of `host`, with `port`
+    pub(crate) async fn lookup(&self, host: &str, port: u16) -> IoResult<Vec<SocketAddr>> {
+        let with_port = |addrs: &[IpAddr]| addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect();
+        if let Ok(ip) = host.parse::<IpAddr>() { return Ok(vec![SocketAddr::new(ip, port)]) }
+        let stale = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get_mut(host) {
+                Some(e) if e.resolved.elapsed() < self.ttl => return Ok(with_port(&e.addrs)),
+                Some(e) if e.resolved.elapsed() < self.ttl * 2 => {
+                    let start_refresh = e.refreshing.is_none_or(|t| t.elapsed() >= self.ttl);
+                    if start_refresh { e.refreshing = Some(Instant::now()) }
+                    Some((with_port(&e.addrs), start_refresh))
+                }
+                _ => None
+            }
+        };
+        match stale {
+            Some((addrs, start_refresh)) => {
+                if start_refresh { self.refresh(host.to_owned()) }
+                Ok(addrs)
+            }
+            None => {
+                let addrs = resolve(host).await?;
+                self.insert(host, addrs.clone());
+                Ok(with_port(&addrs))
+            }
+        }
+    }
+
+    fn refresh(&self, host: String) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            match resolve(&host).await {
+                Ok(addrs) if !addrs.is_empty() => cache.insert(&host, addrs),
+                _ => { cache.entries.lock().unwrap().remove(&host); }
+            }
+        });
+    }
+
+    /// Drops the entry of `host`, e.g. after none of its addresses could be connected to
+    pub(crate) fn forget(&self, host: &str) { self.entries.lock().unwrap().remove(host); }
+
+    /// Caches non-empty `addrs` of `host`, evicting the oldest entry if the cache is full
+    pub(crate) fn insert(&self, host: &str, addrs: Vec<IpAddr>) {
+        if addrs.is_empty() || self.max_entries == 0 { return }
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(host) && entries.len() >= self.max_entries {
+            let oldest = entries.iter().min_by_key(|(_, e)| e.resolved).map(|(h, _)| h.clone());
+            if let Some(h) = oldest { entries.remove(&h); }
+        }
+        entries.insert(host.to_owned(), Entry { addrs, resolved: Instant::now(), refreshing: None });
+    }
+}
+
+#[test]
+fn test_dns_cache() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+    let cache = DnsCache::new(Duration::from_secs(60), 2);
+    cache.insert("nn.invalid", vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+    assert_eq!(rt.block_on(cache.lookup("nn.invalid", 9870)).unwrap(),
+        vec!["10.0.0.1:9870".parse::<SocketAddr>().unwrap(), "10.0.0.2:9870".parse().unwrap()]);
+    assert_eq!(rt.block_on(cache.lookup("10.1.1.1", 80)).unwrap(), vec!["10.1.1.1:80".parse::<SocketAddr>().unwrap()]);
+    assert!(!rt.block_on(cache.lookup("localhost", 80)).unwrap().is_empty());
+    cache.insert("dn.invalid", vec![ip("10.0.0.3")]);
+    // capacity 2: the oldest entry (nn.invalid) has been evicted
+    let hosts = |c: &DnsCache| { let mut h: Vec<_> = c.entries.lock().unwrap().keys().cloned().collect(); h.sort(); h };
+    assert_eq!(hosts(&cache), vec!["dn.invalid", "localhost"]);
+
+    // expired entries are served while being refreshed; a failed refresh drops the entry
+    let cache = DnsCache::new(Duration::from_secs(60), 10);
+    let age = |c: &DnsCache, resolved: u64, refreshing: Option<u64>| {
+        let mut entries = c.entries.lock().unwrap();
+        let e = entries.get_mut("nn.invalid").unwrap();
+        e.resolved = Instant::now() - Duration::from_secs(resolved);
+        e.refreshing = refreshing.map(|r| Instant::now() - Duration::from_secs(r));
+    };
+    let refreshed = |c: &DnsCache| rt.block_on(async {
+        for _ in 0..1000 {
+            if hosts(c).is_empty() { return true }
+            tokio::time::sleep(Duration::from_millis(10)).await
+        }
+        false
+    });
+    cache.insert("nn.invalid", vec![ip("10.0.0.1")]);
+    age(&cache, 90, None);
+    assert_eq!(rt.block_on(cache.lookup("nn.invalid", 9870)).unwrap(), vec!["10.0.0.1:9870".parse::<SocketAddr>().unwrap()]);
+    assert!(refreshed(&cache));
+    assert!(rt.block_on(cache.lookup("nn.invalid", 9870)).is_err());
+
+    // a refresh running for longer than the TTL is started again
+    cache.insert("nn.invalid", vec![ip("10.0.0.1")]);
+    age(&cache, 90, Some(30));
+    assert!(rt.block_on(cache.lookup("nn.invalid", 9870)).is_ok());
+    assert!(cache.entries.lock().unwrap()["nn.invalid"].refreshing.unwrap().elapsed() >= Duration::from_secs(30));
+    cache.insert("nn.invalid", vec![ip("10.0.0.1")]);
+    age(&cache, 90, Some(61));
+    assert!(rt.block_on(cache.lookup("nn.invalid", 9870)).is_ok());
+    assert!(refreshed(&cache));
+
+    // entries older than twice the TTL are resolved anew
+    cache.insert("nn.invalid", vec![ip("10.0.0.1")]);
+    age(&cache, 150, Some(1));
+    assert!(rt.block_on(cache.lookup("nn.invalid", 9870)).is_err());
+
+    cache.insert("nn.invalid", vec![ip("10.0.0.1")]);
+    cache.forget("nn.invalid");
+    assert!(hosts(&cache).is_empty());
+}
@@ -1,6 +1,7 @@
 
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use crate::redact::Redacted;
 
 pub use std::result::Result as StdResult;
 pub type Result<T> = StdResult<T, Error>;
@@ -19,19 +20,37 @@ pub enum Cause {
     Io(std::io::Error),
     Tls(native_tls::Error),
     //IntConversion(std::num::TryFromIntError),
-    RemoteException(crate::datatypes::RemoteException),
+    RemoteException(Box<crate::datatypes::RemoteException>),
+    QuotaExceeded(crate::datatypes::QuotaExceeded),
+    CreateOptionIgnored(crate::datatypes::CreateOptionIgnored),
+    SourceChanged(crate::datatypes::SourceChanged),
+    LeaseConflict(Box<crate::datatypes::LeaseConflict>),
+    /// All failover attempts have failed; contains the error of each attempt, along with its endpoint
+    AllNamenodesUnavailable(Vec<(String, Error)>),
     HttpRedirect(u16, String),
-    Timeout
+    /// Error HTTP status, without a RemoteException in the response
+    HttpStatus(u16),
+    Timeout,
+    /// The request has not been sent, as the circuit of the endpoint is open (see `CircuitBreaker`)
+    CircuitOpen(String),
+    /// A response body exceeds the limit (in bytes) set by `HdfsClientBuilder::max_response_body_size`
+    ResponseTooLarge(u64),
+    /// A request body exceeds the limit (in bytes) set by `HdfsClientBuilder::max_request_body_size`
+    RequestTooLarge(u64),
+    /// The client has been shut down (see `HdfsClient::shutdown`)
+    ShutDown
 }
 
 #[derive(Debug)]
 pub struct Error {
     msg: Option<Cow<'static, str>>,
-    cause: Cause
+    cause: Cause,
+    /// Set if the error has occurred in the datanode step of a two-step request
+    datanode: bool
 }
 
 impl Error {
-    pub fn new(msg: Option<Cow<'static, str>>, cause: Cause) -> Self { Error { msg, cause } }
+    pub fn new(msg: Option<Cow<'static, str>>, cause: Cause) -> Self { Error { msg, cause, datanode: false } }
     pub fn anon(cause: Cause) -> Self { Self::new(None, cause) }
     pub fn with_msg_prepended(self, msg: Cow<'static, str>) -> Self {        
         Error { 
@@ -39,7 +58,7 @@ impl Error {
                 Some(m) => msg + "\n" + m,
                 None => msg
             }),
-            cause: self.cause 
+            ..self
         }
     }
     pub fn app_c(msg: &'static str) -> Self { Error::new(Some(Cow::Borrowed(msg)), Cause::None) }
@@ -51,6 +70,47 @@ impl Error {
         }
     }
     pub fn cause(&self) -> &Cause { &self.cause }
+    /// Remote exception reported by the server, if this error is caused by one
+    pub fn remote_exception(&self) -> Option<&crate::datatypes::RemoteException> {
+        if let Cause::RemoteException(e) = &self.cause { Some(&**e) } else { None }
+    }
+    /// Per-attempt endpoints and errors, if all failover attempts have failed
+    pub fn namenode_failures(&self) -> Option<&[(String, Error)]> {
+        if let Cause::AllNamenodesUnavailable(v) = &self.cause { Some(v) } else { None }
+    }
+    /// Quota that would be exceeded, if this error is reported by a quota pre-check
+    pub fn quota_exceeded(&self) -> Option<&crate::datatypes::QuotaExceeded> {
+        if let Cause::QuotaExceeded(e) = &self.cause { Some(e) } else { None }
+    }
+    /// CREATE option not honored by the server, if this error is reported by CREATE verification
+    pub fn create_option_ignored(&self) -> Option<&crate::datatypes::CreateOptionIgnored> {
+        if let Cause::CreateOptionIgnored(e) = &self.cause { Some(e) } else { None }
+    }
+    /// Expected and actual file state, if the file changed during a resumed download
+    pub fn source_changed(&self) -> Option<&crate::datatypes::SourceChanged> {
+        if let Cause::SourceChanged(e) = &self.cause { Some(e) } else { None }
+    }
+    /// Lease holder and the exception reported, if a write has failed because of a lease conflict
+    pub fn lease_conflict(&self) -> Option<&crate::datatypes::LeaseConflict> {
+        if let Cause::LeaseConflict(e) = &self.cause { Some(&**e) } else { None }
+    }
+    /// Whether the file is being written by another client, or its lease is being recovered. 
+    /// Such errors are transient: the write may succeed once the lease is released or recovered.
+    pub fn is_lease_conflict(&self) -> bool {
+        match &self.cause {
+            Cause::LeaseConflict(_) => true,
+            Cause::RemoteException(e) => is_lease_exception(e),
+            _ => false
+        }
+    }
+    /// Converts a lease conflict remote exception to `LeaseConflict`; other errors are returned as is
+    pub(crate) fn into_lease_conflict(self, path: &str, attempts: u32) -> Self {
+        match self.cause {
+            Cause::RemoteException(e) if is_lease_exception(&e) =>
+                Error::new(self.msg, Cause::LeaseConflict(Box::new(crate::datatypes::LeaseConflict::new(path.to_owned(), attempts, *e)))),
+            cause => Error::new(self.msg, cause)
+        }
+    }
     pub fn from_http_redirect(status: u16, location: String) -> Self {
         Self::new(None, Cause::HttpRedirect(status, location))
     }
@@ -60,10 +120,78 @@ impl Error {
             other => Err(Self::new(self.msg, other))
         }
     }
+    pub fn from_http_status(status: u16, msg: String) -> Self {
+        Self::new(Some(Cow::Owned(msg)), Cause::HttpStatus(status))
+    }
+    /// HTTP status of an error response without a RemoteException
+    pub fn http_status(&self) -> Option<u16> {
+        if let Cause::HttpStatus(s) = &self.cause { Some(*s) } else { None }
+    }
+    /// Whether the error has occurred in the datanode step of a two-step request (e.g. reading file data).
+    /// Such errors do not cause namenode failover.
+    pub fn is_datanode_error(&self) -> bool { self.datanode }
+    pub(crate) fn at_datanode(self) -> Self { Error { datanode: true, ..self } }
+    /// Whether the request has been rejected by a circuit breaker, without being sent
+    pub fn is_circuit_open(&self) -> bool { matches!(self.cause, Cause::CircuitOpen(_)) }
+    /// Whether a response has been rejected as its body exceeds the configured limit
+    pub fn is_response_too_large(&self) -> bool { matches!(self.cause, Cause::ResponseTooLarge(_)) }
+    /// Whether a request has not been sent as its body exceeds the configured limit
+    pub fn is_request_too_large(&self) -> bool { matches!(self.cause, Cause::RequestTooLarge(_)) }
+    /// Whether a call has been rejected, or a request aborted, as the client has been shut down
+    pub fn is_shut_down(&self) -> bool { matches!(self.cause, Cause::ShutDown) }
+    /// The closest `std::io::ErrorKind`, used when converting to `std::io::Error`. Remote exceptions are mapped by 
+    /// exception class, and error responses without one by HTTP status.
+    pub fn io_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match &self.cause {
+            Cause::Io(e) => e.kind(),
+            Cause::Timeout => ErrorKind::TimedOut,
+            Cause::Hyper(e) if e.is_timeout() => ErrorKind::TimedOut,
+            Cause::Hyper(e) => {
+                let mut source = std::error::Error::source(e);
+                while let Some(s) = source {
+                    if let Some(io) = s.downcast_ref::<std::io::Error>() { return io.kind() }
+                    source = s.source();
+                }
+                ErrorKind::Other
+            }
+            Cause::RemoteException(e) => remote_io_kind(e),
+            Cause::LeaseConflict(e) => remote_io_kind(&e.exception),
+            Cause::HttpStatus(s) => match s {
+                400 => ErrorKind::InvalidInput,
+                401 | 403 => ErrorKind::PermissionDenied,
+                404 => ErrorKind::NotFound,
+                409 => ErrorKind::AlreadyExists,
+                408 | 504 => ErrorKind::TimedOut,
+                _ => ErrorKind::Other
+            }
+            Cause::AllNamenodesUnavailable(v) => v.last().map(|(_, e)| e.io_kind()).unwrap_or(ErrorKind::Other),
+            Cause::ResponseTooLarge(_) => ErrorKind::InvalidData,
+            Cause::RequestTooLarge(_) => ErrorKind::InvalidInput,
+            Cause::ShutDown => ErrorKind::ConnectionAborted,
+            _ => ErrorKind::Other
+        }
+    }
     //pub fn timeout() -> Self { Self::new(None, Cause::Timeout) }
     pub fn timeout_c(msg: &'static str) -> Self { Self::new(Some(Cow::Borrowed(msg)), Cause::Timeout) }
 }
 
+fn is_lease_exception(e: &crate::datatypes::RemoteException) -> bool {
+    e.exception == "AlreadyBeingCreatedException" || e.exception == "RecoveryInProgressException"
+}
+
+fn remote_io_kind(e: &crate::datatypes::RemoteException) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+    match e.exception.as_str() {
+        "FileNotFoundException" => ErrorKind::NotFound,
+        "AccessControlException" | "SecurityException" | "AuthorizationException" => ErrorKind::PermissionDenied,
+        "FileAlreadyExistsException" | "AlreadyBeingCreatedException" => ErrorKind::AlreadyExists,
+        "IllegalArgumentException" | "HadoopIllegalArgumentException" | "InvalidPathException" => ErrorKind::InvalidInput,
+        "UnsupportedOperationException" => ErrorKind::Unsupported,
+        _ => ErrorKind::Other
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "AppError: {}", self.msg_s())?;
@@ -80,8 +208,21 @@ impl Display for Error {
             Cause::Tls(e) => write!(f, "; caused by native_tls::Error: {}", e),
             //Cause::IntConversion(e) => write!(f, "; caused by std::num::TryFromIntError: {}", e),
             Cause::RemoteException(e) => write!(f, "; caused by RemoteException {}", e),
-            Cause::HttpRedirect(code, location) => write!(f, "; caused by HTTP redirect {} {}", code, location),
+            Cause::QuotaExceeded(e) => write!(f, "; caused by QuotaExceeded: {}", e),
+            Cause::CreateOptionIgnored(e) => write!(f, "; caused by CreateOptionIgnored: {}", e),
+            Cause::SourceChanged(e) => write!(f, "; caused by SourceChanged: {}", e),
+            Cause::LeaseConflict(e) => write!(f, "; caused by LeaseConflict: {}", e),
+            Cause::AllNamenodesUnavailable(v) => {
+                write!(f, "; caused by AllNamenodesUnavailable:")?;
+                v.iter().try_for_each(|(endpoint, e)| write!(f, " [{}: {}]", endpoint, e))
+            }
+            Cause::HttpRedirect(code, location) => write!(f, "; caused by HTTP redirect {} {}", code, Redacted(location)),
+            Cause::HttpStatus(code) => write!(f, "; caused by HTTP status {}", code),
             Cause::Timeout => write!(f, "; caused by Timeout"),
+            Cause::CircuitOpen(endpoint) => write!(f, "; caused by open circuit of {}", endpoint),
+            Cause::ResponseTooLarge(limit) => write!(f, "; caused by ResponseTooLarge: body exceeds {} bytes", limit),
+            Cause::RequestTooLarge(limit) => write!(f, "; caused by RequestTooLarge: body exceeds {} bytes", limit),
+            Cause::ShutDown => write!(f, "; caused by ShutDown"),
             Cause::None => Ok(())
         }
     }
@@ -101,9 +242,19 @@ impl std::error::Error for Error {
             Cause::Io(e) => Some(e),
             Cause::Tls(e) => Some(e),
             //Cause::IntConversion(e) => Some(e),
-            Cause::RemoteException(e) => Some(e),
+            Cause::RemoteException(e) => Some(&**e),
+            Cause::QuotaExceeded(e) => Some(e),
+            Cause::CreateOptionIgnored(e) => Some(e),
+            Cause::SourceChanged(e) => Some(e),
+            Cause::LeaseConflict(e) => Some(&**e),
+            Cause::AllNamenodesUnavailable(v) => v.last().map(|(_, e)| e as &(dyn std::error::Error + 'static)),
             Cause::HttpRedirect(_, _) => None,
+            Cause::HttpStatus(_) => None,
             Cause::Timeout => None,
+            Cause::CircuitOpen(_) => None,
+            Cause::ResponseTooLarge(_) => None,
+            Cause::RequestTooLarge(_) => None,
+            Cause::ShutDown => None,
             Cause::None => None
         }
     }
@@ -196,8 +347,39 @@ macro_rules! error_conversions_noarg {
 }
 
 
+/// A response body aborted by `HdfsClient::shutdown` fails with a hyper error wrapping the `ShutDown` error
+fn hyper_cause(e: hyper::Error) -> Cause {
+    let source = std::error::Error::source(&e).and_then(|s| s.downcast_ref::<Error>());
+    if source.is_some_and(Error::is_shut_down) { Cause::ShutDown } else { Cause::Hyper(e) }
+}
+
+impl From<hyper::Error> for Error {
+    #[cfg(panic_on_error)]
+    fn from(e: hyper::Error) -> Self {  panic!(Error::anon(hyper_cause(e)).to_string()) }
+    #[cfg(not(panic_on_error))]
+    fn from(e: hyper::Error) -> Self {  Error::anon(hyper_cause(e)) }
+}
+
+impl IntoErrorAnnotated for hyper::Error {
+    fn into_with(self, msg: Cow<'static, str>) -> Error {
+        Error::new(Some(msg), hyper_cause(self))
+    }
+}
+
+impl From<crate::datatypes::RemoteException> for Error {
+    #[cfg(panic_on_error)]
+    fn from(e: crate::datatypes::RemoteException) -> Self {  panic!(Error::anon(Cause::RemoteException(Box::new(e))).to_string()) }
+    #[cfg(not(panic_on_error))]
+    fn from(e: crate::datatypes::RemoteException) -> Self {  Error::anon(Cause::RemoteException(Box::new(e))) }
+}
+
+impl IntoErrorAnnotated for crate::datatypes::RemoteException {
+    fn into_with(self, msg: Cow<'static, str>) -> Error {
+        Error::new(Some(msg), Cause::RemoteException(Box::new(self)))
+    }
+}
+
 error_conversions!{
-    Hyper(hyper::Error),
     HyperHeaderToStr(hyper::header::ToStrError),
     MimeFromStr(mime::FromStrError),
     SerdeJson(serde_json::Error),
@@ -208,7 +390,9 @@ error_conversions!{
     Io(std::io::Error),
     Tls(native_tls::Error),
     //IntConversion(std::num::TryFromIntError),
-    RemoteException(crate::datatypes::RemoteException)
+    QuotaExceeded(crate::datatypes::QuotaExceeded),
+    CreateOptionIgnored(crate::datatypes::CreateOptionIgnored),
+    SourceChanged(crate::datatypes::SourceChanged)
 }
 
 error_conversions_noarg!{
@@ -219,10 +403,29 @@ impl From<Error> for std::io::Error {
     fn from(e: Error) -> Self {
         use std::io::{Error as IoError, ErrorKind as IoErrorKind };
         match e {
-            Error { msg: None, cause: Cause::Io(io) } => io,
-            Error { msg: Some(m), cause: Cause::Timeout } => IoError::new(IoErrorKind::TimedOut, m), 
-            Error { msg: None, cause: Cause::Timeout } => IoError::from(IoErrorKind::TimedOut), 
-            other => IoError::new(std::io::ErrorKind::Other, other)
+            Error { msg: None, cause: Cause::Io(io), .. } => io,
+            Error { msg: Some(m), cause: Cause::Timeout, .. } => IoError::new(IoErrorKind::TimedOut, m), 
+            Error { msg: None, cause: Cause::Timeout, .. } => IoError::from(IoErrorKind::TimedOut), 
+            other => IoError::new(other.io_kind(), other)
         }
     }
 }
+
+#[test]
+fn test_io_kind() {
+    use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+    let re = |exception: &str| Error::anon(Cause::RemoteException(Box::new(crate::datatypes::RemoteException { 
+        exception: exception.to_owned(), java_class_name: String::new(), message: String::new(), extra: Default::default() 
+    })));
+    assert_eq!(IoError::from(re("FileNotFoundException")).kind(), IoErrorKind::NotFound);
+    assert_eq!(IoError::from(re("AccessControlException")).kind(), IoErrorKind::PermissionDenied);
+    assert_eq!(IoError::from(re("FileAlreadyExistsException")).kind(), IoErrorKind::AlreadyExists);
+    assert_eq!(IoError::from(re("StandbyException")).kind(), IoErrorKind::Other);
+    assert_eq!(IoError::from(Error::from_http_status(403, "denied".to_owned())).kind(), IoErrorKind::PermissionDenied);
+    assert_eq!(IoError::from(Error::from_http_status(500, "oops".to_owned())).kind(), IoErrorKind::Other);
+    assert_eq!(IoError::from(Error::app_c("generic")).kind(), IoErrorKind::Other);
+    let all = Error::anon(Cause::AllNamenodesUnavailable(vec![("nn".to_owned(), Error::timeout_c("t"))]));
+    assert_eq!(IoError::from(all).kind(), IoErrorKind::TimedOut);
+    let e = IoError::from(re("FileNotFoundException"));
+    assert!(e.get_ref().unwrap().downcast_ref::<Error>().unwrap().remote_exception().is_some());
+}
@@ -0,0 +1,217 @@
+//! Client-side path-based routing to multiple clusters.
+//!
+//! A `MountTable` maps path prefixes to clients, as viewfs does: with `/warehouse` mounted from cluster A and `/logs`
+//! from cluster B, `/warehouse/t1` is `/warehouse/t1` on cluster A. A mount may also point to a different directory
+//! of the cluster (`/logs` -> `/data/logs`). The longest matching prefix wins; `/` can be mounted as a fallback.
+//!
+//! `FederatedClient` exposes the usual file system operations over a mount table. Paths are normalized before
+//! routing, and paths returned by the server (`dir_abs`) are mapped back to the federated namespace. Renames across
+//! mount points are rejected. Each call starts at the last known active namenode of the mount's cluster.
+use std::sync::Arc;
+use tokio::io::AsyncWrite;
+use crate::error::*;
+use crate::path::HdfsPath;
+use crate::datatypes::*;
+use crate::op::*;
+use crate::async_client::{HdfsClient, FOR, Data, DResult, ErrorD, BinaryStream};
+use crate::registry::ClientRegistry;
+
+struct Mount {
+    prefix: String,
+    target: String,
+    client: Arc<HdfsClient>
+}
+
+/// Mapping of path prefixes to clients
+#[derive(Default)]
+pub struct MountTable {
+    /// Sorted by prefix length, longest first
+    mounts: Vec<Mount>
+}
+
+/// Whether `path` is `prefix` or lies under it
+fn under(path: &str, prefix: &str) -> Option<usize> {
+    if prefix == "/" {
+        Some(0)
+    } else if path.starts_with(prefix) && (path.len() == prefix.len() || path.as_bytes()[prefix.len()] == b'/') {
+        Some(prefix.len())
+    } else {
+        None
+    }
+}
+
+impl MountTable {
+    pub fn new() -> Self { Self { mounts: vec![] } }
+
+    /// Mounts directory `target` of `client`'s cluster at `prefix`. Both must be absolute paths.
+    pub fn mount(mut self, prefix: &str, client: Arc<HdfsClient>, target: &str) -> Result<Self> {
+        let norm = |p: &str| {
+            let n = HdfsPath::new(p).normalize()?;
+            if n.is_absolute() { Ok(n.into_string()) } else { Err(app_error!(generic "Mount path '{}' is not absolute", p)) }
+        };
+        let (prefix, target) = (norm(prefix)?, norm(target)?);
+        if self.mounts.iter().any(|m| m.prefix == prefix) {
+            return Err(app_error!(generic "Duplicate mount point '{}'", prefix))
+        }
+        self.mounts.push(Mount { prefix, target, client });
+        self.mounts.sort_by_key(|m| std::cmp::Reverse(m.prefix.len()));
+        Ok(self)
+    }
+
+    /// Builds a mount table from `(prefix, cluster id)` pairs, using the clients of `registry`.
+    /// Each prefix is mounted to the same path on its cluster.
+    pub fn from_registry<'a>(registry: &ClientRegistry, mounts: impl IntoIterator<Item=(&'a str, &'a str)>) -> Result<Self> {
+        mounts.into_iter().try_fold(Self::new(), |t, (prefix, id)| t.mount(prefix, registry.client(id)?, prefix))
+    }
+
+    /// Mount points, longest first
+    pub fn prefixes(&self) -> Vec<&str> { self.mounts.iter().map(|m| m.prefix.as_str()).collect() }
+
+    fn find(&self, path: &str) -> Result<(&Mount, String)> {
+        let path = HdfsPath::new(path).normalize()?.into_string();
+        if !path.starts_with('/') {
+            return Err(app_error!(generic "Path '{}' is not absolute", path))
+        }
+        self.mounts.iter()
+            .find_map(|m| under(&path, &m.prefix).map(|n| (m, crate::path::join(&m.target, &path[n..]))))
+            .ok_or_else(|| app_error!(generic "No mount point for '{}'", path))
+    }
+
+    /// Client and cluster path that federated path `path` is routed to
+    pub fn resolve(&self, path: &str) -> Result<(Arc<HdfsClient>, String)> {
+        self.find(path).map(|(m, p)| (m.client.clone(), p))
+    }
+}
+
+/// File system operations routed by a `MountTable`
+pub struct FederatedClient {
+    table: MountTable
+}
+
+macro_rules! routed {
+    ($s:expr, $path:expr, |$c:ident, $p:ident| $call:expr) => { {
+        let ($c, $p) = $s.table.resolve(AsRef::<HdfsPath>::as_ref($path).as_str())?;
+        let $p = $p.as_str();
+        FOR::split($call.await).0
+    } };
+}
+
+impl FederatedClient {
+    pub fn new(table: MountTable) -> Self { Self { table } }
+
+    pub fn mount_table(&self) -> &MountTable { &self.table }
+
+    /// Stat a file /dir
+    pub async fn stat(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileStatusResponse> {
+        routed!(self, path, |c, p| c.stat(c.active_fostate(), p))
+    }
+
+    /// Get directory listing
+    pub async fn dir(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ListStatusResponse> {
+        routed!(self, path, |c, p| c.dir(c.active_fostate(), p))
+    }
+
+    /// Get directory listing, with entries paired with their absolute (federated) paths
+    pub async fn dir_abs(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<Vec<(String, FileStatus)>> {
+        let path = HdfsPath::new(path.as_ref().as_str()).normalize()?.into_string();
+        let r = routed!(self, &path, |c, p| c.dir_abs(c.active_fostate(), p))?;
+        Ok(r.into_iter().map(|(_, fs)| (crate::path::join(&path, &fs.path_suffix), fs)).collect())
+    }
+
+    /// Get content summary of a file /dir. Mount points below `path` are not included.
+    pub async fn content_summary(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ContentSummaryResponse> {
+        routed!(self, path, |c, p| c.content_summary(c.active_fostate(), p))
+    }
+
+    /// Get file checksum
+    pub async fn checksum(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileChecksumResponse> {
+        routed!(self, path, |c, p| c.checksum(c.active_fostate(), p))
+    }
+
+    /// Read file data
+    pub async fn open(&self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: OpenOptions)
+    -> Result<BinaryStream> {
+        routed!(self, path, |c, p| c.open(c.active_fostate(), p, opts))
+    }
+
+    /// Read file data into `output`, see `HdfsClient::get_to`
+    pub async fn get_to(&self, path: &(impl AsRef<HdfsPath> + ?Sized), output: impl AsyncWrite + Unpin) -> Result<u64> {
+        routed!(self, path, |c, p| c.get_to(c.active_fostate(), p, output))
+    }
+
+    /// Create file
+    pub async fn create(&self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) -> DResult<()> {
+        let (c, p) = match self.table.resolve(path.as_ref().as_str()) { Ok(r) => r, Err(e) => return Err(ErrorD::d(e, data)) };
+        FOR::split(c.create(c.active_fostate(), &p, data, opts).await).0
+    }
+
+    /// Append to a file
+    pub async fn append(&self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: AppendOptions) -> DResult<()> {
+        let (c, p) = match self.table.resolve(path.as_ref().as_str()) { Ok(r) => r, Err(e) => return Err(ErrorD::d(e, data)) };
+        FOR::split(c.append(c.active_fostate(), &p, data, opts).await).0
+    }
+
+    /// Make a directory
+    pub async fn mkdirs(&self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: MkdirsOptions) -> Result<bool> {
+        routed!(self, path, |c, p| c.mkdirs(c.active_fostate(), p, opts))
+    }
+
+    /// Rename a file /dir. Both paths must be under the same mount point.
+    pub async fn rename(&self, path: &(impl AsRef<HdfsPath> + ?Sized), destination: &str) -> Result<bool> {
+        let (src_mount, src) = self.table.find(path.as_ref().as_str())?;
+        let (dst_mount, dst) = self.table.find(destination)?;
+        if src_mount.prefix != dst_mount.prefix {
+            return Err(app_error!(generic "Cannot rename across mount points: {} -> {}", path.as_ref().as_str(), destination))
+        }
+        let c = &src_mount.client;
+        FOR::split(c.rename(c.active_fostate(), &src, dst).await).0
+    }
+
+    /// Delete a file /dir
+    pub async fn delete(&self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: DeleteOptions) -> Result<bool> {
+        routed!(self, path, |c, p| c.delete(c.active_fostate(), p, opts))
+    }
+}
+
+#[test]
+fn test_federated_client() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let (a, b) = (FakeHdfs::new(), FakeHdfs::new());
+    a.put("/warehouse/t1/part-0", b"a");
+    a.put("/other", b"");
+    b.put("/data/logs/app.log", b"log");
+    let (ac, bc) = (FakeCluster::start(&a), FakeCluster::start(&b));
+    let ca = Arc::new(ac.builder().build());
+    let cb = Arc::new(bc.builder().build());
+    let table = MountTable::new()
+        .mount("/warehouse", ca.clone(), "/warehouse").unwrap()
+        .mount("/logs/", cb.clone(), "/data/logs").unwrap();
+    assert!(MountTable::new().mount("/x", ca.clone(), "/").unwrap().mount("/x/", cb.clone(), "/").is_err());
+    assert!(MountTable::new().mount("x", ca.clone(), "/").is_err());
+    assert_eq!(table.resolve("/logs//app.log").unwrap().1, "/data/logs/app.log");
+    assert_eq!(table.resolve("/logs").unwrap().1, "/data/logs");
+    assert!(table.resolve("/logsx").is_err());
+    assert!(table.resolve("/other").is_err());
+    let fc = FederatedClient::new(table);
+
+    ac.rt.block_on(async {
+        assert_eq!(fc.stat("/warehouse/t1/part-0").await.unwrap().file_status.length, 1);
+        assert_eq!(fc.stat("/logs/app.log").await.unwrap().file_status.length, 3);
+        let l = fc.dir_abs("/logs").await.unwrap();
+        assert_eq!(l.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), vec!["/logs/app.log"]);
+        fc.create("/logs/new.log", Data::Borrowed(b"n"), CreateOptions::new()).await.map_err(ErrorD::drop).unwrap();
+        assert_eq!(b.get("/data/logs/new.log").unwrap().data, b"n");
+        assert!(fc.rename("/logs/new.log", "/logs/renamed.log").await.unwrap());
+        assert!(b.get("/data/logs/renamed.log").is_some());
+        assert!(fc.rename("/logs/renamed.log", "/warehouse/x").await.unwrap_err().to_string().contains("across mount points"));
+        let mut out = vec![];
+        fc.get_to("/warehouse/t1/part-0", &mut out).await.unwrap();
+        assert_eq!(out, b"a");
+    });
+
+    let mut registry = ClientRegistry::new();
+    registry.register("a".to_owned(), ac.builder().build());
+    let table = MountTable::from_registry(&registry, vec![("/", "a")]).unwrap();
+    assert_eq!(table.resolve("/other").unwrap().1, "/other");
+    assert!(MountTable::from_registry(&registry, vec![("/", "b")]).is_err());
+}
@@ -0,0 +1,133 @@
+//! Client-side filtering of directory listings.
+//!
+//! WebHDFS `LISTSTATUS` has no server-side filtering. `FileFilter` emulates it: `HdfsClient::dir_filtered` applies
+//! the filter to entries as the listing is streamed, so that only matching entries are ever kept in memory.
+use crate::datatypes::FileStatus;
+
+/// Directory entry filter. All conditions set must hold for an entry to match; an empty filter matches everything.
+/// ```
+/// use webhdfs::filter::FileFilter;
+/// use webhdfs::dirent_type;
+/// let f = FileFilter::new().type_(dirent_type::FILE).glob("*.csv").min_size(1024);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    type_: Option<String>,
+    glob: Option<String>,
+    min_size: Option<i64>,
+    modified_after: Option<i64>,
+    modified_before: Option<i64>
+}
+
+impl FileFilter {
+    pub fn new() -> Self { Self::default() }
+    /// Entry type, one of `dirent_type` constants
+    pub fn type_(self, type_: &str) -> Self { Self { type_: Some(type_.to_owned()), ..self } }
+    /// Glob pattern the entry name (`path_suffix`) must match, see `glob_match`
+    pub fn glob(self, pattern: &str) -> Self { Self { glob: Some(pattern.to_owned()), ..self } }
+    /// Minimum length, in bytes
+    pub fn min_size(self, min_size: i64) -> Self { Self { min_size: Some(min_size), ..self } }
+    /// Modification time lower bound (inclusive), in milliseconds since the epoch
+    pub fn modified_after(self, ms: i64) -> Self { Self { modified_after: Some(ms), ..self } }
+    /// Modification time upper bound (exclusive), in milliseconds since the epoch
+    pub fn modified_before(self, ms: i64) -> Self { Self { modified_before: Some(ms), ..self } }
+
+    /// Whether `fs` matches the filter
+    pub fn matches(&self, fs: &FileStatus) -> bool {
+        self.type_.as_ref().is_none_or(|t| *t == fs.type_)
+            && self.min_size.is_none_or(|s| fs.length >= s)
+            && self.modified_after.is_none_or(|t| fs.modification_time >= t)
+            && self.modified_before.is_none_or(|t| fs.modification_time < t)
+            && self.glob.as_ref().is_none_or(|g| glob_match(g, &fs.path_suffix))
+    }
+}
+
+/// Matches `name` against a shell-style glob `pattern`: `*` matches any sequence of characters, `?` matches any
+/// single character, `[abc]`, `[a-z]` match a character of the set, `[!abc]` one not in the set. `\` escapes the
+/// next character. There is no special treatment of `/` or leading dots.
+/// ```
+/// use webhdfs::filter::glob_match;
+/// assert!(glob_match("part-*.csv", "part-00001.csv"));
+/// assert!(glob_match("[!_]*", "data"));
+/// assert!(!glob_match("[!_]*", "_SUCCESS"));
+/// ```
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last `*` seen, and the name position it is currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        let step = match p.get(pi) {
+            Some('*') => { star = Some((pi + 1, ni)); pi += 1; continue }
+            Some(_) => match_one(&p[pi..], n[ni]),
+            None => None
+        };
+        match (step, star) {
+            (Some(len), _) => { pi += len; ni += 1 }
+            (None, Some((sp, sn))) => { pi = sp; ni = sn + 1; star = Some((sp, sn + 1)) }
+            (None, None) => return false
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the pattern element at the start of `p`, returning the element length if matched
+fn match_one(p: &[char], c: char) -> Option<usize> {
+    match p[0] {
+        '?' => Some(1),
+        '\\' if p.len() > 1 => if p[1] == c { Some(2) } else { None },
+        '[' => {
+            // A `]` right after `[` or `[!` is a member of the set, not its end
+            let (negate, start) = match p.get(1) { Some('!') | Some('^') => (true, 2), _ => (false, 1) };
+            match p.iter().skip(start + 1).position(|&x| x == ']') {
+                Some(end) => {
+                    let end = end + start + 1;
+                    let set = &p[start..end];
+                    let mut found = false;
+                    let mut i = 0;
+                    while i < set.len() {
+                        if i + 2 < set.len() && set[i + 1] == '-' {
+                            found |= set[i] <= c && c <= set[i + 2];
+                            i += 3;
+                        } else {
+                            found |= set[i] == c;
+                            i += 1;
+                        }
+                    }
+                    if found != negate { Some(end + 1) } else { None }
+                }
+                // Unterminated: a literal `[`
+                None => if c == '[' { Some(1) } else { None }
+            }
+        }
+        x => if x == c { Some(1) } else { None }
+    }
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("", ""));
+    assert!(!glob_match("", "a"));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("*", "abc"));
+    assert!(glob_match("a*c", "abbbc"));
+    assert!(glob_match("a*c", "ac"));
+    assert!(!glob_match("a*c", "acb"));
+    assert!(glob_match("*.csv", "x.y.csv"));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+    assert!(!glob_match("a*b*c", "aXbYbZ"));
+    assert!(glob_match("?b?", "abc"));
+    assert!(!glob_match("?", ""));
+    assert!(glob_match("[a-c]x", "bx"));
+    assert!(!glob_match("[a-c]x", "dx"));
+    assert!(glob_match("[!a-c]x", "dx"));
+    assert!(glob_match("[]]", "]"));
+    assert!(glob_match("[-a]", "-"));
+    assert!(glob_match("a[", "a["));
+    assert!(glob_match("[!]]", "a"));
+    assert!(!glob_match("[!]]", "]"));
+    assert!(glob_match("\\*", "*"));
+    assert!(!glob_match("\\*", "a"));
+    assert!(glob_match("файл-?.txt", "файл-1.txt"));
+}
@@ -0,0 +1,171 @@
+//! Blocking file system functions mirroring `std::fs`.
+//!
+//! The functions take a `SyncHdfsClient` and absolute HDFS paths, and follow their `std::fs` counterparts
+//! closely, so that code written against the local file system can be ported by replacing `std::fs::read(p)`
+//! with `webhdfs::fs::read(&mut cx, p)` and so on. Errors convert to `std::io::Error` with the matching kind
+//! (e.g. `NotFound`). Where `std::fs` distinguishes files from directories and HDFS does not (e.g. DELETE removes
+//! either), the path is checked with an extra GETFILESTATUS request, which makes these checks non-atomic.
+//!
+//! Differences from `std::fs`: `metadata` does not follow symbolic links, and renaming a path onto itself fails.
+use std::io::{BufWriter, Write};
+use std::time::SystemTime;
+use crate::error::*;
+use crate::datatypes::{FileStatus, dirent_type};
+use crate::permission::Permission;
+use crate::sync_client::{SyncHdfsClient, ReadHdfsFile, WriteHdfsFile, CreateOptions, AppendOptions, DeleteOptions, MkdirsOptions};
+
+/// Metadata of a file or directory, see `metadata`
+#[derive(Debug)]
+pub struct Metadata(FileStatus);
+
+impl Metadata {
+    pub(crate) fn new(fs: FileStatus) -> Self { Self(fs) }
+    pub fn is_dir(&self) -> bool { self.0.type_ == dirent_type::DIRECTORY }
+    pub fn is_file(&self) -> bool { self.0.type_ == dirent_type::FILE }
+    pub fn is_symlink(&self) -> bool { self.0.type_ == dirent_type::SYMLINK }
+    pub fn len(&self) -> u64 { self.0.size() }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    pub fn modified(&self) -> SystemTime { self.0.modified() }
+    pub fn accessed(&self) -> SystemTime { self.0.accessed() }
+    pub fn permissions(&self) -> Result<Permission> { Permission::from_octal(&self.0.permission) }
+    /// All attributes, as reported by the server
+    pub fn file_status(&self) -> &FileStatus { &self.0 }
+}
+
+/// Directory entry, see `read_dir`
+#[derive(Debug)]
+pub struct DirEntry {
+    path: String,
+    metadata: Metadata
+}
+
+impl DirEntry {
+    pub(crate) fn new(path: String, fs: FileStatus) -> Self { Self { path, metadata: Metadata(fs) } }
+    /// Absolute path of the entry
+    pub fn path(&self) -> &str { &self.path }
+    pub fn file_name(&self) -> &str { &self.metadata.0.path_suffix }
+    /// Metadata of the entry, as returned by the directory listing (no request is made)
+    pub fn metadata(&self) -> &Metadata { &self.metadata }
+}
+
+/// Iterator over the entries of a directory, see `read_dir`. The directory is read in full upon `read_dir`,
+/// so iteration never fails.
+pub struct ReadDir(std::vec::IntoIter<DirEntry>);
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+    fn next(&mut self) -> Option<Self::Item> { self.0.next().map(Ok) }
+}
+
+pub(crate) fn not_found(path: &str) -> Error { std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} not found", path)).into() }
+
+/// Reads the entire contents of a file
+pub fn read(cx: &mut SyncHdfsClient, path: &str) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    cx.get_file(path, &mut data)?;
+    Ok(data)
+}
+
+/// Reads the entire contents of a file into a string
+pub fn read_to_string(cx: &mut SyncHdfsClient, path: &str) -> Result<String> {
+    String::from_utf8(read(cx, path)?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+/// Writes `contents` to a file, replacing it if it exists
+pub fn write(cx: &mut SyncHdfsClient, path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+    cx.create(path, contents.as_ref().to_vec().into(), CreateOptions::new().overwrite(true)).map_err(|e| e.error)
+}
+
+/// Copies the contents of file `from` to file `to`, replacing it if it exists. Returns the number of bytes copied.
+pub fn copy(cx: &mut SyncHdfsClient, from: &str, to: &str) -> Result<u64> {
+    let mut input = ReadHdfsFile::open(cx.clone(), from.to_owned())?;
+    let output = WriteHdfsFile::create(cx.clone(), to.to_owned(), CreateOptions::new().overwrite(true), AppendOptions::new())?;
+    let mut output = BufWriter::with_capacity(ReadHdfsFile::DEFAULT_BUFFER_SIZE, output);
+    let n = std::io::copy(&mut input, &mut output)?;
+    output.flush()?;
+    Ok(n)
+}
+
+/// Renames a file or directory, replacing `to` atomically if it exists. As with `std::fs::rename`, a directory
+/// can only replace an empty directory, and a file only a file; `from` is never moved into directory `to`.
+pub fn rename(cx: &mut SyncHdfsClient, from: &str, to: &str) -> Result<()> {
+    cx.exec0(crate::afs::rename(cx.async_client(), from, to))?
+}
+
+/// Creates a directory and all of its missing parents
+pub fn create_dir_all(cx: &mut SyncHdfsClient, path: &str) -> Result<()> {
+    if cx.mkdirs(path, MkdirsOptions::new())? { Ok(()) } else { Err(app_error!(generic "Cannot create directory {}", path)) }
+}
+
+pub(crate) fn is_a_directory(path: &str) -> Error {
+    std::io::Error::new(std::io::ErrorKind::IsADirectory, format!("{} is a directory", path)).into()
+}
+
+pub(crate) fn not_a_directory(path: &str) -> Error {
+    std::io::Error::new(std::io::ErrorKind::NotADirectory, format!("{} is not a directory", path)).into()
+}
+
+/// Removes a file. Fails with `IsADirectory` if `path` is a directory.
+pub fn remove_file(cx: &mut SyncHdfsClient, path: &str) -> Result<()> {
+    if metadata(cx, path)?.is_dir() { return Err(is_a_directory(path)) }
+    if cx.delete(path, DeleteOptions::new().recursive(false))? { Ok(()) } else { Err(not_found(path)) }
+}
+
+/// Removes a directory with all its contents. Fails with `NotADirectory` if `path` is not a directory.
+pub fn remove_dir_all(cx: &mut SyncHdfsClient, path: &str) -> Result<()> {
+    if !metadata(cx, path)?.is_dir() { return Err(not_a_directory(path)) }
+    if cx.delete(path, DeleteOptions::new().recursive(true))? { Ok(()) } else { Err(not_found(path)) }
+}
+
+/// Metadata of a file or directory. Symbolic links are not followed.
+pub fn metadata(cx: &mut SyncHdfsClient, path: &str) -> Result<Metadata> {
+    Ok(Metadata::new(cx.stat(path)?.file_status))
+}
+
+/// Entries of a directory, in the order returned by the server
+pub fn read_dir(cx: &mut SyncHdfsClient, path: &str) -> Result<ReadDir> {
+    let entries: Vec<DirEntry> = cx.dir_abs(path)?.into_iter().map(|(path, fs)| DirEntry::new(path, fs)).collect();
+    Ok(ReadDir(entries.into_iter()))
+}
+
+#[test]
+fn test_fs() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    let cl = FakeCluster::start(&f);
+    let mut cx = cl.sync_builder().build().unwrap();
+
+    create_dir_all(&mut cx, "/d/sub").unwrap();
+    write(&mut cx, "/d/a", "text").unwrap();
+    write(&mut cx, "/d/a", "text 2").unwrap();
+    assert_eq!(read_to_string(&mut cx, "/d/a").unwrap(), "text 2");
+    assert_eq!(copy(&mut cx, "/d/a", "/d/sub/b").unwrap(), 6);
+    assert_eq!(read(&mut cx, "/d/sub/b").unwrap(), b"text 2");
+    assert_eq!(read(&mut cx, "/d/none").unwrap_err().io_kind(), std::io::ErrorKind::NotFound);
+
+    let m = metadata(&mut cx, "/d/a").unwrap();
+    assert!(m.is_file() && !m.is_dir());
+    assert_eq!(m.len(), 6);
+    assert!(metadata(&mut cx, "/d/sub").unwrap().is_dir());
+    let mut names: Vec<String> = read_dir(&mut cx, "/d").unwrap().map(|e| e.unwrap().file_name().to_owned()).collect();
+    names.sort();
+    assert_eq!(names, vec!["a", "sub"]);
+
+    write(&mut cx, "/d/c", "c").unwrap();
+    rename(&mut cx, "/d/c", "/d/a").unwrap();
+    assert_eq!(read(&mut cx, "/d/a").unwrap(), b"c");
+    // a file is not moved into a directory, nor a directory over a non-empty one
+    assert!(rename(&mut cx, "/d/a", "/d/sub").is_err());
+    create_dir_all(&mut cx, "/e").unwrap();
+    assert!(rename(&mut cx, "/e", "/d").is_err());
+    assert!(metadata(&mut cx, "/d/a").unwrap().is_file() && metadata(&mut cx, "/e").unwrap().is_dir());
+    create_dir_all(&mut cx, "/e2").unwrap();
+    rename(&mut cx, "/e2", "/e").unwrap();
+    assert!(metadata(&mut cx, "/e2").is_err() && metadata(&mut cx, "/e").unwrap().is_empty());
+    assert_eq!(remove_file(&mut cx, "/d/sub").unwrap_err().io_kind(), std::io::ErrorKind::IsADirectory);
+    remove_file(&mut cx, "/d/a").unwrap();
+    assert_eq!(remove_dir_all(&mut cx, "/d/sub/b").unwrap_err().io_kind(), std::io::ErrorKind::NotADirectory);
+    remove_dir_all(&mut cx, "/d").unwrap();
+    assert!(f.paths().iter().all(|p| !p.starts_with("/d")));
+    assert_eq!(remove_dir_all(&mut cx, "/d").unwrap_err().io_kind(), std::io::ErrorKind::NotFound);
+}
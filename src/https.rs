@@ -1,13 +1,17 @@
-use hyper::client::HttpConnector;
-use hyper_tls::HttpsConnector;
 use native_tls::{TlsConnector, Identity, Protocol, Certificate};
 use crate::config::HttpsConfig;
 use crate::error::*;
 
-pub type HttpsConnectorType = HttpsConnector<HttpConnector>;
+pub type TlsConnectorType = tokio_native_tls::TlsConnector;
 
 pub struct HttpsSettings {
-    hc: HttpsConnectorType
+    tls: TlsConnectorType,
+    config: HttpsConfig
+}
+
+impl HttpsSettings {
+    /// Configuration the settings have been built from
+    pub fn config(&self) -> &HttpsConfig { &self.config }
 }
 
 impl From<HttpsConfig> for HttpsSettings {
@@ -16,20 +20,20 @@ impl From<HttpsConfig> for HttpsSettings {
     }
 }
 
-pub type HttpsSettingsPtr = std::rc::Rc<HttpsSettings>;
+pub type HttpsSettingsPtr = std::sync::Arc<HttpsSettings>;
 
 #[inline]
 pub fn https_settings_ptr(https_settings: HttpsSettings) -> HttpsSettingsPtr {
-    std::rc::Rc::new(https_settings)
+    std::sync::Arc::new(https_settings)
 }
 
-pub fn https_connector(cfg: &HttpsSettingsPtr) -> HttpsConnectorType {
-    cfg.hc.clone()
+pub fn tls_connector(cfg: &HttpsSettingsPtr) -> TlsConnectorType {
+    cfg.tls.clone()
 }
 
 fn _test_types() {
     fn is_clone<T: Clone>() { }
-    is_clone::<HttpsConnectorType>();
+    is_clone::<TlsConnectorType>();
 }
 
 
@@ -51,6 +55,7 @@ pub fn read_cert_file(file_path: &str) -> Result<Certificate> {
 
 /// fallible version of convert_https_settings
 fn https_settings_from_config_f(config: HttpsConfig) -> Result<HttpsSettings> {
+    let saved = config.clone();
     let identity_password: &str = if let Some(s) = &config.identity_password { &s } else { "" };
 
     fn pv(s: String) -> Result<Option<Protocol>> {
@@ -75,8 +80,5 @@ fn https_settings_from_config_f(config: HttpsConfig) -> Result<HttpsSettings> {
     }
     if let Some(w) = config.root_certificates { for c in w { cb.add_root_certificate(read_cert_file(&c)?); } }
     let tc = cb.build().unwrap_or_else(|e| panic!("HttpsConnector::new() failure: {}", e));
-    let mut httpc = HttpConnector::new();
-    httpc.enforce_http(false);
-    let hc: HttpsConnectorType = (httpc, tc.into()).into();
-    Ok(HttpsSettings { hc })
+    Ok(HttpsSettings { tls: tc.into(), config: saved })
 }
@@ -0,0 +1,152 @@
+//! Inventory reports.
+//!
+//! `write_inventory` walks a directory tree and writes one record (path, type, size, owner, group, modification time,
+//! replication) per entry. Records are written as directories are listed, so reports of large trees are not held
+//! in memory.
+//!
+//! `largest_children` finds the immediate children of a directory taking the most space.
+use std::io::Write;
+use crate::error::*;
+use crate::datatypes::{FileStatus, dirent_type};
+use crate::sync_client::SyncHdfsClient;
+
+/// Report format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    /// Comma-separated values with a header line
+    Csv,
+    /// One JSON object per line
+    JsonLines
+}
+
+/// CSV header, in record field order
+pub const CSV_HEADER: &str = "path,type,size,owner,group,mtime,replication";
+
+/// Quotes a CSV field if needed
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+fn write_record(out: &mut dyn Write, format: ReportFormat, path: &str, fs: &FileStatus) -> Result<()> {
+    match format {
+        ReportFormat::Csv => writeln!(
+            out, "{},{},{},{},{},{},{}",
+            csv_field(path), fs.type_, fs.length, csv_field(&fs.owner), csv_field(&fs.group), fs.modification_time, fs.replication
+        )?,
+        ReportFormat::JsonLines => writeln!(out, "{}", serde_json::json!({
+            "path": path,
+            "type": fs.type_,
+            "size": fs.length,
+            "owner": fs.owner,
+            "group": fs.group,
+            "mtime": fs.modification_time,
+            "replication": fs.replication
+        }))?
+    }
+    Ok(())
+}
+
+/// Writes an inventory report of the tree under `root` (excluding `root` itself) to `out`.
+/// Modification times are in milliseconds since the epoch; sizes of directories are 0.
+/// Returns the total size of files in bytes.
+pub fn write_inventory(cx: &mut SyncHdfsClient, root: &str, format: ReportFormat, out: &mut dyn Write) -> Result<u64> {
+    if format == ReportFormat::Csv { writeln!(out, "{}", CSV_HEADER)? }
+    let mut total = 0;
+    let mut dirs = vec![root.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = cx.dir_abs(&dir)?;
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (path, fs) in entries {
+            write_record(out, format, &path, &fs)?;
+            match fs.type_.as_str() {
+                dirent_type::DIRECTORY => dirs.push(path),
+                dirent_type::FILE => total += fs.length as u64,
+                _ => ()
+            }
+        }
+    }
+    out.flush()?;
+    Ok(total)
+}
+
+/// Size of a directory entry, including everything below it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildUsage {
+    pub path: String,
+    /// `dirent_type`
+    pub type_: String,
+    /// Total length of files, in bytes
+    pub length: i64,
+    /// Space consumed, including replicas
+    pub space_consumed: i64
+}
+
+/// Returns the `n` largest (by length) immediate children of directory `path`, largest first. Sizes of 
+/// subdirectories are taken from their content summaries, which the namenode computes without listing them.
+pub fn largest_children(cx: &mut SyncHdfsClient, path: &str, n: usize) -> Result<Vec<ChildUsage>> {
+    let mut r = vec![];
+    for (path, fs) in cx.dir_abs(path)? {
+        let (length, space_consumed) = if fs.type_ == dirent_type::DIRECTORY {
+            let cs = cx.content_summary(&path)?.content_summary;
+            (cs.length, cs.space_consumed)
+        } else {
+            (fs.length, fs.length * fs.replication as i64)
+        };
+        r.push(ChildUsage { path, type_: fs.type_, length, space_consumed });
+    }
+    r.sort_by(|a, b| b.length.cmp(&a.length).then_with(|| a.path.cmp(&b.path)));
+    r.truncate(n);
+    Ok(r)
+}
+
+#[test]
+fn test_write_inventory() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.put("/d/a,b", b"0123");
+    f.put("/d/sub/c", b"456");
+    f.mkdirs("/d/empty");
+    f.update("/d/sub/c", |e| e.mtime = 2);
+    let cl = FakeCluster::start(&f);
+    let mut cx = cl.sync_builder().build().unwrap();
+
+    let mut csv = vec![];
+    assert_eq!(write_inventory(&mut cx, "/d", ReportFormat::Csv, &mut csv).unwrap(), 7);
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], CSV_HEADER);
+    assert!(lines[1].starts_with("\"/d/a,b\",FILE,4,"));
+    assert!(lines[2].starts_with("/d/empty,DIRECTORY,0,"));
+    assert!(lines[4].starts_with("/d/sub/c,FILE,3,"));
+    assert!(lines[4].ends_with(",2,3"));
+
+    let mut jsonl = vec![];
+    write_inventory(&mut cx, "/d", ReportFormat::JsonLines, &mut jsonl).unwrap();
+    let records: Vec<serde_json::Value> = jsonl.split(|b| *b == b'\n').filter(|l| !l.is_empty())
+        .map(|l| serde_json::from_slice(l).unwrap()).collect();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[3]["path"], "/d/sub/c");
+    assert_eq!(records[3]["size"], 3);
+}
+
+#[test]
+fn test_largest_children() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.put("/d/a", b"0123");
+    f.put("/d/big/x", b"0123");
+    f.put("/d/big/sub/y", b"45678");
+    f.put("/d/b", b"0");
+    f.mkdirs("/d/empty");
+    let cl = FakeCluster::start(&f);
+    let mut cx = cl.sync_builder().build().unwrap();
+    let top = largest_children(&mut cx, "/d", 3).unwrap();
+    let top: Vec<_> = top.iter().map(|c| (c.path.as_str(), c.type_.as_str(), c.length)).collect();
+    assert_eq!(top, vec![("/d/big", "DIRECTORY", 9), ("/d/a", "FILE", 4), ("/d/b", "FILE", 1)]);
+    assert_eq!(cl.nn.requests().iter().filter(|r| r.op() == "GETCONTENTSUMMARY").count(), 2);
+}
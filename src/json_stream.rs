@@ -0,0 +1,94 @@
+//! Incremental parsing of the objects of a JSON array, e.g. the entries of a LISTSTATUS response,
+//! as the response body is received.
+
+use serde::de::DeserializeOwned;
+use crate::error::*;
+
+enum State { Before, Between, Item, Done }
+
+/// Extracts the objects of the first array in a JSON document, from the document fed in arbitrary chunks.
+/// Only objects are expected as array elements; anything else between them is skipped.
+pub struct ArrayObjects {
+    state: State,
+    item: Vec<u8>,
+    depth: u32,
+    in_string: bool,
+    escape: bool
+}
+
+impl ArrayObjects {
+    pub fn new() -> Self { Self { state: State::Before, item: vec![], depth: 0, in_string: false, escape: false } }
+
+    /// Feeds the next chunk of the document, returns the objects completed by it
+    pub fn push<T: DeserializeOwned>(&mut self, chunk: &[u8]) -> Result<Vec<T>> {
+        let mut out = vec![];
+        for &b in chunk {
+            match self.state {
+                State::Before => if b == b'[' { self.state = State::Between },
+                State::Between => match b {
+                    b']' => self.state = State::Done,
+                    b'{' => {
+                        self.item.clear();
+                        self.item.push(b);
+                        self.depth = 1;
+                        self.state = State::Item;
+                    }
+                    _ => ()
+                }
+                State::Item => {
+                    self.item.push(b);
+                    if self.in_string {
+                        if self.escape { self.escape = false }
+                        else if b == b'\\' { self.escape = true }
+                        else if b == b'"' { self.in_string = false }
+                        continue
+                    }
+                    match b {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => self.depth += 1,
+                        b'}' | b']' => {
+                            self.depth -= 1;
+                            if self.depth == 0 {
+                                out.push(serde_json::from_slice(&self.item).aerr("JSON deseriaization error")?);
+                                self.state = State::Between;
+                            }
+                        }
+                        _ => ()
+                    }
+                }
+                State::Done => break
+            }
+        }
+        Ok(out)
+    }
+
+    /// Checks that the end of the array has been reached
+    pub fn finish(&self) -> Result<()> {
+        match self.state {
+            State::Done => Ok(()),
+            _ => Err(app_error!(generic "Unexpected end of JSON array"))
+        }
+    }
+}
+
+#[test]
+fn test_array_objects() {
+    let doc = br#"{"FileStatuses":{"FileStatus":[
+        {"pathSuffix":"a","v":[1,{"x":"}"}]},
+        {"pathSuffix":"b\"]{","v":[]}
+    ]}}"#;
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct E { path_suffix: String }
+    for split in 1..doc.len() {
+        let mut p = ArrayObjects::new();
+        let mut items: Vec<E> = p.push(&doc[..split]).unwrap();
+        assert!(split == doc.len() || doc[split..].contains(&b']') == p.finish().is_err());
+        items.extend(p.push::<E>(&doc[split..]).unwrap());
+        p.finish().unwrap();
+        assert_eq!(items, vec![E { path_suffix: "a".to_owned() }, E { path_suffix: "b\"]{".to_owned() }]);
+    }
+    let mut p = ArrayObjects::new();
+    assert!(p.push::<E>(br#"{"FileStatuses":{"FileStatus":[{"pathSuffix":"a"}"#).unwrap().len() == 1 && p.finish().is_err());
+    assert!(ArrayObjects::new().push::<E>(br#"[{"pathSuffix":1}]"#).is_err());
+}
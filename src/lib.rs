@@ -21,18 +21,61 @@
 mod error;
 mod https;
 mod rest_client;
+mod connector;
+mod dns_cache;
 mod natmap;
 mod uri_tools;
+mod json_stream;
 mod op;
+mod shutdown;
+#[cfg(unix)]
+pub mod unix;
+#[cfg(test)]
+mod mock_server;
 pub mod config;
+pub mod metrics;
+pub mod path;
+pub mod redact;
+pub mod schema;
+pub mod permission;
 pub mod datatypes;
 pub mod async_client;
 pub mod sync_client;
+pub mod transfer;
+pub mod compare;
+pub mod inventory;
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "snappy"))]
+pub mod codec;
+pub mod federation;
+pub mod pool;
+pub mod rate_limit;
+pub mod circuit_breaker;
+pub mod filter;
+pub mod registry;
+pub mod resolver;
+pub mod write_queue;
+pub mod trash;
+pub mod tree_delete;
+pub mod publish;
+pub mod lock;
+pub mod pack;
+pub mod fs;
+pub mod afs;
+#[cfg(feature = "object-store")]
+pub mod objstore;
+#[cfg(feature = "checksum")]
+pub mod dedupe;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "simple")]
+pub mod simple;
 
 pub use natmap::NatMap;
+pub use metrics::{Metrics, MetricsPtr};
 pub use error::{Error, Result};
 pub use datatypes::*;
 pub use op::*;
-pub use async_client::{HdfsClient, HdfsClientBuilder};
+pub use permission::Permission;
+pub use async_client::{HdfsClient, HdfsClientBuilder, Strictness, ExceptionClass};
 pub use sync_client::{SyncHdfsClient, SyncHdfsClientBuilder};
 pub use http::Uri;
\ No newline at end of file
@@ -0,0 +1,251 @@
+//! Advisory lock files.
+//!
+//! A lock is a file created with `overwrite=false`: CREATE fails if the file exists, so only one client acquires it.
+//! The file holds a JSON `LockInfo` with the owner, a random token and the expiration time.
+//!
+//! A lock file is only ever removed by a client holding its claim, i.e. the lock file `{path}.takeover-{token}`
+//! named after the token of the lock being removed. Claims are created with `overwrite=false` too, so a given lock
+//! is claimed by one party at a time, and a lock that has been taken over is never touched by its former owner
+//! or by a contender that read it before. To take over an expired lock, a contender claims it, checks that the lock
+//! file still holds the expired lock, renames it to a name unique to the contender, deletes the renamed copy if it
+//! is the expired lock (otherwise renames it back), releases the claim and creates the lock anew. `unlock` claims
+//! the lock the same way before removing it. A claim left by a crashed client expires after `CLAIM_TTL` and is
+//! taken over like any lock.
+//!
+//! A lock file whose content cannot be parsed is being written, unless it has not been modified for `CLAIM_TTL`:
+//! its writer is then assumed to have failed, and the file is treated as an expired lock (see `read_lock`).
+//!
+//! Clocks of the participating hosts are assumed to be roughly in sync, and no client is assumed to stall longer
+//! than the TTL of a lock (or claim) it holds.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use log::{info, warn};
+use crate::error::*;
+use crate::sync_client::{SyncHdfsClient, CreateOptions, DeleteOptions};
+
+/// Time a takeover or an unlock may take, after which its claim may be taken over
+const CLAIM_TTL: Duration = Duration::from_secs(60);
+
+/// Content of a lock file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub owner: String,
+    /// Unique per acquisition
+    pub token: String,
+    /// Expiration time, in milliseconds since the epoch
+    pub expires: i64
+}
+
+impl LockInfo {
+    pub fn is_expired(&self) -> bool { self.expires <= now_ms() }
+}
+
+/// Acquired lock (see `try_lock`)
+#[derive(Debug, Clone)]
+pub struct HdfsLock {
+    pub path: String,
+    pub info: LockInfo
+}
+
+fn now_ms() -> i64 { SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64 }
+
+/// 128 random bits, hex-encoded
+fn new_token() -> Result<String> {
+    let mut b = [0u8; 16];
+    getrandom::fill(&mut b).map_err(|e| app_error!(generic "Cannot generate lock token: {}", e))?;
+    Ok(b.iter().map(|x| format!("{:02x}", x)).collect())
+}
+
+fn claim_path(path: &str, lock: &LockInfo) -> String { format!("{}.takeover-{}", path, lock.token) }
+
+/// Reads the lock file at `path`. Returns `None` if there is no lock file, or if it is still being written
+/// (its content is not complete yet). A lock file left incomplete, i.e. not modified for `CLAIM_TTL`, is returned
+/// as an expired lock with no owner, identified by its modification time.
+pub fn read_lock(cx: &mut SyncHdfsClient, path: &str) -> Result<Option<LockInfo>> {
+    let not_found = |e: &Error| e.io_kind() == std::io::ErrorKind::NotFound;
+    let mut buf = vec![];
+    match cx.get_file(path, &mut buf) {
+        Ok(()) => (),
+        Err(e) if not_found(&e) => return Ok(None),
+        Err(e) => return Err(e)
+    }
+    if let Ok(info) = serde_json::from_slice(&buf) { return Ok(Some(info)) }
+    let mtime = match cx.stat(path) {
+        Ok(s) => s.file_status.modification_time,
+        Err(e) if not_found(&e) => return Ok(None),
+        Err(e) => return Err(e)
+    };
+    let expires = mtime + CLAIM_TTL.as_millis() as i64;
+    if expires > now_ms() { return Ok(None) }
+    Ok(Some(LockInfo { owner: String::new(), token: format!("incomplete-{}", mtime), expires }))
+}
+
+/// Tries to acquire the lock at `path` for `ttl`, taking over an expired lock. Returns `None` if the lock is held.
+pub fn try_lock(cx: &mut SyncHdfsClient, path: &str, owner: &str, ttl: Duration) -> Result<Option<HdfsLock>> {
+    let info = LockInfo { owner: owner.to_owned(), token: new_token()?, expires: now_ms() + ttl.as_millis() as i64 };
+    let data = serde_json::to_vec(&info)?;
+    for _ in 0..2 {
+        match cx.create(path, data.clone().into(), CreateOptions::new().overwrite(false)) {
+            Ok(()) => return Ok(Some(HdfsLock { path: path.to_owned(), info })),
+            Err(e) if e.error.io_kind() == std::io::ErrorKind::AlreadyExists => (),
+            Err(e) => return Err(e.error)
+        }
+        let held = match read_lock(cx, path)? {
+            Some(held) => held,
+            None => continue
+        };
+        if !held.is_expired() || !take_over(cx, path, &held, owner)? { return Ok(None) }
+    }
+    Ok(None)
+}
+
+/// Removes expired lock `held` at `path`. Returns `false` if the lock is claimed by another contender,
+/// or has been changed meanwhile.
+fn take_over(cx: &mut SyncHdfsClient, path: &str, held: &LockInfo, owner: &str) -> Result<bool> {
+    let claim = match try_lock(cx, &claim_path(path, held), owner, CLAIM_TTL)? {
+        Some(claim) => claim,
+        None => return Ok(false)
+    };
+    let removed = remove(cx, path, held, &claim.info.token);
+    if let Ok(true) = removed { info!("Took over lock {} of {} expired at {}", path, held.owner, held.expires) }
+    release(cx, &claim)?;
+    removed
+}
+
+/// Deletes the lock file at `path` if it holds `held`: renames it to a name unique to `token`, then deletes the
+/// renamed copy if it is `held`, or renames it back otherwise. Returns whether the lock has been deleted.
+fn remove(cx: &mut SyncHdfsClient, path: &str, held: &LockInfo, token: &str) -> Result<bool> {
+    if read_lock(cx, path)?.as_ref() != Some(held) { return Ok(false) }
+    let aside = format!("{}.removing-{}", path, token);
+    if !cx.rename(path, aside.clone())? { return Ok(false) }
+    if read_lock(cx, &aside)?.as_ref() == Some(held) {
+        cx.delete(&aside, DeleteOptions::new())?;
+        return Ok(true)
+    }
+    if !cx.rename(&aside, path.to_owned())? { warn!("Cannot restore lock {} from {}", path, aside) }
+    Ok(false)
+}
+
+/// Releases a claim. A claim that has expired and has been taken over meanwhile is left alone.
+fn release(cx: &mut SyncHdfsClient, claim: &HdfsLock) -> Result<()> {
+    if !remove(cx, &claim.path, &claim.info, &claim.info.token)? { warn!("Claim {} has been lost", claim.path) }
+    Ok(())
+}
+
+/// Releases `lock`. Fails if the lock has expired and has been taken over (or is being taken over).
+pub fn unlock(cx: &mut SyncHdfsClient, lock: HdfsLock) -> Result<()> {
+    let removed = match try_lock(cx, &claim_path(&lock.path, &lock.info), &lock.info.owner, CLAIM_TTL)? {
+        Some(claim) => {
+            let removed = remove(cx, &lock.path, &lock.info, &claim.info.token);
+            release(cx, &claim)?;
+            removed?
+        }
+        None => false
+    };
+    if removed { Ok(()) } else { Err(app_error!(generic "Lock {} has been lost (expired and taken over)", lock.path)) }
+}
+
+#[test]
+fn test_lock() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.mkdirs("/locks");
+    let c = FakeCluster::start(&f);
+    let mut cx = c.sync_builder().build().unwrap();
+
+    let a = try_lock(&mut cx, "/locks/job", "a", Duration::from_secs(60)).unwrap().unwrap();
+    assert!(try_lock(&mut cx, "/locks/job", "b", Duration::from_secs(60)).unwrap().is_none());
+    assert_eq!(read_lock(&mut cx, "/locks/job").unwrap().unwrap().owner, "a");
+    unlock(&mut cx, a).unwrap();
+    assert!(read_lock(&mut cx, "/locks/job").unwrap().is_none());
+
+    let a = try_lock(&mut cx, "/locks/job", "a", Duration::from_millis(0)).unwrap().unwrap();
+    let b = try_lock(&mut cx, "/locks/job", "b", Duration::from_secs(60)).unwrap().unwrap();
+    assert_eq!(f.paths().iter().filter(|p| p.starts_with("/locks/")).count(), 1);
+    assert!(unlock(&mut cx, a).is_err());
+    unlock(&mut cx, b).unwrap();
+}
+
+#[cfg(test)]
+fn lock_info(owner: &str, token: &str, expires: i64) -> Vec<u8> {
+    serde_json::to_vec(&LockInfo { owner: owner.to_owned(), token: token.to_owned(), expires }).unwrap()
+}
+
+#[test]
+fn test_lock_takeover_races() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.mkdirs("/locks");
+    let cl = FakeCluster::start(&f);
+    let mut cx = cl.sync_builder().build().unwrap();
+    let locks = |f: &FakeHdfs| f.paths().into_iter().filter(|p| p.starts_with("/locks/")).collect::<Vec<_>>();
+
+    // a contender that read the expired lock before it has been taken over does not touch the new lock
+    let a = try_lock(&mut cx, "/locks/job", "a", Duration::from_millis(0)).unwrap().unwrap();
+    let b = try_lock(&mut cx, "/locks/job", "b", Duration::from_secs(60)).unwrap().unwrap();
+    assert!(!take_over(&mut cx, "/locks/job", &a.info, "c").unwrap());
+    assert_eq!(read_lock(&mut cx, "/locks/job").unwrap(), Some(b.info.clone()));
+    assert!(unlock(&mut cx, a).is_err());
+    assert_eq!(read_lock(&mut cx, "/locks/job").unwrap(), Some(b.info.clone()));
+    unlock(&mut cx, b).unwrap();
+    assert!(locks(&f).is_empty());
+
+    // a lock file being written is held; one left incomplete for longer than CLAIM_TTL is taken over
+    f.put("/locks/job", b"{\"owner\":");
+    f.update("/locks/job", |e| e.mtime = now_ms());
+    assert_eq!(read_lock(&mut cx, "/locks/job").unwrap(), None);
+    assert!(try_lock(&mut cx, "/locks/job", "c", Duration::from_secs(60)).unwrap().is_none());
+    f.update("/locks/job", |e| e.mtime = now_ms() - CLAIM_TTL.as_millis() as i64 - 1);
+    assert!(read_lock(&mut cx, "/locks/job").unwrap().unwrap().is_expired());
+    let c = try_lock(&mut cx, "/locks/job", "c", Duration::from_secs(60)).unwrap().unwrap();
+    assert_eq!(read_lock(&mut cx, "/locks/job").unwrap(), Some(c.info.clone()));
+    assert_eq!(locks(&f), vec!["/locks/job"]);
+    unlock(&mut cx, c).unwrap();
+
+    // so is a stale empty lock file
+    f.put("/locks/job", b"");
+    let c = try_lock(&mut cx, "/locks/job", "c", Duration::from_secs(60)).unwrap().unwrap();
+    assert_eq!(locks(&f), vec!["/locks/job"]);
+    unlock(&mut cx, c).unwrap();
+
+    // an expired lock being taken over by another contender is held; the claim of a crashed contender expires
+    f.put("/locks/job", &lock_info("a", "t1", 0));
+    f.put("/locks/job.takeover-t1", &lock_info("b", "t2", now_ms() + 60_000));
+    assert!(try_lock(&mut cx, "/locks/job", "c", Duration::from_secs(60)).unwrap().is_none());
+    f.put("/locks/job.takeover-t1", &lock_info("b", "t2", 0));
+    let c = try_lock(&mut cx, "/locks/job", "c", Duration::from_secs(60)).unwrap().unwrap();
+    assert_eq!(locks(&f), vec!["/locks/job"]);
+    unlock(&mut cx, c).unwrap();
+}
+
+#[test]
+fn test_lock_contenders() {
+    use std::sync::{Arc, Barrier};
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    use crate::sync_client::SyncHdfsClientBuilder;
+    const CONTENDERS: usize = 6;
+    let f = FakeHdfs::new();
+    f.mkdirs("/locks");
+    let c = FakeCluster::start(&f);
+    let uri = c.nn.uri();
+
+    for round in 0..4 {
+        // free lock in even rounds, expired lock in odd ones
+        if round % 2 == 1 { f.put("/locks/job", &lock_info("old", &format!("old{}", round), 0)) }
+        let barrier = Arc::new(Barrier::new(CONTENDERS));
+        let threads: Vec<_> = (0..CONTENDERS).map(|i| {
+            let (barrier, uri) = (barrier.clone(), uri.clone());
+            std::thread::spawn(move || {
+                let mut cx = SyncHdfsClientBuilder::new(uri.parse().unwrap()).build().unwrap();
+                barrier.wait();
+                try_lock(&mut cx, "/locks/job", &format!("c{}", i), Duration::from_secs(60)).unwrap()
+            })
+        }).collect();
+        let winners: Vec<HdfsLock> = threads.into_iter().filter_map(|t| t.join().unwrap()).collect();
+        assert_eq!(winners.len(), 1, "round {}", round);
+        let mut cx = c.sync_builder().build().unwrap();
+        assert_eq!(read_lock(&mut cx, "/locks/job").unwrap(), Some(winners[0].info.clone()));
+        assert_eq!(f.paths().into_iter().filter(|p| p.starts_with("/locks/")).collect::<Vec<_>>(), vec!["/locks/job"]);
+        unlock(&mut cx, winners.into_iter().next().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Client metrics.
+//!
+//! A `Metrics` instance is shared (via `MetricsPtr`) between the client and the application. The client
+//! increments named counters, keyed by counter name and endpoint (URI authority); the application may query
+//! them at any time. Pass your own instance to `HdfsClientBuilder::metrics` to keep a handle to it.
+//!
+//! Timers are pairs of counters: counter `name` holds the number of observations, and counter `name` with
+//! `counter::TIME_US_SUFFIX` appended holds their total duration in microseconds (see `Metrics::observe`).
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Counter names
+pub mod counter {
+    /// Number of failovers away from the endpoint
+    pub const FAILOVER: &str = "failover";
+    /// Number of requests retried against the endpoint after a failover
+    pub const RETRY: &str = "retry";
+    /// Timer: resolution of the endpoint host name
+    pub const DNS: &str = "dns";
+    /// Timer: TCP connection establishment, excluding host name resolution
+    pub const CONNECT: &str = "connect";
+    /// Timer: TLS handshake
+    pub const TLS_HANDSHAKE: &str = "tls_handshake";
+    /// Timer: time to first byte, from sending a request until its response headers are received. 
+    /// Includes connection establishment if the request could not reuse a pooled connection.
+    pub const TTFB: &str = "ttfb";
+    /// Number of HTTP requests sent to the endpoint
+    pub const REQUESTS: &str = "requests";
+    /// Request body bytes sent to the endpoint
+    pub const BYTES_OUT: &str = "bytes_out";
+    /// Response body bytes received from the endpoint
+    pub const BYTES_IN: &str = "bytes_in";
+    /// Number of windows in which a read from the endpoint (a datanode) was slower than the threshold 
+    /// (see `SlowReadPolicy`)
+    pub const SLOW_READ: &str = "slow_read";
+    /// Number of requests to the endpoint rejected by an open circuit (see `CircuitBreaker`)
+    pub const CIRCUIT_OPEN: &str = "circuit_open";
+    /// Suffix of timer counters holding total duration in microseconds
+    pub const TIME_US_SUFFIX: &str = "_us";
+}
+
+/// Counters collected by the client
+pub struct Metrics {
+    counters: Mutex<BTreeMap<(String, String), u64>>
+}
+
+impl Metrics {
+    pub fn new() -> Self { Self { counters: Mutex::new(BTreeMap::new()) } }
+
+    /// Adds `delta` to the counter `name` of `endpoint`
+    pub fn incr(&self, name: &str, endpoint: &str, delta: u64) {
+        let mut c = self.counters.lock().unwrap();
+        *c.entry((name.to_owned(), endpoint.to_owned())).or_insert(0) += delta;
+    }
+
+    /// Value of the counter `name` of `endpoint`
+    pub fn counter(&self, name: &str, endpoint: &str) -> u64 {
+        let c = self.counters.lock().unwrap();
+        c.get(&(name.to_owned(), endpoint.to_owned())).cloned().unwrap_or(0)
+    }
+
+    /// Records an observation of the timer `name` of `endpoint`
+    pub fn observe(&self, name: &str, endpoint: &str, elapsed: Duration) {
+        let mut c = self.counters.lock().unwrap();
+        *c.entry((name.to_owned(), endpoint.to_owned())).or_insert(0) += 1;
+        *c.entry((name.to_owned() + counter::TIME_US_SUFFIX, endpoint.to_owned())).or_insert(0) += elapsed.as_micros() as u64;
+    }
+
+    /// Mean duration of the timer `name` of `endpoint`, `None` if there were no observations
+    pub fn mean(&self, name: &str, endpoint: &str) -> Option<Duration> {
+        let n = self.counter(name, endpoint);
+        if n == 0 { return None }
+        Some(Duration::from_micros(self.counter(&(name.to_owned() + counter::TIME_US_SUFFIX), endpoint) / n))
+    }
+
+    /// Mean duration of the timer `name` over all endpoints, `None` if there were no observations
+    pub fn total_mean(&self, name: &str) -> Option<Duration> {
+        let n = self.total(name);
+        if n == 0 { return None }
+        Some(Duration::from_micros(self.total(&(name.to_owned() + counter::TIME_US_SUFFIX)) / n))
+    }
+
+    /// Value of the counter `name`, summed over all endpoints
+    pub fn total(&self, name: &str) -> u64 {
+        let c = self.counters.lock().unwrap();
+        c.iter().filter(|((n, _), _)| n == name).map(|(_, v)| v).sum()
+    }
+
+    /// Snapshot of all counters, as `(name, endpoint, value)`
+    pub fn counters(&self) -> Vec<(String, String, u64)> {
+        let c = self.counters.lock().unwrap();
+        c.iter().map(|((n, e), v)| (n.clone(), e.clone(), *v)).collect()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self { Self::new() }
+}
+
+pub type MetricsPtr = Arc<Metrics>;
+
+/// Snapshot of client state and statistics, see `HdfsClient::stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStats {
+    /// Entrypoint (URI authority) of the last known active namenode
+    pub active_endpoint: String,
+    /// Number of HTTP requests sent, to namenodes and datanodes
+    pub requests: u64,
+    pub failovers: u64,
+    pub retries: u64,
+    /// Response body bytes received
+    pub bytes_in: u64,
+    /// Request body bytes sent
+    pub bytes_out: u64,
+    /// Mean time to first byte over all requests, `None` if no responses have been received
+    pub mean_latency: Option<Duration>
+}
+
+#[inline]
+pub fn metrics_ptr(metrics: Metrics) -> MetricsPtr { Arc::new(metrics) }
+
+#[test]
+fn test_metrics() {
+    let m = Metrics::new();
+    m.incr(counter::FAILOVER, "nn1:50070", 1);
+    m.incr(counter::FAILOVER, "nn1:50070", 1);
+    m.incr(counter::FAILOVER, "nn2:50070", 1);
+    assert_eq!(m.counter(counter::FAILOVER, "nn1:50070"), 2);
+    assert_eq!(m.counter(counter::RETRY, "nn1:50070"), 0);
+    assert_eq!(m.total(counter::FAILOVER), 3);
+    assert_eq!(m.counters().len(), 2);
+}
+
+#[test]
+fn test_timers() {
+    let m = Metrics::new();
+    m.observe(counter::TTFB, "dn1:50075", Duration::from_millis(10));
+    m.observe(counter::TTFB, "dn1:50075", Duration::from_millis(30));
+    assert_eq!(m.counter(counter::TTFB, "dn1:50075"), 2);
+    assert_eq!(m.counter("ttfb_us", "dn1:50075"), 40000);
+    assert_eq!(m.mean(counter::TTFB, "dn1:50075"), Some(Duration::from_millis(20)));
+    assert_eq!(m.mean(counter::TTFB, "dn2:50075"), None);
+}
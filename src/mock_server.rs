@@ -0,0 +1,457 @@
+//! Minimal HTTP/1.1 server used by unit tests in place of namenodes and datanodes.
+//!
+//! Each connection is served by a separate thread and closed after a single exchange.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path_and_query: String,
+    /// URI scheme the server is reachable with (`http`, or `unix` for unix socket servers)
+    pub scheme: String,
+    /// `Host` header, i.e. the authority of this server as seen by the client
+    pub host: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl MockRequest {
+    /// Value of query parameter `p` (not decoded)
+    pub fn param(&self, p: &str) -> Option<&str> {
+        let q = self.path_and_query.splitn(2, '?').nth(1)?;
+        q.split('&').filter_map(|kv| {
+            let mut i = kv.splitn(2, '=');
+            match (i.next(), i.next()) { (Some(k), Some(v)) if k == p => Some(v), _ => None }
+        }).next()
+    }
+    pub fn op(&self) -> &str { self.param("op").unwrap_or("") }
+    pub fn path(&self) -> &str { self.path_and_query.splitn(2, '?').next().unwrap_or("") }
+    pub fn header(&self, h: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(h)).map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Pause in sending the body, after the given number of bytes
+    pub pause: Option<(usize, std::time::Duration)>
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self { Self { status, headers: vec![], body: vec![], pause: None } }
+    pub fn header(mut self, k: &str, v: &str) -> Self { self.headers.push((k.to_owned(), v.to_owned())); self }
+    pub fn pause(self, after: usize, duration: std::time::Duration) -> Self { Self { pause: Some((after, duration)), ..self } }
+    pub fn json(status: u16, body: &str) -> Self {
+        Self { body: body.as_bytes().to_vec(), ..Self::new(status) }.header("Content-Type", "application/json")
+    }
+    pub fn binary(body: &[u8]) -> Self {
+        Self { body: body.to_vec(), ..Self::new(200) }.header("Content-Type", "application/octet-stream")
+    }
+    pub fn redirect(location: &str) -> Self { Self::new(307).header("Location", location) }
+    pub fn remote_exception(status: u16, exception: &str, java_class_name: &str, message: &str) -> Self {
+        Self::json(status, &serde_json::json!({
+            "RemoteException": {"exception": exception, "javaClassName": java_class_name, "message": message}
+        }).to_string())
+    }
+    pub fn standby() -> Self {
+        Self::remote_exception(403, "StandbyException", "org.apache.hadoop.ipc.StandbyException",
+            "Operation category WRITE is not supported in state standby")
+    }
+}
+
+/// `FileStatus` object JSON
+pub fn file_status_json(type_: &str, length: i64) -> String {
+    format!(r#"{{"FileStatus":{{"accessTime":0,"blockSize":134217728,"group":"supergroup","length":{},
+"modificationTime":1320173277227,"owner":"webuser","pathSuffix":"","permission":"644","replication":1,"type":"{}"}}}}"#,
+        length, type_)
+}
+
+pub type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+pub struct MockServer {
+    scheme: &'static str,
+    authority: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>
+}
+
+impl MockServer {
+    pub fn start(handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let authority = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let r = requests.clone();
+        std::thread::spawn(move || for s in listener.incoming() {
+            if let Ok(s) = s {
+                let (handler, r) = (handler.clone(), r.clone());
+                std::thread::spawn(move || serve("http", s.try_clone().unwrap(), s, handler, r));
+            }
+        });
+        Self { scheme: "http", authority, requests }
+    }
+
+    /// Starts a server listening on unix socket `path` (replaced if exists). See `crate::unix`.
+    #[cfg(unix)]
+    pub fn start_unix(path: &std::path::Path, handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let _ = std::fs::remove_file(path);
+        let listener = std::os::unix::net::UnixListener::bind(path).unwrap();
+        let uri = crate::unix::socket_uri(path).unwrap();
+        let authority = uri.authority().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let r = requests.clone();
+        std::thread::spawn(move || for s in listener.incoming() {
+            if let Ok(s) = s {
+                let (handler, r) = (handler.clone(), r.clone());
+                std::thread::spawn(move || serve(crate::unix::SCHEME, s.try_clone().unwrap(), s, handler, r));
+            }
+        });
+        Self { scheme: crate::unix::SCHEME, authority, requests }
+    }
+
+    pub fn authority(&self) -> String { self.authority.clone() }
+    pub fn uri(&self) -> String { format!("{}://{}", self.scheme, self.authority) }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> { self.requests.lock().unwrap().clone() }
+}
+
+/// Common setup of client tests: a mock namenode, and a runtime to run client calls on
+pub struct FakeCluster {
+    pub nn: MockServer,
+    pub rt: tokio::runtime::Runtime
+}
+
+impl FakeCluster {
+    /// Starts a namenode serving `fs`, which remains shared with the caller
+    pub fn start(fs: &FakeHdfs) -> Self { Self::with_server(fs.start()) }
+
+    /// Uses `nn` as the namenode, e.g. a `MockServer` wrapping `FakeHdfs::handle`
+    pub fn with_server(nn: MockServer) -> Self {
+        Self { nn, rt: tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap() }
+    }
+
+    /// Builder of a client of the namenode
+    pub fn builder(&self) -> crate::async_client::HdfsClientBuilder {
+        crate::async_client::HdfsClientBuilder::new(self.nn.uri().parse().unwrap())
+    }
+
+    /// Builder of a sync client of the namenode
+    pub fn sync_builder(&self) -> crate::sync_client::SyncHdfsClientBuilder {
+        crate::sync_client::SyncHdfsClientBuilder::new(self.nn.uri().parse().unwrap())
+    }
+
+    /// Runs client call `f`, dropping the failover state
+    pub fn run<T, E>(&self, f: impl std::future::Future<Output=crate::async_client::FOStdResult<T, E>>) -> std::result::Result<T, E> {
+        crate::async_client::FOR::split(self.rt.block_on(f)).0
+    }
+}
+
+fn serve(scheme: &str, rd: impl Read, mut s: impl Write, handler: Arc<Handler>, requests: Arc<Mutex<Vec<MockRequest>>>) {
+    let mut rd = BufReader::new(rd);
+    let mut line = String::new();
+    if rd.read_line(&mut line).unwrap_or(0) == 0 { return }
+    let mut rl = line.trim_end().split(' ');
+    let method = rl.next().unwrap_or("").to_owned();
+    let path_and_query = rl.next().unwrap_or("").to_owned();
+
+    let mut headers = vec![];
+    loop {
+        let mut h = String::new();
+        if rd.read_line(&mut h).unwrap_or(0) == 0 { break }
+        let h = h.trim_end();
+        if h.is_empty() { break }
+        let mut kv = h.splitn(2, ':');
+        headers.push((kv.next().unwrap_or("").trim().to_owned(), kv.next().unwrap_or("").trim().to_owned()));
+    }
+
+    let mut req = MockRequest { method, path_and_query, scheme: scheme.to_owned(), host: String::new(), headers, body: vec![] };
+    req.host = req.header("host").unwrap_or("").to_owned();
+    let cl: usize = req.header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let mut body = vec![0u8; cl];
+    if rd.read_exact(&mut body).is_err() { return }
+    req.body = body;
+
+    let resp = handler(&req);
+    requests.lock().unwrap().push(req);
+
+    let mut out = format!("HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n", resp.status, resp.body.len());
+    for (k, v) in &resp.headers { out += &format!("{}: {}\r\n", k, v); }
+    out += "\r\n";
+    let _ = s.write_all(out.as_bytes());
+    let (head, tail) = resp.body.split_at(resp.pause.map(|(n, _)| n.min(resp.body.len())).unwrap_or(0));
+    let _ = s.write_all(head);
+    let _ = s.flush();
+    if let Some((_, d)) = resp.pause { std::thread::sleep(d) }
+    let _ = s.write_all(tail);
+    let _ = s.flush();
+}
+
+/// In-memory HDFS namenode/datanode: serves a subset of WebHDFS operations over a map of absolute paths.
+/// Two-step operations are redirected back to the same server, with `datanode=true` added.
+#[derive(Clone)]
+pub struct FakeHdfs {
+    fs: Arc<Mutex<std::collections::BTreeMap<String, FakeEntry>>>,
+    /// Directory quotas, as (namespace quota, space quota)
+    quotas: Arc<Mutex<std::collections::HashMap<String, (i64, i64)>>>
+}
+
+#[derive(Clone)]
+pub struct FakeEntry {
+    pub dir: bool,
+    pub data: Vec<u8>,
+    pub mtime: i64,
+    pub target: Option<String>
+}
+
+impl FakeHdfs {
+    const PREFIX: &'static str = "/webhdfs/v1";
+
+    pub fn new() -> Self {
+        let f = Self { fs: Arc::new(Mutex::new(std::collections::BTreeMap::new())), quotas: Arc::new(Mutex::new(Default::default())) };
+        f.mkdirs("/");
+        f
+    }
+
+    /// Starts a server on top of this file system
+    pub fn start(&self) -> MockServer {
+        let f = self.clone();
+        MockServer::start(move |r| f.handle(r))
+    }
+
+    /// Starts a unix socket server on top of this file system
+    #[cfg(unix)]
+    pub fn start_unix(&self, path: &std::path::Path) -> MockServer {
+        let f = self.clone();
+        MockServer::start_unix(path, move |r| f.handle(r))
+    }
+
+    pub fn put(&self, path: &str, data: &[u8]) {
+        self.mkdirs(&parent(path));
+        self.fs.lock().unwrap().insert(path.to_owned(), FakeEntry { dir: false, data: data.to_vec(), mtime: 1, target: None });
+    }
+
+    pub fn mkdirs(&self, path: &str) {
+        let mut fs = self.fs.lock().unwrap();
+        let mut p = String::new();
+        fs.entry("/".to_owned()).or_insert(FakeEntry { dir: true, data: vec![], mtime: 1, target: None });
+        for c in path.split('/').filter(|c| !c.is_empty()) {
+            p = p + "/" + c;
+            fs.entry(p.clone()).or_insert(FakeEntry { dir: true, data: vec![], mtime: 1, target: None });
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<FakeEntry> { self.fs.lock().unwrap().get(path).cloned() }
+
+    pub fn update(&self, path: &str, f: impl FnOnce(&mut FakeEntry)) {
+        if let Some(e) = self.fs.lock().unwrap().get_mut(path) { f(e) }
+    }
+
+    /// Sets namespace and space quotas of a directory (reported by GETCONTENTSUMMARY only)
+    pub fn set_quota(&self, path: &str, quota: i64, space_quota: i64) {
+        self.quotas.lock().unwrap().insert(path.to_owned(), (quota, space_quota));
+    }
+
+    pub fn paths(&self) -> Vec<String> { self.fs.lock().unwrap().keys().cloned().collect() }
+
+    fn children(&self, path: &str) -> Vec<(String, FakeEntry)> {
+        let pfx = if path == "/" { "/".to_owned() } else { format!("{}/", path) };
+        self.fs.lock().unwrap().iter()
+            .filter(|(k, _)| k.starts_with(&pfx) && k.len() > pfx.len() && !k[pfx.len()..].contains('/'))
+            .map(|(k, v)| (k[pfx.len()..].to_owned(), v.clone()))
+            .collect()
+    }
+
+    fn status(suffix: &str, e: &FakeEntry) -> serde_json::Value {
+        let type_ = if e.target.is_some() { "SYMLINK" } else if e.dir { "DIRECTORY" } else { "FILE" };
+        let mut v = serde_json::json!({
+            "accessTime": 0, "blockSize": if e.dir { 0 } else { 134217728 }, "group": "supergroup", 
+            "length": e.data.len(), "modificationTime": e.mtime, "owner": "webuser", "pathSuffix": suffix,
+            "permission": if e.dir { "755" } else { "644" }, "replication": if e.dir { 0 } else { 3 }, "type": type_
+        });
+        if let Some(t) = &e.target { v["symlink"] = serde_json::Value::String(t.clone()); }
+        v
+    }
+
+    fn not_found(path: &str) -> MockResponse {
+        MockResponse::remote_exception(404, "FileNotFoundException", "java.io.FileNotFoundException", 
+            &format!("File does not exist: {}", path))
+    }
+
+    fn already_exists(path: &str) -> MockResponse {
+        MockResponse::remote_exception(403, "FileAlreadyExistsException", "org.apache.hadoop.fs.FileAlreadyExistsException", 
+            &format!("{} already exists", path))
+    }
+
+    fn boolean(b: bool) -> MockResponse { MockResponse::json(200, &format!(r#"{{"boolean":{}}}"#, b)) }
+
+    fn param(r: &MockRequest, p: &str) -> Option<String> {
+        r.param(p).map(|v| crate::uri_tools::uri_part_decode(v).unwrap())
+    }
+
+    pub fn handle(&self, r: &MockRequest) -> MockResponse {
+        let path = crate::uri_tools::uri_part_decode(&r.path()[Self::PREFIX.len()..]).unwrap();
+        let path = if path.len() > 1 { path.trim_end_matches('/').to_owned() } else { "/".to_owned() };
+        let datanode = r.param("datanode").is_some();
+        let redirect = || {
+            let location = format!("{}://{}{}&datanode=true", r.scheme, r.host, r.path_and_query);
+            if Self::param(r, "noredirect").as_deref() == Some("true") {
+                MockResponse::json(200, &serde_json::json!({"Location": location}).to_string())
+            } else {
+                MockResponse::redirect(&location)
+            }
+        };
+        let num = |p: &str| Self::param(r, p).and_then(|v| v.parse::<usize>().ok());
+        match r.op() {
+            "GETFILESTATUS" => match self.get(&path) {
+                Some(e) => MockResponse::json(200, &serde_json::json!({"FileStatus": Self::status("", &e)}).to_string()),
+                None => Self::not_found(&path)
+            }
+            "LISTSTATUS" => match self.get(&path) {
+                Some(e) => {
+                    let l: Vec<_> = if e.dir { 
+                        self.children(&path).iter().map(|(n, e)| Self::status(n, e)).collect() 
+                    } else { 
+                        vec![Self::status("", &e)] 
+                    };
+                    MockResponse::json(200, &serde_json::json!({"FileStatuses": {"FileStatus": l}}).to_string())
+                }
+                None => Self::not_found(&path)
+            }
+            "OPEN" if !datanode => if self.get(&path).is_some() { redirect() } else { Self::not_found(&path) }
+            "OPEN" => match self.get(&path) {
+                Some(e) => {
+                    let o = num("offset").unwrap_or(0).min(e.data.len());
+                    let l = num("length").unwrap_or(e.data.len()).min(e.data.len() - o);
+                    MockResponse::binary(&e.data[o..o + l])
+                }
+                None => Self::not_found(&path)
+            }
+            "CREATE" if !datanode => match self.get(&path) {
+                Some(_) if Self::param(r, "overwrite").as_deref() != Some("true") => Self::already_exists(&path),
+                _ => redirect()
+            }
+            "CREATE" => {
+                //checked again atomically, like the datanode's create on the namenode, so that concurrent creates conflict
+                self.mkdirs(&parent(&path));
+                let mut fs = self.fs.lock().unwrap();
+                if fs.contains_key(&path) && Self::param(r, "overwrite").as_deref() != Some("true") {
+                    return Self::already_exists(&path)
+                }
+                fs.insert(path, FakeEntry { dir: false, data: r.body.clone(), mtime: 1, target: None });
+                MockResponse::new(201)
+            }
+            "APPEND" if !datanode => if self.get(&path).is_some() { redirect() } else { Self::not_found(&path) }
+            "APPEND" => { self.update(&path, |e| { e.data.extend(&r.body); e.mtime += 1; }); MockResponse::new(200) }
+            "GETCONTENTSUMMARY" => match self.get(&path) {
+                Some(_) => {
+                    let pfx = if path == "/" { "/".to_owned() } else { format!("{}/", path) };
+                    let fs = self.fs.lock().unwrap();
+                    let t: Vec<_> = fs.iter().filter(|(k, _)| **k == path || k.starts_with(&pfx)).map(|(_, e)| e).collect();
+                    let length: usize = t.iter().map(|e| e.data.len()).sum();
+                    let (quota, space_quota) = self.quotas.lock().unwrap().get(&path).cloned().unwrap_or((-1, -1));
+                    MockResponse::json(200, &serde_json::json!({"ContentSummary": {
+                        "directoryCount": t.iter().filter(|e| e.dir).count(), "fileCount": t.iter().filter(|e| !e.dir).count(),
+                        "length": length, "quota": quota, "spaceConsumed": length * 3, "spaceQuota": space_quota
+                    }}).to_string())
+                }
+                None => Self::not_found(&path)
+            }
+            "GETFILECHECKSUM" if !datanode => if self.get(&path).is_some() { redirect() } else { Self::not_found(&path) }
+            "GETFILECHECKSUM" => match self.get(&path) {
+                Some(e) => {
+                    //FNV-1a, as a stand-in for MD5-of-MD5-of-CRC32
+                    let h = e.data.iter().fold(0xcbf29ce484222325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+                    MockResponse::json(200, &serde_json::json!({"FileChecksum": {
+                        "algorithm": "FNV-1a", "bytes": format!("{:016x}", h), "length": 8
+                    }}).to_string())
+                }
+                None => Self::not_found(&path)
+            }
+            "GETHOMEDIRECTORY" => MockResponse::json(200, &serde_json::json!({
+                "Path": format!("/user/{}", Self::param(r, "user.name").unwrap_or_else(|| "webuser".to_owned()))
+            }).to_string()),
+            "MKDIRS" => { self.mkdirs(&path); Self::boolean(true) }
+            "SETQUOTA" => {
+                let mut quotas = self.quotas.lock().unwrap();
+                let q = quotas.entry(path).or_insert((-1, -1));
+                let arg = |p: &str| Self::param(r, p).and_then(|v| v.parse::<i64>().ok()).filter(|v| *v != i64::MAX);
+                if let Some(v) = arg("namespacequota") { q.0 = v }
+                if let Some(v) = arg("storagespacequota") { q.1 = v }
+                MockResponse::new(200)
+            }
+            "DELETE" => match self.get(&path) {
+                Some(e) if e.dir && Self::param(r, "recursive").as_deref() != Some("true") && !self.children(&path).is_empty() => 
+                    MockResponse::remote_exception(403, "PathIsNotEmptyDirectoryException", 
+                        "org.apache.hadoop.fs.PathIsNotEmptyDirectoryException", &format!("{} is non empty", path)),
+                Some(_) => {
+                    let pfx = format!("{}/", path);
+                    self.fs.lock().unwrap().retain(|k, _| k != &path && !k.starts_with(&pfx));
+                    Self::boolean(true)
+                }
+                None => Self::boolean(false)
+            }
+            "RENAME" if r.param("renameoptions").is_some() => {
+                let dst = Self::param(r, "destination").unwrap();
+                let overwrite = Self::param(r, "renameoptions").as_deref() == Some("OVERWRITE");
+                let io_error = |m: String| MockResponse::remote_exception(403, "IOException", "java.io.IOException", &m);
+                let mut fs = self.fs.lock().unwrap();
+                let src_dir = match fs.get(&path) { Some(e) => e.dir, None => return Self::not_found(&path) };
+                if !fs.contains_key(&parent(&dst)) { return Self::not_found(&parent(&dst)) }
+                if dst.starts_with(&format!("{}/", path)) { return io_error(format!("{} is under {}", dst, path)) }
+                if let Some(d) = fs.get(&dst) {
+                    let pfx = format!("{}/", dst);
+                    if !overwrite || dst == path { return Self::already_exists(&dst) }
+                    if d.dir != src_dir { return io_error(format!("Source {} and destination {} differ in type", path, dst)) }
+                    if fs.keys().any(|k| k.starts_with(&pfx)) { return io_error(format!("rename destination directory is not empty: {}", dst)) }
+                    fs.remove(&dst);
+                }
+                let pfx = format!("{}/", path);
+                let moved: Vec<_> = fs.keys().filter(|k| *k == &path || k.starts_with(&pfx)).cloned().collect();
+                for k in moved {
+                    let e = fs.remove(&k).unwrap();
+                    fs.insert(format!("{}{}", dst, &k[path.len()..]), e);
+                }
+                MockResponse::new(200)
+            }
+            "RENAME" => {
+                let dst = Self::param(r, "destination").unwrap();
+                let mut fs = self.fs.lock().unwrap();
+                if !fs.contains_key(&path) || fs.contains_key(&dst) || !fs.contains_key(&parent(&dst)) {
+                    return Self::boolean(false)
+                }
+                let pfx = format!("{}/", path);
+                let moved: Vec<_> = fs.keys().filter(|k| *k == &path || k.starts_with(&pfx)).cloned().collect();
+                for k in moved {
+                    let e = fs.remove(&k).unwrap();
+                    fs.insert(format!("{}{}", dst, &k[path.len()..]), e);
+                }
+                Self::boolean(true)
+            }
+            "CREATESYMLINK" => {
+                let dst = Self::param(r, "destination").unwrap();
+                self.fs.lock().unwrap().insert(path, FakeEntry { dir: false, data: vec![], mtime: 1, target: Some(dst) });
+                MockResponse::new(200)
+            }
+            "CONCAT" => {
+                let mut data = vec![];
+                for src in Self::param(r, "sources").unwrap().split(',') {
+                    data.extend(self.get(src).map(|e| e.data).unwrap_or_default());
+                    self.fs.lock().unwrap().remove(src);
+                }
+                self.update(&path, |e| e.data.extend(data));
+                MockResponse::new(200)
+            }
+            op => MockResponse::remote_exception(400, "IllegalArgumentException", "java.lang.IllegalArgumentException", 
+                &format!("Invalid value for webhdfs parameter \"op\": {}", op))
+        }
+    }
+}
+
+fn parent(path: &str) -> String {
+    match path.rfind('/') { Some(0) | None => "/".to_owned(), Some(i) => path[..i].to_owned() }
+}
@@ -16,6 +16,10 @@ impl NatMap {
             |mut m, (k, v)| v.parse().aerr_f(|| format!("cannot parse NAT value for k={}", k)).map(|v| { m.insert(k, v); m } )
         ).map(|natmap| NatMap { natmap })
     }
+    /// Mappings, as `(from, to)` authority strings
+    pub fn entries(&self) -> impl Iterator<Item=(&str, String)> {
+        self.natmap.iter().map(|(k, v)| (k.as_str(), v.to_string()))
+    }
     pub fn translate(&self, uri: Uri) -> Result<Uri> {
         if self.natmap.is_empty() {
             Ok(uri)
@@ -24,7 +28,7 @@ impl NatMap {
                 if let Some(replacement) = self.natmap.get(s.as_str()) {
                     let mut parts = uri.into_parts();
                     parts.authority = Some(replacement.clone());
-                    Ok(http::uri::Uri::from_parts(parts).aerr("Could not assemble redirect uri after NAT")?)
+                    Ok(http::uri::Uri::from_parts(parts).aerr("Could not assemble uri after NAT")?)
                 } else {
                     Ok(uri)
                 }        
@@ -49,6 +53,17 @@ impl NatMapPtr {
         NatMapPtr { ptr: None }
     }
 
+    /// Mappings, or `None` if there are none
+    pub fn to_map(&self) -> Option<std::collections::HashMap<String, String>> {
+        self.ptr.as_ref().map(|p| p.entries().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    /// Copy of the mappings with mapping `from` -> `to` added (or replaced)
+    pub fn with_entry(&self, from: String, to: String) -> Result<NatMapPtr> {
+        let entries: Vec<(String, String)> = self.ptr.iter().flat_map(|p| p.entries().map(|(k, v)| (k.to_owned(), v))).collect();
+        NatMap::new(entries.into_iter().chain(std::iter::once((from, to)))).map(NatMapPtr::new)
+    }
+
     pub fn translate(&self, uri: Uri) -> Result<Uri> {
         if let Some(p) = &self.ptr {
             p.translate(uri)
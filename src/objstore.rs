@@ -0,0 +1,381 @@
+//! `object_store::ObjectStore` implementation (feature `object-store`), for use with Arrow, DataFusion and other
+//! users of the `object_store` crate.
+//!
+//! `HdfsObjectStore` maps object paths to HDFS paths under a root directory: with root `/warehouse`, object
+//! `t1/part-0.parquet` is file `/warehouse/t1/part-0.parquet`. Directories are not objects; they show up as common
+//! prefixes in delimited listings. Since HDFS files have no ETags or versions, conditional requests based on them
+//! are not supported; modification time conditions are. Multipart uploads are buffered in memory and written
+//! on completion. Copies are done by reading and rewriting the data, as WebHDFS has no server-side copy.
+//! Each call starts at the last known active namenode.
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use futures::{stream::{self, BoxStream}, StreamExt};
+use object_store::{
+    path::Path, GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartUpload, ObjectMeta,
+    ObjectStore, PutMode, PutMultipartOpts, PutOptions, PutPayload, PutResult, UploadPart, Attributes
+};
+use crate::error::Error;
+use crate::datatypes::{FileStatus, dirent_type};
+use crate::op::{CreateOptions, AppendOptions, DeleteOptions, MkdirsOptions, OpenOptions};
+use crate::async_client::{HdfsClient, FOR, Data};
+
+const STORE: &str = "WebHDFS";
+
+type OsResult<T> = object_store::Result<T>;
+
+/// `ObjectStore` backed by an `HdfsClient`
+#[derive(Clone)]
+pub struct HdfsObjectStore {
+    client: Arc<HdfsClient>,
+    root: String
+}
+
+impl HdfsObjectStore {
+    /// Store of the objects under HDFS directory `root`
+    pub fn new(client: Arc<HdfsClient>, root: &str) -> Self {
+        let root = root.trim_end_matches('/');
+        Self { client, root: if root.is_empty() { "/".to_owned() } else { root.to_owned() } }
+    }
+
+    fn hdfs_path(&self, location: &Path) -> String { crate::path::join(&self.root, location.as_ref()) }
+
+    /// Object path of an HDFS path under the root
+    fn location(&self, path: &str) -> Path {
+        Path::from(path.strip_prefix(self.root.as_str()).unwrap_or(path).trim_start_matches('/'))
+    }
+
+    fn meta(&self, path: &str, fs: &FileStatus) -> ObjectMeta {
+        ObjectMeta {
+            location: self.location(path),
+            last_modified: Utc.timestamp_millis_opt(fs.modification_time).single().unwrap_or_default(),
+            size: fs.length.max(0) as usize,
+            e_tag: None,
+            version: None
+        }
+    }
+
+    async fn stat(&self, location: &Path) -> OsResult<ObjectMeta> {
+        let path = self.hdfs_path(location);
+        let r = self.client.stat(self.client.active_fostate(), &path).await;
+        let fs = FOR::split(r).0.map_err(|e| os_error(e, &path))?.file_status;
+        if fs.type_ == dirent_type::DIRECTORY {
+            return Err(object_store::Error::NotFound { path: path.clone(), source: format!("{} is a directory", path).into() })
+        }
+        Ok(self.meta(&path, &fs))
+    }
+
+    async fn write(&self, path: &str, data: Data, overwrite: bool) -> OsResult<()> {
+        let r = self.client.create(self.client.active_fostate(), path, data, CreateOptions::new().overwrite(overwrite)).await;
+        FOR::split(r).0.map_err(|e| os_error(e.error, path))
+    }
+
+    async fn append(&self, path: &str, data: Data) -> OsResult<()> {
+        let r = self.client.append(self.client.active_fostate(), path, data, AppendOptions::new()).await;
+        FOR::split(r).0.map_err(|e| os_error(e.error, path))
+    }
+
+    /// Writes `parts` to a new file `path`: the first one by CREATE, the rest by APPENDs
+    async fn write_parts(&self, path: &str, parts: impl IntoIterator<Item=Vec<u8>>, overwrite: bool) -> OsResult<()> {
+        let mut first = true;
+        for part in parts {
+            if first {
+                self.write(path, Data::Owned(part), overwrite).await?;
+                first = false;
+            } else if !part.is_empty() {
+                self.append(path, Data::Owned(part)).await?;
+            }
+        }
+        if first { self.write(path, Data::Owned(vec![]), overwrite).await? }
+        Ok(())
+    }
+
+    async fn copy_file(&self, from: &Path, to: &Path, overwrite: bool) -> OsResult<()> {
+        let (from, to) = (self.hdfs_path(from), self.hdfs_path(to));
+        let opts = CreateOptions::new().overwrite(overwrite);
+        let r = self.client.copy_file(self.client.active_fostate(), &from, &to, opts).await;
+        FOR::split(r).0.map(|_| ()).map_err(|e| {
+            let path = if e.io_kind() == std::io::ErrorKind::AlreadyExists { &to } else { &from };
+            os_error(e, path)
+        })
+    }
+
+    /// Renames with rename options, so that an existing file is replaced atomically (if `overwrite` is set) and
+    /// `from` is never moved into an existing directory. Missing parents of `to` are created first.
+    async fn rename_file(&self, from: &Path, to: &Path, overwrite: bool) -> OsResult<()> {
+        let (from, to) = (self.hdfs_path(from), self.hdfs_path(to));
+        if let Some(parent) = crate::path::HdfsPath::new(&to).parent() {
+            let r = self.client.mkdirs(self.client.active_fostate(), parent, MkdirsOptions::new()).await;
+            FOR::split(r).0.map_err(|e| os_error(e, parent.as_str()))?;
+        }
+        let r = self.client.rename2(self.client.active_fostate(), &from, to.clone(), overwrite).await;
+        FOR::split(r).0.map_err(|e| {
+            let path = if e.io_kind() == std::io::ErrorKind::AlreadyExists { &to } else { &from };
+            os_error(e, path)
+        })
+    }
+
+    /// Files and directories in directory `prefix`; nothing if it does not exist
+    async fn dir(&self, prefix: Option<&Path>) -> OsResult<Vec<(String, FileStatus)>> {
+        let path = prefix.map(|p| self.hdfs_path(p)).unwrap_or_else(|| self.root.clone());
+        match FOR::split(self.client.dir_abs(self.client.active_fostate(), &path).await).0 {
+            Ok(entries) => Ok(entries),
+            Err(e) if e.io_kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(os_error(e, &path))
+        }
+    }
+}
+
+impl fmt::Debug for HdfsObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "HdfsObjectStore({})", self.root) }
+}
+
+impl fmt::Display for HdfsObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "HdfsObjectStore({})", self.root) }
+}
+
+fn os_error(e: Error, path: &str) -> object_store::Error {
+    match e.io_kind() {
+        std::io::ErrorKind::NotFound => object_store::Error::NotFound { path: path.to_owned(), source: Box::new(e) },
+        std::io::ErrorKind::AlreadyExists => object_store::Error::AlreadyExists { path: path.to_owned(), source: Box::new(e) },
+        _ => object_store::Error::Generic { store: STORE, source: Box::new(e) }
+    }
+}
+
+fn not_supported(what: &str) -> object_store::Error {
+    object_store::Error::NotSupported { source: format!("{} is not supported by {}", what, STORE).into() }
+}
+
+/// Byte range of an object of length `len`
+fn range(r: &GetRange, len: usize, location: &Path) -> OsResult<Range<usize>> {
+    let invalid = || object_store::Error::Generic {
+        store: STORE, source: format!("Invalid range {:?} of {} (length {})", r, location, len).into()
+    };
+    match r {
+        GetRange::Bounded(r) if r.start < r.end && r.start < len => Ok(r.start..r.end.min(len)),
+        GetRange::Offset(o) if *o < len => Ok(*o..len),
+        GetRange::Suffix(n) => Ok(len.saturating_sub(*n)..len),
+        _ => Err(invalid())
+    }
+}
+
+/// Checks the conditions of `options` against `meta`
+fn check_preconditions(options: &GetOptions, meta: &ObjectMeta) -> OsResult<()> {
+    if options.if_match.as_deref().is_some_and(|m| m != "*") || options.if_none_match.is_some() {
+        return Err(not_supported("ETag matching"))
+    }
+    if options.version.is_some() { return Err(not_supported("Object versioning")) }
+    let path = meta.location.to_string();
+    if let Some(date) = options.if_unmodified_since {
+        if meta.last_modified > date {
+            return Err(object_store::Error::Precondition { path, source: format!("modified at {}", meta.last_modified).into() })
+        }
+    } else if let Some(date) = options.if_modified_since {
+        if meta.last_modified <= date {
+            return Err(object_store::Error::NotModified { path, source: format!("modified at {}", meta.last_modified).into() })
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ObjectStore for HdfsObjectStore {
+    async fn put_opts(&self, location: &Path, payload: PutPayload, opts: PutOptions) -> OsResult<PutResult> {
+        let overwrite = match opts.mode {
+            PutMode::Overwrite => true,
+            PutMode::Create => false,
+            PutMode::Update(_) => return Err(not_supported("Conditional update"))
+        };
+        let data = Bytes::from(payload);
+        self.write(&self.hdfs_path(location), Data::Owned(data.to_vec()), overwrite).await?;
+        Ok(PutResult { e_tag: None, version: None })
+    }
+
+    async fn put_multipart_opts(&self, location: &Path, _opts: PutMultipartOpts) -> OsResult<Box<dyn MultipartUpload>> {
+        Ok(Box::new(HdfsMultipartUpload { store: self.clone(), location: location.clone(), parts: vec![] }))
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> OsResult<GetResult> {
+        let meta = self.stat(location).await?;
+        check_preconditions(&options, &meta)?;
+        let range = match &options.range {
+            Some(r) => range(r, meta.size, location)?,
+            None => 0..meta.size
+        };
+        let payload = if options.head || range.is_empty() {
+            stream::empty().boxed()
+        } else {
+            let path = self.hdfs_path(location);
+            let opts = OpenOptions::new().offset(range.start as i64).length(range.len() as i64);
+            let r = self.client.open(self.client.active_fostate(), &path, opts).await;
+            let s = FOR::split(r).0.map_err(|e| os_error(e, &path))?;
+            s.map(move |r| r.map_err(|e| os_error(e, &path))).boxed()
+        };
+        Ok(GetResult { payload: GetResultPayload::Stream(payload), meta, range, attributes: Attributes::default() })
+    }
+
+    async fn head(&self, location: &Path) -> OsResult<ObjectMeta> { self.stat(location).await }
+
+    async fn delete(&self, location: &Path) -> OsResult<()> {
+        let path = self.hdfs_path(location);
+        let r = self.client.delete(self.client.active_fostate(), &path, DeleteOptions::new().recursive(false)).await;
+        FOR::split(r).0.map(|_| ()).map_err(|e| os_error(e, &path))
+    }
+
+    /// Lists objects under `prefix` recursively, directory by directory
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, OsResult<ObjectMeta>> {
+        let start = prefix.cloned();
+        stream::unfold(Some(vec![start]), move |dirs| async move {
+            let mut dirs = dirs?;
+            let dir = dirs.pop()?;
+            let entries = match self.dir(dir.as_ref()).await {
+                Ok(entries) => entries,
+                Err(e) => return Some((vec![Err(e)], None))
+            };
+            let mut objects = vec![];
+            for (path, fs) in entries {
+                match fs.type_.as_str() {
+                    dirent_type::DIRECTORY => dirs.push(Some(self.location(&path))),
+                    dirent_type::FILE => objects.push(Ok(self.meta(&path, &fs))),
+                    _ => ()
+                }
+            }
+            Some((objects, Some(dirs)))
+        })
+        .flat_map(stream::iter)
+        .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> OsResult<ListResult> {
+        let mut r = ListResult { common_prefixes: vec![], objects: vec![] };
+        for (path, fs) in self.dir(prefix).await? {
+            match fs.type_.as_str() {
+                dirent_type::DIRECTORY => r.common_prefixes.push(self.location(&path)),
+                dirent_type::FILE => r.objects.push(self.meta(&path, &fs)),
+                _ => ()
+            }
+        }
+        Ok(r)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> OsResult<()> { self.copy_file(from, to, true).await }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> OsResult<()> { self.copy_file(from, to, false).await }
+
+    async fn rename(&self, from: &Path, to: &Path) -> OsResult<()> { self.rename_file(from, to, true).await }
+
+    async fn rename_if_not_exists(&self, from: &Path, to: &Path) -> OsResult<()> { self.rename_file(from, to, false).await }
+}
+
+/// Multipart upload to an `HdfsObjectStore`: parts are kept in memory until completion
+#[derive(Debug)]
+struct HdfsMultipartUpload {
+    store: HdfsObjectStore,
+    location: Path,
+    parts: Vec<PutPayload>
+}
+
+#[async_trait]
+impl MultipartUpload for HdfsMultipartUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        self.parts.push(data);
+        Box::pin(futures::future::ready(Ok(())))
+    }
+
+    async fn complete(&mut self) -> OsResult<PutResult> {
+        let parts = std::mem::take(&mut self.parts).into_iter().map(|p| Bytes::from(p).to_vec());
+        self.store.write_parts(&self.store.hdfs_path(&self.location), parts, true).await?;
+        Ok(PutResult { e_tag: None, version: None })
+    }
+
+    async fn abort(&mut self) -> OsResult<()> {
+        self.parts.clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_object_store() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    use futures::TryStreamExt;
+    let f = FakeHdfs::new();
+    f.put("/root/a/x", b"0123456789");
+    f.mkdirs("/root/b");
+    let c = FakeCluster::start(&f);
+    let store = HdfsObjectStore::new(Arc::new(c.builder().build()), "/root/");
+    c.rt.block_on(async {
+        let x = Path::from("a/x");
+        let meta = store.head(&x).await.unwrap();
+        assert_eq!((meta.location.as_ref(), meta.size), ("a/x", 10));
+        assert_eq!(store.get(&x).await.unwrap().bytes().await.unwrap(), &b"0123456789"[..]);
+        assert_eq!(store.get_range(&x, 2..5).await.unwrap(), &b"234"[..]);
+        let opts = GetOptions { range: Some(GetRange::Suffix(3)), ..Default::default() };
+        assert_eq!(store.get_opts(&x, opts).await.unwrap().bytes().await.unwrap(), &b"789"[..]);
+        assert!(matches!(store.head(&Path::from("a/y")).await, Err(object_store::Error::NotFound { .. })));
+        assert!(matches!(store.head(&Path::from("b")).await, Err(object_store::Error::NotFound { .. })));
+
+        store.put(&Path::from("b/c/y"), PutPayload::from_static(b"new")).await.unwrap();
+        assert_eq!(f.get("/root/b/c/y").unwrap().data, b"new");
+        let opts = PutOptions { mode: PutMode::Create, ..Default::default() };
+        let r = store.put_opts(&Path::from("b/c/y"), PutPayload::from_static(b"again"), opts).await;
+        assert!(matches!(r, Err(object_store::Error::AlreadyExists { .. })), "{:?}", r);
+
+        let mut u = store.put_multipart(&Path::from("m")).await.unwrap();
+        u.put_part(PutPayload::from_static(b"ab")).await.unwrap();
+        u.put_part(PutPayload::from_static(b"cd")).await.unwrap();
+        u.complete().await.unwrap();
+        assert_eq!(f.get("/root/m").unwrap().data, b"abcd");
+
+        let mut all: Vec<String> = store.list(None).map_ok(|m| m.location.to_string()).try_collect().await.unwrap();
+        all.sort();
+        assert_eq!(all, vec!["a/x", "b/c/y", "m"]);
+        let l = store.list_with_delimiter(Some(&Path::from("b"))).await.unwrap();
+        assert_eq!((l.common_prefixes, l.objects.len()), (vec![Path::from("b/c")], 0));
+        assert_eq!(store.list(Some(&Path::from("none"))).count().await, 0);
+
+        store.copy(&x, &Path::from("b/x2")).await.unwrap();
+        assert_eq!(f.get("/root/b/x2").unwrap().data, b"0123456789");
+        assert!(store.copy_if_not_exists(&x, &Path::from("b/x2")).await.is_err());
+        store.rename(&Path::from("m"), &Path::from("b/x2")).await.unwrap();
+        assert_eq!(f.get("/root/b/x2").unwrap().data, b"abcd");
+        assert!(f.get("/root/m").is_none());
+        store.delete(&Path::from("b/x2")).await.unwrap();
+        assert!(f.get("/root/b/x2").is_none());
+    });
+}
+
+#[test]
+fn test_object_store_rename() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.put("/root/a", b"a");
+    f.put("/root/b", b"b");
+    f.put("/root/d/x", b"x");
+    let c = FakeCluster::start(&f);
+    let store = HdfsObjectStore::new(Arc::new(c.builder().build()), "/root");
+    c.rt.block_on(async {
+        let r = store.rename_if_not_exists(&Path::from("a"), &Path::from("b")).await;
+        assert!(matches!(r, Err(object_store::Error::AlreadyExists { .. })), "{:?}", r);
+        let r = store.rename_if_not_exists(&Path::from("none"), &Path::from("c")).await;
+        assert!(matches!(r, Err(object_store::Error::NotFound { .. })), "{:?}", r);
+        // missing parents of the target are created
+        store.rename_if_not_exists(&Path::from("a"), &Path::from("new/dir/a")).await.unwrap();
+        assert_eq!(f.get("/root/new/dir/a").unwrap().data, b"a");
+
+        // a directory target is an error, and nothing is moved or lost
+        assert!(store.rename(&Path::from("b"), &Path::from("d")).await.is_err());
+        assert_eq!((f.get("/root/b").unwrap().data, f.get("/root/d/x").unwrap().data), (b"b".to_vec(), b"x".to_vec()));
+        assert!(f.get("/root/d/b").is_none());
+        let r = store.rename(&Path::from("none"), &Path::from("b")).await;
+        assert!(matches!(r, Err(object_store::Error::NotFound { .. })), "{:?}", r);
+        assert_eq!(f.get("/root/b").unwrap().data, b"b");
+
+        store.rename(&Path::from("b"), &Path::from("d/x")).await.unwrap();
+        assert_eq!(f.get("/root/d/x").unwrap().data, b"b");
+        assert!(f.get("/root/b").is_none());
+    });
+    assert!(c.nn.requests().iter().all(|r| r.op() != "DELETE"));
+}
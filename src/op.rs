@@ -1,4 +1,5 @@
 use crate::uri_tools::QueryEncoder;
+use crate::permission::Permission as Perm;
 
 #[derive(Debug, Clone)]
 pub(crate) enum Op {
@@ -11,7 +12,15 @@ pub(crate) enum Op {
     MKDIRS,
     RENAME,
     CREATESYMLINK,
-    DELETE
+    DELETE,
+    GETCONTENTSUMMARY,
+    GETFILECHECKSUM,
+    GETSNAPSHOTDIFF,
+    GETSNAPSHOTTABLEDIRECTORYLIST,
+    SETQUOTA,
+    SETQUOTABYSTORAGETYPE,
+    GETSERVERDEFAULTS,
+    GETHOMEDIRECTORY
 }
 
 impl Op {
@@ -27,7 +36,15 @@ impl Op {
             MKDIRS => "MKDIRS",
             RENAME => "RENAME",
             CREATESYMLINK => "CREATESYMLINK",
-            DELETE => "DELETE"
+            DELETE => "DELETE",
+            GETCONTENTSUMMARY => "GETCONTENTSUMMARY",
+            GETFILECHECKSUM => "GETFILECHECKSUM",
+            GETSNAPSHOTDIFF => "GETSNAPSHOTDIFF",
+            GETSNAPSHOTTABLEDIRECTORYLIST => "GETSNAPSHOTTABLEDIRECTORYLIST",
+            SETQUOTA => "SETQUOTA",
+            SETQUOTABYSTORAGETYPE => "SETQUOTABYSTORAGETYPE",
+            GETSERVERDEFAULTS => "GETSERVERDEFAULTS",
+            GETHOMEDIRECTORY => "GETHOMEDIRECTORY"
         }
     }
 }
@@ -48,15 +65,36 @@ pub(crate) enum OpArg {
     /// `[&replication=<SHORT>]`
     Replication(i16),
     /// `[&permission=<OCTAL>]`
-    Permission(u16),
+    Permission(Perm),
     /// `&sources=<PATHS>`
     Sources(Vec<String>),
     /// `&destination=<PATH>`
     Destination(String),
+    /// `[&renameoptions=<OPTIONS>]`: `OVERWRITE` if set, `NONE` otherwise
+    RenameOverwrite(bool),
     /// `[&createParent=<true|false>]`
     CreateParent(bool),
     /// `[&recursive=<true|false>]`
-    Recursive(bool)
+    Recursive(bool),
+    /// `[&startAfter=<CHILD>]`
+    StartAfter(String),
+    /// `[&noredirect=<true|false>]`
+    NoRedirect(bool),
+    /// `[&createflag=<FLAGS>]`
+    CreateFlag(CreateFlag),
+    /// `&oldsnapshotname=<SNAPSHOTNAME>`
+    OldSnapshotName(String),
+    /// `&snapshotname=<SNAPSHOTNAME>`
+    SnapshotName(String),
+    /// `[&namespacequota=<LONG>]`
+    NamespaceQuota(i64),
+    /// `[&storagespacequota=<LONG>]`
+    StoragespaceQuota(i64),
+    /// `&storagetype=<STORAGETYPE>`
+    StorageType(String),
+    /// `[&<NAME>=<VALUE>]*`, for multi-valued arguments such as `xattr.name`
+    #[allow(dead_code)]
+    Repeated(&'static str, Vec<String>)
 }
 
 impl OpArg {
@@ -70,16 +108,78 @@ impl OpArg {
             Overwrite(v) => qe.add_pb("overwrite", *v),
             Blocksize(v) => qe.add_pi("blocksize", *v),
             Replication(v) => qe.add_pi("replication", *v as i64),
-            Permission(v) => qe.add_po("permission", *v),
+            Permission(v) => qe.add_po("permission", v.mode()),
             Sources(v) => qe.add_pv("sources", &v.join(",")),
             Destination(v)=> qe.add_pv("destination", v),
+            RenameOverwrite(v) => qe.add_pv("renameoptions", if *v { "OVERWRITE" } else { "NONE" }),
             CreateParent(v) => qe.add_pb("createParent", *v),
             Recursive(v) => qe.add_pb("recursive", *v),
+            StartAfter(v) => qe.add_pv("startAfter", v),
+            NoRedirect(v) => qe.add_pb("noredirect", *v),
+            CreateFlag(v) => qe.add_pv("createflag", &v.to_string()),
+            OldSnapshotName(v) => qe.add_pv("oldsnapshotname", v),
+            SnapshotName(v) => qe.add_pv("snapshotname", v),
+            NamespaceQuota(v) => qe.add_pi("namespacequota", *v),
+            StoragespaceQuota(v) => qe.add_pi("storagespacequota", *v),
+            StorageType(v) => qe.add_pv("storagetype", v),
+            Repeated(p, v) => qe.add_pv_repeated(p, v),
         }
     }
 }
 
+/// Flags of the `createflag` CREATE parameter (Hadoop 2.8+), combined with `|`. 
+/// `SYNC_BLOCK` makes datanodes sync each block to disk when it is closed, for durability-critical data.
+/// ```
+/// use webhdfs::CreateFlag;
+/// assert_eq!((CreateFlag::CREATE | CreateFlag::SYNC_BLOCK).to_string(), "create,sync_block");
+/// assert!((CreateFlag::CREATE | CreateFlag::OVERWRITE).contains(CreateFlag::OVERWRITE));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CreateFlag(u8);
+
+impl CreateFlag {
+    /// Create the file if it does not exist
+    pub const CREATE: CreateFlag = CreateFlag(1);
+    /// Truncate the file if it exists
+    pub const OVERWRITE: CreateFlag = CreateFlag(2);
+    /// Append to the file if it exists
+    pub const APPEND: CreateFlag = CreateFlag(4);
+    /// Sync each block to disk when it is closed
+    pub const SYNC_BLOCK: CreateFlag = CreateFlag(8);
+
+    const NAMES: [(CreateFlag, &'static str); 4] = [
+        (Self::CREATE, "create"), (Self::OVERWRITE, "overwrite"), (Self::APPEND, "append"), (Self::SYNC_BLOCK, "sync_block")
+    ];
+
+    /// Whether all flags of `other` are set
+    pub fn contains(self, other: CreateFlag) -> bool { self.0 & other.0 == other.0 }
+}
+
+impl std::ops::BitOr for CreateFlag {
+    type Output = CreateFlag;
+    fn bitor(self, rhs: CreateFlag) -> CreateFlag { CreateFlag(self.0 | rhs.0) }
+}
+
+impl std::fmt::Display for CreateFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = Self::NAMES.iter().filter(|(flag, _)| self.contains(*flag)).map(|(_, name)| *name).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// `args` completed with those of `defaults` not set in `args`
+pub(crate) fn with_defaults(mut args: Vec<OpArg>, defaults: &[OpArg]) -> Vec<OpArg> {
+    use std::mem::discriminant;
+    for d in defaults {
+        if !args.iter().any(|a| discriminant(a) == discriminant(d)) { args.push(d.clone()) }
+    }
+    args
+}
+
 macro_rules! opt {
+    ($tag:ident, impl Into<$tp:ty>, $op_tag:ident) => {
+        pub fn $tag(mut self, v:impl Into<$tp>) -> Self { self.o.push(OpArg::$op_tag(v.into())); self }
+    };
     ($tag:ident, $tp:ty, $op_tag:ident) => {
         pub fn $tag(mut self, v:$tp) -> Self { self.o.push(OpArg::$op_tag(v)); self }
     };
@@ -97,14 +197,16 @@ macro_rules! opts {
     (blocksize) => { opt! { blocksize, i64, Blocksize } };
     // `[&replication=<SHORT>]`
     (replication) => { opt! { replication, i16, Replication } };
-    // `[&permission=<OCTAL>]`
-    (permission) => { opt! { permission, u16, Permission } };
+    // `[&permission=<OCTAL>]`, from a `Permission` or raw mode bits
+    (permission) => { opt! { permission, impl Into<Perm>, Permission } };
     // `[&buffersize=<INT>]`
     (buffersize) => { opt! { buffersize, i32, BufferSize } };
     // `[&createParent=<true|false>]`
     (create_parent) => { opt! { create_parent, bool, CreateParent } };
     // `[&recursive=<true|false>]`
     (recursive) => { opt! { recursive, bool, Recursive } };
+    // `[&createflag=<FLAGS>]`
+    (create_flag) => { opt! { create_flag, CreateFlag, CreateFlag } };
 }
 
 macro_rules! op_builder {
@@ -124,8 +226,8 @@ op_builder! { OpenOptions => offset, length, buffersize }
 
 //curl -i -X PUT "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=CREATE
 //           [&overwrite=<true |false>][&blocksize=<LONG>][&replication=<SHORT>]
-//           [&permission=<OCTAL>][&buffersize=<INT>]"
-op_builder! { CreateOptions => overwrite, blocksize, replication, permission, buffersize }
+//           [&permission=<OCTAL>][&buffersize=<INT>][&createflag=<FLAGS>]"
+op_builder! { CreateOptions => overwrite, blocksize, replication, permission, buffersize, create_flag }
 
 //curl -i -X POST "http://<HOST>:<PORT>/webhdfs/v1/<PATH>?op=APPEND[&buffersize=<INT>]"
 op_builder! { AppendOptions => buffersize }
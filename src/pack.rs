@@ -0,0 +1,162 @@
+//! Small-file packing.
+//!
+//! `pack` concatenates the files of a directory tree into a few large container files (`part-NNNNN`) in an archive
+//! directory, and writes an index (`_index`, one JSON `PackEntry` per line) recording where each file's data is.
+//! Single files are read back with `read_entry`, by an OPEN of the container with offset and length. The source
+//! files are left in place.
+use std::io::{BufWriter, Write};
+use serde::{Serialize, Deserialize};
+use crate::error::*;
+use crate::datatypes::dirent_type;
+use crate::sync_client::{SyncHdfsClient, WriteHdfsFile, CreateOptions, AppendOptions, OpenOptions, MkdirsOptions};
+
+/// Name of the index file in an archive directory
+pub const INDEX_FILE: &str = "_index";
+
+/// Location of a packed file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackEntry {
+    /// Path relative to the packed directory
+    pub path: String,
+    /// Container file name
+    pub part: String,
+    pub offset: u64,
+    pub length: u64
+}
+
+/// Contents of an archive
+#[derive(Debug, Clone, Default)]
+pub struct PackIndex {
+    pub entries: Vec<PackEntry>
+}
+
+impl PackIndex {
+    /// Reads the index of archive `archive_dir`
+    pub fn read(cx: &mut SyncHdfsClient, archive_dir: &str) -> Result<Self> {
+        let mut buf = vec![];
+        cx.get_file(&crate::path::join(archive_dir, INDEX_FILE), &mut buf)?;
+        let entries = buf.split(|b| *b == b'\n').filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).aerr("Invalid archive index entry"))
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Entry of a file, by path relative to the packed directory
+    pub fn find(&self, path: &str) -> Option<&PackEntry> { self.entries.iter().find(|e| e.path == path) }
+}
+
+/// Packing settings
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    part_size: u64,
+    buffer_size: usize
+}
+
+impl PackOptions {
+    pub fn new() -> Self { Self { part_size: 1 << 30, buffer_size: 8 << 20 } }
+    /// Size after which a new container file is started (1GB by default). Files are not split between containers.
+    pub fn part_size(self, part_size: u64) -> Self { Self { part_size, ..self } }
+    /// Size of data blocks appended to containers (8MB by default)
+    pub fn buffer_size(self, buffer_size: usize) -> Self { Self { buffer_size, ..self } }
+}
+
+impl Default for PackOptions {
+    fn default() -> Self { Self::new() }
+}
+
+/// Counts bytes written through it
+struct Counting<W> { inner: W, n: u64 }
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.n += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> { self.inner.flush() }
+}
+
+type Part = Counting<BufWriter<WriteHdfsFile>>;
+
+fn close_part(part: Part) -> Result<()> {
+    part.inner.into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+/// Packs the files under `src_dir` (recursively; symbolic links are skipped) into new archive directory `archive_dir`.
+pub fn pack(cx: &mut SyncHdfsClient, src_dir: &str, archive_dir: &str, opts: &PackOptions) -> Result<PackIndex> {
+    cx.mkdirs(archive_dir, MkdirsOptions::new())?;
+    let mut files = vec![];
+    let mut dirs = vec![(src_dir.to_owned(), String::new())];
+    while let Some((dir, rel)) = dirs.pop() {
+        for (path, fs) in cx.dir_abs(&dir)? {
+            let rel = if rel.is_empty() { fs.path_suffix.clone() } else { format!("{}/{}", rel, fs.path_suffix) };
+            match fs.type_.as_str() {
+                dirent_type::DIRECTORY => dirs.push((path, rel)),
+                dirent_type::FILE => files.push((path, rel)),
+                _ => ()
+            }
+        }
+    }
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut index = PackIndex::default();
+    let mut part: Option<(String, Part)> = None;
+    let mut parts = 0;
+    for (path, rel) in files {
+        let (name, mut w) = match part.take() {
+            Some((name, w)) if w.n < opts.part_size => (name, w),
+            other => {
+                if let Some((_, w)) = other { close_part(w)? }
+                let name = format!("part-{:05}", parts);
+                parts += 1;
+                let f = WriteHdfsFile::create(cx.clone(), crate::path::join(archive_dir, &name), CreateOptions::new(), AppendOptions::new())?;
+                (name, Counting { inner: BufWriter::with_capacity(opts.buffer_size, f), n: 0 })
+            }
+        };
+        let offset = w.n;
+        cx.get_file(&path, &mut w)?;
+        index.entries.push(PackEntry { path: rel, part: name.clone(), offset, length: w.n - offset });
+        part = Some((name, w));
+    }
+    if let Some((_, w)) = part { close_part(w)? }
+
+    let mut data = vec![];
+    for e in &index.entries { writeln!(data, "{}", serde_json::to_string(e)?)? }
+    cx.create(&crate::path::join(archive_dir, INDEX_FILE), data.into(), CreateOptions::new()).map_err(|e| e.error)?;
+    Ok(index)
+}
+
+/// Writes the data of packed file `entry` of archive `archive_dir` to `out`
+pub fn read_entry(cx: &mut SyncHdfsClient, archive_dir: &str, entry: &PackEntry, out: &mut dyn Write) -> Result<()> {
+    if entry.length == 0 { return Ok(()) }
+    let opts = OpenOptions::new().offset(entry.offset as i64).length(entry.length as i64);
+    let input = cx.open(&crate::path::join(archive_dir, &entry.part), opts)?;
+    cx.for_each_chunk(input, |b| Ok(out.write_all(b)?))
+}
+
+#[test]
+fn test_pack() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let f = FakeHdfs::new();
+    f.put("/in/a", b"aaa");
+    f.put("/in/b", b"");
+    f.put("/in/sub/c", b"cccc");
+    f.put("/in/sub/d", b"dd");
+    let cl = FakeCluster::start(&f);
+    let mut cx = cl.sync_builder().build().unwrap();
+
+    let index = pack(&mut cx, "/in", "/archive", &PackOptions::new().part_size(5)).unwrap();
+    let loc: Vec<_> = index.entries.iter().map(|e| (e.path.as_str(), e.part.as_str(), e.offset, e.length)).collect();
+    assert_eq!(loc, vec![
+        ("a", "part-00000", 0, 3), ("b", "part-00000", 3, 0), ("sub/c", "part-00000", 3, 4), ("sub/d", "part-00001", 0, 2)
+    ]);
+    assert_eq!(f.get("/archive/part-00000").unwrap().data, b"aaacccc");
+
+    let index = PackIndex::read(&mut cx, "/archive").unwrap();
+    let mut out = vec![];
+    read_entry(&mut cx, "/archive", index.find("sub/c").unwrap(), &mut out).unwrap();
+    assert_eq!(out, b"cccc");
+    assert!(index.find("c").is_none());
+    assert!(pack(&mut cx, "/in", "/archive", &PackOptions::new()).is_err());
+}
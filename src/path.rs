@@ -0,0 +1,225 @@
+//! HDFS path helpers
+//!
+//! Client methods take plain (decoded) paths and perform percent-encoding themselves. Paths that have been 
+//! taken from URLs (e.g. WebHDFS or Namenode UI links) must be decoded first, otherwise they are encoded twice.
+//!
+//! Client methods accept anything that is `AsRef<HdfsPath>`: `&str`, `String`, `HdfsPath` or `HdfsPathBuf`.
+use std::borrow::Borrow;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::Deref;
+use crate::error::*;
+use crate::uri_tools::{uri_part_decode, uri_part_encoder_iter};
+
+/// Joins a directory path and a directory entry name (e.g. `FileStatus::path_suffix`).
+/// Handles root and trailing slashes. An empty name (as returned by `LISTSTATUS` on a file) yields
+/// the directory path itself.
+/// ```
+/// use webhdfs::path::join;
+/// assert_eq!(join("/", "a"), "/a");
+/// assert_eq!(join("", "a"), "/a");
+/// assert_eq!(join("/user/", "a"), "/user/a");
+/// assert_eq!(join("/user//", "/a"), "/user/a");
+/// assert_eq!(join("/user/f.txt", ""), "/user/f.txt");
+/// assert_eq!(join("/", ""), "/");
+/// ```
+pub fn join(dir: &str, name: &str) -> String {
+    let dir = dir.trim_end_matches('/');
+    let name = name.trim_start_matches('/');
+    match (dir.is_empty(), name.is_empty()) {
+        (true, true) => "/".to_owned(),
+        (false, true) => dir.to_owned(),
+        (true, false) => format!("/{}", name),
+        (false, false) => format!("{}/{}", dir, name)
+    }
+}
+
+/// Decodes a percent-encoded path (or path component), such as one taken from a URL.
+/// ```
+/// use webhdfs::path::decode;
+/// assert_eq!(decode("/user/%D0%90%D0%91/a%20b.txt").unwrap(), "/user/АБ/a b.txt");
+/// assert!(decode("/a%2").is_err());
+/// ```
+pub fn decode(path: &str) -> Result<String> {
+    uri_part_decode(path).map_err(Error::app_s)
+}
+
+/// Percent-encodes a single path segment, the same way client methods encode paths. All characters except
+/// RFC 3986 unreserved ones (`A-Z a-z 0-9 - . _ ~`) are encoded, including `/`, so the result is always 
+/// a single segment. Non-ASCII characters are encoded as UTF-8.
+/// ```
+/// use webhdfs::path::encode_path_segment;
+/// assert_eq!(encode_path_segment("a/b c%.txt"), "a%2Fb%20c%25.txt");
+/// assert_eq!(encode_path_segment("АБ"), "%D0%90%D0%91");
+/// ```
+pub fn encode_path_segment(segment: &str) -> String {
+    uri_part_encoder_iter(segment, false).map(char::from).collect()
+}
+
+/// Percent-encodes a path the same way client methods do. Same as `encode_path_segment`, 
+/// except that `/` separators are kept as is.
+/// ```
+/// use webhdfs::path::{encode_path, decode};
+/// assert_eq!(encode_path("/user/a b/c%.txt"), "/user/a%20b/c%25.txt");
+/// assert_eq!(decode(&encode_path("/user/АБ/?&=")).unwrap(), "/user/АБ/?&=");
+/// ```
+pub fn encode_path(path: &str) -> String {
+    uri_part_encoder_iter(path, true).map(char::from).collect()
+}
+
+/// Borrowed HDFS path, a `str` wrapper (like `std::path::Path`). Any string is a valid `HdfsPath`; 
+/// use `normalize` to validate and canonicalize it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct HdfsPath(str);
+
+impl HdfsPath {
+    pub fn new<S: AsRef<str> + ?Sized>(s: &S) -> &HdfsPath {
+        // HdfsPath is a repr(transparent) wrapper of str
+        unsafe { &*(s.as_ref() as *const str as *const HdfsPath) }
+    }
+
+    pub fn as_str(&self) -> &str { &self.0 }
+
+    pub fn is_absolute(&self) -> bool { self.0.starts_with('/') }
+
+    /// Appends `name` (see `join`)
+    /// ```
+    /// use webhdfs::path::HdfsPath;
+    /// assert_eq!(HdfsPath::new("/user/").join("a").as_str(), "/user/a");
+    /// ```
+    pub fn join(&self, name: &str) -> HdfsPathBuf { HdfsPathBuf(join(&self.0, name)) }
+
+    /// Path without its last component; `None` for the root and for single-component relative paths. 
+    /// Trailing slashes are ignored.
+    /// ```
+    /// use webhdfs::path::HdfsPath;
+    /// assert_eq!(HdfsPath::new("/user/a/").parent().unwrap().as_str(), "/user");
+    /// assert_eq!(HdfsPath::new("/user").parent().unwrap().as_str(), "/");
+    /// assert_eq!(HdfsPath::new("a/b").parent().unwrap().as_str(), "a");
+    /// assert!(HdfsPath::new("/").parent().is_none());
+    /// assert!(HdfsPath::new("a").parent().is_none());
+    /// ```
+    pub fn parent(&self) -> Option<&HdfsPath> {
+        let p = self.0.trim_end_matches('/');
+        match p.rfind('/') {
+            _ if p.is_empty() => None,
+            Some(i) => match p[..i].trim_end_matches('/') {
+                "" => Some(HdfsPath::new("/")),
+                d => Some(HdfsPath::new(d))
+            }
+            None => None
+        }
+    }
+
+    /// Last component of the path; `None` for the root, and if the last component is `.` or `..`
+    /// ```
+    /// use webhdfs::path::HdfsPath;
+    /// assert_eq!(HdfsPath::new("/user/a.txt").file_name(), Some("a.txt"));
+    /// assert_eq!(HdfsPath::new("/user/").file_name(), Some("user"));
+    /// assert_eq!(HdfsPath::new("/"), HdfsPath::new("/"));
+    /// assert!(HdfsPath::new("/").file_name().is_none());
+    /// assert!(HdfsPath::new("/user/..").file_name().is_none());
+    /// ```
+    pub fn file_name(&self) -> Option<&str> {
+        match self.0.trim_end_matches('/').rsplit('/').next() {
+            None | Some("") | Some(".") | Some("..") => None,
+            Some(n) => Some(n)
+        }
+    }
+
+    /// Collapses repeated slashes, removes trailing slash and resolves `.` and `..` components. 
+    /// Fails if the path contains NUL characters, or if `..` goes above the root of an absolute path. 
+    /// Leading `..` components of relative paths are kept.
+    /// ```
+    /// use webhdfs::path::HdfsPath;
+    /// assert_eq!(HdfsPath::new("//user/./a/../b/").normalize().unwrap().as_str(), "/user/b");
+    /// assert_eq!(HdfsPath::new("/user/..").normalize().unwrap().as_str(), "/");
+    /// assert_eq!(HdfsPath::new("../a//b/..").normalize().unwrap().as_str(), "../a");
+    /// assert_eq!(HdfsPath::new("a/..").normalize().unwrap().as_str(), ".");
+    /// assert!(HdfsPath::new("/..").normalize().is_err());
+    /// assert!(HdfsPath::new("/a\0b").normalize().is_err());
+    /// ```
+    pub fn normalize(&self) -> Result<HdfsPathBuf> {
+        if self.0.contains('\0') {
+            return Err(app_error!(generic "Invalid path '{}': contains NUL", self.0.escape_debug()))
+        }
+        let mut c: Vec<&str> = vec![];
+        for s in self.0.split('/') {
+            match s {
+                "" | "." => (),
+                ".." => match c.last() {
+                    Some(&l) if l != ".." => { c.pop(); }
+                    _ if self.is_absolute() => return Err(app_error!(generic "Invalid path '{}': goes above root", &self.0)),
+                    _ => c.push(s)
+                }
+                s => c.push(s)
+            }
+        }
+        let p = c.join("/");
+        Ok(HdfsPathBuf(if self.is_absolute() { format!("/{}", p) } else if p.is_empty() { ".".to_owned() } else { p }))
+    }
+
+    /// Checks that the path is acceptable as a path argument of a call (e.g. the destination of a rename), as the
+    /// namenode requires: absolute, without NUL characters, and without `.` or `..` components.
+    /// ```
+    /// use webhdfs::path::HdfsPath;
+    /// assert!(HdfsPath::new("/user/a b/").validate().is_ok());
+    /// assert!(HdfsPath::new("user/a").validate().is_err());
+    /// assert!(HdfsPath::new("/user/../a").validate().is_err());
+    /// assert!(HdfsPath::new("/a\0b").validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        if !self.is_absolute() {
+            return Err(app_error!(generic "Invalid path '{}': not absolute", self.0.escape_debug()))
+        }
+        if self.0.contains('\0') {
+            return Err(app_error!(generic "Invalid path '{}': contains NUL", self.0.escape_debug()))
+        }
+        if self.0.split('/').any(|s| s == "." || s == "..") {
+            return Err(app_error!(generic "Invalid path '{}': contains '.' or '..'", self.0.escape_debug()))
+        }
+        Ok(())
+    }
+
+    pub fn to_path_buf(&self) -> HdfsPathBuf { HdfsPathBuf(self.0.to_owned()) }
+}
+
+impl AsRef<HdfsPath> for HdfsPath { fn as_ref(&self) -> &HdfsPath { self } }
+impl AsRef<HdfsPath> for str { fn as_ref(&self) -> &HdfsPath { HdfsPath::new(self) } }
+impl AsRef<HdfsPath> for String { fn as_ref(&self) -> &HdfsPath { HdfsPath::new(self) } }
+impl AsRef<str> for HdfsPath { fn as_ref(&self) -> &str { &self.0 } }
+
+impl Display for HdfsPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult { f.write_str(&self.0) }
+}
+
+impl ToOwned for HdfsPath {
+    type Owned = HdfsPathBuf;
+    fn to_owned(&self) -> HdfsPathBuf { self.to_path_buf() }
+}
+
+/// Owned HDFS path (see `HdfsPath`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct HdfsPathBuf(String);
+
+impl HdfsPathBuf {
+    pub fn new(s: impl Into<String>) -> Self { Self(s.into()) }
+    pub fn into_string(self) -> String { self.0 }
+    /// Appends `name` in place (see `join`)
+    pub fn push(&mut self, name: &str) { self.0 = join(&self.0, name) }
+}
+
+impl Deref for HdfsPathBuf {
+    type Target = HdfsPath;
+    fn deref(&self) -> &HdfsPath { HdfsPath::new(&self.0) }
+}
+
+impl Borrow<HdfsPath> for HdfsPathBuf { fn borrow(&self) -> &HdfsPath { self } }
+impl AsRef<HdfsPath> for HdfsPathBuf { fn as_ref(&self) -> &HdfsPath { self } }
+impl AsRef<str> for HdfsPathBuf { fn as_ref(&self) -> &str { &self.0 } }
+impl From<String> for HdfsPathBuf { fn from(s: String) -> Self { Self(s) } }
+impl From<&str> for HdfsPathBuf { fn from(s: &str) -> Self { Self(s.to_owned()) } }
+
+impl Display for HdfsPathBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult { f.write_str(&self.0) }
+}
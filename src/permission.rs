@@ -0,0 +1,152 @@
+//! HDFS permissions.
+//!
+//! `Permission` holds permission bits (`rwx` for user, group and others, plus the sticky bit) and converts them
+//! from and to octal (`"644"`, `"1777"`) and symbolic (`"rw-r--r--"`) notation. Option setters such as
+//! `CreateOptions::permission` accept either a `Permission` or raw mode bits, so
+//! `.permission("644".parse::<Permission>()?)` can be used instead of `.permission(0o644)`. Raw mode bits are sent
+//! as is; `Permission::from_mode_checked` catches the easy-to-miss `.permission(644)` (which is `0o1204`).
+use std::fmt;
+use std::str::FromStr;
+use crate::error::*;
+
+const STICKY: u16 = 0o1000;
+const MASK: u16 = 0o1777;
+
+/// HDFS permission bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permission(u16);
+
+impl Permission {
+    /// Creates a permission from mode bits, ignoring bits other than `0o1777`
+    pub fn from_mode(mode: u16) -> Self { Permission(mode & MASK) }
+    /// Creates a permission from mode bits, failing on modes above `0o777`, which are most likely written in
+    /// decimal (e.g. `644`). Use `from_mode` to set the sticky bit.
+    pub fn from_mode_checked(mode: u16) -> Result<Self> {
+        if mode <= 0o777 { Ok(Permission(mode)) } else {
+            Err(app_error!(generic "Invalid permission {0} (0o{0:o}): modes above 0o777 are taken as written in decimal; use 0o{0}", mode))
+        }
+    }
+    /// Mode bits
+    pub fn mode(&self) -> u16 { self.0 }
+
+    /// Parses octal notation: 3 or 4 digits (e.g. `644`, `0755`, `1777`)
+    pub fn from_octal(s: &str) -> Result<Self> {
+        if !(3..=4).contains(&s.len()) || !s.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            return Err(app_error!(generic "Invalid octal permission '{}'", s))
+        }
+        let mode = s.bytes().fold(0u16, |m, b| (m << 3) | (b - b'0') as u16);
+        if mode & !MASK != 0 {
+            return Err(app_error!(generic "Unsupported permission bits in '{}'", s))
+        }
+        Ok(Permission(mode))
+    }
+
+    /// Parses symbolic notation (e.g. `rwxr-x---`), case-insensitively. A leading file type character
+    /// (as in `drwxr-xr-x`) is skipped. The sticky bit is given as `t` (with `x`) or `T` (without `x`)
+    /// in the last position.
+    pub fn from_symbolic(s: &str) -> Result<Self> {
+        let err = || app_error!(generic "Invalid symbolic permission '{}'", s);
+        let b = s.as_bytes();
+        let b = match b.len() {
+            9 => b,
+            10 if b"-dl".contains(&b[0].to_ascii_lowercase()) => &b[1..],
+            _ => return Err(err())
+        };
+        let mut mode = 0;
+        for (i, c) in b.iter().enumerate() {
+            let bit = 1 << (8 - i);
+            match (c.to_ascii_lowercase(), i % 3) {
+                (b'-', _) => (),
+                (b'r', 0) | (b'w', 1) | (b'x', 2) => mode |= bit,
+                (b't', 2) if i == 8 => mode |= STICKY | if *c == b't' { bit } else { 0 },
+                _ => return Err(err())
+            }
+        }
+        Ok(Permission(mode))
+    }
+
+    /// Symbolic notation, e.g. `rwxr-x---`
+    pub fn symbolic(&self) -> String {
+        let mut s: Vec<u8> = (0..9).map(|i| if self.0 & (1 << (8 - i)) != 0 { b"rwx"[i % 3] } else { b'-' }).collect();
+        if self.is_sticky() {
+            s[8] = if s[8] == b'x' { b't' } else { b'T' };
+        }
+        String::from_utf8(s).unwrap()
+    }
+
+    pub fn is_sticky(&self) -> bool { self.0 & STICKY != 0 }
+    pub fn is_world_readable(&self) -> bool { self.0 & 0o004 != 0 }
+    pub fn is_world_writable(&self) -> bool { self.0 & 0o002 != 0 }
+    pub fn is_world_executable(&self) -> bool { self.0 & 0o001 != 0 }
+    pub fn is_group_readable(&self) -> bool { self.0 & 0o040 != 0 }
+    pub fn is_group_writable(&self) -> bool { self.0 & 0o020 != 0 }
+}
+
+/// Octal notation, as used by WebHDFS (e.g. `644`, `1777`)
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:03o}", self.0) }
+}
+
+/// Parses octal or symbolic notation
+impl FromStr for Permission {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        if s.bytes().all(|b| b.is_ascii_digit()) { Self::from_octal(s) } else { Self::from_symbolic(s) }
+    }
+}
+
+impl From<u16> for Permission {
+    fn from(mode: u16) -> Self { Self::from_mode(mode) }
+}
+
+impl From<Permission> for u16 {
+    fn from(p: Permission) -> Self { p.0 }
+}
+
+#[test]
+fn test_permission() {
+    let p: Permission = "750".parse().unwrap();
+    assert_eq!(p.mode(), 0o750);
+    assert_eq!(p.symbolic(), "rwxr-x---");
+    assert_eq!(p, "rwxr-x---".parse().unwrap());
+    assert_eq!(p, "RWXR-X---".parse().unwrap());
+    assert_eq!(p, "drwxr-x---".parse().unwrap());
+    assert_eq!(p, "0750".parse().unwrap());
+    assert!(!p.is_world_readable() && p.is_group_readable() && !p.is_group_writable());
+    assert_eq!(p.to_string(), "750");
+
+    let p = Permission::from(0o644);
+    assert_eq!(p.to_string(), "644");
+    assert!(p.is_world_readable() && !p.is_world_writable() && !p.is_world_executable());
+    assert_eq!(Permission::from(0o7).to_string(), "007");
+
+    let p: Permission = "1777".parse().unwrap();
+    assert!(p.is_sticky() && p.is_world_writable());
+    assert_eq!(p.symbolic(), "rwxrwxrwt");
+    assert_eq!(p, "drwxrwxrwt".parse().unwrap());
+    assert_eq!("rwxrwxrwT".parse::<Permission>().unwrap().mode(), 0o1776);
+    assert_eq!(Permission::from(0o1776).symbolic(), "rwxrwxrwT");
+
+    assert_eq!(Permission::from_mode_checked(0o750).unwrap().mode(), 0o750);
+    //decimal 644 is 0o1204
+    assert!(Permission::from_mode_checked(644).unwrap_err().to_string().contains("0o644"));
+    assert!(Permission::from_mode_checked(0o1777).is_err());
+
+    for bad in &["", "64", "648", "7777", "12345", "rwxr-x--", "rwsr-x---", "wrxr-x---", "xrwxr-x---", "rwtr-x---"] {
+        assert!(bad.parse::<Permission>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_permission_option() {
+    use crate::op::MkdirsOptions;
+    use crate::uri_tools::PathEncoder;
+    let query = |o: MkdirsOptions| {
+        let q = o.into().iter().fold(PathEncoder::new("/d").query(), |q, a| a.add_to_url(q));
+        String::from_utf8(q.result()).unwrap()
+    };
+    assert_eq!(query(MkdirsOptions::new().permission(0o750)), "/d?permission=750");
+    assert_eq!(query(MkdirsOptions::new().permission("rwxr-x---".parse::<Permission>().unwrap())), "/d?permission=750");
+    assert_eq!(query(MkdirsOptions::new().permission(Permission::from_mode(0o1777))), "/d?permission=1777");
+    assert_eq!(query(MkdirsOptions::new().permission(0o1777)), "/d?permission=1777");
+}
@@ -0,0 +1,162 @@
+//! Client pool for multi-threaded applications.
+//!
+//! `SyncClientPool` owns an `HdfsClient` and a multi-threaded runtime. The handles it hands out (`PooledClient`)
+//! are cheap to clone, `Send` and `Sync`, and can be used from any number of threads concurrently, e.g. from
+//! request handlers of a web server. A call blocks the calling thread while the runtime's worker threads drive the
+//! connections, which are shared by all handles. Each call starts at the last known active namenode.
+//!
+//! As with `SyncHdfsClient`, handles must not be used from within an asynchronous context.
+use std::future::Future;
+use std::io::Write;
+use std::sync::Arc;
+use futures::StreamExt;
+use tokio::runtime::{Builder, Runtime};
+use crate::error::*;
+use crate::path::HdfsPath;
+use crate::datatypes::*;
+use crate::op::*;
+use crate::async_client::{HdfsClient, FOState, FOR, FOStdResult, Data, ErrorD};
+use crate::metrics::ClientStats;
+
+struct Inner {
+    acx: HdfsClient,
+    rt: Runtime
+}
+
+/// Owner of the shared client and runtime
+pub struct SyncClientPool {
+    inner: Arc<Inner>
+}
+
+impl SyncClientPool {
+    /// Creates a pool running `acx` on a runtime with `worker_threads` worker threads
+    pub fn new(acx: HdfsClient, worker_threads: usize) -> Result<Self> {
+        let rt = Builder::new_multi_thread()
+            .worker_threads(worker_threads.max(1))
+            .thread_name("webhdfs-pool")
+            .enable_all()
+            .build()?;
+        Ok(Self { inner: Arc::new(Inner { acx, rt }) })
+    }
+
+    /// Returns a new handle. Handles remain usable after the pool is dropped.
+    pub fn client(&self) -> PooledClient { PooledClient { inner: self.inner.clone() } }
+}
+
+/// Handle to a `SyncClientPool`
+#[derive(Clone)]
+pub struct PooledClient {
+    inner: Arc<Inner>
+}
+
+impl PooledClient {
+    /// Underlying asynchronous client
+    pub fn async_client(&self) -> &HdfsClient { &self.inner.acx }
+
+    /// Snapshot of client state and statistics, see `HdfsClient::stats`
+    pub fn stats(&self) -> ClientStats { self.inner.acx.stats() }
+
+    /// Runs `f` on the pool's runtime, passing it the client and the failover state of the last known active
+    /// namenode, and blocks until it completes. Use it for operations not exposed by `PooledClient` directly.
+    pub fn call<'a, F: Future + 'a>(&'a self, f: impl FnOnce(&'a HdfsClient, FOState) -> F) -> F::Output {
+        let acx = &self.inner.acx;
+        self.inner.rt.block_on(f(acx, acx.active_fostate()))
+    }
+
+    fn exec<'a, T, E, F>(&'a self, f: impl FnOnce(&'a HdfsClient, FOState) -> F) -> StdResult<T, E>
+    where F: Future<Output=FOStdResult<T, E>> + 'a, E: From<tokio::time::error::Elapsed> {
+        let acx = &self.inner.acx;
+        let f = f(acx, acx.active_fostate());
+        let timeout = acx.default_timeout();
+        match self.inner.rt.block_on(async move { tokio::time::timeout(timeout, f).await }) {
+            Ok(r) => FOR::split(r).0,
+            Err(e) => Err(e.into())
+        }
+    }
+
+    /// Stat a file /dir
+    pub fn stat(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileStatusResponse> {
+        self.exec(|acx, fostate| acx.stat(fostate, path))
+    }
+
+    /// Get directory listing
+    pub fn dir(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ListStatusResponse> {
+        self.exec(|acx, fostate| acx.dir(fostate, path))
+    }
+
+    /// Get directory listing, with entries paired with their absolute paths
+    pub fn dir_abs(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<Vec<(String, FileStatus)>> {
+        self.exec(|acx, fostate| acx.dir_abs(fostate, path))
+    }
+
+    /// Get content summary of a file /dir
+    pub fn content_summary(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ContentSummaryResponse> {
+        self.exec(|acx, fostate| acx.content_summary(fostate, path))
+    }
+
+    /// Get file checksum
+    pub fn checksum(&self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileChecksumResponse> {
+        self.exec(|acx, fostate| acx.checksum(fostate, path))
+    }
+
+    /// Create file. Unlike `SyncHdfsClient::create`, the data of a failed call is not returned.
+    pub fn create(&self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) -> Result<()> {
+        self.exec(|acx, fostate| acx.create(fostate, path, data, opts)).map_err(ErrorD::drop)
+    }
+
+    /// Append to a file. Unlike `SyncHdfsClient::append`, the data of a failed call is not returned.
+    pub fn append(&self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: AppendOptions) -> Result<()> {
+        self.exec(|acx, fostate| acx.append(fostate, path, data, opts)).map_err(ErrorD::drop)
+    }
+
+    /// Make a directory
+    pub fn mkdirs(&self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: MkdirsOptions) -> Result<bool> {
+        self.exec(|acx, fostate| acx.mkdirs(fostate, path, opts))
+    }
+
+    /// Rename a file /dir
+    pub fn rename(&self, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String) -> Result<bool> {
+        self.exec(|acx, fostate| acx.rename(fostate, path, destination))
+    }
+
+    /// Delete a file /dir
+    pub fn delete(&self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: DeleteOptions) -> Result<bool> {
+        self.exec(|acx, fostate| acx.delete(fostate, path, opts))
+    }
+
+    /// Get a file (read it from hdfs and write to `output`). The default timeout applies to each chunk.
+    pub fn get_file<W: Write>(&self, path: &(impl AsRef<HdfsPath> + ?Sized), output: &mut W) -> Result<()> {
+        let mut s = self.exec(|acx, fostate| acx.open(fostate, path, OpenOptions::new()))?;
+        let timeout = self.inner.acx.default_timeout();
+        while let Some(chunk) = self.inner.rt.block_on(async { tokio::time::timeout(timeout, s.next()).await })? {
+            output.write_all(&chunk?)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pooled_client() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PooledClient>();
+
+    let fs = FakeHdfs::new();
+    fs.mkdirs("/t");
+    let c = FakeCluster::start(&fs);
+    let pool = c.sync_builder().build_pool(2).unwrap();
+    let threads: Vec<_> = (0..8).map(|i| {
+        let cx = pool.client();
+        std::thread::spawn(move || {
+            let path = format!("/t/f{}", i);
+            cx.create(&path, Data::Owned(vec![b'0' + i; 3]), CreateOptions::new()).unwrap();
+            let mut data = vec![];
+            cx.get_file(&path, &mut data).unwrap();
+            assert_eq!(data, vec![b'0' + i; 3]);
+            cx.stat(&path).unwrap().file_status.length
+        })
+    }).collect();
+    drop(pool);
+    for t in threads { assert_eq!(t.join().unwrap(), 3) }
+    assert_eq!(fs.paths().iter().filter(|p| p.starts_with("/t/")).count(), 8);
+}
@@ -0,0 +1,98 @@
+//! Dataset publication by directory swap.
+//!
+//! `publish` replaces directory `live_dir` with `new_dir` by two renames: `live_dir` to a backup name, then
+//! `new_dir` to `live_dir`. Readers see either the old or the new dataset, never a partially written one (though
+//! `live_dir` is briefly missing between the renames). If the second rename fails, the first one is rolled back.
+use log::warn;
+use crate::error::*;
+use crate::sync_client::{SyncHdfsClient, DeleteOptions};
+
+fn rename(cx: &mut SyncHdfsClient, from: &str, to: &str) -> Result<()> {
+    if cx.rename(from, to.to_owned())? {
+        Ok(())
+    } else {
+        Err(app_error!(generic "Cannot rename {} to {}", from, to))
+    }
+}
+
+/// Makes `new_dir` the `live_dir`. The previous `live_dir`, if any, is renamed to `<live_dir>.old-<millis>`, and
+/// deleted afterwards if `delete_old` is set. Returns the backup path if a backup has been kept.
+/// On failure, `live_dir` is restored, unless it has been created meanwhile; if it is not restored, the error tells
+/// where the previous data is.
+pub fn publish(cx: &mut SyncHdfsClient, new_dir: &str, live_dir: &str, delete_old: bool) -> Result<Option<String>> {
+    cx.stat(new_dir)?;
+    match cx.stat(live_dir) {
+        Ok(_) => (),
+        Err(e) if e.io_kind() == std::io::ErrorKind::NotFound => return rename(cx, new_dir, live_dir).map(|_| None),
+        Err(e) => return Err(e)
+    }
+    let old = format!("{}.old-{}", live_dir.trim_end_matches('/'),
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis());
+    rename(cx, live_dir, &old)?;
+    if let Err(e) = rename(cx, new_dir, live_dir) {
+        //renaming onto an existing directory would move the previous data into it
+        let restored = match cx.stat(live_dir) {
+            Ok(_) => Err(app_error!(generic "{} exists", live_dir)),
+            Err(se) if se.io_kind() == std::io::ErrorKind::NotFound => rename(cx, &old, live_dir),
+            Err(se) => Err(se)
+        };
+        return match restored {
+            Ok(()) => Err(e),
+            Err(re) => Err(app_error!(generic "Publishing {} failed: {}; rollback failed: {}; previous data is in {}", new_dir, e, re, old))
+        }
+    }
+    if !delete_old { return Ok(Some(old)) }
+    match cx.delete(&old, DeleteOptions::new().recursive(true)) {
+        Ok(_) => Ok(None),
+        Err(e) => { warn!("Cannot delete {} after publishing {}: {}", old, live_dir, e); Ok(Some(old)) }
+    }
+}
+
+#[test]
+fn test_publish() {
+    use crate::mock_server::*;
+    let f = FakeHdfs::new();
+    f.put("/staging/v1/part-0", b"1");
+    let c = FakeCluster::start(&f);
+    let mut cx = c.sync_builder().build().unwrap();
+
+    assert_eq!(publish(&mut cx, "/staging/v0", "/data/live", false).unwrap_err().io_kind(), std::io::ErrorKind::NotFound);
+    assert!(publish(&mut cx, "/staging/v1", "/data/live", false).is_err());
+    assert!(f.get("/staging/v1/part-0").is_some());
+    f.mkdirs("/data");
+    assert_eq!(publish(&mut cx, "/staging/v1", "/data/live", false).unwrap(), None);
+    assert_eq!(f.get("/data/live/part-0").unwrap().data, b"1");
+
+    f.put("/staging/v2/part-0", b"2");
+    let old = publish(&mut cx, "/staging/v2", "/data/live", false).unwrap().unwrap();
+    assert!(old.starts_with("/data/live.old-"));
+    assert_eq!(f.get("/data/live/part-0").unwrap().data, b"2");
+    assert_eq!(f.get(&format!("{}/part-0", old)).unwrap().data, b"1");
+
+    f.put("/staging/v3/part-0", b"3");
+    assert_eq!(publish(&mut cx, "/staging/v3", "/data/live", true).unwrap(), None);
+    assert_eq!(f.paths().iter().filter(|p| p.starts_with("/data/live.old-")).count(), 2);
+
+    //the second rename fails (new_dir has moved with live_dir): live_dir is restored
+    f.mkdirs("/data/live/next");
+    assert!(publish(&mut cx, "/data/live/next", "/data/live", true).is_err());
+    assert_eq!(f.get("/data/live/part-0").unwrap().data, b"3");
+
+    //live_dir is created by someone else while the second rename fails: it is left alone
+    f.put("/staging/v4/part-0", b"4");
+    let f1 = f.clone();
+    let c = FakeCluster::with_server(MockServer::start(move |r| {
+        if r.op() == "RENAME" && r.path().ends_with("/staging/v4") {
+            f1.put("/data/live/part-0", b"other");
+            return MockResponse::remote_exception(403, "IOException", "java.io.IOException", "rename failed")
+        }
+        f1.handle(r)
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let e = publish(&mut cx, "/staging/v4", "/data/live", false).unwrap_err();
+    let old = f.paths().into_iter().find(|p| p.starts_with("/data/live.old-") && p.ends_with("/part-0")
+        && f.get(p).unwrap().data == b"3").unwrap();
+    assert!(e.to_string().contains(old.trim_end_matches("/part-0")), "{}", e);
+    assert_eq!(f.get("/data/live/part-0").unwrap().data, b"other");
+    assert!(f.paths().iter().all(|p| !p.starts_with("/data/live/live.old-")));
+}
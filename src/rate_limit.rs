@@ -0,0 +1,86 @@
+//! Namenode request rate limiting.
+//!
+//! `RateLimiter` is a token bucket. It is a cheap-to-clone handle: clones share the same bucket, so a single limiter
+//! passed to several clients (see `HdfsClientBuilder::rate_limiter`), e.g. one per tenant, enforces a process-wide
+//! cap on namenode requests no matter how many clients are constructed. Each namenode request, including each
+//! failover attempt, takes one token; datanode requests are not limited.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    /// Available tokens; negative if tokens have been reserved by waiting callers
+    tokens: f64,
+    last: Instant
+}
+
+/// Token bucket shared by all its clones
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Arc<Mutex<Bucket>>
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate` requests per second on average, and bursts of up to `burst` requests.
+    /// The bucket starts full.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = burst.max(1) as f64;
+        Self { rate: rate.max(f64::MIN_POSITIVE), burst, bucket: Arc::new(Mutex::new(Bucket { tokens: burst, last: Instant::now() })) }
+    }
+
+    /// Refills the bucket and takes a token, possibly reserving it ahead. Returns the time to wait before using it.
+    fn take(&self, reserve: bool) -> Option<Duration> {
+        let mut b = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        b.tokens = (b.tokens + now.duration_since(b.last).as_secs_f64() * self.rate).min(self.burst);
+        b.last = now;
+        if b.tokens >= 1.0 {
+            b.tokens -= 1.0;
+            Some(Duration::from_secs(0))
+        } else if reserve {
+            let wait = Duration::from_secs_f64((1.0 - b.tokens) / self.rate);
+            b.tokens -= 1.0;
+            Some(wait)
+        } else {
+            None
+        }
+    }
+
+    /// Waits for a token. Waiting callers are served in order of arrival.
+    pub async fn acquire(&self) {
+        let wait = self.take(true).unwrap_or_default();
+        if wait > Duration::from_secs(0) { tokio::time::sleep(wait).await }
+    }
+
+    /// Waits for a token, blocking the current thread (for use outside async code)
+    pub fn acquire_blocking(&self) {
+        let wait = self.take(true).unwrap_or_default();
+        if wait > Duration::from_secs(0) { std::thread::sleep(wait) }
+    }
+
+    /// Takes a token if one is available immediately
+    pub fn try_acquire(&self) -> bool { self.take(false).is_some() }
+
+    /// Number of requests per second
+    pub fn rate(&self) -> f64 { self.rate }
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RateLimiter {{ rate: {}, burst: {} }}", self.rate, self.burst)
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let l = RateLimiter::new(10.0, 2);
+    let shared = l.clone();
+    assert!(l.try_acquire());
+    assert!(shared.try_acquire());
+    assert!(!l.try_acquire());
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let t = Instant::now();
+    rt.block_on(async { futures::join!(l.acquire(), shared.acquire()) });
+    assert!(t.elapsed() >= Duration::from_millis(180), "{:?}", t.elapsed());
+}
@@ -0,0 +1,281 @@
+//! Recording and replay of HTTP exchanges (the `record` feature).
+//!
+//! A `Recorder` (see `HdfsClientBuilder::record`) writes every request sent by a client, with the response received,
+//! to a trace file: one JSON `Exchange` per line. Bodies are written as text if they are valid UTF-8, as hex
+//! otherwise. Delegation tokens in request and redirect URIs, and `Authorization` headers, are redacted.
+//!
+//! A `Replayer` (see `HdfsClientBuilder::replay`) serves the responses of a trace back instead of sending requests,
+//! so that a trace attached to a bug report can be replayed, and tests can run without a cluster. A request is
+//! answered with the first exchange not yet served having the same method, path and query (redacted);
+//! the authority is ignored, so a trace can be replayed against any entrypoint. Requests not found in the trace
+//! fail.
+//!
+//! Request bodies are buffered in full to be recorded, so recording is not meant for large uploads.
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+use bytes::Bytes;
+use futures::StreamExt;
+use http::{Method, Uri, HeaderMap};
+use hyper::{Request, Response, Body};
+use crate::error::*;
+use crate::config::REDACTED;
+
+/// Recorded request or response body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordedBody {
+    Text(String),
+    Hex(String)
+}
+
+impl RecordedBody {
+    fn new(data: &[u8]) -> Self {
+        match std::str::from_utf8(data) {
+            Ok(s) => RecordedBody::Text(s.to_owned()),
+            Err(_) => RecordedBody::Hex(data.iter().map(|b| format!("{:02x}", b)).collect())
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self { RecordedBody::Text(s) | RecordedBody::Hex(s) => s.is_empty() }
+    }
+
+    /// Body bytes
+    pub fn data(&self) -> Result<Vec<u8>> {
+        match self {
+            RecordedBody::Text(s) => Ok(s.as_bytes().to_vec()),
+            RecordedBody::Hex(s) if s.len() % 2 == 0 => (0..s.len()).step_by(2)
+                .map(|i| u8::from_str_radix(s.get(i..i + 2).unwrap_or("-"), 16)
+                    .map_err(|_| app_error!(generic "Invalid hex body in trace")))
+                .collect(),
+            RecordedBody::Hex(_) => Err(app_error!(generic "Invalid hex body in trace"))
+        }
+    }
+}
+
+impl Default for RecordedBody {
+    fn default() -> Self { RecordedBody::Text(String::new()) }
+}
+
+/// Request and response, as recorded in a trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "RecordedBody::is_empty")]
+    pub request_body: RecordedBody,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: RecordedBody
+}
+
+/// Replaces delegation tokens in URIs (`config::REDACTED` is not valid in a URI)
+const REDACTED_TOKEN: &str = "redacted";
+
+/// Request (or redirect) URI with the delegation token redacted
+fn redact_uri(uri: &Uri) -> String {
+    let query = uri.query().map(|q| q.split('&')
+        .map(|kv| if kv.starts_with("delegation=") { format!("delegation={}", REDACTED_TOKEN) } else { kv.to_owned() })
+        .collect::<Vec<_>>()
+        .join("&"));
+    let mut s = String::new();
+    if let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) {
+        s.push_str(&format!("{}://{}", scheme, authority));
+    }
+    s.push_str(uri.path());
+    if let Some(q) = query { s.push('?'); s.push_str(&q) }
+    s
+}
+
+/// Path and query of a (redacted) URI string, which replay matches on
+fn match_key(method: &str, uri: &str) -> (String, String) {
+    let path_and_query = match uri.find("://") {
+        Some(i) => uri[i + 3..].find('/').map(|j| &uri[i + 3 + j..]).unwrap_or("/"),
+        None => uri
+    };
+    (method.to_owned(), path_and_query.to_owned())
+}
+
+fn headers(h: &HeaderMap) -> Vec<(String, String)> {
+    h.iter().map(|(k, v)| {
+        let v = String::from_utf8_lossy(v.as_bytes()).into_owned();
+        let v = match *k {
+            http::header::AUTHORIZATION => REDACTED.to_owned(),
+            http::header::LOCATION => v.parse().map(|uri| redact_uri(&uri)).unwrap_or(v),
+            _ => v
+        };
+        (k.as_str().to_owned(), v)
+    }).collect()
+}
+
+/// Writes exchanges to a trace. Clones share the output.
+#[derive(Clone)]
+pub struct Recorder {
+    out: Arc<Mutex<Box<dyn Write + Send>>>
+}
+
+impl Recorder {
+    /// Records to `out`
+    pub fn new(out: impl Write + Send + 'static) -> Self { Self { out: Arc::new(Mutex::new(Box::new(out))) } }
+
+    /// Records to a new file (truncating an existing one)
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let f = std::fs::File::create(path.as_ref()).aerr_f(|| format!("cannot create trace {}", path.as_ref().display()))?;
+        Ok(Self::new(f))
+    }
+
+    fn write(&self, e: &Exchange) {
+        let line = serde_json::to_string(e).expect("Exchange is serializable");
+        let mut out = self.out.lock().unwrap();
+        if let Err(err) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+            log::warn!("Cannot record {} {}: {}", e.method, e.uri, err)
+        }
+    }
+
+    /// Buffers the request body, returning the rebuilt request and the exchange to complete with the response
+    pub(crate) async fn request(&self, request: Request<Body>) -> Result<(Request<Body>, Exchange)> {
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let e = Exchange {
+            method: parts.method.to_string(), uri: redact_uri(&parts.uri), request_headers: headers(&parts.headers),
+            request_body: RecordedBody::new(&body), status: 0, headers: vec![], body: Default::default()
+        };
+        Ok((Request::from_parts(parts, Body::from(body)), e))
+    }
+
+    /// Records the exchange once the response body has been received (or dropped)
+    pub(crate) fn response(&self, mut e: Exchange, response: Response<Body>) -> Response<Body> {
+        struct Pending { recorder: Recorder, e: Exchange, body: Vec<u8> }
+        impl Drop for Pending {
+            fn drop(&mut self) {
+                self.e.body = RecordedBody::new(&self.body);
+                self.recorder.write(&self.e)
+            }
+        }
+        e.status = response.status().as_u16();
+        e.headers = headers(response.headers());
+        let (parts, body) = response.into_parts();
+        let pending = Pending { recorder: self.clone(), e, body: vec![] };
+        let body = futures::stream::unfold(Some((body, pending)), |state| async move {
+            let (mut body, mut pending) = state?;
+            match body.next().await? {
+                Ok(chunk) => { pending.body.extend_from_slice(&chunk); Some((Ok(chunk), Some((body, pending)))) }
+                Err(e) => Some((Err(e), None))
+            }
+        });
+        Response::from_parts(parts, Body::wrap_stream(body))
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "Recorder") }
+}
+
+/// Exchanges not served yet, by method and path and query
+type Unserved = HashMap<(String, String), VecDeque<Exchange>>;
+
+/// Serves responses from a trace. Clones share the exchanges not yet served.
+#[derive(Clone, Debug)]
+pub struct Replayer {
+    exchanges: Arc<Mutex<Unserved>>
+}
+
+impl Replayer {
+    pub fn new(exchanges: impl IntoIterator<Item=Exchange>) -> Self {
+        let mut m: HashMap<_, VecDeque<_>> = HashMap::new();
+        for e in exchanges { m.entry(match_key(&e.method, &e.uri)).or_default().push_back(e) }
+        Self { exchanges: Arc::new(Mutex::new(m)) }
+    }
+
+    /// Reads a trace written by `Recorder`. Empty lines are skipped.
+    pub fn read(r: impl std::io::Read) -> Result<Self> {
+        let mut exchanges = vec![];
+        for (n, line) in BufReader::new(r).lines().enumerate() {
+            let line = line.aerr("cannot read trace")?;
+            if line.trim().is_empty() { continue }
+            exchanges.push(serde_json::from_str(&line).aerr_f(|| format!("invalid trace entry at line {}", n + 1))?);
+        }
+        Ok(Self::new(exchanges))
+    }
+
+    /// Reads a trace file written by `Recorder`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let f = std::fs::File::open(path.as_ref()).aerr_f(|| format!("cannot open trace {}", path.as_ref().display()))?;
+        Self::read(f)
+    }
+
+    /// Number of exchanges not served yet
+    pub fn remaining(&self) -> usize { self.exchanges.lock().unwrap().values().map(|v| v.len()).sum() }
+
+    pub(crate) fn respond(&self, method: &Method, uri: &Uri) -> Result<Response<Body>> {
+        let key = match_key(method.as_str(), &redact_uri(uri));
+        let e = self.exchanges.lock().unwrap().get_mut(&key).and_then(|v| v.pop_front())
+            .ok_or_else(|| app_error!(generic "No recorded exchange for {} {}", key.0, key.1))?;
+        let mut b = Response::builder().status(e.status);
+        for (k, v) in &e.headers { b = b.header(k.as_str(), v.as_str()) }
+        b.body(Body::from(Bytes::from(e.body.data()?))).aerr("invalid recorded response")
+    }
+}
+
+#[test]
+fn test_recorded_body() {
+    assert_eq!(RecordedBody::new(b"ab"), RecordedBody::Text("ab".to_owned()));
+    let b = RecordedBody::new(&[0xff, 0, 0x10]);
+    assert_eq!(b, RecordedBody::Hex("ff0010".to_owned()));
+    assert_eq!(b.data().unwrap(), vec![0xff, 0, 0x10]);
+    assert!(RecordedBody::Hex("f".to_owned()).data().is_err());
+    assert_eq!(redact_uri(&"http://nn:1/webhdfs/v1/a?op=OPEN&delegation=x".parse().unwrap()),
+        "http://nn:1/webhdfs/v1/a?op=OPEN&delegation=redacted");
+    assert_eq!(match_key("GET", "http://nn:1/a?b"), ("GET".to_owned(), "/a?b".to_owned()));
+}
+
+#[test]
+fn test_record_replay() {
+    use crate::{HdfsClientBuilder, CreateOptions, OpenOptions};
+    use crate::async_client::{Data, FOState, FOR};
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.0.lock().unwrap().write(buf) }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let run = |cx: &crate::HdfsClient| rt.block_on(async {
+        FOR::split(cx.create(FOState::PRIMARY, "/f", Data::Borrowed(b"\xffdata"), CreateOptions::new()).await).0.map_err(|e| e.error)?;
+        let (s, _) = cx.stat(FOState::PRIMARY, "/f").await.map_err(|e| e.0)?;
+        let (mut r, _) = cx.open(FOState::PRIMARY, "/f", OpenOptions::new()).await.map_err(|e| e.0)?;
+        let mut data = vec![];
+        while let Some(c) = r.next().await { data.extend_from_slice(&c?) }
+        Result::Ok((s.file_status.length, data))
+    });
+
+    let fs = crate::mock_server::FakeHdfs::new();
+    let nn = fs.start();
+    let trace = Shared::default();
+    let cx = HdfsClientBuilder::new(nn.uri().parse().unwrap())
+        .delegation_token("secret".to_owned())
+        .record(Recorder::new(trace.clone()))
+        .build();
+    assert_eq!(run(&cx).unwrap(), (5, b"\xffdata".to_vec()));
+    let trace = trace.0.lock().unwrap().clone();
+    let text = String::from_utf8(trace.clone()).unwrap();
+    assert!(!text.contains("secret"));
+    assert!(text.contains(r#""body":{"hex":"ff64617461"}"#));
+
+    let replayer = Replayer::read(&trace[..]).unwrap();
+    assert_eq!(replayer.remaining(), 5);
+    let cx = HdfsClientBuilder::new("http://nowhere.invalid:1".parse().unwrap())
+        .delegation_token("other".to_owned())
+        .replay(replayer.clone())
+        .build();
+    assert_eq!(run(&cx).unwrap(), (5, b"\xffdata".to_vec()));
+    assert_eq!(replayer.remaining(), 0);
+    assert!(run(&cx).is_err());
+}
@@ -0,0 +1,100 @@
+//! Redaction of secrets in logs and error messages.
+//!
+//! URIs logged by the library (at `trace` level) and included in errors (e.g. redirect locations) may carry secrets
+//! in their query strings, such as delegation tokens. The values of sensitive query parameters are replaced with
+//! `config::REDACTED` there. Which parameters are sensitive is decided by a process-wide `Redactor`:
+//! `DefaultRedactor` unless another one is set with `set_redactor`. `set_redactor(NoRedaction)` opts out.
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::RwLock;
+use crate::config::REDACTED;
+
+/// Decides which query parameters are sensitive
+pub trait Redactor: Send + Sync {
+    fn is_sensitive(&self, param: &str) -> bool;
+}
+
+impl<F> Redactor for F where F: Fn(&str) -> bool + Send + Sync {
+    fn is_sensitive(&self, param: &str) -> bool { self(param) }
+}
+
+/// Treats as sensitive `delegation`, and parameters whose names contain `token`, `password`, `passwd`, `secret`
+/// or `signature` (case-insensitively)
+pub struct DefaultRedactor;
+
+impl Redactor for DefaultRedactor {
+    fn is_sensitive(&self, param: &str) -> bool {
+        let p = param.to_ascii_lowercase();
+        p == "delegation" || ["token", "password", "passwd", "secret", "signature"].iter().any(|s| p.contains(s))
+    }
+}
+
+/// Treats no parameter as sensitive
+pub struct NoRedaction;
+
+impl Redactor for NoRedaction {
+    fn is_sensitive(&self, _param: &str) -> bool { false }
+}
+
+static REDACTOR: RwLock<Option<Box<dyn Redactor>>> = RwLock::new(None);
+
+/// Replaces the process-wide redactor
+pub fn set_redactor(redactor: impl Redactor + 'static) {
+    *REDACTOR.write().unwrap() = Some(Box::new(redactor));
+}
+
+fn redact_with<'a>(redactor: &dyn Redactor, uri: &'a str) -> Cow<'a, str> {
+    let (base, query) = match uri.split_once('?') {
+        Some(p) => p,
+        None => return Cow::Borrowed(uri)
+    };
+    let (query, fragment) = match query.split_once('#') {
+        Some((q, f)) => (q, Some(f)),
+        None => (query, None)
+    };
+    let sensitive = |kv: &str| kv.split_once('=').is_some_and(|(k, v)| !v.is_empty() && redactor.is_sensitive(k));
+    if !query.split('&').any(sensitive) { return Cow::Borrowed(uri) }
+    let query = query.split('&')
+        .map(|kv| if sensitive(kv) { format!("{}={}", kv.split('=').next().unwrap_or(""), REDACTED) } else { kv.to_owned() })
+        .collect::<Vec<_>>()
+        .join("&");
+    Cow::Owned(match fragment {
+        Some(f) => format!("{}?{}#{}", base, query, f),
+        None => format!("{}?{}", base, query)
+    })
+}
+
+/// `uri` with the values of sensitive query parameters redacted
+/// ```
+/// use webhdfs::redact::redact_uri;
+/// assert_eq!(redact_uri("http://dn:9864/webhdfs/v1/f?op=OPEN&delegation=XYZ&offset=0"),
+///     "http://dn:9864/webhdfs/v1/f?op=OPEN&delegation=<redacted>&offset=0");
+/// ```
+pub fn redact_uri(uri: &str) -> Cow<'_, str> {
+    match &*REDACTOR.read().unwrap() {
+        Some(r) => redact_with(r.as_ref(), uri),
+        None => redact_with(&DefaultRedactor, uri)
+    }
+}
+
+/// Displays the wrapped value (typically a URI) with sensitive query parameters redacted
+pub struct Redacted<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&redact_uri(&self.0.to_string()))
+    }
+}
+
+#[test]
+fn test_redact() {
+    let r = |s| redact_with(&DefaultRedactor, s).into_owned();
+    assert_eq!(r("/a?op=OPEN"), "/a?op=OPEN");
+    assert_eq!(r("/a?delegation="), "/a?delegation=");
+    assert_eq!(r("/a?Delegation=x&op=OPEN&x-access-token=y#f"), "/a?Delegation=<redacted>&op=OPEN&x-access-token=<redacted>#f");
+    assert_eq!(r("/a?identity_password=p"), "/a?identity_password=<redacted>");
+    assert_eq!(redact_with(&NoRedaction, "/a?delegation=x"), "/a?delegation=x");
+    assert_eq!(redact_with(&|p: &str| p == "user.name", "/a?user.name=u&delegation=x"), "/a?user.name=<redacted>&delegation=x");
+    let e = crate::Error::from_http_redirect(307, "http://dn:1/f?op=OPEN&delegation=x".to_owned());
+    assert!(e.to_string().ends_with("http://dn:1/f?op=OPEN&delegation=<redacted>"));
+}
@@ -0,0 +1,118 @@
+//! Multi-cluster client registry.
+//!
+//! `ClientRegistry` holds one shared `HdfsClient` per cluster, keyed by nameservice id. Each client is built from
+//! its own `Config`, so entrypoints, credentials (user name, delegation token), https settings and NAT maps
+//! are kept per cluster. Clusters may be loaded from separate configuration files, or from a single file
+//! with a table per cluster:
+//!
+//! ```toml
+//! [clusters.prod]
+//! entrypoint = "http://nn1.prod:9870"
+//! alt_entrypoint = "http://nn2.prod:9870"
+//!
+//! [clusters.dev]
+//! entrypoint = "http://nn.dev:9870"
+//! user_name = "johnd"
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use serde::Deserialize;
+use crate::error::*;
+use crate::config::{Config, read_config_file};
+use crate::async_client::{HdfsClient, HdfsClientBuilder};
+
+#[derive(Deserialize)]
+struct RegistryConfig {
+    clusters: BTreeMap<String, Config>
+}
+
+/// Shared clients, keyed by nameservice id
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: HashMap<String, Arc<HdfsClient>>
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self { Self { clients: HashMap::new() } }
+
+    /// Builds clients for named configurations
+    pub fn from_configs(configs: impl IntoIterator<Item=(String, Config)>) -> Result<Self> {
+        let mut r = Self::new();
+        for (id, config) in configs {
+            let client = HdfsClientBuilder::from_explicit_config(config).try_build()
+                .map_err(|e| e.with_msg_prepended(format!("cluster '{}'", id).into()))?;
+            r.register(id, client);
+        }
+        Ok(r)
+    }
+
+    /// Reads a configuration file with `[clusters.<id>]` tables (see module docs)
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let c: RegistryConfig = toml::from_slice(&std::fs::read(path)?)?;
+        Self::from_configs(c.clusters)
+    }
+
+    /// Reads a separate configuration file per cluster
+    pub fn from_files<P: AsRef<Path>>(files: impl IntoIterator<Item=(String, P)>) -> Result<Self> {
+        let configs = files.into_iter()
+            .map(|(id, p)| read_config_file(p.as_ref()).map(|c| (id, c)))
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_configs(configs)
+    }
+
+    /// Adds (or replaces) the client for `id`
+    pub fn register(&mut self, id: String, client: HdfsClient) {
+        self.clients.insert(id, Arc::new(client));
+    }
+
+    /// Client for nameservice `id`
+    pub fn get(&self, id: &str) -> Option<Arc<HdfsClient>> { self.clients.get(id).cloned() }
+
+    /// Client for nameservice `id`, or an error naming the unknown id
+    pub fn client(&self, id: &str) -> Result<Arc<HdfsClient>> {
+        self.get(id).ok_or_else(|| app_error!(generic "Unknown cluster '{}'", id))
+    }
+
+    /// Registered nameservice ids, sorted
+    pub fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<_> = self.clients.keys().map(|k| k.as_str()).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+#[test]
+fn test_registry() {
+    use crate::mock_server::*;
+    use crate::async_client::FOState;
+    fn is_send_sync<T: Send + Sync>(_: &T) {}
+
+    let (prod, dev) = (FakeHdfs::new(), FakeHdfs::new());
+    prod.put("/p", b"");
+    dev.put("/d", b"");
+    let (prod_nn, dev_nn) = (prod.start(), dev.start());
+    let dir = std::env::temp_dir().join(format!("webhdfs-test-registry-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("clusters.toml");
+    std::fs::write(&file, format!(
+        "[clusters.prod]\nentrypoint = \"{}\"\n\n[clusters.dev]\nentrypoint = \"{}\"\nuser_name = \"johnd\"\n", 
+        prod_nn.uri(), dev_nn.uri()
+    )).unwrap();
+
+    let r = ClientRegistry::from_file(&file).unwrap();
+    assert_eq!(r.ids(), vec!["dev", "prod"]);
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let c = r.client("prod").unwrap();
+    is_send_sync(&c);
+    assert!(rt.block_on(c.stat(FOState::PRIMARY, "/p")).is_ok());
+    assert!(rt.block_on(r.client("dev").unwrap().stat(FOState::PRIMARY, "/d")).is_ok());
+    assert_eq!(dev_nn.requests()[0].param("user.name"), Some("johnd"));
+    assert!(r.client("test").is_err());
+
+    let dev_file = dir.join("dev.toml");
+    std::fs::write(&dev_file, format!("entrypoint = \"{}\"\n", dev_nn.uri())).unwrap();
+    let r = ClientRegistry::from_files(vec![("dev".to_owned(), &dev_file)]).unwrap();
+    assert_eq!(r.ids(), vec!["dev"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
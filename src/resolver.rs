@@ -0,0 +1,312 @@
+//! Entrypoint resolution.
+//!
+//! When an `EndpointResolver` is set (see `HdfsClientBuilder::endpoint_resolver`), the client asks it for
+//! namenode entrypoints on the first request, and again on every failover, instead of using the entrypoints
+//! given to the builder. `DnsSrvResolver` looks the entrypoints up in DNS SRV records; other discovery
+//! mechanisms can be plugged in by implementing the trait.
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use http::Uri;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use crate::error::*;
+
+/// Source of namenode entrypoints
+pub trait EndpointResolver: Send + Sync {
+    /// Returns namenode entrypoints, in order of preference. The first entrypoint is used as the primary one,
+    /// the second (if any) as the alternative one; the rest are ignored.
+    fn resolve(&self) -> BoxFuture<'_, Result<Vec<Uri>>>;
+}
+
+pub type EndpointResolverPtr = Arc<dyn EndpointResolver>;
+
+/// Resolves entrypoints from DNS SRV records (RFC 2782), e.g. `_webhdfs._tcp.example.com`.
+/// Records are ordered by priority, then by weight (heaviest first). The query is sent over UDP, and again over
+/// TCP if the response is truncated.
+#[derive(Debug, Clone)]
+pub struct DnsSrvResolver {
+    name: String,
+    https: bool,
+    nameserver: Option<SocketAddr>,
+    timeout: Duration
+}
+
+impl DnsSrvResolver {
+    const DEFAULT_TIMEOUT_S: u64 = 5;
+    const RESOLV_CONF: &'static str = "/etc/resolv.conf";
+    const TYPE_SRV: u16 = 33;
+    const CLASS_IN: u16 = 1;
+
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), https: false, nameserver: None, timeout: Duration::from_secs(Self::DEFAULT_TIMEOUT_S) }
+    }
+    /// Whether the resolved entrypoints are `https` rather than `http` (default `false`)
+    pub fn https(self, https: bool) -> Self { Self { https, ..self } }
+    /// Name server to query (by default, the first one listed in `/etc/resolv.conf`)
+    pub fn nameserver(self, nameserver: SocketAddr) -> Self { Self { nameserver: Some(nameserver), ..self } }
+    /// Limit on waiting for the DNS response (5s by default)
+    pub fn timeout(self, timeout: Duration) -> Self { Self { timeout, ..self } }
+
+    fn system_nameserver() -> Result<SocketAddr> {
+        let conf = std::fs::read_to_string(Self::RESOLV_CONF).aerr("Cannot read /etc/resolv.conf")?;
+        conf.lines()
+            .filter_map(|l| match l.split_whitespace().collect::<Vec<_>>()[..] {
+                ["nameserver", addr, ..] => addr.parse::<IpAddr>().ok(),
+                _ => None
+            })
+            .next()
+            .map(|ip| SocketAddr::new(ip, 53))
+            .ok_or_else(|| app_error!(generic "No nameserver found in {}", Self::RESOLV_CONF))
+    }
+
+    fn query(&self, id: u16) -> Result<Vec<u8>> {
+        let mut q = vec![];
+        q.extend(&id.to_be_bytes());
+        q.extend(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]); //RD; QDCOUNT=1
+        for label in self.name.trim_end_matches('.').split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(app_error!(generic "Invalid DNS name '{}'", self.name))
+            }
+            q.push(label.len() as u8);
+            q.extend(label.as_bytes());
+        }
+        q.push(0);
+        q.extend(&Self::TYPE_SRV.to_be_bytes());
+        q.extend(&Self::CLASS_IN.to_be_bytes());
+        Ok(q)
+    }
+
+    /// Whether `response` answers `query`: same id and question
+    fn is_response_to(response: &[u8], query: &[u8]) -> bool {
+        response.len() >= query.len() && response[..2] == query[..2]
+            && response[12..query.len()].eq_ignore_ascii_case(&query[12..])
+    }
+
+    /// Sends `query` over UDP, ignoring datagrams that are not the response to it
+    async fn exchange_udp(&self, nameserver: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+        let local: SocketAddr = if nameserver.is_ipv4() { ([0u8; 4], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(local).await.aerr("Cannot bind UDP socket")?;
+        socket.connect(nameserver).await.aerr_f(|| format!("Cannot connect to nameserver {}", nameserver))?;
+        socket.send(query).await.aerr("Cannot send DNS query")?;
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let (n, from) = tokio::time::timeout(self.timeout, socket.recv_from(&mut buf)).await
+                .aerr_f(|| format!("DNS query for {} timed out", self.name))?
+                .aerr("Cannot receive DNS response")?;
+            if from == nameserver && Self::is_response_to(&buf[..n], query) {
+                buf.truncate(n);
+                return Ok(buf)
+            }
+        }
+    }
+
+    /// Sends `query` over TCP (RFC 1035, 4.2.2)
+    async fn exchange_tcp(&self, nameserver: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+        let exchange = async {
+            let mut stream = TcpStream::connect(nameserver).await
+                .aerr_f(|| format!("Cannot connect to nameserver {}", nameserver))?;
+            let mut msg = (query.len() as u16).to_be_bytes().to_vec();
+            msg.extend(query);
+            stream.write_all(&msg).await.aerr("Cannot send DNS query")?;
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).await.aerr("Cannot receive DNS response")?;
+            let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut buf).await.aerr("Cannot receive DNS response")?;
+            Ok::<_, Error>(buf)
+        };
+        let buf = tokio::time::timeout(self.timeout, exchange).await
+            .aerr_f(|| format!("DNS query for {} timed out", self.name))??;
+        if !Self::is_response_to(&buf, query) { return Err(app_error!(generic "Unexpected DNS response for {}", self.name)) }
+        Ok(buf)
+    }
+
+    async fn lookup(&self) -> Result<Vec<Uri>> {
+        let nameserver = match self.nameserver { Some(ns) => ns, None => Self::system_nameserver()? };
+        let mut id = [0u8; 2];
+        getrandom::fill(&mut id).map_err(|e| app_error!(generic "Cannot generate DNS query id: {}", e))?;
+        let query = self.query(u16::from_be_bytes(id))?;
+        let mut response = self.exchange_udp(nameserver, &query).await?;
+        if is_truncated(&response) { response = self.exchange_tcp(nameserver, &query).await? }
+        let mut records = parse_srv_response(&response).aerr_f(|| format!("DNS query for {} failed", self.name))?;
+        records.sort_by_key(|r| (r.priority, std::cmp::Reverse(r.weight)));
+        let scheme = if self.https { "https" } else { "http" };
+        records.into_iter()
+            .map(|r| format!("{}://{}:{}", scheme, r.target, r.port).parse().aerr("Invalid SRV record target"))
+            .collect()
+    }
+}
+
+impl EndpointResolver for DnsSrvResolver {
+    fn resolve(&self) -> BoxFuture<'_, Result<Vec<Uri>>> { Box::pin(self.lookup()) }
+}
+
+#[derive(Debug, PartialEq)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String
+}
+
+fn dns_format_error() -> Error { app_error!(generic "Malformed DNS response") }
+
+fn read_u16(b: &[u8], pos: usize) -> Result<u16> {
+    b.get(pos..pos + 2).map(|s| u16::from_be_bytes([s[0], s[1]])).ok_or_else(dns_format_error)
+}
+
+/// Reads a (possibly compressed) domain name at `pos`. Returns the name and the position following it.
+fn read_name(b: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let mut labels = vec![];
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *b.get(pos).ok_or_else(dns_format_error)? as usize;
+        match len {
+            0 => return Ok((labels.join("."), end.unwrap_or(pos + 1))),
+            l if l & 0xC0 == 0xC0 => {
+                let ptr = read_u16(b, pos)? as usize & 0x3FFF;
+                end.get_or_insert(pos + 2);
+                pos = ptr;
+            }
+            l => {
+                let label = b.get(pos + 1..pos + 1 + l).ok_or_else(dns_format_error)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    Err(dns_format_error())
+}
+
+/// Whether the TC flag of the response is set
+fn is_truncated(b: &[u8]) -> bool { read_u16(b, 2).is_ok_and(|flags| flags & 0x0200 != 0) }
+
+fn parse_srv_response(b: &[u8]) -> Result<Vec<SrvRecord>> {
+    let flags = read_u16(b, 2)?;
+    if is_truncated(b) {
+        return Err(app_error!(generic "Truncated DNS response"))
+    }
+    match flags & 0x000F {
+        0 => (),
+        3 => return Err(app_error!(generic "DNS name not found")),
+        rcode => return Err(app_error!(generic "DNS error (rcode={})", rcode))
+    }
+    let (qdcount, ancount) = (read_u16(b, 4)?, read_u16(b, 6)?);
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = read_name(b, pos)?.1 + 4;
+    }
+    let mut records = vec![];
+    for _ in 0..ancount {
+        pos = read_name(b, pos)?.1;
+        let (rtype, rdlength) = (read_u16(b, pos)?, read_u16(b, pos + 8)? as usize);
+        let rdata = pos + 10;
+        if rtype == DnsSrvResolver::TYPE_SRV {
+            records.push(SrvRecord {
+                priority: read_u16(b, rdata)?,
+                weight: read_u16(b, rdata + 2)?,
+                port: read_u16(b, rdata + 4)?,
+                target: read_name(b, rdata + 6)?.0
+            })
+        }
+        pos = rdata + rdlength;
+    }
+    if records.is_empty() {
+        return Err(app_error!(generic "No SRV records found"))
+    }
+    Ok(records)
+}
+
+/// SRV response to `query` for `_webhdfs._tcp.example.com`, with header `flags`, and answers unless truncated
+#[cfg(test)]
+fn srv_response(query: &[u8], flags: u16) -> Vec<u8> {
+    let question = &query[12..];
+    assert_eq!(&question[..question.len() - 4], b"\x08_webhdfs\x04_tcp\x07example\x03com\x00");
+    assert_eq!(&question[question.len() - 4..], &[0, 33, 0, 1]);
+    let truncated = flags & 0x0200 != 0;
+    let mut r = vec![query[0], query[1]];
+    r.extend(&flags.to_be_bytes());
+    r.extend(&[0, 1, 0, if truncated { 0 } else { 3 }, 0, 0, 0, 0]);
+    r.extend(question);
+    if truncated { return r }
+    let mut answer = |rtype: u16, rdata: &[u8]| {
+        r.extend(&[0xC0, 12]); //name pointer to the question
+        r.extend(&rtype.to_be_bytes());
+        r.extend(&[0, 1, 0, 0, 0, 60]);
+        r.extend(&(rdata.len() as u16).to_be_bytes());
+        r.extend(rdata);
+    };
+    answer(33, b"\x00\x0A\x00\x05\x26\xAC\x03nn2\x07example\x03com\x00");
+    answer(5, b"\x05alias\xC0\x1A");
+    answer(33, b"\x00\x0A\x00\x32\x26\xAC\x03nn1\xC0\x1A"); //target suffix compressed
+    r
+}
+
+#[test]
+fn test_dns_srv_resolver() {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let (n, peer) = server.recv_from(&mut buf).unwrap();
+        let query = &buf[..n];
+        //responses with another id, or from another address, are ignored
+        let mut stray = srv_response(query, 0x8183);
+        stray[1] ^= 1;
+        server.send_to(&stray, peer).unwrap();
+        let other = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        other.send_to(&srv_response(query, 0x8183), peer).unwrap();
+        server.send_to(&srv_response(query, 0x8180), peer).unwrap();
+    });
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let r = rt.block_on(DnsSrvResolver::new("_webhdfs._tcp.example.com.").nameserver(addr).resolve()).unwrap();
+    assert_eq!(r, vec![
+        "http://nn1.example.com:9900".parse::<Uri>().unwrap(),
+        "http://nn2.example.com:9900".parse::<Uri>().unwrap()
+    ]);
+}
+
+#[test]
+fn test_dns_srv_tcp_fallback() {
+    use std::io::{Read, Write};
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let tcp = std::net::TcpListener::bind(addr).unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let (n, peer) = server.recv_from(&mut buf).unwrap();
+        server.send_to(&srv_response(&buf[..n], 0x8380), peer).unwrap();
+        let (mut s, _) = tcp.accept().unwrap();
+        let mut len = [0u8; 2];
+        s.read_exact(&mut len).unwrap();
+        let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+        s.read_exact(&mut query).unwrap();
+        let r = srv_response(&query, 0x8180);
+        s.write_all(&(r.len() as u16).to_be_bytes()).unwrap();
+        s.write_all(&r).unwrap();
+    });
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let r = rt.block_on(DnsSrvResolver::new("_webhdfs._tcp.example.com").nameserver(addr).resolve()).unwrap();
+    assert_eq!(r.len(), 2);
+}
+
+#[test]
+fn test_parse_srv_response_errors() {
+    let header = |flags: u16, ancount: u16| {
+        let mut r = vec![0, 1];
+        r.extend(&flags.to_be_bytes());
+        r.extend(&[0, 0]);
+        r.extend(&ancount.to_be_bytes());
+        r.extend(&[0, 0, 0, 0]);
+        r
+    };
+    assert!(parse_srv_response(&header(0x8183, 0)).unwrap_err().to_string().contains("not found"));
+    assert!(parse_srv_response(&header(0x8380, 0)).unwrap_err().to_string().contains("Truncated"));
+    assert!(parse_srv_response(&header(0x8180, 0)).unwrap_err().to_string().contains("No SRV"));
+    assert!(parse_srv_response(&header(0x8180, 1)).is_err());
+    //pointer loop
+    let mut r = header(0x8180, 1);
+    r.extend(&[0xC0, 12]);
+    assert!(parse_srv_response(&r).is_err());
+}
@@ -1,19 +1,27 @@
 
-use futures::{Stream, FutureExt, StreamExt};
+use std::time::{Duration, Instant};
+use futures::{Stream, StreamExt};
 use hyper::{
     Request, Response, Body, Uri,
-    client::{Client, ResponseFuture, HttpConnector},
-    body::to_bytes
+    client::{Client, ResponseFuture},
+    body::{to_bytes, HttpBody}
 };
-use hyper_tls::HttpsConnector;
-use http::{uri::Scheme, request::Builder as RequestBuilder, method::Method};
+use http::{request::Builder as RequestBuilder, method::Method, HeaderMap};
 use bytes::{Bytes, Buf};
 use mime::Mime;
-use log::{debug,trace};
+use log::{debug,trace,warn};
 use crate::error::*;
-use crate::datatypes::RemoteExceptionResponse;
+use crate::datatypes::{RemoteExceptionResponse, LocationResponse};
 use crate::natmap::NatMapPtr;
 use crate::https::*;
+use crate::connector::{TimedConnector, TlsObserverPtr};
+use crate::dns_cache::DnsCache;
+use crate::metrics::{MetricsPtr, counter};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::redact::Redacted;
+use crate::schema::SchemaObserverPtr;
+use crate::shutdown::{Lifecycle, InFlight};
+use serde::{Serialize, Deserialize};
 
 /// Required response content-type
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -40,8 +48,251 @@ fn redirect_filter(res: Response<Body>) -> Result<Response<Body>> {
     }
 }
 
+/// Response content-type checking strictness
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum Strictness {
+    /// Content type must match exactly what WebHDFS specifies
+    Strict,
+    /// Any JSON-like or missing content type is accepted for JSON responses, any content type is accepted 
+    /// for binary and empty responses. Error responses are checked for `RemoteException` JSON regardless 
+    /// of their content type. Intended for non-compliant gateways and proxies.
+    Lenient
+}
+
+/// Hook invoked with every request just before it is sent, including both steps of two-step operations
+/// (i.e. the namenode request and the redirected datanode request, unless a separate datanode hook is set). 
+/// It may add or modify headers, e.g. to sign the request for a gateway. An error returned by the hook 
+/// fails the request without sending it.
+pub trait RequestHook: Send + Sync {
+    fn before_send(&self, method: &Method, uri: &Uri, headers: &mut HeaderMap) -> Result<()>;
+}
+
+impl<F> RequestHook for F where F: Fn(&Method, &Uri, &mut HeaderMap) -> Result<()> + Send + Sync {
+    fn before_send(&self, method: &Method, uri: &Uri, headers: &mut HeaderMap) -> Result<()> { self(method, uri, headers) }
+}
+
+pub type RequestHookPtr = std::sync::Arc<dyn RequestHook>;
+
+/// Which redirects are followed. By default, a single redirect (from the namenode to a datanode) is followed,
+/// to any host, but not from https to http.
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    max_hops: u32,
+    allow_scheme_downgrade: bool,
+    allowed_hosts: Vec<String>
+}
+
+impl RedirectPolicy {
+    pub fn new() -> Self { Self { max_hops: 1, allow_scheme_downgrade: false, allowed_hosts: vec![] } }
+    /// Maximum number of redirects followed per request (1 by default). More than one hop is only followed 
+    /// by read requests (OPEN, GETFILECHECKSUM), as data submissions cannot be resent.
+    pub fn max_hops(self, max_hops: u32) -> Self { Self { max_hops: max_hops.max(1), ..self } }
+    /// Whether redirects from https to http locations are followed (`false` by default)
+    pub fn allow_scheme_downgrade(self, allow_scheme_downgrade: bool) -> Self { Self { allow_scheme_downgrade, ..self } }
+    /// Adds a pattern of hosts redirects may point to: a host name, or `*.domain` for any host in the domain. 
+    /// Locations are matched before NAT translation. If no patterns are added, any host is allowed.
+    pub fn allowed_host(mut self, pattern: impl Into<String>) -> Self { self.allowed_hosts.push(pattern.into()); self }
+
+    fn host_allowed(&self, host: &str) -> bool {
+        self.allowed_hosts.is_empty() || self.allowed_hosts.iter().any(|p| match p.strip_prefix('*') {
+            Some(suffix) => host.len() > suffix.len() && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix),
+            None => p.eq_ignore_ascii_case(host)
+        })
+    }
+
+    /// Validates redirect `location` received in response to a request to `from`
+    fn check(&self, from: &Uri, location: &Uri) -> Result<()> {
+        if !self.allow_scheme_downgrade && from.scheme_str() == Some("https") && location.scheme_str() != Some("https") {
+            return Err(app_error!(generic "Redirect from {} to {} rejected: scheme downgrade", Redacted(from), Redacted(location)))
+        }
+        if !self.host_allowed(location.host().unwrap_or("")) {
+            return Err(app_error!(generic "Redirect to {} rejected: host not allowed", Redacted(location)))
+        }
+        Ok(())
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self { Self::new() }
+}
+
+/// What is done when a datanode read is found slow (see `SlowReadPolicy`)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SlowReadAction {
+    /// Log a warning and count the event (`metrics::counter::SLOW_READ`, by datanode)
+    Warn,
+    /// As `Warn`, then re-issue OPEN at the current offset, excluding the slow datanode (`excludedatanodes`)
+    Reconnect
+}
+
+/// Detection of slow datanodes on OPEN. A read is slow if less than `min_rate` bytes per second are received 
+/// during a `window` of time spent waiting for the datanode. Time spent by the reader between chunks is not counted.
+#[derive(Debug, Clone)]
+pub struct SlowReadPolicy {
+    min_rate: u64,
+    window: Duration,
+    action: SlowReadAction,
+    max_reconnects: u32
+}
+
+impl SlowReadPolicy {
+    pub fn new(min_rate: u64, window: Duration) -> Self { 
+        Self { min_rate, window, action: SlowReadAction::Warn, max_reconnects: 3 } 
+    }
+    /// Action on a slow read (`SlowReadAction::Warn` by default)
+    pub fn action(self, action: SlowReadAction) -> Self { Self { action, ..self } }
+    /// Maximum number of reconnects per OPEN (3 by default). Slow reads beyond that are only reported.
+    pub fn max_reconnects(self, max_reconnects: u32) -> Self { Self { max_reconnects, ..self } }
+
+    fn is_slow(&self, bytes: u64, elapsed: Duration) -> bool { (bytes as f64) < self.min_rate as f64 * elapsed.as_secs_f64() }
+}
+
+/// Default of `RestSettings::connect_timeout`, in seconds
+pub const DEFAULT_CONNECT_TIMEOUT_S: u64 = 10;
+
+/// `User-Agent` sent by default
+pub const DEFAULT_USER_AGENT: &str = concat!("webhdfs-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Transport-level settings shared by all requests of a client
+#[derive(Clone)]
+pub struct RestSettings {
+    pub strictness: Strictness,
+    pub request_hook: Option<RequestHookPtr>,
+    /// Receives connection and time-to-first-byte timers
+    pub metrics: Option<MetricsPtr>,
+    /// Limit on waiting for a response, and for each chunk of a response body
+    pub timeout: Option<Duration>,
+    /// Hook for datanode (redirected) requests, replacing `request_hook` for them
+    pub datanode_request_hook: Option<RequestHookPtr>,
+    /// Delegation token for datanode requests, replacing the one in the redirect location
+    pub datanode_delegation: Option<String>,
+    pub redirect_policy: RedirectPolicy,
+    /// `User-Agent` header of all requests; not sent if `None`
+    pub user_agent: Option<String>,
+    /// Checking of success responses where no content is expected (writes and other mutations). 
+    /// `Strictness::Lenient` accepts any content type and body.
+    pub empty_response_strictness: Strictness,
+    /// Slow datanode detection on OPEN; disabled if `None`
+    pub slow_read: Option<SlowReadPolicy>,
+    /// Per-endpoint circuit breaking of all requests; disabled if `None`
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Limit on buffered response bodies (JSON, error and empty-expected responses), in bytes. 
+    /// Streamed bodies (file data, `dir_stream` listings) are not limited.
+    pub max_response_body: Option<u64>,
+    /// Limit on request bodies (file data submitted in a single request), in bytes
+    pub max_request_body: Option<u64>,
+    /// TCP keepalive of connections, as (idle time, probe interval); disabled if `None`
+    pub tcp_keepalive: Option<(Duration, Duration)>,
+    /// Limit on connecting to each address of a host; connecting is only limited by the OS if `None`
+    pub connect_timeout: Option<Duration>,
+    /// Cache of host name resolutions; the system resolver is queried on each connection if `None`
+    pub(crate) dns_cache: Option<DnsCache>,
+    /// Receives unknown fields of JSON responses (strict schema mode); unknown fields are ignored if `None`
+    pub schema_observer: Option<SchemaObserverPtr>,
+    /// Receives peer certificates of TLS connections
+    pub tls_observer: Option<TlsObserverPtr>,
+    /// Shutdown state, shared by all clients derived from one another
+    pub(crate) lifecycle: Lifecycle,
+    /// Recording of all exchanges to a trace
+    #[cfg(feature = "record")]
+    pub recorder: Option<crate::record::Recorder>,
+    /// Replay of a trace instead of sending requests
+    #[cfg(feature = "record")]
+    pub replayer: Option<crate::record::Replayer>
+}
+
+impl RestSettings {
+    pub fn new() -> Self { 
+        Self { 
+            strictness: Strictness::Strict, request_hook: None, metrics: None, timeout: None, 
+            datanode_request_hook: None, datanode_delegation: None, redirect_policy: RedirectPolicy::new(),
+            user_agent: Some(DEFAULT_USER_AGENT.to_owned()), empty_response_strictness: Strictness::Lenient,
+            slow_read: None, circuit_breaker: None, max_response_body: None, max_request_body: None,
+            tcp_keepalive: None, connect_timeout: Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_S)), dns_cache: None, schema_observer: None, tls_observer: None, lifecycle: Lifecycle::new(),
+            #[cfg(feature = "record")]
+            recorder: None,
+            #[cfg(feature = "record")]
+            replayer: None
+        } 
+    }
+
+    /// Content type strictness for responses where no content is expected
+    fn empty_ct_strictness(&self) -> Strictness {
+        match self.empty_response_strictness {
+            Strictness::Lenient => Strictness::Lenient,
+            Strictness::Strict => self.strictness
+        }
+    }
+
+    /// Settings for datanode requests
+    fn datanode(&self) -> Self {
+        match &self.datanode_request_hook {
+            Some(hook) => Self { request_hook: Some(hook.clone()), ..self.clone() },
+            None => self.clone()
+        }
+    }
+
+    /// Applies datanode authentication parameters to a redirect location
+    fn decorate_datanode_uri(&self, uri: Uri) -> Result<Uri> {
+        match &self.datanode_delegation {
+            Some(dt) => replace_query_param(uri, "delegation", dt),
+            None => Ok(uri)
+        }
+    }
+}
+
+/// Replaces all values of query parameter `name` (if any) with a single `value`
+fn replace_query_param(uri: Uri, name: &str, value: &str) -> Result<Uri> {
+    use crate::uri_tools::uri_part_encoder_iter;
+    let mut parts = uri.into_parts();
+    let (path, query) = match &parts.path_and_query {
+        Some(pq) => (pq.path().to_owned(), pq.query().unwrap_or("").to_owned()),
+        None => ("/".to_owned(), String::new())
+    };
+    let mut q: Vec<String> = query.split('&')
+        .filter(|kv| !kv.is_empty() && kv.split('=').next() != Some(name))
+        .map(|kv| kv.to_owned())
+        .collect();
+    q.push(format!("{}={}", name, uri_part_encoder_iter(value, false).map(char::from).collect::<String>()));
+    parts.path_and_query = Some(format!("{}?{}", path, q.join("&")).parse()?);
+    Ok(Uri::from_parts(parts)?)
+}
+
+tokio::task_local! {
+    /// Per-call override of `RestSettings::timeout`
+    static CALL_TIMEOUT: Duration;
+}
+
+/// Runs `f` with `timeout` overriding `RestSettings::timeout` for all requests sent by `f`
+pub(crate) async fn with_call_timeout<F: std::future::Future>(timeout: Duration, f: F) -> F::Output {
+    CALL_TIMEOUT.scope(timeout, f).await
+}
+
+impl Default for RestSettings {
+    fn default() -> Self { Self::new() }
+}
+
+/// Buffers a response body, failing with `ResponseTooLarge` as soon as it is known to exceed `limit`
+async fn body_bytes(res: Response<Body>, limit: Option<u64>) -> Result<Bytes> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(to_bytes(res.into_body()).await?)
+    };
+    let too_large = || Error::new(Some(format!("Response body exceeds {} bytes", limit).into()), Cause::ResponseTooLarge(limit));
+    let length = res.headers().get(hyper::header::CONTENT_LENGTH).and_then(|l| l.to_str().ok()).and_then(|l| l.parse::<u64>().ok());
+    if length.is_some_and(|l| l > limit) { return Err(too_large()) }
+    let mut body = res.into_body();
+    let mut buf = Vec::with_capacity(length.unwrap_or(0) as usize);
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (buf.len() + chunk.len()) as u64 > limit { return Err(too_large()) }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.into())
+}
+
 #[inline]
-async fn error_and_ct_filter(ct_required: RCT, res: Response<Body>) -> Result<Response<Body>> {
+async fn error_and_ct_filter(ct_required: RCT, strictness: Strictness, limit: Option<u64>, res: Response<Body>) -> Result<Response<Body>> {
 
     #[inline]
     fn content_type_extractor(res: &Response<Body>) -> Result<Option<Mime>> {
@@ -71,10 +322,24 @@ async fn error_and_ct_filter(ct_required: RCT, res: Response<Body>) -> Result<Re
         }
     }
 
-    let ct = content_type_extractor(&res)?;
+    #[inline]
+    fn match_mimes_lenient(ct: &Option<Mime>, ct_required: RCT) -> bool {
+        match (ct, ct_required) {
+            (Some(ct), RCT::JSON) => 
+                ct.subtype() == mime::JSON || ct.suffix() == Some(mime::JSON) || ct.type_() == mime::TEXT,
+            _ => true
+        }
+    }
+
+    let ct = match (content_type_extractor(&res), strictness) {
+        (Ok(ct), _) => ct,
+        (Err(_), Strictness::Lenient) => None,
+        (Err(e), Strictness::Strict) => return Err(e)
+    };
     let status = res.status();
+    let lenient = strictness == Strictness::Lenient;
     if status.is_success() {
-        if match_mimes(&ct, ct_required) {
+        if match_mimes(&ct, ct_required) || (lenient && match_mimes_lenient(&ct, ct_required)) {
             Ok(res)
         } else {
             Err(app_error!(generic "Invald content type: required='{:?}' found='{:?}'", ct_required, ct))
@@ -82,30 +347,43 @@ async fn error_and_ct_filter(ct_required: RCT, res: Response<Body>) -> Result<Re
     } else {
         //Failure: try to retrieve JSON error message
         if match_mimes(&ct, RCT::JSON) {
-            match to_bytes(res.into_body()).await {
+            match body_bytes(res, limit).await {
                 Ok(buf) => match serde_json::from_reader::<_, RemoteExceptionResponse>(buf.clone().reader()) {
                     Ok(rer) => Err(rer.remote_exception.into()),
-                    Err(e) => Err(app_error!(generic "JSON-error deseriaization error: {}, recovered text: '{}'", 
+                    Err(e) => Err(Error::from_http_status(status.as_u16(), format!("JSON-error deseriaization error: {}, recovered text: '{}'", 
                         e, String::from_utf8_lossy(buf.chunk().as_ref())
-                    ))
+                    )))
                 }
-                Err(e) => Err(app_error!(generic "JSON-error aggregation error: {}", e))
+                Err(e) if e.is_response_too_large() => Err(e.with_msg_prepended(format!("Remote error: {}", status).into())),
+                Err(e) => Err(Error::from_http_status(status.as_u16(), format!("JSON-error aggregation error: {}", e)))
+            }
+        } else if lenient {
+            match body_bytes(res, limit).await {
+                Ok(buf) => match serde_json::from_reader::<_, RemoteExceptionResponse>(buf.reader()) {
+                    Ok(rer) => Err(rer.remote_exception.into()),
+                    Err(_) => Err(Error::from_http_status(status.as_u16(), format!("Remote error: {}, content-type: {:?}", status, ct)))
+                }
+                Err(e) if e.is_response_too_large() => Err(e.with_msg_prepended(format!("Remote error: {}", status).into())),
+                Err(e) => Err(Error::from_http_status(status.as_u16(), format!("Remote error: {}, content-type: {:?}, aggregation error: {}", status, ct, e)))
             }
         } else {
             debug!("Remote error w/o JSON content: {:?}", res);
-            Err(app_error!(generic "Remote error: {}, content-type: {:?}", status, ct))
+            Err(Error::from_http_status(status.as_u16(), format!("Remote error: {}, content-type: {:?}", status, ct)))
         }
     }
 }
 
 #[inline]
-async fn extract_json<R>(res: Response<Body>) -> Result<R>
+async fn extract_json<R>(res: Response<Body>, settings: &RestSettings) -> Result<R>
 where R: serde::de::DeserializeOwned + Send { 
     trace!("HTTP JSON Response {} ct={:?} cl={:?}", 
         res.status(), res.headers().get(hyper::header::CONTENT_TYPE), res.headers().get(hyper::header::CONTENT_LENGTH)
     );
-    let buf = to_bytes(res.into_body()).await?;
-    serde_json::from_reader(buf.reader()).aerr("JSON deseriaization error")
+    let buf = body_bytes(res, settings.max_response_body).await?;
+    match &settings.schema_observer {
+        Some(observer) => crate::schema::from_slice_checked(&buf, observer.as_ref()),
+        None => serde_json::from_reader(buf.reader()).aerr("JSON deseriaization error")
+    }
 }
 
 #[inline]
@@ -118,16 +396,22 @@ async fn extract_binary(res: Response<Body>) -> impl Stream<Item=Result<Bytes>>
     res.into_body().map(|r| r.aerr("Binary sream read error"))
 }
 
+/// Consumes a response where no content is expected. Returns the `Location` header, if any 
+/// (e.g. sent with `201 Created` in response to CREATE).
 #[inline]
-async fn extract_empty(res: Response<Body>) -> Result<()> {
+async fn extract_empty(res: Response<Body>, strictness: Strictness, limit: Option<u64>) -> Result<Option<String>> {
     trace!("HTTP Empty Response {} ct={:?} cl={:?}", 
         res.status(), 
         res.headers().get(hyper::header::CONTENT_TYPE), 
         res.headers().get(hyper::header::CONTENT_LENGTH)
     );
-    let buf = to_bytes(res.into_body()).await?;
+    let location = res.headers().get(hyper::header::LOCATION).and_then(|l| l.to_str().ok()).map(|l| l.to_owned());
+    let buf = body_bytes(res, limit).await?;
     if !buf.has_remaining() {
-        Ok(())
+        Ok(location)
+    } else if strictness == Strictness::Lenient {
+        debug!("Ignoring unexpected response content: '{}'", String::from_utf8_lossy(buf.chunk()));
+        Ok(location)
     } else {
         Err(app_error!(generic "Unexpected non-empty response received, where empty is expected"))
     }
@@ -158,21 +442,75 @@ fn http_binary_body(request: RequestBuilder, payload: Data) -> Result<Request<Bo
     Ok(request.body(Body::from(payload))?)
 }
 
+/// What to do with data recovered from an unsuccessful write operation
+#[derive(Debug, Clone, Default)]
+pub enum RecoveryPolicy {
+    /// Return the data in `ErrorD::data_opt` (default)
+    #[default]
+    Keep,
+    /// Write data larger than `threshold` bytes to a file in `dir`, and return its path in `ErrorD::data_opt`
+    Spill { threshold: usize, dir: std::path::PathBuf },
+    /// Drop data larger than `threshold` bytes
+    Drop { threshold: usize }
+}
+
+/// Data recovered from an unsuccessful write operation
+#[derive(Debug)]
+pub enum Recovered {
+    Data(Data),
+    /// File holding the data, which has been spilled (see `RecoveryPolicy::Spill`).
+    /// The file is owned by the caller; `ErrorD::take_data` removes it.
+    Spilled(Box<std::path::Path>)
+}
+
 /// Error that contains optional data recovered from an unsuccessful write operation
 pub struct ErrorD {
     pub error: Error,
-    pub data_opt: Option<Data>
+    pub data_opt: Option<Recovered>
 }
 
 impl ErrorD {
     #[inline]
-    pub fn new(error: Error, data_opt: Option<Data>) -> Self { Self { error, data_opt } }
+    pub fn new(error: Error, data_opt: Option<Data>) -> Self { Self { error, data_opt: data_opt.map(Recovered::Data) } }
     #[inline]
     pub fn d(error: Error, data: Data) -> Self { Self::new(error, Some(data)) }
     #[inline]
     pub fn lift(error: Error) -> Self { Self::new(error, None) }
     #[inline]
-    pub fn drop(Self { error, data_opt: _ } : Self) -> Error { error }
+    pub fn drop(Self { error, .. } : Self) -> Error { error }
+
+    /// Same as `d`, but the data is handled according to `policy`. If spilling fails, the data is kept.
+    pub fn recovered(error: Error, data: Data, policy: &RecoveryPolicy) -> Self {
+        static SPILL_SEQ: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        match policy {
+            RecoveryPolicy::Spill { threshold, dir } if data.len() > *threshold => {
+                let seq = SPILL_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let p = dir.join(format!("webhdfs-recovered-{}-{}", std::process::id(), seq));
+                match std::fs::write(&p, &data) {
+                    Ok(()) => Self { error, data_opt: Some(Recovered::Spilled(p.into_boxed_path())) },
+                    Err(e) => {
+                        warn!("Cannot spill recovered data to {}: {}", p.display(), e);
+                        Self::d(error, data)
+                    }
+                }
+            }
+            RecoveryPolicy::Drop { threshold } if data.len() > *threshold => Self::lift(error),
+            _ => Self::d(error, data)
+        }
+    }
+
+    /// Takes the recovered data, reading it back from the spill file (which is then removed) if it has been spilled
+    pub fn take_data(&mut self) -> Result<Option<Data>> {
+        match self.data_opt.take() {
+            Some(Recovered::Spilled(p)) => {
+                let data = std::fs::read(&p).aerr_f(|| format!("Cannot read spilled data from {}", p.display()))?;
+                let _ = std::fs::remove_file(&p);
+                Ok(Some(Data::Owned(data)))
+            }
+            Some(Recovered::Data(d)) => Ok(Some(d)),
+            None => Ok(None)
+        }
+    }
 }
 
 impl From<tokio::time::error::Elapsed> for ErrorD {
@@ -195,155 +533,457 @@ impl HttpxEndpoint {
 }
 
 /// HTTP(S) client
-/// TODO seems like HttpsConnector supports http:// urls as well, check it
 enum Httpx {
-    Http(Client<HttpConnector, Body>),
-    Https(Client<HttpsConnector<HttpConnector>, Body>)
+    Tcp(Client<TimedConnector, Body>),
+    #[cfg(unix)]
+    Unix(Client<crate::unix::UnixConnector, Body>)
 }
 
 impl Httpx {
-    fn new(endpoint: &HttpxEndpoint) -> Httpx {
-        if Some(&Scheme::HTTPS) == endpoint.uri.scheme() {
-            let connector = if let Some(cfg) = &endpoint.https_settings {
-                https_connector(cfg)
-            } else {
-                HttpsConnector::new()
-            };
-            Httpx::Https(Client::builder().build::<_, hyper::Body>(connector))
-        } else {
-            Httpx::Http(Client::new())
+    fn new(endpoint: &HttpxEndpoint, settings: &RestSettings) -> Httpx {
+        #[cfg(unix)]
+        {
+            if endpoint.uri.scheme_str() == Some(crate::unix::SCHEME) {
+                return Httpx::Unix(Client::builder().build::<_, hyper::Body>(crate::unix::UnixConnector))
+            }
         }
+        let tls = if endpoint.uri.scheme_str() != Some("https") {
+            None
+        } else if let Some(cfg) = &endpoint.https_settings {
+            Some(tls_connector(cfg))
+        } else {
+            Some(native_tls::TlsConnector::new().unwrap_or_else(|e| panic!("TlsConnector::new() failure: {}", e)).into())
+        };
+        Httpx::Tcp(Client::builder().build::<_, hyper::Body>(TimedConnector::new(tls, settings.metrics.clone(), settings.tcp_keepalive, settings.tls_observer.clone(), 
+            settings.dns_cache.clone(), settings.connect_timeout)))
     }
 
     fn request_raw(&self, r: Request<Body>) -> ResponseFuture {
         match self {
-            Httpx::Http(c) => c.request(r),
-            Httpx::Https(c) => c.request(r),
+            Httpx::Tcp(c) => c.request(r),
+            #[cfg(unix)]
+            Httpx::Unix(c) => c.request(r),
         }
     }
 }
 
+/// Counts response body bytes as they are received, and limits waiting for each body chunk to `timeout`.
+/// The request is kept in flight (see `crate::shutdown`) until the body has been consumed or dropped.
+fn wrap_body(response: Response<Body>, metrics: Option<MetricsPtr>, endpoint: String, timeout: Option<Duration>, in_flight: InFlight) 
+-> Response<Body> {
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+    let (parts, body) = response.into_parts();
+    let body = futures::stream::unfold(Some((body, metrics, endpoint, in_flight)), move |state| async move {
+        let (mut body, metrics, endpoint, in_flight) = state?;
+        let next = async {
+            match timeout {
+                Some(t) => tokio::time::timeout(t, body.next()).await.map_err(|_| 
+                    std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out waiting for response body")
+                ),
+                None => Ok(body.next().await)
+            }
+        };
+        let chunk = match in_flight.lifecycle().abortable(next).await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => return Some((Err(BoxError::from(e)), None)),
+            None => return Some((Err(BoxError::from(Lifecycle::aborted_error())), None))
+        };
+        match chunk? {
+            Ok(c) => {
+                if let Some(m) = &metrics { m.incr(counter::BYTES_IN, &endpoint, c.len() as u64) }
+                Some((Ok(c), Some((body, metrics, endpoint, in_flight))))
+            }
+            Err(e) => Some((Err(BoxError::from(e)), None))
+        }
+    });
+    Response::from_parts(parts, Body::wrap_stream(body))
+}
+
 struct HttpxClient {
-    endpoint: Httpx
+    endpoint: Httpx,
+    request_hook: Option<RequestHookPtr>,
+    metrics: Option<MetricsPtr>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    circuit_breaker: Option<CircuitBreaker>,
+    lifecycle: Lifecycle,
+    #[cfg(feature = "record")]
+    recorder: Option<crate::record::Recorder>,
+    #[cfg(feature = "record")]
+    replayer: Option<crate::record::Replayer>
 }
 
 impl HttpxClient
 {
-    fn new(endpoint: &HttpxEndpoint) -> Self { Self { endpoint: Httpx::new(endpoint) } }
+    fn new(endpoint: &HttpxEndpoint, settings: &RestSettings) -> Self { 
+        Self { 
+            endpoint: Httpx::new(endpoint, settings), 
+            request_hook: settings.request_hook.clone(),
+            metrics: settings.metrics.clone(),
+            timeout: settings.timeout,
+            user_agent: settings.user_agent.clone(),
+            circuit_breaker: settings.circuit_breaker.clone(),
+            lifecycle: settings.lifecycle.clone(),
+            #[cfg(feature = "record")]
+            recorder: settings.recorder.clone(),
+            #[cfg(feature = "record")]
+            replayer: settings.replayer.clone()
+        } 
+    }
+
+    /// Sends the request through the transport, or the replayer. The exchange is recorded if a recorder is set.
+    async fn request_raw(&self, request: Request<Body>) -> Result<Response<Body>> {
+        #[cfg(feature = "record")]
+        {
+            if let Some(r) = &self.replayer { return r.respond(request.method(), request.uri()) }
+            if let Some(r) = &self.recorder {
+                let (request, e) = r.request(request).await?;
+                let response = self.endpoint.request_raw(request).await?;
+                return Ok(r.response(e, response))
+            }
+        }
+        Ok(self.endpoint.request_raw(request).await?)
+    }
 
     #[inline]
     fn create_request(&self, method: Method, uri: Uri) -> RequestBuilder {
-        trace!("{} {}", method, uri);
-        RequestBuilder::new()
+        trace!("{} {}", method, Redacted(&uri));
+        let b = RequestBuilder::new()
             .method(method)
-            .uri(uri)
+            .uri(uri);
+        match &self.user_agent {
+            Some(ua) => b.header(hyper::header::USER_AGENT, ua.as_str()),
+            None => b
+        }
+    }
+
+    #[inline]
+    async fn send(&self, mut request: Request<Body>) -> Result<Response<Body>> {
+        if let Some(hook) = &self.request_hook {
+            let (method, uri) = (request.method().clone(), request.uri().clone());
+            hook.before_send(&method, &uri, request.headers_mut())?;
+        }
+        let endpoint = request.uri().authority().map(|a| a.as_str().to_owned()).unwrap_or_default();
+        if let Some(b) = &self.circuit_breaker {
+            if !b.allow(&endpoint) {
+                if let Some(m) = &self.metrics { m.incr(counter::CIRCUIT_OPEN, &endpoint, 1) }
+                return Err(Error::new(Some(format!("circuit of {} is open", endpoint).into()), Cause::CircuitOpen(endpoint)))
+            }
+        }
+        let in_flight = self.lifecycle.enter()?;
+        if let Some(m) = &self.metrics {
+            m.incr(counter::REQUESTS, &endpoint, 1);
+            m.incr(counter::BYTES_OUT, &endpoint, HttpBody::size_hint(request.body()).exact().unwrap_or(0));
+        }
+        let timeout = CALL_TIMEOUT.try_with(|t| *t).ok().or(self.timeout);
+        let t = std::time::Instant::now();
+        let response = async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, self.request_raw(request)).await
+                    .aerr_f(|| format!("timed out waiting for response from {}", endpoint))
+                    .and_then(|r| r),
+                None => self.request_raw(request).await
+            }
+        };
+        let response = self.lifecycle.abortable(response).await.unwrap_or_else(|| Err(Lifecycle::aborted_error()));
+        if let Some(b) = &self.circuit_breaker {
+            if response.is_ok() { b.success(&endpoint) } else { b.failure(&endpoint) }
+        }
+        let response = response?;
+        if let Some(m) = &self.metrics { m.observe(counter::TTFB, &endpoint, t.elapsed()) }
+        Ok(wrap_body(response, self.metrics.clone(), endpoint, timeout, in_flight))
     }
 
     #[inline]
     async fn get_like_future(&self, uri: Uri, method: Method) -> Result<Response<Body>> {
         let builder = self.create_request(method, uri);
         let body = http_empty_body(builder)?;
-        let request = self.endpoint.request_raw(body);
-        let response = request.await?;
-        Ok(response)
+        self.send(body).await
     }
 
     #[inline]
     async fn post_like_future(&self, uri: Uri, method: Method, payload: Data) -> Result<Response<Body>> {
         let builder = self.create_request(method, uri);
         let body = http_binary_body(builder, payload)?;
-        let request = self.endpoint.request_raw(body);
-        let response = request.await?;
-        Ok(response)
+        self.send(body).await
+    }
+
+    async fn new_get_like(endpoint: HttpxEndpoint, method: Method, settings: &RestSettings) -> Result<Response<Body>> {
+        Self::new(&endpoint, settings).get_like_future(endpoint.uri, method).await
     }
 
-    async fn new_get_like(endpoint: HttpxEndpoint, method: Method) -> Result<Response<Body>> {
-        Self::new(&endpoint).get_like_future(endpoint.uri, method).await
+    async fn new_post_like(endpoint: HttpxEndpoint, method: Method, payload: Data, settings: &RestSettings) -> Result<Response<Body>> {
+        Self::new(&endpoint, settings).post_like_future(endpoint.uri, method, payload).await
     }
+}
+
+/// File data stream, as returned by OPEN
+pub type BinaryStream = Box<dyn Stream<Item=Result<Bytes>> + Unpin + Send>;
 
-    async fn new_post_like(endpoint: HttpxEndpoint, method: Method, payload: Data) -> Result<Response<Body>> {
-        Self::new(&endpoint).post_like_future(endpoint.uri, method, payload).await
+/// Datanode serving a read: host name as sent by the namenode, and authority connected to (after NAT translation)
+struct ReadSource {
+    host: String,
+    authority: String
+}
+
+/// State of a read watched for slowness (see `SlowReadPolicy`)
+struct ReadState {
+    body: BinaryStream,
+    source: ReadSource,
+    /// Namenode request, re-sent (with a new offset) on reconnect
+    nn: HttpxEndpoint,
+    natmap: NatMapPtr,
+    settings: RestSettings,
+    policy: SlowReadPolicy,
+    /// Bytes received so far
+    read: u64,
+    /// Time spent waiting for the datanode in the current window, bytes received in it
+    window: (Duration, u64),
+    reconnects: u32,
+    excluded: Vec<String>
+}
+
+impl ReadState {
+    fn new(body: BinaryStream, source: ReadSource, nn: HttpxEndpoint, natmap: NatMapPtr, settings: RestSettings, policy: SlowReadPolicy) -> Self {
+        Self { body, source, nn, natmap, settings, policy, read: 0, window: (Duration::ZERO, 0), reconnects: 0, excluded: vec![] }
+    }
+
+    fn query_param(&self, name: &str) -> Option<u64> {
+        self.nn.uri.query()?.split('&').find_map(|kv| kv.strip_prefix(name)?.strip_prefix('=')?.parse().ok())
+    }
+
+    /// Re-sends OPEN for the remaining data, excluding the datanodes found slow so far
+    async fn reconnect(&mut self) -> Result<()> {
+        let offset = self.query_param("offset").unwrap_or(0) + self.read;
+        let uri = replace_query_param(self.nn.uri.clone(), "offset", &offset.to_string())?;
+        let uri = match self.query_param("length") {
+            Some(length) => replace_query_param(uri, "length", &length.saturating_sub(self.read).to_string())?,
+            None => uri
+        };
+        let mut excluded = self.excluded.clone();
+        if !excluded.contains(&self.source.host) { excluded.push(self.source.host.clone()) }
+        let uri = replace_query_param(uri, "excludedatanodes", &excluded.join(","))?;
+        let endpoint = HttpxEndpoint::new(uri, self.nn.https_settings.clone());
+        let (source, body) = HttpyClient::open_datanode(endpoint, &self.natmap, &self.settings).await?;
+        self.source = source;
+        self.body = body;
+        self.excluded = excluded;
+        Ok(())
+    }
+
+    /// Checks the throughput of the window just ended, reporting (and possibly replacing) a slow datanode
+    async fn end_window(&mut self) {
+        let (elapsed, bytes) = self.window;
+        if self.policy.is_slow(bytes, elapsed) {
+            warn!("Slow read from datanode {}: {} bytes in {:?}, at {} bytes read", self.source.authority, bytes, elapsed, self.read);
+            if let Some(m) = &self.settings.metrics { m.incr(counter::SLOW_READ, &self.source.authority, 1) }
+            if self.policy.action == SlowReadAction::Reconnect && self.reconnects < self.policy.max_reconnects {
+                self.reconnects += 1;
+                match self.reconnect().await {
+                    Ok(()) => debug!("Reconnected read to datanode {}", self.source.authority),
+                    //the slow datanode is still better than none
+                    Err(e) => warn!("Cannot reconnect read from slow datanode {}: {}", self.source.authority, e)
+                }
+            }
+        }
+        self.window = (Duration::ZERO, 0);
     }
 }
 
+/// Watches the throughput of a read, see `SlowReadPolicy`
+fn monitor_read(state: ReadState) -> impl Stream<Item=Result<Bytes>> + Unpin + Send {
+    Box::pin(futures::stream::unfold(Some(state), |state| async move {
+        let mut s = state?;
+        loop {
+            let remaining = s.policy.window.saturating_sub(s.window.0);
+            if remaining.is_zero() {
+                s.end_window().await;
+                continue
+            }
+            let started = Instant::now();
+            let r = tokio::time::timeout(remaining, s.body.next()).await;
+            s.window.0 += started.elapsed();
+            match r {
+                Ok(Some(Ok(chunk))) => {
+                    s.read += chunk.len() as u64;
+                    s.window.1 += chunk.len() as u64;
+                    return Some((Ok(chunk), Some(s)))
+                }
+                Ok(Some(Err(e))) => return Some((Err(e), None)),
+                Ok(None) => return None,
+                Err(_) => ()
+            }
+        }
+    }))
+}
+
 pub struct HttpyClient {
     endpoint: HttpxEndpoint, 
-    natmap: NatMapPtr
+    natmap: NatMapPtr,
+    settings: RestSettings
 }
 
 impl HttpyClient {
-    pub fn new(endpoint: HttpxEndpoint, natmap: NatMapPtr) -> Self { Self { endpoint, natmap } }
+    pub fn new(endpoint: HttpxEndpoint, natmap: NatMapPtr, settings: RestSettings) -> Self { Self { endpoint, natmap, settings } }
 
     #[inline]
-    async fn redirect_uri(endpoint: HttpxEndpoint, method: Method, natmap: NatMapPtr)-> Result<HttpxEndpoint> {
+    async fn redirect_uri(endpoint: HttpxEndpoint, method: Method, natmap: &NatMapPtr, settings: &RestSettings) -> Result<HttpxEndpoint> {
         let https_settings = endpoint.https_settings().clone();
-        let r = HttpxClient::new_get_like(endpoint, method).await?;
+        let uri = HttpyClient::redirect_location(endpoint, method, settings).await?;
+        Ok(HttpxEndpoint::new(natmap.translate(uri)?, https_settings))
+    }
+
+    /// Sends the request and returns redirect location (not NAT-translated)
+    async fn redirect_location(endpoint: HttpxEndpoint, method: Method, settings: &RestSettings) -> Result<Uri> {
+        let from = endpoint.uri.clone();
+        let r = HttpxClient::new_get_like(endpoint, method, settings).await?;
         trace!("Redirect: Response {} location={:?}", 
-            r.status(), r.headers().get(hyper::header::LOCATION) 
+            r.status(), r.headers().get(hyper::header::LOCATION).map(|l| Redacted(String::from_utf8_lossy(l.as_bytes())).to_string())
         );
-        match redirect_filter(r) {
-            Ok(b) => Err(app_error!(generic "Expected redirect, found non-redirect response status={}", b.status())),
+        let uri: Uri = match redirect_filter(r) {
+            //location returned in the body, in response to `noredirect=true`
+            Ok(b) if b.status().is_success() => {
+                let b = error_and_ct_filter(RCT::JSON, settings.strictness, settings.max_response_body, b).await
+                    .aerr("Expected redirect or JSON location")?;
+                let l: LocationResponse = extract_json(b, settings).await?;
+                l.location.parse().aerr("Cannot parse location URI returned by namenode")
+            }
+            //remote errors (e.g. StandbyException) are reported by the namenode here
+            Ok(b) => error_and_ct_filter(RCT::None, settings.strictness, settings.max_response_body, b).await.and_then(|b| 
+                Err(app_error!(generic "Expected redirect, found non-redirect response status={}", b.status()))
+            ),
             Err(e) => match e.to_http_redirect() {
-                Ok((_code, location)) => match location.parse() {
-                    Ok(uri) => Ok(HttpxEndpoint::new(natmap.translate(uri)?, https_settings)),
+                Ok((_code, location)) => match location.parse::<Uri>() {
+                    Ok(uri) => Ok(uri),
                     Err(e) => Err(app_error!((cause=e) "Cannot parse location URI returned by redirect"))
                 }
                 Err(e) => Err(e)
             }
+        }?;
+        settings.redirect_policy.check(&from, &uri)?;
+        settings.decorate_datanode_uri(uri)
+    }
+
+    /// Sends a GET to a datanode location, following further redirects as allowed by the redirect policy
+    async fn get_following(endpoint: HttpxEndpoint, natmap: &NatMapPtr, settings: &RestSettings) -> Result<Response<Body>> {
+        let dn_settings = settings.datanode();
+        let mut endpoint = endpoint;
+        let mut hops = 1;
+        loop {
+            let https_settings = endpoint.https_settings().clone();
+            let from = endpoint.uri.clone();
+            let r = HttpxClient::new_get_like(endpoint, Method::GET, &dn_settings).await.map_err(Error::at_datanode)?;
+            let location = match redirect_filter(r) {
+                Ok(r) => return Ok(r),
+                Err(e) => e.to_http_redirect()?.1
+            };
+            if hops >= settings.redirect_policy.max_hops {
+                return Err(app_error!(generic "Too many redirects (max_hops={}), last location: {}", hops, Redacted(&location)))
+            }
+            let uri: Uri = location.parse().aerr("Cannot parse location URI returned by redirect")?;
+            settings.redirect_policy.check(&from, &uri)?;
+            endpoint = HttpxEndpoint::new(natmap.translate(uri)?, https_settings);
+            hops += 1;
         }
     }
     
     /// single-step request to nn (no redirects expected), no input, json output
     pub async fn get_json<R>(self) -> Result<R>
         where R: serde::de::DeserializeOwned + Send + 'static {
-        let Self { endpoint, natmap:_ } = self;
-        let result = HttpxClient::new_get_like(endpoint, Method::GET).await?;
-        let result_filtered = error_and_ct_filter(RCT::JSON, result).await?;
-        extract_json(result_filtered).await
+        let Self { endpoint, natmap:_, settings } = self;
+        let result = HttpxClient::new_get_like(endpoint, Method::GET, &settings).await?;
+        let result_filtered = error_and_ct_filter(RCT::JSON, settings.strictness, settings.max_response_body, result).await?;
+        extract_json(result_filtered, &settings).await
+    }
+
+    /// single-step request to nn (no redirects expected), no input, json output returned unparsed
+    pub async fn get_json_stream(self) -> Result<BinaryStream> {
+        let Self { endpoint, natmap:_, settings } = self;
+        let result = HttpxClient::new_get_like(endpoint, Method::GET, &settings).await?;
+        let result_filtered = error_and_ct_filter(RCT::JSON, settings.strictness, settings.max_response_body, result).await?;
+        Ok(Box::new(extract_binary(result_filtered).await))
     }
 
     /// single-step mutation request (no redirects expected), empty input, json output
     pub async fn op_json<R>(self, method: Method) -> Result<R> 
      where R: serde::de::DeserializeOwned + Send + 'static {
-        let Self { endpoint, natmap: _ } = self;
-        let result = HttpxClient::new_post_like(endpoint, method, data_empty()).await?;
-        let result_filtered = error_and_ct_filter(RCT::JSON, result).await?;
-        extract_json(result_filtered).await
+        let Self { endpoint, natmap: _, settings } = self;
+        let result = HttpxClient::new_post_like(endpoint, method, data_empty(), &settings).await?;
+        let result_filtered = error_and_ct_filter(RCT::JSON, settings.strictness, settings.max_response_body, result).await?;
+        extract_json(result_filtered, &settings).await
     }
 
     /// single-step mutation request (no redirects expected), empty input, empty output
     pub async fn op_empty(self, method: Method) -> Result<()> {
-        let Self { endpoint, natmap:_ } = self;
-        let result = HttpxClient::new_post_like(endpoint, method, data_empty()).await?;
-        let result_filtered = error_and_ct_filter(RCT::None, result).await?;
-        extract_empty(result_filtered).await
+        let Self { endpoint, natmap:_, settings } = self;
+        let result = HttpxClient::new_post_like(endpoint, method, data_empty(), &settings).await?;
+        let result_filtered = error_and_ct_filter(RCT::None, settings.empty_ct_strictness(), settings.max_response_body, result).await?;
+        extract_empty(result_filtered, settings.empty_response_strictness, settings.max_response_body).await.map(|_| ())
     }
     
 
     /// two-step data retrieval request, no input, binary output.
     /// returns pointer
-    pub async fn get_binary(self) -> Result<Box<dyn Stream<Item=Result<Bytes>> + Unpin>> {
-        let Self { endpoint, natmap } = self;
-        let uri = HttpyClient::redirect_uri(endpoint, Method::GET, natmap).await?;
-        let result = HttpxClient::new_get_like(uri, Method::GET).await?;
-        let r = error_and_ct_filter(RCT::Binary, result).await?;
-        let xb = extract_binary(r).await;
-        Ok(Box::new(xb))
-    }
-
-    /// two-step data submission request, data input, empty output. data returned back on error
-    pub async fn post_binary(self, method: Method, data: Data) -> DResult<()> {
-        async fn inner(endpoint: HttpxEndpoint, method: Method, data: Data) -> Result<()> {
-            let result = HttpxClient::new_post_like(endpoint, method, data).await?;
-            let result_filtered = error_and_ct_filter(RCT::None, result).await?;
-            extract_empty(result_filtered).await
+    pub async fn get_binary(self) -> Result<BinaryStream> {
+        let Self { endpoint, natmap, settings } = self;
+        let nn = HttpxEndpoint::new(endpoint.uri.clone(), endpoint.https_settings.clone());
+        let (source, xb) = HttpyClient::open_datanode(endpoint, &natmap, &settings).await?;
+        match settings.slow_read.clone() {
+            Some(policy) => Ok(Box::new(monitor_read(ReadState::new(xb, source, nn, natmap, settings, policy)))),
+            None => Ok(xb)
         }
+    }
 
-        let Self { endpoint, natmap } = self;
-        match HttpyClient::redirect_uri(endpoint, method.clone(), natmap).await {
-            Ok(endpoint) => inner(endpoint, method, data).map(|fr| fr.map_err(ErrorD::lift)).await,
-            Err(e) => Err(ErrorD::d(e, data))
-        }
+    /// Sends a two-step read request, returns the datanode serving it and the response body
+    async fn open_datanode(endpoint: HttpxEndpoint, natmap: &NatMapPtr, settings: &RestSettings) -> Result<(ReadSource, BinaryStream)> {
+        let https_settings = endpoint.https_settings().clone();
+        let location = HttpyClient::redirect_location(endpoint, Method::GET, settings).await?;
+        let host = location.host().unwrap_or("").to_owned();
+        let uri = natmap.translate(location)?;
+        let authority = uri.authority().map(|a| a.as_str().to_owned()).unwrap_or_default();
+        let result = HttpyClient::get_following(HttpxEndpoint::new(uri, https_settings), natmap, settings).await?;
+        let r = error_and_ct_filter(RCT::Binary, settings.strictness, settings.max_response_body, result).await
+            .map_err(Error::at_datanode)?;
+        Ok((ReadSource { host, authority }, Box::new(extract_binary(r).await)))
+    }
+
+    /// two-step data retrieval request, no input, json output
+    pub async fn get_json_redirected<R>(self) -> Result<R>
+        where R: serde::de::DeserializeOwned + Send + 'static {
+        let Self { endpoint, natmap, settings } = self;
+        let uri = HttpyClient::redirect_uri(endpoint, Method::GET, &natmap, &settings).await?;
+        let result = HttpyClient::get_following(uri, &natmap, &settings).await?;
+        let result_filtered = error_and_ct_filter(RCT::JSON, settings.strictness, settings.max_response_body, result).await
+            .map_err(Error::at_datanode)?;
+        extract_json(result_filtered, &settings).await.map_err(Error::at_datanode)
+    }
+
+    /// first step of a two-step request: obtain redirect location from namenode.
+    /// Returns client for the (NAT-translated) location
+    pub async fn redirect(self, method: Method) -> Result<HttpyClient> {
+        let Self { endpoint, natmap, settings } = self;
+        let endpoint = HttpyClient::redirect_uri(endpoint, method, &natmap, &settings).await?;
+        Ok(Self { endpoint, natmap, settings: settings.datanode() })
+    }
+
+    /// first step of a two-step request: obtain redirect location from namenode, without following it.
+    /// The location is NAT-translated if `translate` is set
+    pub async fn location(self, method: Method, translate: bool) -> Result<Uri> {
+        let Self { endpoint, natmap, settings } = self;
+        let uri = HttpyClient::redirect_location(endpoint, method, &settings).await?;
+        if translate { natmap.translate(uri) } else { Ok(uri) }
+    }
+
+    /// second step of a two-step data submission request (redirect already followed), 
+    /// data input, empty output. Returns the `Location` header of the response, if any
+    pub async fn post_binary_redirected(self, method: Method, data: Data) -> Result<Option<String>> {
+        let Self { endpoint, natmap: _, settings } = self;
+        let dn_step = async {
+            let result = HttpxClient::new_post_like(endpoint, method, data, &settings).await?;
+            let result_filtered = error_and_ct_filter(RCT::None, settings.empty_ct_strictness(), settings.max_response_body, result).await?;
+            extract_empty(result_filtered, settings.empty_response_strictness, settings.max_response_body).await
+        };
+        dn_step.await.map_err(Error::at_datanode)
     }
 }
 
@@ -385,3 +1025,116 @@ mod client_tests {
     }
 }
 */
+
+#[test]
+fn test_content_type_strictness() {
+    use crate::mock_server::*;
+    use crate::datatypes::FileStatusResponse;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|r| if r.path().ends_with("/missing") {
+        let mut r = MockResponse::remote_exception(404, "FileNotFoundException", "java.io.FileNotFoundException", "File does not exist");
+        r.headers.clear();
+        r
+    } else {
+        let mut r = MockResponse::json(200, &file_status_json("FILE", 10));
+        r.headers[0].1 = "text/json".to_owned();
+        r
+    });
+    let stat = |path: &str, strictness| {
+        let c = HttpyClient::new(
+            HttpxEndpoint::new(format!("{}{}", nn.uri(), path).parse().unwrap(), None), 
+            NatMapPtr::empty(), 
+            RestSettings { strictness, ..RestSettings::new() }
+        );
+        rt.block_on(c.get_json::<FileStatusResponse>())
+    };
+    assert!(stat("/f", Strictness::Strict).is_err());
+    assert_eq!(stat("/f", Strictness::Lenient).unwrap().file_status.length, 10);
+    assert!(!matches!(stat("/missing", Strictness::Strict).unwrap_err().cause(), Cause::RemoteException(_)));
+    assert!(matches!(stat("/missing", Strictness::Lenient).unwrap_err().cause(), Cause::RemoteException(_)));
+}
+
+#[test]
+fn test_empty_response_strictness() {
+    use crate::mock_server::*;
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let nn = MockServer::start(|r| match r.path() {
+        "/created" => MockResponse::new(201).header("Location", "hdfs://nn:8020/created"),
+        "/json" => MockResponse::json(200, ""),
+        _ => MockResponse::json(200, "{}")
+    });
+    let post = |path: &str, empty_response_strictness| {
+        let c = HttpyClient::new(
+            HttpxEndpoint::new(format!("{}{}", nn.uri(), path).parse().unwrap(), None), 
+            NatMapPtr::empty(), 
+            RestSettings { empty_response_strictness, ..RestSettings::new() }
+        );
+        rt.block_on(c.post_binary_redirected(Method::PUT, Data::Borrowed(b"x")))
+    };
+    assert_eq!(post("/created", Strictness::Strict).unwrap().as_deref(), Some("hdfs://nn:8020/created"));
+    assert!(post("/json", Strictness::Strict).is_err());
+    assert_eq!(post("/json", Strictness::Lenient).unwrap(), None);
+    assert!(post("/body", Strictness::Strict).is_err());
+    assert_eq!(post("/body", Strictness::Lenient).unwrap(), None);
+}
+
+#[test]
+fn test_body_chunk_timeout() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (mut tx, body) = Body::channel();
+    let r = wrap_body(Response::new(body), None, String::new(), Some(Duration::from_millis(50)), Lifecycle::new().enter().unwrap());
+    let r = rt.block_on(async move {
+        tx.send_data(Bytes::from_static(b"first")).await.unwrap();
+        let r = to_bytes(r.into_body()).await;
+        drop(tx);
+        r
+    });
+    let e: Error = r.unwrap_err().into();
+    assert_eq!(e.io_kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn test_replace_query_param() {
+    let r = |uri: &str| replace_query_param(uri.parse().unwrap(), "delegation", "a b").unwrap().to_string();
+    assert_eq!(r("http://dn:1/f?op=OPEN&delegation=x&offset=1"), "http://dn:1/f?op=OPEN&offset=1&delegation=a%20b");
+    assert_eq!(r("http://dn:1/f"), "http://dn:1/f?delegation=a%20b");
+}
+
+#[test]
+fn test_redirect_policy_check() {
+    let u = |s: &str| s.parse::<Uri>().unwrap();
+    let p = RedirectPolicy::new();
+    assert!(p.check(&u("https://nn:1/"), &u("https://dn:2/")).is_ok());
+    assert!(p.check(&u("https://nn:1/"), &u("http://dn:2/")).is_err());
+    assert!(p.check(&u("http://nn:1/"), &u("http://dn:2/")).is_ok());
+    assert!(p.clone().allow_scheme_downgrade(true).check(&u("https://nn:1/"), &u("http://dn:2/")).is_ok());
+    let p = p.allowed_host("*.dn.example.com").allowed_host("gw");
+    assert!(p.check(&u("http://nn/"), &u("http://a.DN.example.com:2/")).is_ok());
+    assert!(p.check(&u("http://nn/"), &u("http://GW:2/")).is_ok());
+    assert!(p.check(&u("http://nn/"), &u("http://dn.example.com:2/")).is_err());
+    assert!(p.check(&u("http://nn/"), &u("http://evil.com/x.dn.example.com")).is_err());
+}
+
+#[test]
+fn test_error_d_recovery_policy() {
+    let dir = std::env::temp_dir().join(format!("webhdfs-test-recovered-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let e = || Error::app_c("failed");
+    let spill = RecoveryPolicy::Spill { threshold: 2, dir: dir.clone() };
+
+    let mut r = ErrorD::recovered(e(), Data::Borrowed(b"01"), &spill);
+    assert!(matches!(r.data_opt, Some(Recovered::Data(_))));
+    assert_eq!(&r.take_data().unwrap().unwrap()[..], b"01");
+
+    let mut r = ErrorD::recovered(e(), Data::Borrowed(b"012"), &spill);
+    let p = match &r.data_opt { Some(Recovered::Spilled(p)) => p.clone(), other => panic!("not spilled: {:?}", other) };
+    assert_eq!(std::fs::read(&p).unwrap(), b"012");
+    assert_eq!(&r.take_data().unwrap().unwrap()[..], b"012");
+    assert!(!p.exists() && r.take_data().unwrap().is_none());
+
+    let r = ErrorD::recovered(e(), Data::Borrowed(b"012"), &RecoveryPolicy::Drop { threshold: 2 });
+    assert!(r.data_opt.is_none());
+    let mut r = ErrorD::recovered(e(), Data::Borrowed(b"012"), &RecoveryPolicy::Keep);
+    assert_eq!(&r.take_data().unwrap().unwrap()[..], b"012");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,168 @@
+//! Schema tolerance of JSON responses.
+//!
+//! Hadoop distributions differ in the fields they return: some add fields (e.g. `fileId`, `childrenNum`,
+//! `storagePolicy` in `FileStatus`), some omit fields that are not essential. Response types accept both: unknown
+//! fields are ignored, and missing non-essential fields take default values. In strict schema mode
+//! (`HdfsClientBuilder::strict_schema`), unknown fields are additionally reported to a `SchemaObserver`,
+//! which is useful to spot server-side schema changes. The call still succeeds.
+//!
+//! Strict schema mode applies to buffered JSON responses; `dir_stream` listings are not checked.
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use serde::de::{self, Deserializer, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::de::value::BorrowedStrDeserializer;
+use serde_json::Value;
+use crate::error::*;
+
+/// Receives fields of JSON responses unknown to the library (see `HdfsClientBuilder::strict_schema`)
+pub trait SchemaObserver: Send + Sync {
+    /// Called once per response and per response type (e.g. `FileStatus`) having unknown `fields`
+    fn unexpected_fields(&self, type_name: &str, fields: &[String]);
+}
+
+impl<F> SchemaObserver for F where F: Fn(&str, &[String]) + Send + Sync {
+    fn unexpected_fields(&self, type_name: &str, fields: &[String]) { self(type_name, fields) }
+}
+
+pub type SchemaObserverPtr = std::sync::Arc<dyn SchemaObserver>;
+
+type Unexpected = RefCell<BTreeMap<&'static str, BTreeSet<String>>>;
+
+/// Deserializes `R` from `json`, reporting unknown fields to `observer`
+pub(crate) fn from_slice_checked<R>(json: &[u8], observer: &dyn SchemaObserver) -> Result<R>
+where R: de::DeserializeOwned {
+    let value: Value = serde_json::from_slice(json).aerr("JSON deseriaization error")?;
+    let unexpected = Unexpected::default();
+    let r = R::deserialize(Checked { value: &value, unexpected: &unexpected }).aerr("JSON deseriaization error")?;
+    for (type_name, fields) in unexpected.into_inner() {
+        observer.unexpected_fields(type_name, &fields.into_iter().collect::<Vec<_>>());
+    }
+    Ok(r)
+}
+
+/// Deserializer over a JSON value recording unknown fields of structs
+struct Checked<'de> {
+    value: &'de Value,
+    unexpected: &'de Unexpected
+}
+
+impl<'de> Checked<'de> {
+    fn child(&self, value: &'de Value) -> Self { Self { value, unexpected: self.unexpected } }
+}
+
+impl<'de> Deserializer<'de> for Checked<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Object(m) => visitor.visit_map(CheckedMap { iter: m.iter(), value: None, parent: self }),
+            Value::Array(a) => visitor.visit_seq(CheckedSeq { iter: a.iter(), parent: self }),
+            v => v.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V)
+    -> std::result::Result<V::Value, Self::Error> {
+        if let Value::Object(m) = self.value {
+            let unknown: Vec<_> = m.keys().filter(|k| !fields.contains(&k.as_str())).cloned().collect();
+            if !unknown.is_empty() {
+                self.unexpected.borrow_mut().entry(name).or_default().extend(unknown);
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V)
+    -> std::result::Result<V::Value, Self::Error> {
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct
+        seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+struct CheckedMap<'de> {
+    iter: serde_json::map::Iter<'de>,
+    value: Option<&'de Value>,
+    parent: Checked<'de>
+}
+
+impl<'de> MapAccess<'de> for CheckedMap<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((k, v)) => {
+                self.value = Some(v);
+                let k = BorrowedStrDeserializer::<'de, Self::Error>::new(k);
+                seed.deserialize(k).map(Some)
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> std::result::Result<S::Value, Self::Error> {
+        match self.value.take() {
+            Some(v) => seed.deserialize(self.parent.child(v)),
+            None => Err(de::Error::custom("value is missing"))
+        }
+    }
+}
+
+struct CheckedSeq<'de> {
+    iter: std::slice::Iter<'de, Value>,
+    parent: Checked<'de>
+}
+
+impl<'de> SeqAccess<'de> for CheckedSeq<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> std::result::Result<Option<S::Value>, Self::Error> {
+        match self.iter.next() {
+            Some(v) => seed.deserialize(self.parent.child(v)).map(Some),
+            None => Ok(None)
+        }
+    }
+}
+
+#[test]
+fn test_unexpected_fields() {
+    use std::sync::Mutex;
+    use crate::datatypes::*;
+    let seen = Mutex::new(vec![]);
+    let observer = |t: &str, f: &[String]| seen.lock().unwrap().push(format!("{}: {}", t, f.join(",")));
+    let r: ListStatusResponse = from_slice_checked(br#"{"FileStatuses":{"FileStatus":[
+        {"length":1,"pathSuffix":"a","type":"FILE","fileId":16387,"storagePolicy":0},
+        {"length":0,"pathSuffix":"b","type":"DIRECTORY","childrenNum":2,"fileId":16388,"symlink":null}
+    ]}}"#, &observer).unwrap();
+    let fs = &r.file_statuses.file_status;
+    assert_eq!((fs[0].length, fs[0].path_suffix.as_str(), fs[0].owner.as_str(), fs[0].replication), (1, "a", "", 0));
+    assert_eq!(fs[1].symlink, None);
+    assert_eq!(*seen.lock().unwrap(), vec!["FileStatus: childrenNum,fileId,storagePolicy".to_owned()]);
+
+    seen.lock().unwrap().clear();
+    let r: ContentSummaryResponse = from_slice_checked(br#"{"ContentSummary":{"directoryCount":1,"fileCount":2,"length":3,
+        "ecPolicy":"","typeQuota":{}}}"#, &observer).unwrap();
+    assert_eq!((r.content_summary.quota, r.content_summary.space_quota, r.content_summary.space_consumed), (-1, -1, 0));
+    assert_eq!(*seen.lock().unwrap(), vec!["ContentSummary: ecPolicy,typeQuota".to_owned()]);
+
+    seen.lock().unwrap().clear();
+    let r: RemoteExceptionResponse = from_slice_checked(br#"{"RemoteException":{"exception":"X","exceptionId":"1"}}"#, &observer).unwrap();
+    assert_eq!(r.remote_exception.extra["exceptionId"], "1");
+    assert!(seen.lock().unwrap().is_empty());
+
+    assert!(from_slice_checked::<Boolean>(br#"{"boolean":"yes"}"#, &observer).is_err());
+}
@@ -0,0 +1,126 @@
+//! Graceful shutdown of a client (see `HdfsClient::shutdown`).
+//!
+//! A request is in flight from the moment it is sent until its response body has been consumed or dropped.
+//! The state is shared by all clients derived from one another (e.g. by `HdfsClient::with_context`).
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use futures::future::Either;
+use tokio::sync::Notify;
+use crate::error::*;
+
+#[derive(Default)]
+struct State {
+    closed: AtomicBool,
+    aborted: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    abort: Notify
+}
+
+/// Shutdown state of a client
+#[derive(Clone, Default)]
+pub(crate) struct Lifecycle(Arc<State>);
+
+fn shut_down_error(msg: &'static str) -> Error { Error::new(Some(msg.into()), Cause::ShutDown) }
+
+impl Lifecycle {
+    pub(crate) fn new() -> Self { Self::default() }
+
+    /// Whether `shutdown` has been called
+    pub(crate) fn is_closed(&self) -> bool { self.0.closed.load(Ordering::SeqCst) }
+
+    /// Fails if the client is closed to new calls
+    pub(crate) fn check_open(&self) -> Result<()> {
+        if self.is_closed() { Err(shut_down_error("client is shut down")) } else { Ok(()) }
+    }
+
+    /// Registers a request about to be sent. The request is in flight until the returned guard is dropped.
+    pub(crate) fn enter(&self) -> Result<InFlight> {
+        if self.0.aborted.load(Ordering::SeqCst) { return Err(shut_down_error("client is shut down")) }
+        self.0.in_flight.fetch_add(1, Ordering::SeqCst);
+        Ok(InFlight(self.clone()))
+    }
+
+    /// Number of requests in flight
+    pub(crate) fn in_flight(&self) -> usize { self.0.in_flight.load(Ordering::SeqCst) }
+
+    /// Completes when in-flight requests are to be aborted
+    pub(crate) async fn aborted(&self) {
+        loop {
+            let n = self.0.abort.notified();
+            if self.0.aborted.load(Ordering::SeqCst) { return }
+            n.await
+        }
+    }
+
+    /// The error in-flight requests fail with when aborted
+    pub(crate) fn aborted_error() -> Error { shut_down_error("request aborted by client shutdown") }
+
+    /// Runs `f` to completion, unless in-flight requests are aborted first (`None`)
+    pub(crate) async fn abortable<T>(&self, f: impl Future<Output=T>) -> Option<T> {
+        // abortion is checked first, so that an aborted body stream does not yield data already received
+        let (aborted, f) = (std::pin::pin!(self.aborted()), std::pin::pin!(f));
+        match futures::future::select(aborted, f).await {
+            Either::Left(_) => None,
+            Either::Right((r, _)) => Some(r)
+        }
+    }
+
+    /// Closes the client, waits for in-flight requests up to `grace`, then aborts the rest.
+    /// Returns the number of requests aborted.
+    pub(crate) async fn shutdown(&self, grace: Duration) -> usize {
+        self.0.closed.store(true, Ordering::SeqCst);
+        let drained = async {
+            loop {
+                let n = self.0.idle.notified();
+                if self.in_flight() == 0 { return }
+                n.await
+            }
+        };
+        if tokio::time::timeout(grace, drained).await.is_ok() { return 0 }
+        let n = self.in_flight();
+        self.0.aborted.store(true, Ordering::SeqCst);
+        self.0.abort.notify_waiters();
+        n
+    }
+}
+
+/// A request in flight (see `Lifecycle::enter`)
+pub(crate) struct InFlight(Lifecycle);
+
+impl InFlight {
+    pub(crate) fn lifecycle(&self) -> &Lifecycle { &self.0 }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if (self.0).0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 { (self.0).0.idle.notify_waiters() }
+    }
+}
+
+#[test]
+fn test_lifecycle() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let l = Lifecycle::new();
+    assert!(l.check_open().is_ok());
+    let a = l.enter().unwrap();
+    let b = l.enter().unwrap();
+    assert_eq!(l.in_flight(), 2);
+    drop(b);
+    let l2 = l.clone();
+    let aborted = rt.block_on(async move {
+        let release = async move { tokio::time::sleep(Duration::from_millis(20)).await; drop(a) };
+        futures::join!(l2.shutdown(Duration::from_secs(5)), release).0
+    });
+    assert_eq!(aborted, 0);
+    assert!(l.check_open().unwrap_err().is_shut_down());
+    assert!(l.enter().is_ok());
+
+    let l = Lifecycle::new();
+    let _a = l.enter().unwrap();
+    let (aborted, ()) = rt.block_on(futures::future::join(l.shutdown(Duration::from_millis(20)), l.aborted()));
+    assert_eq!(aborted, 1);
+    assert!(l.enter().err().unwrap().is_shut_down());
+}
@@ -0,0 +1,66 @@
+//! Convenience functions for quick scripts (requires feature `simple`).
+//!
+//! Each function takes a full URL of a file or directory, such as `http://namenode:9870/user/johnd/in.txt`, 
+//! and makes a short-lived `SyncHdfsClient` for the URL's scheme and authority. The `/webhdfs/v1` prefix 
+//! may be included in the path, and the user name may be given as `user.name` query parameter.
+//!
+//! ```no_run
+//! let data = webhdfs::simple::read_to_vec("http://namenode:9870/user/johnd/in.txt").unwrap();
+//! webhdfs::simple::write_bytes("http://namenode:9870/user/johnd/out.txt?user.name=johnd", &data).unwrap();
+//! ```
+use http::Uri;
+use crate::error::*;
+use crate::datatypes::FileStatus;
+use crate::sync_client::*;
+use crate::async_client::{Data, ErrorD};
+
+const PREFIX: &str = "/webhdfs/v1";
+
+/// Reads a whole file
+pub fn read_to_vec(url: &str) -> Result<Vec<u8>> {
+    let (mut cx, path) = client(url)?;
+    let mut out = vec![];
+    cx.get_file(&path, &mut out)?;
+    Ok(out)
+}
+
+/// Creates (or overwrites) a file with `data`
+pub fn write_bytes(url: &str, data: &[u8]) -> Result<()> {
+    let (mut cx, path) = client(url)?;
+    cx.create(&path, Data::Owned(data.to_vec()), CreateOptions::new().overwrite(true)).map_err(ErrorD::drop)
+}
+
+/// Lists a directory
+pub fn list(url: &str) -> Result<Vec<FileStatus>> {
+    let (mut cx, path) = client(url)?;
+    Ok(cx.dir(&path)?.file_statuses.file_status)
+}
+
+fn client(url: &str) -> Result<(SyncHdfsClient, String)> {
+    let uri: Uri = url.parse()?;
+    let authority = uri.authority().ok_or_else(|| app_error!(generic "No authority in URL '{}'", url))?;
+    let entrypoint = format!("{}://{}", uri.scheme_str().unwrap_or("http"), authority).parse()?;
+    let path = uri.path();
+    let path = path.strip_prefix(PREFIX).unwrap_or(path);
+    let path = crate::path::decode(if path.is_empty() { "/" } else { path })?;
+    let user_name = uri.query().and_then(|q| q.split('&').find_map(|kv| kv.strip_prefix("user.name=")));
+    let b = SyncHdfsClientBuilder::new(entrypoint);
+    let b = if let Some(u) = user_name { b.user_name(crate::path::decode(u)?) } else { b };
+    Ok((b.build()?, path))
+}
+
+#[test]
+fn test_simple() {
+    let fs = crate::mock_server::FakeHdfs::new();
+    fs.put("/d/a", b"0123");
+    let nn = fs.start();
+    let url = |p: &str| format!("{}{}", nn.uri(), p);
+    assert_eq!(read_to_vec(&url("/d/a")).unwrap(), b"0123");
+    assert_eq!(read_to_vec(&url("/webhdfs/v1/d/a")).unwrap(), b"0123");
+    write_bytes(&url("/d/b?user.name=johnd"), b"45").unwrap();
+    assert_eq!(fs.get("/d/b").unwrap().data, b"45");
+    assert!(nn.requests().iter().any(|r| r.param("user.name") == Some("johnd")));
+    let l = list(&url("/d")).unwrap();
+    assert_eq!(l.iter().map(|s| s.path_suffix.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+    assert!(read_to_vec(&url("/d/missing")).is_err());
+}
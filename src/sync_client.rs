@@ -3,7 +3,7 @@
 //! The main client is `SyncHdfsClient`. It is neither `Send` nor `Sync`, so a separate instance must be created in 
 //! each thread accessing the API.
 
-use std::io::{Read, Write, Seek, SeekFrom, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
+use std::io::{Read, BufRead, Write, Seek, SeekFrom, Result as IoResult, Error as IoError, ErrorKind as IoErrorKind};
 use std::convert::TryInto;
 use std::time::Duration;
 use std::cell::RefCell;
@@ -13,10 +13,18 @@ use tokio::runtime::{Builder, Runtime};
 use futures::{Future, Stream, stream::StreamExt};
 use bytes::Bytes;
 use crate::error::*;
+use crate::path::HdfsPath;
 use crate::datatypes::*;
 use crate::async_client::*;
 use crate::natmap::NatMap;
 use crate::https::HttpsSettings;
+use crate::metrics::MetricsPtr;
+use crate::resolver::EndpointResolver;
+use crate::rate_limit::RateLimiter;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::filter::FileFilter;
+use crate::schema::SchemaObserver;
+use crate::pool::SyncClientPool;
 
 pub use crate::op::*;
 
@@ -54,6 +62,8 @@ impl SyncHdfsClientBuilder {
     pub fn natmap(self, natmap: NatMap) -> Self {
         Self { a: self.a.natmap(natmap), ..self }
     }
+    /// Limit on each call (30s by default). When set, it also limits waiting for each response and each chunk
+    /// of a response body (see `HdfsClientBuilder::default_timeout`).
     pub fn default_timeout(self, timeout: Duration) -> Self {
         Self { a: self.a.default_timeout(timeout), ..self }
     }
@@ -66,13 +76,136 @@ impl SyncHdfsClientBuilder {
     pub fn delegation_token(self, dt: String) -> Self {
         Self { a: self.a.delegation_token(dt), ..self }
     }    
+    pub fn metrics(self, metrics: MetricsPtr) -> Self {
+        Self { a: self.a.metrics(metrics), ..self }
+    }
+    pub fn failover_on_connect_error(self, failover_on_connect_error: bool) -> Self {
+        Self { a: self.a.failover_on_connect_error(failover_on_connect_error), ..self }
+    }
+    pub fn max_failovers(self, max_failovers: u32) -> Self {
+        Self { a: self.a.max_failovers(max_failovers), ..self }
+    }
+    pub fn exception_class(self, exception: impl Into<String>, class: ExceptionClass) -> Self {
+        Self { a: self.a.exception_class(exception, class), ..self }
+    }
+    pub fn failover_backoff(self, initial: Duration, max: Duration) -> Self {
+        Self { a: self.a.failover_backoff(initial, max), ..self }
+    }
+    pub fn lease_retry(self, max_retries: u32, initial: Duration, max: Duration) -> Self {
+        Self { a: self.a.lease_retry(max_retries, initial, max), ..self }
+    }
+    pub fn raw_query_param(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self { a: self.a.raw_query_param(name, value), ..self }
+    }
+    pub fn user_agent(self, user_agent: impl Into<String>) -> Self {
+        Self { a: self.a.user_agent(user_agent), ..self }
+    }
+    pub fn client_id(self, client_id: impl Into<String>) -> Self {
+        Self { a: self.a.client_id(client_id), ..self }
+    }
+    pub fn endpoint_resolver(self, resolver: impl EndpointResolver + 'static) -> Self {
+        Self { a: self.a.endpoint_resolver(resolver), ..self }
+    }
+    pub fn rate_limiter(self, rate_limiter: RateLimiter) -> Self {
+        Self { a: self.a.rate_limiter(rate_limiter), ..self }
+    }
+    pub fn circuit_breaker(self, circuit_breaker: CircuitBreaker) -> Self {
+        Self { a: self.a.circuit_breaker(circuit_breaker), ..self }
+    }
+    pub fn max_response_body_size(self, limit: u64) -> Self {
+        Self { a: self.a.max_response_body_size(limit), ..self }
+    }
+    pub fn max_request_body_size(self, limit: u64) -> Self {
+        Self { a: self.a.max_request_body_size(limit), ..self }
+    }
+    pub fn tcp_keepalive(self, time: Duration, interval: Duration) -> Self {
+        Self { a: self.a.tcp_keepalive(time, interval), ..self }
+    }
+    pub fn connect_timeout(self, timeout: Option<Duration>) -> Self {
+        Self { a: self.a.connect_timeout(timeout), ..self }
+    }
+    pub fn dns_cache(self, ttl: Duration, max_entries: usize) -> Self {
+        Self { a: self.a.dns_cache(ttl, max_entries), ..self }
+    }
+    pub fn strict_schema(self, observer: impl SchemaObserver + 'static) -> Self {
+        Self { a: self.a.strict_schema(observer), ..self }
+    }
+    pub fn tls_observer(self, observer: impl TlsObserver + 'static) -> Self {
+        Self { a: self.a.tls_observer(observer), ..self }
+    }
+    pub fn default_create_options(self, opts: CreateOptions) -> Self {
+        Self { a: self.a.default_create_options(opts), ..self }
+    }
+    pub fn default_append_options(self, opts: AppendOptions) -> Self {
+        Self { a: self.a.default_append_options(opts), ..self }
+    }
+    pub fn default_delete_options(self, opts: DeleteOptions) -> Self {
+        Self { a: self.a.default_delete_options(opts), ..self }
+    }
+    pub fn deny_recursive_delete(self, deny: bool) -> Self {
+        Self { a: self.a.deny_recursive_delete(deny), ..self }
+    }
+    #[cfg(feature = "record")]
+    pub fn record(self, recorder: crate::record::Recorder) -> Self {
+        Self { a: self.a.record(recorder), ..self }
+    }
+    #[cfg(feature = "record")]
+    pub fn replay(self, replayer: crate::record::Replayer) -> Self {
+        Self { a: self.a.replay(replayer), ..self }
+    }
+    pub fn natmap_entrypoints(self, natmap_entrypoints: bool) -> Self {
+        Self { a: self.a.natmap_entrypoints(natmap_entrypoints), ..self }
+    }
+    pub fn content_type_strictness(self, strictness: Strictness) -> Self {
+        Self { a: self.a.content_type_strictness(strictness), ..self }
+    }
+    pub fn empty_response_strictness(self, strictness: Strictness) -> Self {
+        Self { a: self.a.empty_response_strictness(strictness), ..self }
+    }
+    pub fn request_hook(self, hook: impl RequestHook + 'static) -> Self {
+        Self { a: self.a.request_hook(hook), ..self }
+    }
+    pub fn default_port(self, default_port: u16) -> Self {
+        Self { a: self.a.default_port(default_port), ..self }
+    }
+    pub fn redirect_policy(self, policy: RedirectPolicy) -> Self {
+        Self { a: self.a.redirect_policy(policy), ..self }
+    }
+    pub fn slow_read_policy(self, policy: SlowReadPolicy) -> Self {
+        Self { a: self.a.slow_read_policy(policy), ..self }
+    }
+    pub fn datanode_request_hook(self, hook: impl RequestHook + 'static) -> Self {
+        Self { a: self.a.datanode_request_hook(hook), ..self }
+    }
+    pub fn datanode_delegation_token(self, dt: String) -> Self {
+        Self { a: self.a.datanode_delegation_token(dt), ..self }
+    }
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { a: self.a.dry_run(dry_run), ..self }
+    }
+    pub fn verify_create_options(self, verify_create_options: bool) -> Self {
+        Self { a: self.a.verify_create_options(verify_create_options), ..self }
+    }
+    pub fn recovery_policy(self, recovery_policy: RecoveryPolicy) -> Self {
+        Self { a: self.a.recovery_policy(recovery_policy), ..self }
+    }
+    pub fn write_strategy(self, write_strategy: WriteStrategy) -> Self {
+        Self { a: self.a.write_strategy(write_strategy), ..self }
+    }
+    pub fn upload_chunk_size(self, upload_chunk_size: usize) -> Self {
+        Self { a: self.a.upload_chunk_size(upload_chunk_size), ..self }
+    }
     pub fn build(self) -> Result<SyncHdfsClient> {
          Ok(SyncHdfsClient { 
-            acx: Rc::new(self.a.build()), 
+            acx: Rc::new(self.a.try_build()?), 
             rt: Rc::new(RefCell::new(single_threaded_runtime()?)),
             fostate: FOState::PRIMARY
         })
     }
+    /// Builds a pool of clients usable from many threads, see `crate::pool`
+    pub fn build_pool(self, worker_threads: usize) -> Result<SyncClientPool> {
+        SyncClientPool::new(self.a.try_build()?, worker_threads)
+    }
 }
 
 impl SyncHdfsClient {
@@ -87,6 +220,57 @@ impl SyncHdfsClient {
     pub fn fostate(&self) -> FOState { self.fostate }
 
     pub fn with_fostate(self, fostate: FOState) -> Self { Self { fostate, ..self } }
+
+    /// View of the client making requests with the authentication fields of `ctx`, sharing the runtime with the 
+    /// client (see `HdfsClient::with_context`)
+    pub fn with_context(&self, ctx: &RequestContext) -> Self { 
+        Self { acx: Rc::new(self.acx.with_context(ctx)), rt: self.rt.clone(), fostate: self.fostate }
+    }
+    /// Shorthand for `with_context` authenticating as `user_name` (with neither `doas` nor delegation token)
+    pub fn as_user(&self, user_name: impl Into<String>) -> Self { 
+        self.with_context(&RequestContext::new().user_name(user_name))
+    }
+
+    /// Metrics updated by this client
+    pub fn metrics(&self) -> &MetricsPtr { self.acx.metrics() }
+
+    /// Configuration reconstructed from the client settings, with secrets redacted, see `HdfsClient::effective_config`
+    pub fn effective_config(&self) -> crate::config::Config { self.acx.effective_config() }
+
+    /// Same as `effective_config`, but with secrets included
+    pub fn effective_config_with_secrets(&self) -> crate::config::Config { self.acx.effective_config_with_secrets() }
+
+    /// Snapshot of client state and statistics, see `HdfsClient::stats`
+    pub fn stats(&self) -> crate::metrics::ClientStats { self.acx.stats() }
+
+    /// Rebuilds the internal runtime, dropping all connections and tasks it owns, and resets failover state.
+    /// Use it to recover the client (and all its clones, e.g. those held by `ReadHdfsFile`) after a runtime 
+    /// failure such as a panic in a task.
+    pub fn reset(&mut self) -> Result<()> {
+        let rt = single_threaded_runtime()?;
+        let rt = match self.rt.try_borrow_mut() {
+            Ok(mut r) => { *r = rt; None }
+            Err(_) => Some(rt)
+        };
+        if let Some(rt) = rt {
+            // still borrowed by an unwound call: detach this instance from the broken runtime
+            self.rt = Rc::new(RefCell::new(rt));
+        }
+        self.fostate = FOState::PRIMARY;
+        Ok(())
+    }
+
+    /// Shuts the client down gracefully, see `HdfsClient::shutdown`. Readers and writers still open (e.g. 
+    /// `ReadHdfsFile`) cannot make progress while this call waits, so they are aborted after `grace`.
+    pub fn shutdown(&mut self, grace: Duration) -> usize { self.block_on(self.acx.shutdown(grace)) }
+
+    /// Whether `shutdown` has been called
+    pub fn is_shut_down(&self) -> bool { self.acx.is_shut_down() }
+
+    /// Checks that the cluster is reachable by stat'ing the root directory.
+    pub fn health_check(&mut self) -> Result<()> {
+        self.stat("/").map(|_| ())
+    }
     
     #[inline]
     fn exec<R, E>(&self, f: impl Future<Output=FOStdResult<R, E>>) -> FOStdResult<R, E> where E: From<tokio::time::error::Elapsed>{
@@ -95,17 +279,23 @@ impl SyncHdfsClient {
         where E: From<tokio::time::error::Elapsed> {
             Ok(tokio::time::timeout(timeout, f).await.map_err(|e| (e.into(), fostate))??)
         }
-        self.rt.borrow_mut().block_on(with_timeout(f, self.fostate, self.acx.default_timeout().clone()))
+        self.rt.borrow_mut().block_on(with_timeout(f, self.fostate, self.acx.default_timeout()))
     }
     
     #[inline]
-    fn exec0<R>(&self, f: impl Future<Output=R>) -> Result<R> {
+    pub(crate) fn exec0<R>(&self, f: impl Future<Output=R>) -> Result<R> {
         async fn with_timeout<R>(f: impl Future<Output=R>, timeout: Duration) -> Result<R> {
             Ok(tokio::time::timeout(timeout, f).await?)
         }
-        self.rt.borrow_mut().block_on(with_timeout(f, self.acx.default_timeout().clone()))
+        self.rt.borrow_mut().block_on(with_timeout(f, self.acx.default_timeout()))
     }
 
+    /// Underlying asynchronous client
+    pub(crate) fn async_client(&self) -> &HdfsClient { &self.acx }
+
+    /// Runs `f` to completion on the client's runtime, without timeout
+    pub(crate) fn block_on<R>(&self, f: impl Future<Output=R>) -> R { self.rt.borrow_mut().block_on(f) }
+
     #[inline]
     fn foresult<T, E>(&mut self, r: FOStdResult<T, E>) -> StdResult<T, E> {
         let (r, fostate) = FOR::split(r);
@@ -114,40 +304,102 @@ impl SyncHdfsClient {
     }
 
     /// Open a file for reading
-    pub fn open(&mut self, path: &str, open_options: OpenOptions) -> Result<Box<dyn Stream<Item=Result<Bytes>>+Unpin>> {
+    pub fn open(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), open_options: OpenOptions) -> Result<BinaryStream> {
+        let path = path.as_ref().as_str();
         let fs = self.acx.open(self.fostate, path, open_options);
         let r = self.exec0(fs)?;
         self.foresult(r)
     }
 
+    /// Open the last `n` bytes of a file with a single request, see `HdfsClient::open_tail`
+    pub fn open_tail(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), n: u64) -> Result<(u64, BinaryStream)> {
+        let path = path.as_ref().as_str();
+        let r = self.exec0(self.acx.open_tail(self.fostate, path, n))?;
+        self.foresult(r)
+    }
+
+    /// Read the last `n` bytes of a file, returning the file length and the data, see `HdfsClient::read_tail`
+    pub fn read_tail(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), n: u64) -> Result<(u64, Vec<u8>)> {
+        let path = path.as_ref().as_str();
+        let r = self.exec(self.acx.read_tail(self.fostate, path, n));
+        self.foresult(r)
+    }
+
     /// Append to a file
-    pub fn append(&mut self, path: &str, data: Data, append_options: AppendOptions) -> DResult<()> {
+    pub fn append(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, append_options: AppendOptions) -> DResult<()> {
+        let path = path.as_ref().as_str();
         let f = self.acx.append(self.fostate, path, data, append_options);
         let r = self.exec(f);
         self.foresult(r)
     }
 
     /// Create file
-    pub fn create(&mut self, path: &str, data: Data, opts: CreateOptions) -> DResult<()> {
+    pub fn create(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) -> DResult<()> {
+        let path = path.as_ref().as_str();
         let f = self.acx.create(self.fostate, path, data, opts);
         let r = self.exec(f);
         self.foresult(r)
     }
 
+    /// Create a file, returning its `Location` if reported (see `HdfsClient::create_with_location`)
+    pub fn create_with_location(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), data: Data, opts: CreateOptions) -> DResult<Option<String>> {
+        let path = path.as_ref().as_str();
+        let f = self.acx.create_with_location(self.fostate, path, data, opts);
+        let r = self.exec(f);
+        self.foresult(r)
+    }
+
+    /// Get datanode location to read a file from, without reading it (see `HdfsClient::open_location`)
+    pub fn open_location(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: OpenOptions, translate: bool) -> Result<Uri> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.open_location(self.fostate, path, opts, translate);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Checks whether datanodes are reachable, suggesting a NAT mapping if not (see `HdfsClient::detect_nat`). 
+    /// The mapping is to be added with `SyncHdfsClientBuilder::natmap`.
+    pub fn detect_nat(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), timeout: Duration) -> Result<Option<(String, String)>> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.detect_nat(self.fostate, path, timeout);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get datanode location to write a file to, without writing it (see `HdfsClient::create_location`)
+    pub fn create_location(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: CreateOptions, translate: bool) -> Result<Uri> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.create_location(self.fostate, path, opts, translate);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get datanode URL a file can be uploaded to directly (see `HdfsClient::create_upload_url`)
+    pub fn create_upload_url(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: CreateOptions) -> Result<Uri> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.create_upload_url(self.fostate, path, opts);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
     fn save_stream<W: Write>(&self, input: impl Stream<Item=Result<Bytes>>, output: &mut W) -> Result<()> {
-        fn write_bytes<W: Write>(b: &Bytes, w: &mut W) -> Result<()> {
-            if w.write(&b)? != b.len() {
+        self.for_each_chunk(input, |b| {
+            if output.write(b)? != b.len() {
                 Err(app_error!(generic "Short write"))
             } else {
                 Ok(())
             }
-        }
+        })
+    }
+
+    /// Pulls `input` to the end, passing each chunk to `f`
+    pub(crate) fn for_each_chunk(&self, input: impl Stream<Item=Result<Bytes>>, mut f: impl FnMut(&Bytes) -> Result<()>) -> Result<()> {
         let mut input = Box::pin(input);
         loop {
-            let f = input.into_future();
-            let (ob, input2) = self.exec0(f)?;
+            let fut = input.into_future();
+            let (ob, input2) = self.exec0(fut)?;
             match ob {
-                Some(Ok(bytes)) => write_bytes(&bytes, output)?,
+                Some(Ok(bytes)) => f(&bytes)?,
                 Some(Err(e)) => break Err(e),
                 None => break Ok(())
             }
@@ -157,55 +409,228 @@ impl SyncHdfsClient {
 
     /// Get a file (read it from hdfs and save to local fs)
     #[inline]
-    pub fn get_file<W: Write>(&mut self, input: &str, output: &mut W) -> Result<()> {    
+    pub fn get_file<W: Write>(&mut self, input: &(impl AsRef<HdfsPath> + ?Sized), output: &mut W) -> Result<()> {    
         let s = self.open(input, OpenOptions::new())?;
         self.save_stream(s, output)
     }
 
+    /// Resume an interrupted download from `offset`, checking that the file still matches `expected`,
+    /// see `HdfsClient::resume_to`
+    pub fn resume_file<W: Write>(&mut self, input: &(impl AsRef<HdfsPath> + ?Sized), offset: i64, expected: &FileStatus, output: &mut W)
+    -> Result<()> {
+        let path = input.as_ref().as_str();
+        expected.check_unchanged(path, &self.stat(path)?.file_status)?;
+        let s = self.open(path, OpenOptions::new().offset(offset))?;
+        self.save_stream(s, output)?;
+        Ok(expected.check_unchanged(path, &self.stat(path)?.file_status)?)
+    }
+
+    /// Set namespace and storage space quotas of a directory, see `HdfsClient::set_quota`
+    pub fn set_quota(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), namespace_quota: i64, storagespace_quota: i64) -> Result<()> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.set_quota(self.fostate, path, namespace_quota, storagespace_quota);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Set storage space quota of a directory for a storage type, see `HdfsClient::set_quota_by_storage_type`
+    pub fn set_quota_by_storage_type(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), storage_type: &str, storagespace_quota: i64) 
+    -> Result<()> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.set_quota_by_storage_type(self.fostate, path, storage_type, storagespace_quota);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Whether optional operation `op` may be supported by the server, see `HdfsClient::is_supported`
+    pub fn is_supported(&self, op: &str) -> bool { self.acx.is_supported(op) }
+
     /// Get directory listing
-    pub fn dir(&mut self, path: &str) -> Result<ListStatusResponse> {
+    pub fn dir(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ListStatusResponse> {
+        let path = path.as_ref().as_str();
         let r = self.acx.dir(self.fostate, path);
         let r = self.exec(r);
         self.foresult(r)
     }
 
+    /// Copies file `from` to `to` within the cluster, renaming it if `move_source` allows, see `HdfsClient::copy_within_cluster`
+    pub fn copy_within_cluster(&mut self, from: &(impl AsRef<HdfsPath> + ?Sized), to: &(impl AsRef<HdfsPath> + ?Sized), 
+        opts: CreateOptions, move_source: bool) -> Result<ClusterCopy> {
+        let (from, to) = (from.as_ref().as_str(), to.as_ref().as_str());
+        let r = self.acx.copy_within_cluster(self.fostate, from, to, opts, move_source);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get directory entries matching `filter`, see `HdfsClient::dir_filtered`. Only matching entries are collected.
+    pub fn dir_filtered(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), filter: FileFilter) -> Result<Vec<FileStatus>> {
+        let path = path.as_ref().as_str();
+        let acx = &self.acx;
+        let r = self.exec(async {
+            let (entries, fostate) = acx.dir_filtered(self.fostate, path, filter).await?;
+            match entries.collect::<Vec<_>>().await.into_iter().collect::<Result<Vec<_>>>() {
+                Ok(v) => Ok((v, fostate)),
+                Err(e) => Err((e, fostate))
+            }
+        });
+        self.foresult(r)
+    }
+
+    /// Get directory listing, starting after entry `start_after`, see `HdfsClient::dir_from`
+    pub fn dir_from(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), start_after: &str) -> Result<ListStatusResponse> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.dir_from(self.fostate, path, start_after);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get directory listing, with entries paired with their absolute paths (see `HdfsClient::dir_abs`)
+    pub fn dir_abs(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<Vec<(String, FileStatus)>> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.dir_abs(self.fostate, path);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
     /// Stat a file /dir
-    pub fn stat(&mut self, path: &str) -> Result<FileStatusResponse> {
+    pub fn stat(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileStatusResponse> {
+        let path = path.as_ref().as_str();
         let r = self.acx.stat(self.fostate, path);
         let r = self.exec(r);
         self.foresult(r)
     }
 
+    /// Resolve symbolic links, see `HdfsClient::resolve_path`
+    pub fn resolve_path(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<(String, FileStatus)> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.resolve_path(self.fostate, path);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Stat the file /dir a symbolic link points to, see `HdfsClient::resolve_path`
+    pub fn stat_resolved(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileStatus> {
+        self.resolve_path(path).map(|(_, st)| st)
+    }
+
+    /// Open the file a symbolic link points to, see `HdfsClient::resolve_path`
+    pub fn open_resolved(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), open_options: OpenOptions) 
+    -> Result<BinaryStream> {
+        let (path, _) = self.resolve_path(path)?;
+        self.open(&path, open_options)
+    }
+
+    /// Get encryption info of a file, see `HdfsClient::encryption_info`
+    pub fn encryption_info(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<Option<FileEncryptionInfo>> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.encryption_info(self.fostate, path);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get content summary (sizes and counts) of a directory tree
+    pub fn content_summary(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ContentSummaryResponse> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.content_summary(self.fostate, path);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Pre-flight quota check, see `HdfsClient::check_quota`
+    pub fn check_quota(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), length: u64, files: u64, replication: u16) -> Result<()> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.check_quota(self.fostate, path, length, files, replication);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get the difference between snapshots of a directory, see `HdfsClient::snapshot_diff`
+    pub fn snapshot_diff(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), from: &str, to: &str) -> Result<SnapshotDiffReportResponse> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.snapshot_diff(self.fostate, path, from, to);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// List snapshottable directories visible to the user
+    pub fn snapshottable_dirs(&mut self) -> Result<SnapshottableDirectoryListResponse> {
+        let r = self.acx.snapshottable_dirs(self.fostate);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get defaults the server applies to new files, see `HdfsClient::server_defaults`
+    pub fn server_defaults(&mut self, probe_dir: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<ServerDefaults> {
+        let r = self.acx.server_defaults(self.fostate, probe_dir);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get home directory of the user
+    pub fn home_dir(&mut self) -> Result<String> {
+        let r = self.acx.home_dir(self.fostate);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Delete trash checkpoints older than `older_than` (see `HdfsClient::expunge_trash`)
+    pub fn expunge_trash(&mut self, older_than: Duration) -> Result<Vec<String>> {
+        let r = self.acx.expunge_trash(self.fostate, older_than);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
+    /// Get file checksum (computed by a datanode)
+    pub fn checksum(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized)) -> Result<FileChecksumResponse> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.checksum(self.fostate, path);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
     /// Concat File(s)
-    pub fn concat(&mut self, path: &str, paths: Vec<String>) -> Result<()> {
+    pub fn concat(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), paths: Vec<String>) -> Result<()> {
+        let path = path.as_ref().as_str();
         let r = self.acx.concat(self.fostate, path, paths);
         let r = self.exec(r);
         self.foresult(r)
     }
 
     /// Make a Directory
-    pub fn mkdirs(&mut self, path: &str, opts: MkdirsOptions) -> Result<bool> {
+    pub fn mkdirs(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: MkdirsOptions) -> Result<bool> {
+        let path = path.as_ref().as_str();
         let r = self.acx.mkdirs(self.fostate, path, opts);
         let r = self.exec(r);
         self.foresult(r)
     }
 
     /// Rename a file/directory
-    pub fn rename(&mut self, path: &str, destination: String) -> Result<bool> {
+    pub fn rename(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String) -> Result<bool> {
+        let path = path.as_ref().as_str();
         let r = self.acx.rename(self.fostate, path, destination);
         let r = self.exec(r);
         self.foresult(r)
     }
 
+    /// Rename a file/directory with rename options (see `HdfsClient::rename2`)
+    pub fn rename2(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String, overwrite: bool) -> Result<()> {
+        let path = path.as_ref().as_str();
+        let r = self.acx.rename2(self.fostate, path, destination, overwrite);
+        let r = self.exec(r);
+        self.foresult(r)
+    }
+
     /// Create a Symbolic Link
-    pub fn create_symlink(&mut self, path: &str, destination: String, opts: CreateSymlinkOptions) ->  Result<()> {
+    pub fn create_symlink(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), destination: String, opts: CreateSymlinkOptions) ->  Result<()> {
+        let path = path.as_ref().as_str();
         let r = self.acx.create_symlink(self.fostate, path, destination, opts);
         let r = self.exec(r);
         self.foresult(r)
     }
 
     /// Delete a File/Directory
-    pub fn delete(&mut self, path: &str, opts: DeleteOptions) -> Result<bool> {
+    pub fn delete(&mut self, path: &(impl AsRef<HdfsPath> + ?Sized), opts: DeleteOptions) -> Result<bool> {
+        let path = path.as_ref().as_str();
         let r = self.acx.delete(self.fostate, path, opts);
         let r = self.exec(r);
         self.foresult(r)
@@ -217,49 +642,132 @@ impl SyncHdfsClient {
 /// 
 /// Note about position and offset types: we assume that all hdfs/webhdfs lengths and offsets are actually signed 64-bit integers, 
 /// according to protocol specifications and JVM specifics (no unsigned).
+/// 
+/// Reads are buffered (see `buffer_size`): small reads are served from an internal buffer, which is 
+/// refilled with a single request. `ReadHdfsFile` implements `BufRead`, so there is no need 
+/// to wrap it into `std::io::BufReader` (which would break `Seek`).
+///
+/// Buffering reads ahead: a small read fetches up to `DEFAULT_BUFFER_SIZE` (1 MiB) bytes from the position,
+/// and a seek discards the buffer. Small reads scattered across the file (e.g. reading an index, then records
+/// at random) thus transfer far more data than they return. For such access patterns, lower the buffer size,
+/// disable buffering with `buffer_size(0)`, or use `read_at`, which is never buffered.
 pub struct ReadHdfsFile {
     cx: SyncHdfsClient,
     path: String,
     len: i64,
-    pos: i64
+    /// logical position, i.e. position of the next byte returned to the reader
+    pos: i64,
+    buf: Vec<u8>,
+    buf_start: usize,
+    buf_size: usize,
+    restat_on_seek_end: bool
 }
 
 impl ReadHdfsFile {
-    /// Opens the file specified by `path` for reading
+    /// Read buffer size unless set by `buffer_size`: the amount of data read ahead by a small read
+    pub const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
+
+    /// Opens the file specified by `path` for reading, buffered with `DEFAULT_BUFFER_SIZE`
     pub fn open(mut cx: SyncHdfsClient, path: String) -> Result<ReadHdfsFile> {
         let stat = cx.stat(&&path)?;
         Ok(Self::new(cx, path, stat.file_status.length, 0))
     }
     fn new(cx: SyncHdfsClient, path: String, len: i64, pos: i64) -> Self {
-        Self { cx, path, len, pos }
+        Self { cx, path, len, pos, buf: vec![], buf_start: 0, buf_size: Self::DEFAULT_BUFFER_SIZE, restat_on_seek_end: false }
+    }
+
+    /// Re-reads file length from the server (the file may have been appended to or truncated by another writer 
+    /// since it was opened). Returns the new length. The position is clamped to the new length.
+    pub fn refresh_len(&mut self) -> Result<u64> {
+        let stat = self.cx.stat(&self.path)?;
+        self.len = stat.file_status.length;
+        if self.pos > self.len {
+            self.discard_buffer();
+            self.pos = self.len;
+        }
+        Ok(self.len as u64)
+    }
+
+    /// Whether to re-read file length (see `refresh_len`) on each `SeekFrom::End` seek (default `false`)
+    pub fn restat_on_seek_end(self, restat_on_seek_end: bool) -> Self { Self { restat_on_seek_end, ..self } }
+
+    /// Sets read buffer size (default `DEFAULT_BUFFER_SIZE`), i.e. the amount of data a smaller read fetches
+    /// ahead of the position. Reads not smaller than the buffer bypass it. Zero disables buffering, so that every
+    /// read issues a request for the requested length only.
+    pub fn buffer_size(self, buf_size: usize) -> Self { Self { buf_size, buf: vec![], buf_start: 0, ..self } }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] { &self.buf[self.buf_start..] }
+
+    #[inline]
+    fn discard_buffer(&mut self) { self.buf.clear(); self.buf_start = 0; }
+
+    /// Reads from the file at the current position, bypassing the buffer (which must be empty)
+    fn read_unbuffered(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.read_full(self.pos, buf)?;
+        self.pos += n as i64;
+        Ok(n)
+    }
+
+    /// Reads at `offset` until `buf` is filled or EOF is reached, issuing as many requests as necessary
+    fn read_full(&self, mut offset: i64, buf: &mut [u8]) -> IoResult<usize> {
+        let mut n = 0;
+        while n < buf.len() && offset < self.len {
+            let r = self.read_range(offset, &mut buf[n..])?;
+            if r == 0 {
+                //file has been truncated since open
+                break;
+            }
+            n += r;
+            offset += r as i64;
+        }
+        Ok(n)
     }
     /// File length in bytes
     pub fn len(&self) -> u64 { self.len as u64 }
 
     /// Splits self into `(sync_client, path, (pos, len))`
     pub fn into_parts(self) -> (SyncHdfsClient, String, (i64, i64)) { (self.cx, self.path, (self.pos, self.len)) }
-}
 
-impl Read for ReadHdfsFile {
-    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-        
-        if self.pos == self.len {
-            return Ok(0);
+    /// Positional read: reads up to `buf.len()` bytes starting at `offset` with a single request. 
+    /// Current position is not changed. Returns 0 at EOF.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> IoResult<usize> {
+        let offset: i64 = offset.try_into().map_err(|_| IoError::new(IoErrorKind::InvalidInput, "offset too big"))?;
+        self.read_range(offset, buf)
+    }
+
+    /// Reads exactly `buf.len()` bytes starting at `offset`. Current position is not changed.
+    /// Fails with `UnexpectedEof` if the file ends before the buffer is filled.
+    pub fn read_exact_at(&self, offset: u64, buf: &mut [u8]) -> IoResult<()> {
+        let mut offset: i64 = offset.try_into().map_err(|_| IoError::new(IoErrorKind::InvalidInput, "offset too big"))?;
+        let mut n = 0;
+        while n < buf.len() {
+            let r = self.read_range(offset, &mut buf[n..])?;
+            if r == 0 {
+                return Err(IoError::new(IoErrorKind::UnexpectedEof, "failed to fill whole buffer"));
+            }
+            n += r;
+            offset += r as i64;
         }
+        Ok(())
+    }
 
+    /// Issues a single OPEN request for `buf.len()` bytes at `offset`. Returns the number of bytes received,
+    /// which may be less than requested. Excess bytes (if the server sends any) are discarded.
+    fn read_range(&self, offset: i64, buf: &mut [u8]) -> IoResult<usize> {
         let buf_len: i64 = buf.len().try_into().map_err(|_| IoError::new(IoErrorKind::InvalidInput, "buffer too big"))?;
-        let s = self.cx.open(&self.path, OpenOptions::new().offset(self.pos).length(buf_len))?;
+        //failover state changes are not retained across positional reads
+        let mut cx = self.cx.clone();
+        let s = cx.open(&self.path, OpenOptions::new().offset(offset).length(buf_len))?;
         let mut pos: usize = 0;
         
         let mut s = Box::pin(s);
         loop {
             let f = s.into_future();
-            match self.cx.exec0(f)? {
+            match cx.exec0(f)? {
                 (Some(Ok(chunk)), s1) => {
                     s = s1;
-                    self.pos += chunk.len() as i64;
-                    let bcount = (&mut buf[pos..]).write(&chunk)?;
-                    pos += bcount;
+                    pos += (&mut buf[pos..]).write(&chunk)?;
                 }
                 (Some(Err(e)), _) => {
                     break Err(e.into())
@@ -272,6 +780,81 @@ impl Read for ReadHdfsFile {
     }
 }
 
+impl Read for ReadHdfsFile {
+    /// Reads up to `buf.len()` bytes at the current position. Short server responses are followed by 
+    /// further requests, so the buffer is filled unless EOF is reached. Returns 0 at EOF.
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            if self.buffered().is_empty() && buf.len() - n >= self.buf_size {
+                n += self.read_unbuffered(&mut buf[n..])?;
+                break;
+            }
+            let k = {
+                let b = self.fill_buf()?;
+                let k = std::cmp::min(b.len(), buf.len() - n);
+                buf[n..n + k].copy_from_slice(&b[..k]);
+                k
+            };
+            if k == 0 {
+                break;
+            }
+            self.consume(k);
+            n += k;
+        }
+        Ok(n)
+    }
+
+    /// Appends buffered data and the rest of the file to `buf`, streaming the latter with a single request
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> IoResult<usize> {
+        let start = buf.len();
+        buf.extend_from_slice(self.buffered());
+        self.pos += self.buffered().len() as i64;
+        self.discard_buffer();
+        if self.pos < self.len {
+            let rest = self.len - self.pos;
+            buf.reserve(rest as usize);
+            let s = self.cx.open(&self.path, OpenOptions::new().offset(self.pos).length(rest))?;
+            let mut streamed = 0;
+            let r = self.cx.for_each_chunk(s, |chunk| { buf.extend_from_slice(chunk); streamed += chunk.len(); Ok(()) });
+            self.pos += streamed as i64;
+            r?;
+        }
+        Ok(buf.len() - start)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> IoResult<usize> {
+        let mut b = vec![];
+        let n = self.read_to_end(&mut b)?;
+        let s = std::str::from_utf8(&b).map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
+        buf.push_str(s);
+        Ok(n)
+    }
+}
+
+impl BufRead for ReadHdfsFile {
+    fn fill_buf(&mut self) -> IoResult<&[u8]> {
+        if self.buffered().is_empty() && self.pos < self.len {
+            let mut b = std::mem::take(&mut self.buf);
+            //unbuffered: fetch what is consumed through `BufRead` a byte at a time, rather than report EOF
+            b.resize(self.buf_size.max(1), 0);
+            let r = self.read_full(self.pos, &mut b);
+            let n = *r.as_ref().unwrap_or(&0);
+            b.truncate(n);
+            self.buf = b;
+            self.buf_start = 0;
+            r?;
+        }
+        Ok(self.buffered())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = std::cmp::min(amt, self.buf.len() - self.buf_start);
+        self.buf_start += amt;
+        self.pos += amt as i64;
+    }
+}
+
 impl Seek for ReadHdfsFile {
     fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
         //1. A seek beyond the end of a stream is allowed, but behavior is defined by the implementation --
@@ -285,6 +868,13 @@ impl Seek for ReadHdfsFile {
             }
         }
 
+        if let SeekFrom::Current(0) = pos {
+            return Ok(self.pos as u64);
+        }
+        if let (SeekFrom::End(_), true) = (pos, self.restat_on_seek_end) {
+            self.refresh_len()?;
+        }
+        self.discard_buffer();
         self.pos = match pos {
             SeekFrom::Current(0) => Ok(self.pos),
             SeekFrom::Current(o) => offset(self.pos, o, self.len),
@@ -295,6 +885,116 @@ impl Seek for ReadHdfsFile {
         }?;
         Ok(self.pos as u64)
     }
+
+    /// Returns the current position, keeping the read buffer
+    fn stream_position(&mut self) -> IoResult<u64> { Ok(self.pos as u64) }
+}
+
+
+/// Random-access reader for workloads of small reads at scattered offsets (e.g. footer and index reads of
+/// columnar files). Keeps a window of the file (`DEFAULT_WINDOW_SIZE` by default) around the last read offset;
+/// reads falling into the window are served from it, other reads refetch the window with a single request.
+/// Unlike the buffer of `ReadHdfsFile`, the window is kept across seeks, and also covers data preceding the offset.
+pub struct WindowedReader {
+    file: ReadHdfsFile,
+    window_size: usize,
+    window: Vec<u8>,
+    window_start: u64,
+    pos: u64
+}
+
+impl WindowedReader {
+    pub const DEFAULT_WINDOW_SIZE: usize = 8 * 1024 * 1024;
+
+    pub fn new(file: ReadHdfsFile) -> Self {
+        Self { file, window_size: Self::DEFAULT_WINDOW_SIZE, window: vec![], window_start: 0, pos: 0 }
+    }
+
+    /// Sets window size (default `DEFAULT_WINDOW_SIZE`). Reads not smaller than the window bypass it.
+    pub fn window_size(self, window_size: usize) -> Self { Self { window_size, window: vec![], window_start: 0, ..self } }
+
+    /// File length in bytes
+    pub fn len(&self) -> u64 { self.file.len() }
+
+    /// Whether the file is empty
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Offset and length of the cached window
+    pub fn window(&self) -> (u64, usize) { (self.window_start, self.window.len()) }
+
+    pub fn into_inner(self) -> ReadHdfsFile { self.file }
+
+    /// Reads up to `buf.len()` bytes starting at `offset`, from the window if possible. 
+    /// Current position is not changed. Returns 0 at EOF.
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> IoResult<usize> {
+        let len = self.len();
+        if offset >= len || buf.is_empty() { return Ok(0) }
+        let want = std::cmp::min(buf.len() as u64, len - offset) as usize;
+        if want >= self.window_size {
+            return self.file.read_full(offset as i64, &mut buf[..want])
+        }
+        let end = offset + want as u64;
+        if offset < self.window_start || end > self.window_start + self.window.len() as u64 {
+            self.fetch(offset, want)?;
+        }
+        let from = (offset - self.window_start) as usize;
+        let window = self.window.get(from..).unwrap_or(&[]);
+        let n = std::cmp::min(want, window.len());
+        buf[..n].copy_from_slice(&window[..n]);
+        Ok(n)
+    }
+
+    /// Refetches the window so that it is centered on the `want` bytes at `offset`, within the file.
+    /// A fetch ending short of the window is resumed as long as the file (whose length is then re-read)
+    /// extends beyond the data fetched, so that a short response is not taken for the end of the file.
+    /// Fails if the data is still missing after a retry.
+    fn fetch(&mut self, offset: u64, want: usize) -> IoResult<()> {
+        let len = self.len();
+        let size = std::cmp::min(self.window_size as u64, len);
+        let start = offset.saturating_sub((size - want as u64) / 2).min(len - size);
+        let mut w = std::mem::take(&mut self.window);
+        w.resize(size as usize, 0);
+        let (mut n, mut stalled) = (0, false);
+        let r = loop {
+            let r = match self.file.read_full((start + n as u64) as i64, &mut w[n..]) {
+                Ok(r) => r,
+                Err(e) => break Err(e)
+            };
+            n += r;
+            if n == w.len() { break Ok(()) }
+            match self.file.refresh_len() {
+                Ok(len) if len <= start + n as u64 => break Ok(()),
+                Ok(_) if r == 0 && stalled => break Err(IoError::new(IoErrorKind::UnexpectedEof, 
+                    format!("No data at offset {} of {}", start + n as u64, self.file.path))),
+                Ok(_) => stalled = r == 0,
+                Err(e) => break Err(e.into())
+            }
+        };
+        w.truncate(n);
+        self.window = w;
+        self.window_start = start;
+        r
+    }
+}
+
+impl Read for WindowedReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.read_at(self.pos, buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for WindowedReader {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let p = match pos {
+            SeekFrom::Start(o) => Some(o),
+            SeekFrom::Current(o) => (self.pos as i64).checked_add(o).and_then(|p| p.try_into().ok()),
+            SeekFrom::End(o) => (self.len() as i64).checked_add(o).and_then(|p| p.try_into().ok())
+        };
+        self.pos = p.ok_or_else(|| IoError::new(IoErrorKind::InvalidInput, "attempt to seek before start"))?;
+        Ok(self.pos)
+    }
 }
 
 
@@ -310,6 +1010,13 @@ impl WriteHdfsFile {
         cx.create(&path, crate::rest_client::data_empty(), c_opts).map_err(ErrorD::drop)?;
         Ok(Self { cx, path, opts: a_opts })
     }
+    /// Same as `create`, but creates missing parent directories first (like `hdfs dfs -put`)
+    pub fn create_with_parents(mut cx: SyncHdfsClient, path: String, c_opts: CreateOptions, a_opts: AppendOptions) -> Result<WriteHdfsFile> {
+        if let Some(parent) = HdfsPath::new(&path).parent() {
+            cx.mkdirs(parent, MkdirsOptions::new())?;
+        }
+        Self::create(cx, path, c_opts, a_opts)
+    }
     pub fn append(cx: SyncHdfsClient, path: String, opts: AppendOptions) -> Result<WriteHdfsFile> {
         Ok(Self { cx, path, opts })
     }
@@ -339,3 +1046,471 @@ impl Write for WriteHdfsFile {
         Ok(())
     }
 }
+
+#[test]
+fn test_unicode_paths() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+
+    let dir = "/user/Кириллица и пробелы/日本語";
+    let name = "файл #1 ?&=%.txt";
+    let path = crate::path::join(dir, name);
+    assert!(cx.mkdirs(dir, MkdirsOptions::new()).unwrap());
+    cx.create(&path, Data::Borrowed(b"data"), CreateOptions::new()).map_err(ErrorD::drop).unwrap();
+    assert_eq!(fs.get(&path).unwrap().data, b"data");
+
+    let l = cx.dir_abs(dir).unwrap();
+    assert_eq!(l.len(), 1);
+    assert_eq!(l[0].1.path_suffix, name);
+    assert_eq!(l[0].0, path);
+
+    assert_eq!(cx.stat(&l[0].0).unwrap().file_status.length, 4);
+    let p = HdfsPath::new(dir).join(&format!("./{}//", name)).normalize().unwrap();
+    assert_eq!(cx.stat(&p).unwrap().file_status.length, 4);
+    let mut b = vec![];
+    cx.get_file(&l[0].0, &mut b).unwrap();
+    assert_eq!(b, b"data");
+
+    //a path taken from a URL must be decoded before use
+    let encoded = crate::path::encode_path(&path);
+    assert!(cx.stat(&encoded).is_err());
+    assert_eq!(cx.stat(&crate::path::decode(&encoded).unwrap()).unwrap().file_status.length, 4);
+
+    assert!(cx.delete(&l[0].0, DeleteOptions::new()).unwrap());
+    assert!(fs.get(&path).is_none());
+    assert!(cx.dir(dir).unwrap().file_statuses.file_status.is_empty());
+
+    //names that look percent-encoded are not decoded
+    fs.put(&format!("{}/a%20b", dir), b"x");
+    let l = cx.dir_abs(dir).unwrap();
+    assert_eq!(l[0].0, format!("{}/a%20b", dir));
+    assert_eq!(cx.stat(&l[0].0).unwrap().file_status.length, 1);
+}
+
+#[test]
+fn test_read_short_responses() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123456789");
+    //datanode returns at most 3 bytes per request
+    let f = fs.clone();
+    let c = FakeCluster::with_server(MockServer::start(move |r| {
+        let mut resp = f.handle(r);
+        if r.param("datanode").is_some() { resp.body.truncate(3); }
+        resp
+    }));
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = ReadHdfsFile::open(cx, "/f".to_owned()).unwrap();
+    let mut b = [0u8; 8];
+    assert_eq!(file.read(&mut b).unwrap(), 8);
+    assert_eq!(&b, b"01234567");
+    assert_eq!(file.read(&mut b).unwrap(), 2);
+    assert_eq!(&b[..2], b"89");
+    assert_eq!(file.read(&mut b).unwrap(), 0);
+
+    let mut b = [0u8; 5];
+    assert_eq!(file.read_at(1, &mut b).unwrap(), 3);
+    assert_eq!(&b[..3], b"123");
+    assert_eq!(file.read_at(10, &mut b).unwrap(), 0);
+    file.read_exact_at(4, &mut b).unwrap();
+    assert_eq!(&b, b"45678");
+    assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 10);
+    assert_eq!(file.read_exact_at(7, &mut b).unwrap_err().kind(), IoErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_buffered_read() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"line 1\nline 2\nline 3\n");
+    let c = FakeCluster::start(&fs);
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = ReadHdfsFile::open(cx, "/f".to_owned()).unwrap().buffer_size(10);
+    let mut l = String::new();
+    file.read_line(&mut l).unwrap();
+    assert_eq!(l, "line 1\n");
+    assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 7);
+    assert_eq!(file.seek(SeekFrom::Current(2)).unwrap(), 9);
+    let mut b = [0u8; 1];
+    file.read_exact(&mut b).unwrap();
+    assert_eq!(&b, b"n");
+    let mut b = [0u8; 5];
+    assert_eq!(file.read(&mut b).unwrap(), 5);
+    assert_eq!(&b, b"e 2\nl");
+    let rest: Vec<String> = file.lines().map(|l| l.unwrap()).collect();
+    assert_eq!(rest, vec!["ine 3"]);
+    let dn_reads = c.nn.requests().iter().filter(|r| r.op() == "OPEN" && r.param("datanode").is_some()).count();
+    assert_eq!(dn_reads, 3);
+
+    //unbuffered: reads fetch the requested length only, lines are still read
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = ReadHdfsFile::open(cx, "/f".to_owned()).unwrap().buffer_size(0);
+    let mut b = [0u8; 4];
+    file.read_exact(&mut b).unwrap();
+    assert_eq!(c.nn.requests().last().unwrap().param("length"), Some("4"));
+    let lines: Vec<String> = file.lines().map(|l| l.unwrap()).collect();
+    assert_eq!(lines, vec![" 1", "line 2", "line 3"]);
+}
+
+#[test]
+fn test_refresh_len() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123");
+    let c = FakeCluster::start(&fs);
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = ReadHdfsFile::open(cx, "/f".to_owned()).unwrap();
+    fs.update("/f", |e| e.data.extend(b"4567"));
+    assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 2);
+    assert_eq!(file.refresh_len().unwrap(), 8);
+    assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 6);
+
+    let mut file = file.restat_on_seek_end(true);
+    fs.update("/f", |e| e.data.extend(b"89"));
+    assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 8);
+    let mut s = String::new();
+    file.read_to_string(&mut s).unwrap();
+    assert_eq!(s, "89");
+    assert_eq!(file.stream_position().unwrap(), 10);
+}
+
+#[test]
+fn test_reset_and_health_check() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"data");
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+    cx.health_check().unwrap();
+    let file = ReadHdfsFile::open(cx.clone(), "/f".to_owned()).unwrap();
+    let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cx.exec0(async { panic!("task failure") })));
+    assert!(r.is_err());
+    cx.reset().unwrap();
+    cx.health_check().unwrap();
+    let mut b = [0u8; 4];
+    file.read_exact_at(0, &mut b).unwrap();
+    assert_eq!(&b, b"data");
+
+    let mut dead = SyncHdfsClientBuilder::new("http://127.0.0.1:1".parse().unwrap()).build().unwrap();
+    assert!(dead.health_check().is_err());
+}
+
+#[test]
+fn test_check_quota() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/q/f", b"0123");
+    fs.set_quota("/q", 5, 120);
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+    cx.check_quota("/q", 36, 3, 3).unwrap();
+    let e = cx.check_quota("/q", 36, 4, 3).unwrap_err();
+    assert_eq!(e.quota_exceeded().unwrap().kind, QuotaKind::Namespace);
+    let e = cx.check_quota("/q", 40, 1, 3).unwrap_err();
+    assert_eq!(e.quota_exceeded().unwrap(), &QuotaExceeded { 
+        path: "/q".to_owned(), kind: QuotaKind::Space, quota: 120, used: 12, requested: 120 
+    });
+    assert!(cx.check_quota("/missing", 0, 0, 3).unwrap_err().quota_exceeded().is_none());
+}
+
+#[test]
+fn test_dir_from() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    for f in &["a", "b", "c d", "e"] { fs.put(&format!("/d/{}", f), b""); }
+    let cl = FakeCluster::start(&fs);
+    let mut cx = cl.sync_builder().build().unwrap();
+    let names = |l: ListStatusResponse| l.file_statuses.file_status.into_iter().map(|fs| fs.path_suffix).collect::<Vec<_>>();
+    assert_eq!(names(cx.dir_from("/d", "b").unwrap()), vec!["c d", "e"]);
+    assert_eq!(names(cx.dir_from("/d", "c d").unwrap()), vec!["e"]);
+    assert_eq!(names(cx.dir_from("/d", "").unwrap()).len(), 4);
+    assert_eq!(cl.nn.requests()[1].param("startAfter"), Some("c%20d"));
+}
+
+#[test]
+fn test_create_with_parents() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let cx = c.sync_builder().build().unwrap();
+    let mut f = WriteHdfsFile::create_with_parents(cx, "/a/b/f".to_owned(), CreateOptions::new(), AppendOptions::new()).unwrap();
+    f.write_all(b"data").unwrap();
+    assert_eq!(fs.get("/a/b/f").unwrap().data, b"data");
+    let r = c.nn.requests();
+    assert_eq!((r[0].op(), r[0].path()), ("MKDIRS", "/webhdfs/v1/a/b"));
+    assert_eq!(r[1].op(), "CREATE");
+}
+
+#[test]
+fn test_snapshot_diff() {
+    use crate::mock_server::*;
+    let c = FakeCluster::with_server(MockServer::start(|r| match r.op() {
+        "GETSNAPSHOTDIFF" => MockResponse::json(200, r#"{"SnapshotDiffReport":{"diffList":[
+            {"sourcePath":"","type":"MODIFY"},{"sourcePath":"new","type":"CREATE"}
+        ],"fromSnapshot":"s1","snapshotRoot":"/data","toSnapshot":""}}"#),
+        "GETSNAPSHOTTABLEDIRECTORYLIST" => MockResponse::json(200, r#"{"SnapshottableDirectoryList":[]}"#),
+        _ => MockResponse::new(400)
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let r = cx.snapshot_diff("/data", "s1", "").unwrap().snapshot_diff_report;
+    assert_eq!(r.diff_list.iter().map(|e| e.source_path.as_str()).collect::<Vec<_>>(), vec!["", "new"]);
+    assert!(cx.snapshottable_dirs().unwrap().snapshottable_directory_list.is_empty());
+    let req = &c.nn.requests()[0];
+    assert_eq!(req.path(), "/webhdfs/v1/data");
+    assert_eq!((req.param("oldsnapshotname"), req.param("snapshotname")), (Some("s1"), Some("")));
+}
+
+#[test]
+fn test_encryption_info() {
+    use crate::mock_server::*;
+    let c = FakeCluster::with_server(MockServer::start(|r| {
+        let extra = match r.path() {
+            "/webhdfs/v1/ez/f" => r#","encBit":true,"feInfo":{"keyName":"key1"}}}"#,
+            "/webhdfs/v1/ez/old" => r#","encBit":true}}"#,
+            _ => "}}"
+        };
+        MockResponse::json(200, &file_status_json(dirent_type::FILE, 0).replace("}}", extra))
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    assert_eq!(cx.encryption_info("/ez/f").unwrap().unwrap().key_name, "key1");
+    assert_eq!(cx.encryption_info("/plain").unwrap(), None);
+    assert!(cx.encryption_info("/ez/old").is_err());
+}
+
+#[test]
+fn test_verify_create_options() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().verify_create_options(true).build().unwrap();
+    cx.create("/ok", Data::Borrowed(b"0"), CreateOptions::new().replication(3).blocksize(134217728)).map_err(ErrorD::drop).unwrap();
+    let e = cx.create("/rf", Data::Borrowed(b"0"), CreateOptions::new().replication(2)).map_err(ErrorD::drop).unwrap_err();
+    let i = e.create_option_ignored().unwrap();
+    assert_eq!((i.option, i.requested, i.actual), ("replication", 2, 3));
+    assert!(fs.paths().contains(&"/rf".to_owned()));
+    assert!(cx.create("/plain", Data::Borrowed(b"0"), CreateOptions::new()).is_ok());
+    assert_eq!(c.nn.requests().iter().filter(|r| r.op() == "GETFILESTATUS").count(), 2);
+}
+
+#[test]
+fn test_set_quota() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let fs = FakeHdfs::new();
+    fs.mkdirs("/q");
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+    cx.set_quota("/q", 100, QUOTA_DONT_SET).unwrap();
+    cx.set_quota("/q", QUOTA_DONT_SET, 1 << 20).unwrap();
+    let s = cx.content_summary("/q").unwrap().content_summary;
+    assert_eq!((s.quota, s.space_quota), (100, 1 << 20));
+    cx.set_quota("/q", QUOTA_RESET, QUOTA_DONT_SET).unwrap();
+    assert_eq!(cx.content_summary("/q").unwrap().content_summary.quota, -1);
+    assert!(cx.is_supported("SETQUOTA"));
+
+    //FakeHdfs does not know SETQUOTABYSTORAGETYPE
+    let e = cx.set_quota_by_storage_type("/q", "SSD", 1 << 20).unwrap_err();
+    assert!(e.remote_exception().is_some(), "{}", e);
+    assert!(!cx.is_supported("SETQUOTABYSTORAGETYPE"));
+    let n = c.nn.requests().len();
+    let e = cx.set_quota_by_storage_type("/q", "SSD", 1 << 20).unwrap_err();
+    assert!(e.to_string().contains("not supported"), "{}", e);
+    assert_eq!(c.nn.requests().len(), n);
+}
+
+#[test]
+fn test_server_defaults() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.mkdirs("/tmp");
+    //FakeHdfs does not know GETSERVERDEFAULTS: defaults are derived from a probe file
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+    let d = cx.server_defaults("/tmp").unwrap();
+    assert_eq!((d.block_size, d.replication, d.checksum_type_name()), (134217728, 3, None));
+    assert!(fs.paths().iter().all(|p| !p.contains("probe")), "{:?}", fs.paths());
+    assert!(!cx.is_supported("GETSERVERDEFAULTS"));
+    cx.server_defaults("/tmp").unwrap();
+    assert_eq!(c.nn.requests().iter().filter(|r| r.op() == "GETSERVERDEFAULTS").count(), 1);
+
+    let c = FakeCluster::with_server(MockServer::start(|r| {
+        assert_eq!(r.op(), "GETSERVERDEFAULTS");
+        MockResponse::json(200, r#"{"FsServerDefaults":{"replication":2,"encryptDataTransfer":"false",
+            "defaultStoragePolicyId":7,"writePacketSize":65536,"fileBufferSize":4096,"checksumType":2,
+            "trashInterval":0,"keyProviderUri":"","blockSize":268435456,"bytesPerChecksum":512}}"#)
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let d = cx.server_defaults("/tmp").unwrap();
+    assert_eq!((d.block_size, d.replication, d.bytes_per_checksum), (268435456, 2, Some(512)));
+    assert_eq!(d.checksum_type_name(), Some("CRC32C"));
+    assert_eq!((d.checksum_type(), d.write_packet_size, d.file_buffer_size), (Some(ChecksumType::Crc32c), Some(65536), Some(4096)));
+    let o: Vec<_> = d.create_options().into().iter().map(|a| format!("{:?}", a)).collect();
+    assert_eq!(o, vec!["Blocksize(268435456)", "Replication(2)"]);
+}
+
+#[test]
+fn test_expunge_trash() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let fs = FakeHdfs::new();
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().user_name("alice".to_owned()).build().unwrap();
+    assert_eq!(cx.home_dir().unwrap(), "/user/alice");
+    assert!(cx.expunge_trash(Duration::from_secs(0)).unwrap().is_empty());
+    fs.put("/user/alice/.Trash/200101000000/user/alice/a", b"a");
+    fs.put("/user/alice/.Trash/2001020000-1/user/alice/b", b"b");
+    fs.put("/user/alice/.Trash/991231235959/user/alice/c", b"c");
+    fs.put("/user/alice/.Trash/Current/user/alice/d", b"d");
+    fs.put("/user/alice/.Trash/200101000000.txt", b"");
+    let mut deleted = cx.expunge_trash(Duration::from_secs(86400)).unwrap();
+    deleted.sort();
+    assert_eq!(deleted, vec!["/user/alice/.Trash/200101000000", "/user/alice/.Trash/2001020000-1"]);
+    let left: Vec<_> = fs.paths().into_iter().filter(|p| p.ends_with("/c") || p.ends_with("/d") || p.ends_with(".txt")).collect();
+    assert_eq!(left.len(), 3, "{:?}", fs.paths());
+}
+
+#[test]
+fn test_resolve_path() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    let fs = FakeHdfs::new();
+    fs.put("/d/f", b"data");
+    let cl = FakeCluster::start(&fs);
+    let mut cx = cl.sync_builder().build().unwrap();
+    cx.create_symlink("/l1", "/d/f".to_owned(), CreateSymlinkOptions::new()).unwrap();
+    cx.create_symlink("/d/l2", "../l1".to_owned(), CreateSymlinkOptions::new()).unwrap();
+    assert_eq!(cx.stat("/d/l2").unwrap().file_status.type_, "SYMLINK");
+    let (path, st) = cx.resolve_path("/d/l2").unwrap();
+    assert_eq!((path.as_str(), st.type_.as_str(), st.length), ("/d/f", "FILE", 4));
+    assert_eq!(cx.stat_resolved("/d/f").unwrap().length, 4);
+    let mut data = vec![];
+    let s = cx.open_resolved("/d/l2", OpenOptions::new()).unwrap();
+    cx.save_stream(s, &mut data).unwrap();
+    assert_eq!(data, b"data");
+
+    cx.create_symlink("/a", "/b".to_owned(), CreateSymlinkOptions::new()).unwrap();
+    cx.create_symlink("/b", "a".to_owned(), CreateSymlinkOptions::new()).unwrap();
+    assert!(cx.stat_resolved("/a").unwrap_err().to_string().contains("loop"));
+    for i in 0..40 {
+        cx.create_symlink(&format!("/c{}", i), format!("/c{}", i + 1), CreateSymlinkOptions::new()).unwrap();
+    }
+    assert!(cx.stat_resolved("/c0").unwrap_err().to_string().contains("Too many"));
+    assert!(cx.stat_resolved("/dangling").is_err());
+}
+
+#[test]
+fn test_read_to_end() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let data: Vec<u8> = (0..100u8).collect();
+    fs.put("/f", &data);
+    fs.put("/t", "текст".as_bytes());
+    let c = FakeCluster::start(&fs);
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = ReadHdfsFile::open(cx.clone(), "/f".to_owned()).unwrap().buffer_size(10);
+    let mut b = [0u8; 3];
+    file.read_exact(&mut b).unwrap();
+    let mut rest = vec![];
+    assert_eq!(file.read_to_end(&mut rest).unwrap(), 97);
+    assert_eq!(rest, &data[3..]);
+    assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 100);
+    assert_eq!(file.read_to_end(&mut rest).unwrap(), 0);
+    let dn_reads = c.nn.requests().iter().filter(|r| r.op() == "OPEN" && r.param("datanode").is_some()).count();
+    assert_eq!(dn_reads, 2);
+
+    let mut s = String::new();
+    ReadHdfsFile::open(cx.clone(), "/t".to_owned()).unwrap().read_to_string(&mut s).unwrap();
+    assert_eq!(s, "текст");
+    let mut file = ReadHdfsFile::open(cx, "/t".to_owned()).unwrap();
+    file.seek(SeekFrom::Start(1)).unwrap();
+    assert_eq!(file.read_to_string(&mut s).unwrap_err().kind(), IoErrorKind::InvalidData);
+}
+
+#[test]
+fn test_windowed_reader() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let data: Vec<u8> = (0..100u8).collect();
+    fs.put("/f", &data);
+    let c = FakeCluster::start(&fs);
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = WindowedReader::new(ReadHdfsFile::open(cx, "/f".to_owned()).unwrap()).window_size(20);
+    let dn_reads = || c.nn.requests().iter().filter(|r| r.op() == "OPEN" && r.param("datanode").is_some()).count();
+    let mut b = [0u8; 4];
+    assert_eq!(file.read_at(96, &mut b).unwrap(), 4);
+    assert_eq!(&b, &data[96..]);
+    assert_eq!(file.window(), (80, 20));
+    assert_eq!(file.read_at(85, &mut b).unwrap(), 4);
+    assert_eq!(&b, &data[85..89]);
+    assert_eq!(dn_reads(), 1);
+    assert_eq!(file.read_at(40, &mut b).unwrap(), 4);
+    assert_eq!(&b, &data[40..44]);
+    assert_eq!(file.window(), (32, 20));
+    assert_eq!(dn_reads(), 2);
+
+    file.seek(SeekFrom::Start(34)).unwrap();
+    let mut b = [0u8; 10];
+    file.read_exact(&mut b).unwrap();
+    assert_eq!(&b, &data[34..44]);
+    assert_eq!(dn_reads(), 2);
+    let mut b = [0u8; 30];
+    assert_eq!(file.read_at(90, &mut b).unwrap(), 10);
+    assert_eq!(file.read_at(0, &mut b).unwrap(), 30);
+    assert_eq!(&b[..], &data[..30]);
+    assert_eq!((file.window(), dn_reads()), ((80, 20), 4));
+    assert_eq!(file.read_at(100, &mut b).unwrap(), 0);
+}
+
+#[test]
+fn test_windowed_reader_short_fetch() {
+    use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    let data: Vec<u8> = (0..100u8).collect();
+    fs.put("/f", &data);
+    let f = fs.clone();
+    // datanode reads listed in `empty` get an empty response
+    let (reads, empty) = (Arc::new(AtomicUsize::new(0)), Arc::new(std::sync::Mutex::new(vec![])));
+    let (r1, e1) = (reads.clone(), empty.clone());
+    let c = FakeCluster::with_server(MockServer::start(move |r| {
+        let mut resp = f.handle(r);
+        if r.param("datanode").is_some() && e1.lock().unwrap().contains(&r1.fetch_add(1, Ordering::SeqCst)) {
+            resp.body.clear()
+        }
+        resp
+    }));
+    let cx = c.sync_builder().build().unwrap();
+    let mut file = WindowedReader::new(ReadHdfsFile::open(cx, "/f".to_owned()).unwrap()).window_size(20);
+    assert!(!file.is_empty());
+    let mut b = [0u8; 4];
+    *empty.lock().unwrap() = vec![0];
+    assert_eq!(file.read_at(50, &mut b).unwrap(), 4);
+    assert_eq!(&b, &data[50..54]);
+    assert_eq!(file.window(), (42, 20));
+    *empty.lock().unwrap() = vec![2, 3];
+    assert_eq!(file.read_at(10, &mut b).unwrap_err().kind(), IoErrorKind::UnexpectedEof);
+    fs.put("/f", &data[..5]);
+    assert_eq!(file.read_at(80, &mut b).unwrap(), 0);
+}
+
+#[test]
+fn test_read_tail() {
+    use crate::mock_server::*;
+    let fs = FakeHdfs::new();
+    fs.put("/f", b"0123456789");
+    let f = fs.clone();
+    let c = FakeCluster::with_server(MockServer::start(move |r| {
+        let mut resp = f.handle(r);
+        if r.param("datanode").is_some() { resp.body.truncate(3); }
+        resp
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let (len, s) = cx.open_tail("/f", 2).unwrap();
+    let mut tail = vec![];
+    cx.for_each_chunk(s, |b| { tail.extend_from_slice(b); Ok(()) }).unwrap();
+    assert_eq!((len, tail.as_slice()), (10, &b"89"[..]));
+    assert_eq!(cx.read_tail("/f", 8).unwrap(), (10, b"23456789".to_vec()));
+    assert_eq!(cx.read_tail("/f", 20).unwrap(), (10, b"0123456789".to_vec()));
+    let opens: Vec<_> = c.nn.requests().into_iter().filter(|r| r.op() == "OPEN" && r.param("datanode").is_none())
+        .map(|r| (r.param("offset").map(|o| o.to_owned()), r.param("length").map(|l| l.to_owned()))).collect();
+    assert_eq!(opens[..2], [(Some("8".to_owned()), Some("2".to_owned())), (Some("2".to_owned()), Some("8".to_owned()))]);
+}
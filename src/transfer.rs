@@ -0,0 +1,301 @@
+//! Directory tree transfers.
+//!
+//! `download_dir` copies a directory tree from HDFS to the local file system, reporting progress to a
+//! `TransferObserver`. The total number of bytes is obtained upfront via GETCONTENTSUMMARY; if the summary
+//! is unavailable (e.g. the operation is not permitted), the transfer proceeds with the total unknown.
+//!
+//! `copy_between` copies a file or a directory tree from one cluster to another. Data is streamed from OPEN
+//! into CREATE and APPEND requests in `chunk_size` blocks, so nothing is written to the local disk. Files are
+//! copied concurrently; a file whose copy fails is copied again from the start, up to `retries` times.
+use std::fs::{File, create_dir_all};
+use std::io::Write;
+use std::path::Path;
+use futures::{stream, StreamExt};
+use log::warn;
+use crate::error::*;
+use crate::datatypes::dirent_type;
+use crate::async_client::{HdfsClient, FOState, FOR, Data, ErrorD};
+use crate::sync_client::{SyncHdfsClient, OpenOptions, CreateOptions, AppendOptions, MkdirsOptions};
+
+/// Receives transfer progress notifications. All methods default to no-op.
+pub trait TransferObserver {
+    /// Called once, before any data is transferred. `total_bytes` is `None` if it could not be determined.
+    fn started(&mut self, _total_bytes: Option<u64>) {}
+    /// Called before a file is transferred
+    fn file_started(&mut self, _path: &str, _len: u64) {}
+    /// Called after each chunk of data, with bytes transferred so far for the file and for the whole transfer
+    fn progress(&mut self, _path: &str, _file_bytes: u64, _total_bytes: u64) {}
+    /// Called after a file has been transferred completely
+    fn file_done(&mut self, _path: &str) {}
+}
+
+/// Observer ignoring all notifications
+pub struct NoopObserver;
+
+impl TransferObserver for NoopObserver {}
+
+/// Downloads directory `src` (recursively) into local directory `dst`, which is created if needed.
+/// Symbolic links are skipped. Returns the number of bytes transferred.
+pub fn download_dir(cx: &mut SyncHdfsClient, src: &str, dst: &Path, observer: &mut dyn TransferObserver) -> Result<u64> {
+    let total = match cx.content_summary(src) {
+        Ok(s) => Some(s.content_summary.length as u64),
+        Err(e) => { warn!("Content summary of {} unavailable, total size unknown: {}", src, e); None }
+    };
+    observer.started(total);
+    let mut done = 0;
+    download_dir_r(cx, src, dst, observer, &mut done)?;
+    Ok(done)
+}
+
+/// Checks that a name reported by a directory listing is a single path component, so that it cannot
+/// point outside of the target directory
+fn entry_name<'a>(dir: &str, name: &'a str) -> Result<&'a str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\\') {
+        return Err(app_error!(generic "Invalid entry name '{}' in the listing of {}", name, dir))
+    }
+    Ok(name)
+}
+
+fn download_dir_r(cx: &mut SyncHdfsClient, src: &str, dst: &Path, observer: &mut dyn TransferObserver, done: &mut u64) -> Result<()> {
+    create_dir_all(dst)?;
+    for (path, fs) in cx.dir_abs(src)? {
+        let local = dst.join(entry_name(src, &fs.path_suffix)?);
+        match fs.type_.as_str() {
+            dirent_type::DIRECTORY => download_dir_r(cx, &path, &local, observer, done)?,
+            dirent_type::FILE => download_file(cx, &path, fs.length as u64, &local, observer, done)?,
+            _ => ()
+        }
+    }
+    Ok(())
+}
+
+fn download_file(cx: &mut SyncHdfsClient, src: &str, len: u64, dst: &Path, observer: &mut dyn TransferObserver, done: &mut u64) -> Result<()> {
+    observer.file_started(src, len);
+    let mut out = File::create(dst)?;
+    let input = cx.open(src, OpenOptions::new())?;
+    let mut file_done = 0;
+    cx.for_each_chunk(input, |b| {
+        out.write_all(b)?;
+        file_done += b.len() as u64;
+        *done += b.len() as u64;
+        observer.progress(src, file_done, *done);
+        Ok(())
+    })?;
+    out.flush()?;
+    observer.file_done(src);
+    Ok(())
+}
+
+/// Cross-cluster copy settings
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    parallelism: usize,
+    retries: u32,
+    chunk_size: usize
+}
+
+impl CopyOptions {
+    pub fn new() -> Self { Self { parallelism: 4, retries: 2, chunk_size: 8 << 20 } }
+    /// Maximum number of files copied concurrently (4 by default)
+    pub fn parallelism(self, parallelism: usize) -> Self { Self { parallelism, ..self } }
+    /// Number of times a failed file copy is restarted (2 by default)
+    pub fn retries(self, retries: u32) -> Self { Self { retries, ..self } }
+    /// Size of data blocks sent to the target cluster in a single CREATE or APPEND request (8MB by default)
+    pub fn chunk_size(self, chunk_size: usize) -> Self { Self { chunk_size, ..self } }
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self { Self::new() }
+}
+
+/// Copies file or directory `src_path` on cluster `src` to `dst_path` on cluster `dst`, with default settings.
+/// Existing target files are overwritten; symbolic links are skipped. Returns the number of bytes copied.
+pub async fn copy_between(src: &HdfsClient, src_path: &str, dst: &HdfsClient, dst_path: &str) -> Result<u64> {
+    copy_between_with(src, src_path, dst, dst_path, &CopyOptions::new()).await
+}
+
+/// Same as `copy_between`, with explicit settings
+pub async fn copy_between_with(src: &HdfsClient, src_path: &str, dst: &HdfsClient, dst_path: &str, opts: &CopyOptions)
+-> Result<u64> {
+    let st = FOR::split(src.stat(FOState::PRIMARY, src_path).await).0?.file_status;
+    if st.type_ != dirent_type::DIRECTORY {
+        return copy_file(src, src_path, dst, dst_path, opts).await
+    }
+
+    let mut files = vec![];
+    let mut dirs = vec![(src_path.to_owned(), dst_path.to_owned())];
+    while let Some((s, d)) = dirs.pop() {
+        FOR::split(dst.mkdirs(FOState::PRIMARY, &d, MkdirsOptions::new()).await).0?;
+        for (path, fs) in FOR::split(src.dir_abs(FOState::PRIMARY, &s).await).0? {
+            let target = crate::path::join(&d, entry_name(&s, &fs.path_suffix)?);
+            match fs.type_.as_str() {
+                dirent_type::DIRECTORY => dirs.push((path, target)),
+                dirent_type::FILE => files.push((path, target)),
+                _ => ()
+            }
+        }
+    }
+
+    stream::iter(files)
+        .map(|(s, d)| async move { copy_file(src, &s, dst, &d, opts).await })
+        .buffer_unordered(opts.parallelism.max(1))
+        .collect::<Vec<Result<u64>>>().await
+        .into_iter().sum()
+}
+
+/// Synchronous version of `copy_between_with`. Runs on the runtime of `src`; `default_timeout` does not apply.
+pub fn copy_between_sync(src: &SyncHdfsClient, src_path: &str, dst: &SyncHdfsClient, dst_path: &str, opts: &CopyOptions)
+-> Result<u64> {
+    src.block_on(copy_between_with(src.async_client(), src_path, dst.async_client(), dst_path, opts))
+}
+
+async fn copy_file(src: &HdfsClient, src_path: &str, dst: &HdfsClient, dst_path: &str, opts: &CopyOptions) -> Result<u64> {
+    let mut attempt = 0;
+    loop {
+        match copy_file_once(src, src_path, dst, dst_path, opts.chunk_size.max(1)).await {
+            Err(e) if attempt < opts.retries => {
+                attempt += 1;
+                warn!("Copying {} to {} failed, retrying ({}/{}): {}", src_path, dst_path, attempt, opts.retries, e)
+            }
+            r => return r
+        }
+    }
+}
+
+async fn copy_file_once(src: &HdfsClient, src_path: &str, dst: &HdfsClient, dst_path: &str, chunk_size: usize) -> Result<u64> {
+    let mut input = FOR::split(src.open(FOState::PRIMARY, src_path, OpenOptions::new()).await).0?;
+    let mut buf = Vec::with_capacity(chunk_size);
+    let mut created = false;
+    let mut total = 0;
+    while let Some(b) = input.next().await {
+        buf.extend_from_slice(&b?);
+        if buf.len() >= chunk_size {
+            total += buf.len() as u64;
+            put_chunk(dst, dst_path, std::mem::take(&mut buf), created).await?;
+            created = true;
+        }
+    }
+    if !created || !buf.is_empty() {
+        total += buf.len() as u64;
+        put_chunk(dst, dst_path, buf, created).await?;
+    }
+    Ok(total)
+}
+
+async fn put_chunk(dst: &HdfsClient, path: &str, data: Vec<u8>, append: bool) -> Result<()> {
+    let r = if append {
+        dst.append(FOState::PRIMARY, path, Data::Owned(data), AppendOptions::new()).await
+    } else {
+        dst.create(FOState::PRIMARY, path, Data::Owned(data), CreateOptions::new().overwrite(true)).await
+    };
+    FOR::split(r).0.map_err(ErrorD::drop)
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct RecordingObserver {
+    total: Option<Option<u64>>,
+    files: Vec<(String, u64)>,
+    last_progress: u64
+}
+
+#[cfg(test)]
+impl TransferObserver for RecordingObserver {
+    fn started(&mut self, total_bytes: Option<u64>) { self.total = Some(total_bytes) }
+    fn file_started(&mut self, path: &str, len: u64) { self.files.push((path.to_owned(), len)) }
+    fn progress(&mut self, _path: &str, _file_bytes: u64, total_bytes: u64) { self.last_progress = total_bytes }
+}
+
+#[cfg(test)]
+fn fake_tree() -> crate::mock_server::FakeHdfs {
+    let fs = crate::mock_server::FakeHdfs::new();
+    fs.put("/d/a", b"0123");
+    fs.put("/d/s/b", b"456789");
+    fs.mkdirs("/d/empty");
+    fs
+}
+
+#[test]
+fn test_download_dir() {
+    use crate::mock_server::FakeCluster;
+    let fs = fake_tree();
+    let c = FakeCluster::start(&fs);
+    let mut cx = c.sync_builder().build().unwrap();
+    let dst = std::env::temp_dir().join(format!("webhdfs-test-download-{}", std::process::id()));
+    let mut o = RecordingObserver::default();
+    assert_eq!(download_dir(&mut cx, "/d", &dst, &mut o).unwrap(), 10);
+    assert_eq!(o.total, Some(Some(10)));
+    assert_eq!(o.files, vec![("/d/a".to_owned(), 4), ("/d/s/b".to_owned(), 6)]);
+    assert_eq!(o.last_progress, 10);
+    assert_eq!(std::fs::read(dst.join("s").join("b")).unwrap(), b"456789");
+    assert!(dst.join("empty").is_dir());
+    std::fs::remove_dir_all(&dst).unwrap();
+}
+
+#[test]
+fn test_download_dir_without_summary() {
+    use crate::mock_server::*;
+    let fs = fake_tree();
+    let c = FakeCluster::with_server(MockServer::start(move |r| if r.op() == "GETCONTENTSUMMARY" {
+        MockResponse::remote_exception(403, "AccessControlException", "org.apache.hadoop.security.AccessControlException", "Permission denied")
+    } else {
+        fs.handle(r)
+    }));
+    let mut cx = c.sync_builder().build().unwrap();
+    let dst = std::env::temp_dir().join(format!("webhdfs-test-download-ns-{}", std::process::id()));
+    let mut o = RecordingObserver::default();
+    assert_eq!(download_dir(&mut cx, "/d", &dst, &mut o).unwrap(), 10);
+    assert_eq!(o.total, Some(None));
+    assert_eq!(o.last_progress, 10);
+    std::fs::remove_dir_all(&dst).unwrap();
+}
+
+#[test]
+fn test_download_dir_invalid_names() {
+    use crate::mock_server::*;
+    let dst = std::env::temp_dir().join(format!("webhdfs-test-download-names-{}", std::process::id()));
+    for name in ["", ".", "..", "../x", "a/b", "a\\b"] {
+        let listing = serde_json::json!({"FileStatuses": {"FileStatus": [
+            {"pathSuffix": name, "type": "FILE", "length": 1, "permission": "644"}
+        ]}}).to_string();
+        let c = FakeCluster::with_server(MockServer::start(move |r| match r.op() {
+            "LISTSTATUS" => MockResponse::json(200, &listing),
+            _ => MockResponse::new(500)
+        }));
+        let mut cx = c.sync_builder().build().unwrap();
+        let e = download_dir(&mut cx, "/d", &dst.join("t"), &mut NoopObserver).unwrap_err();
+        assert!(e.to_string().contains("Invalid entry name"), "{}: {}", name, e);
+        assert!(c.nn.requests().iter().all(|r| r.op() != "OPEN"));
+    }
+    assert_eq!(std::fs::read_dir(&dst).unwrap().map(|e| e.unwrap().file_name()).collect::<Vec<_>>(), vec!["t"]);
+    std::fs::remove_dir_all(&dst).unwrap();
+}
+
+#[test]
+fn test_copy_between() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::mock_server::*;
+    let src = fake_tree();
+    src.put("/d/s/empty", b"");
+    let dst = FakeHdfs::new();
+    let sc = FakeCluster::start(&src);
+    let dst1 = dst.clone();
+    let failures = AtomicUsize::new(1);
+    // fail the first data node write once, to exercise retries
+    let dc = FakeCluster::with_server(MockServer::start(move |r| if r.param("datanode").is_some() && failures.fetch_sub(1, Ordering::SeqCst) == 1 {
+        MockResponse::remote_exception(500, "IOException", "java.io.IOException", "Pipeline failed")
+    } else {
+        dst1.handle(r)
+    }));
+    let a = sc.sync_builder().build().unwrap();
+    let b = dc.sync_builder().build().unwrap();
+    let opts = CopyOptions::new().chunk_size(4).parallelism(2);
+    assert_eq!(copy_between_sync(&a, "/d", &b, "/copy", &opts).unwrap(), 10);
+    assert_eq!(dst.get("/copy/a").unwrap().data, b"0123");
+    assert_eq!(dst.get("/copy/s/b").unwrap().data, b"456789");
+    assert_eq!(dst.get("/copy/s/empty").unwrap().data, b"");
+    assert!(dst.get("/copy/empty").is_some());
+    assert_eq!(copy_between_sync(&a, "/d/s/b", &b, "/single", &opts).unwrap(), 6);
+    assert_eq!(dst.get("/single").unwrap().data, b"456789");
+    assert!(copy_between_sync(&a, "/missing", &b, "/x", &opts).is_err());
+}
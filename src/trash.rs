@@ -0,0 +1,48 @@
+//! HDFS trash layout.
+//!
+//! Deleted files are moved (by `hdfs dfs -rm`, not by WebHDFS DELETE) to `<home>/.Trash/Current`. Periodically,
+//! `Current` is renamed to a checkpoint named after the time of checkpointing (`yyMMddHHmmss`, or `yyMMddHHmm` with
+//! older Hadoop versions, possibly followed by `-<n>`). Checkpoints older than the trash interval are deleted by
+//! the namenode, or by `hdfs dfs -expunge` (see `HdfsClient::expunge_trash`).
+
+/// Trash directory name, relative to the user's home directory
+pub const TRASH_DIR: &str = ".Trash";
+/// Name of the directory receiving deleted files
+pub const CURRENT: &str = "Current";
+
+/// Days since 1970-01-01 of a (proleptic Gregorian) date
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Time of a trash checkpoint, in milliseconds since the epoch, parsed from its name. The name is interpreted as UTC.
+/// Returns `None` if `name` is not a checkpoint name.
+pub fn checkpoint_time(name: &str) -> Option<i64> {
+    let ts = match name.split_once('-') {
+        Some((ts, n)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => ts,
+        Some(_) => return None,
+        None => name
+    };
+    if !(ts.len() == 10 || ts.len() == 12) || !ts.chars().all(|c| c.is_ascii_digit()) { return None }
+    let f = |i: usize| ts.get(i..i + 2).map(|s| s.parse::<i64>().unwrap()).unwrap_or(0);
+    let (y, mo, d, h, mi, s) = (2000 + f(0), f(2), f(4), f(6), f(8), f(10));
+    if !(1..=12).contains(&mo) || !(1..=31).contains(&d) || h > 23 || mi > 59 || s > 59 { return None }
+    Some((((days_from_civil(y, mo, d) * 24 + h) * 60 + mi) * 60 + s) * 1000)
+}
+
+#[test]
+fn test_checkpoint_time() {
+    assert_eq!(checkpoint_time("000101000000"), Some(946684800000));
+    assert_eq!(checkpoint_time("240229123005"), Some(1709209805000));
+    assert_eq!(checkpoint_time("2402291230"), Some(1709209800000));
+    assert_eq!(checkpoint_time("240229123005-1"), Some(1709209805000));
+    assert_eq!(checkpoint_time("Current"), None);
+    assert_eq!(checkpoint_time("241301000000"), None);
+    assert_eq!(checkpoint_time("240229123005-"), None);
+    assert_eq!(checkpoint_time("2402291230051"), None);
+}
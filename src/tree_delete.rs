@@ -0,0 +1,101 @@
+//! Client-side recursive delete.
+//!
+//! A recursive DELETE of a directory with millions of entries is a single long-running namenode operation.
+//! `delete_tree` instead walks the tree and deletes it bottom-up, one entry per request, optionally at a bounded
+//! rate, reporting progress to a `DeleteObserver`. The number of entries is checked against `max_entries`
+//! (obtained via GETCONTENTSUMMARY) before anything is deleted.
+use crate::error::*;
+use crate::datatypes::dirent_type;
+use crate::rate_limit::RateLimiter;
+use crate::sync_client::{SyncHdfsClient, DeleteOptions};
+use crate::transfer::NoopObserver;
+
+/// Receives delete progress notifications. All methods default to no-op.
+pub trait DeleteObserver {
+    /// Called once, before anything is deleted, with the number of entries to delete (including the root)
+    fn started(&mut self, _total_entries: u64) {}
+    /// Called after each entry is deleted, with the number of entries deleted so far
+    fn deleted(&mut self, _path: &str, _done: u64) {}
+}
+
+impl DeleteObserver for NoopObserver {}
+
+/// Client-side recursive delete settings
+#[derive(Debug, Clone, Default)]
+pub struct TreeDeleteOptions {
+    max_entries: Option<u64>,
+    rate_limiter: Option<RateLimiter>
+}
+
+impl TreeDeleteOptions {
+    pub fn new() -> Self { Self::default() }
+    /// Refuse to delete trees with more than `max_entries` entries (files, directories and links, including the root)
+    pub fn max_entries(self, max_entries: u64) -> Self { Self { max_entries: Some(max_entries), ..self } }
+    /// Limits the rate of DELETE requests. The limiter may be shared with other clients (see `crate::rate_limit`).
+    pub fn rate_limiter(self, rate_limiter: RateLimiter) -> Self { Self { rate_limiter: Some(rate_limiter), ..self } }
+}
+
+/// Deletes file or directory `path` with all its contents, one entry per request. Returns the number of entries deleted.
+pub fn delete_tree(cx: &mut SyncHdfsClient, path: &str, opts: &TreeDeleteOptions, observer: &mut dyn DeleteObserver) -> Result<u64> {
+    let is_dir = cx.stat(path)?.file_status.type_ == dirent_type::DIRECTORY;
+    let total = if is_dir {
+        let cs = cx.content_summary(path)?.content_summary;
+        (cs.directory_count + cs.file_count) as u64
+    } else {
+        1
+    };
+    if let Some(max) = opts.max_entries {
+        if total > max {
+            return Err(app_error!(generic "Refusing to delete {}: {} entries, more than the limit of {}", path, total, max))
+        }
+    }
+    observer.started(total);
+    let mut done = 0;
+    delete_r(cx, path, is_dir, opts, observer, &mut done)?;
+    Ok(done)
+}
+
+fn delete_r(cx: &mut SyncHdfsClient, path: &str, is_dir: bool, opts: &TreeDeleteOptions, observer: &mut dyn DeleteObserver, done: &mut u64)
+-> Result<()> {
+    if is_dir {
+        for (child, fs) in cx.dir_abs(path)? {
+            delete_r(cx, &child, fs.type_ == dirent_type::DIRECTORY, opts, observer, done)?
+        }
+    }
+    if let Some(l) = &opts.rate_limiter { l.acquire_blocking() }
+    if cx.delete(path, DeleteOptions::new().recursive(false))? {
+        *done += 1;
+        observer.deleted(path, *done);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_delete_tree() {
+    use crate::mock_server::{FakeHdfs, FakeCluster};
+    struct Recorder(Option<u64>, Vec<String>);
+    impl DeleteObserver for Recorder {
+        fn started(&mut self, total: u64) { self.0 = Some(total) }
+        fn deleted(&mut self, path: &str, done: u64) { self.1.push(path.to_owned()); assert_eq!(done, self.1.len() as u64) }
+    }
+    let f = FakeHdfs::new();
+    f.put("/d/a", b"0");
+    f.put("/d/sub/b", b"1");
+    f.mkdirs("/d/empty");
+    let c = FakeCluster::start(&f);
+    let mut cx = c.sync_builder().build().unwrap();
+
+    let e = delete_tree(&mut cx, "/d", &TreeDeleteOptions::new().max_entries(4), &mut NoopObserver).unwrap_err();
+    assert!(e.to_string().contains("5 entries"), "{}", e);
+    assert!(f.get("/d/a").is_some());
+
+    let mut r = Recorder(None, vec![]);
+    let opts = TreeDeleteOptions::new().max_entries(5).rate_limiter(RateLimiter::new(1000.0, 1));
+    assert_eq!(delete_tree(&mut cx, "/d", &opts, &mut r).unwrap(), 5);
+    assert_eq!(r.0, Some(5));
+    assert_eq!(r.1.last().map(|p| p.as_str()), Some("/d"));
+    let sub = r.1.iter().position(|p| p == "/d/sub").unwrap();
+    assert!(r.1.iter().position(|p| p == "/d/sub/b").unwrap() < sub);
+    assert!(f.paths().iter().all(|p| !p.starts_with("/d")));
+    assert!(c.nn.requests().iter().filter(|r| r.op() == "DELETE").all(|r| r.param("recursive") != Some("true")));
+}
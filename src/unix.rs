@@ -0,0 +1,115 @@
+//! WebHDFS over unix domain sockets.
+//!
+//! `http::Uri` cannot hold a socket path in place of the authority, so socket entrypoints are represented as
+//! `unix://<hex-encoded socket path>`. Use `socket_uri` (or the `unix:///path/to/socket` form in configuration
+//! files) to build one. Redirect locations using the same representation are followed over the socket as well.
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UnixStream;
+use crate::error::*;
+
+/// URI scheme of unix socket entrypoints
+pub const SCHEME: &str = "unix";
+
+/// Builds entrypoint URI for the unix socket at `path`
+pub fn socket_uri(path: impl AsRef<Path>) -> Result<Uri> {
+    let hex: String = path.as_ref().as_os_str().as_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}://{}", SCHEME, hex).parse()?)
+}
+
+/// Parses `unix:///path/to/socket` into entrypoint URI. Returns `None` if `s` is not of that form.
+pub fn parse_socket_uri(s: &str) -> Option<Result<Uri>> {
+    let path = s.strip_prefix("unix://")?;
+    if path.starts_with('/') { Some(socket_uri(path)) } else { None }
+}
+
+/// Extracts socket path from an URI built by `socket_uri`
+pub fn socket_path(uri: &Uri) -> Option<PathBuf> {
+    if uri.scheme_str() != Some(SCHEME) { return None }
+    let hex = uri.host()?.as_bytes();
+    if hex.is_empty() || hex.len() % 2 != 0 { return None }
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let bytes: Option<Vec<u8>> = hex.chunks(2).map(|c| Some(digit(c[0])? << 4 | digit(c[1])?)).collect();
+    bytes.map(|b| PathBuf::from(std::ffi::OsString::from_vec(b)))
+}
+
+/// Hyper connector which connects to unix sockets
+#[derive(Clone)]
+pub struct UnixConnector;
+
+impl hyper::service::Service<Uri> for UnixConnector {
+    type Response = UnixConnection;
+    type Error = IoError;
+    type Future = Pin<Box<dyn Future<Output=std::io::Result<UnixConnection>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> { Poll::Ready(Ok(())) }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(async move {
+            let path = socket_path(&uri).ok_or_else(||
+                IoError::new(IoErrorKind::InvalidInput, format!("Not a unix socket URI: {}", uri))
+            )?;
+            Ok(UnixConnection(UnixStream::connect(path).await?))
+        })
+    }
+}
+
+pub struct UnixConnection(UnixStream);
+
+impl AsyncRead for UnixConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl Connection for UnixConnection {
+    fn connected(&self) -> Connected { Connected::new() }
+}
+
+#[test]
+fn test_socket_uri() {
+    let uri = socket_uri("/tmp/webhdfs.sock").unwrap();
+    assert_eq!(uri.to_string(), "unix://2f746d702f776562686466732e736f636b/");
+    assert_eq!(socket_path(&uri).unwrap(), PathBuf::from("/tmp/webhdfs.sock"));
+    assert_eq!(parse_socket_uri("unix:///tmp/webhdfs.sock").unwrap().unwrap(), uri);
+    assert!(parse_socket_uri("http://namenode:9870").is_none());
+    assert!(socket_path(&"http://namenode:9870".parse().unwrap()).is_none());
+}
+
+#[test]
+fn test_unix_socket_client() {
+    use crate::mock_server::*;
+    use crate::sync_client::*;
+    use crate::async_client::{Data, ErrorD};
+    let path = std::env::temp_dir().join(format!("webhdfs-test-{}.sock", std::process::id()));
+    let fs = FakeHdfs::new();
+    fs.put("/a/f", b"data");
+    let _nn = fs.start_unix(&path);
+    let mut cx = SyncHdfsClientBuilder::new(socket_uri(&path).unwrap()).build().unwrap();
+    let mut out = vec![];
+    cx.get_file("/a/f", &mut out).unwrap();
+    assert_eq!(out, b"data");
+    cx.create("/a/g", Data::Borrowed(b"new"), CreateOptions::new()).map_err(ErrorD::drop).unwrap();
+    assert_eq!(fs.get("/a/g").unwrap().data, b"new");
+    assert_eq!(cx.dir("/a").unwrap().file_statuses.file_status.len(), 2);
+    let _ = std::fs::remove_file(&path);
+}
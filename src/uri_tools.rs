@@ -126,6 +126,44 @@ b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'-', b'_', b'.', b'
 }
 
 
+/// Decodes percent-encoded string. Fails on malformed escapes or if the result is not valid UTF-8
+pub fn uri_part_decode(s: &str) -> Result<String, String> {
+    fn hex(b: u8) -> Option<u8> {
+        match b {
+            b'0'..=b'9' => Some(b - b'0'),
+            b'a'..=b'f' => Some(b - b'a' + 10),
+            b'A'..=b'F' => Some(b - b'A' + 10),
+            _ => None
+        }
+    }
+    let mut r = Vec::with_capacity(s.len());
+    let mut i = s.bytes();
+    while let Some(b) = i.next() {
+        if b == b'%' {
+            match (i.next().and_then(hex), i.next().and_then(hex)) {
+                (Some(h), Some(l)) => r.push((h << 4) | l),
+                _ => return Err(format!("malformed percent-encoding in '{}'", s))
+            }
+        } else {
+            r.push(b)
+        }
+    }
+    String::from_utf8(r).map_err(|_| format!("percent-decoded '{}' is not valid UTF-8", s))
+}
+
+#[test]
+fn test_uri_part_decode() {
+    assert_eq!(uri_part_decode("user/a/%D0%9A%D0%B8%D1%80AndEng/u").unwrap(), "user/a/КирAndEng/u");
+    assert_eq!(uri_part_decode("a%2Fb%20c").unwrap(), "a/b c");
+    assert_eq!(uri_part_decode("").unwrap(), "");
+    assert!(uri_part_decode("a%2").is_err());
+    assert!(uri_part_decode("a%zz").is_err());
+    assert!(uri_part_decode("%D0").is_err());
+    let s = "~`!@#$%^&*()_+-={}|[]\\:\";'<>?,./ АБВ";
+    assert_eq!(uri_part_decode(&uri_part_encoder_iter(s, false).map(|b| b as char).collect::<String>()).unwrap(), s);
+}
+
+
 pub struct QueryEncoder {
     path_and_query: Vec<u8>,
     qm_added: bool
@@ -148,6 +186,18 @@ impl QueryEncoder {
         self.path_and_query.extend(uri_part_encoder_iter(v, false));
         self
     }
+    /// add a parameter once per value, e.g. `xattr.name=a&xattr.name=b` (encoding performed)
+    pub fn add_pv_repeated(self, p: &str, vs: &[impl AsRef<str>]) -> QueryEncoder {
+        vs.iter().fold(self, |q, v| q.add_pv(p, v.as_ref()))
+    }
+    /// add pre-encoded value, which is passed through as is (the parameter name is encoded)
+    pub fn add_raw(mut self, p: &str, v: &str) -> QueryEncoder {
+        self.pfx();
+        self.path_and_query.extend(uri_part_encoder_iter(p, false));
+        self.path_and_query.push(b'=');
+        self.path_and_query.extend(v.bytes());
+        self
+    }
     /// add 64-bit int
     pub fn add_pi(mut self, p: &str, v: i64) -> QueryEncoder {
         self.pfx();
@@ -169,7 +219,7 @@ impl QueryEncoder {
         self.pfx();
         self.path_and_query.extend(uri_part_encoder_iter(p, false));
         self.path_and_query.push(b'=');
-        self.path_and_query.extend(format!("{}{}{}", (v & 0o0700) >> 6, (v & 0o0070) >> 3, (v & 0o0007)).bytes());
+        self.path_and_query.extend(format!("{:03o}", v & 0o1777).bytes());
         self
     }    
     pub fn result(self) -> Vec<u8> { self.path_and_query }
@@ -226,4 +276,12 @@ fn path_and_query_encoder_test() {
 
     let q2 = q1.add_pi("g", 128);
     assert_eq!("/a/b/c/d/e/f/g?%D0%BF%D0%B0%D1%80%D0%B0%2F%D0%BC%D0%B5%D1%82%D1%80=%D0%B7%D0%BD%D0%B0%D1%87&g=128".bytes().collect::<Vec<u8>>(), q2.path_and_query);
-}
\ No newline at end of file
+}
+#[test]
+fn query_encoder_repeated_and_raw_test() {
+    let q = PathEncoder::new("/p").query()
+        .add_pv_repeated("xattr.name", &["user.a", "user.b c"])
+        .add_pv_repeated("none", &[] as &[&str])
+        .add_raw("sig", "a%2Fb%3D");
+    assert_eq!(String::from_utf8(q.result()).unwrap(), "/p?xattr.name=user.a&xattr.name=user.b%20c&sig=a%2Fb%3D");
+}
@@ -0,0 +1,320 @@
+//! Safe mode aware write queue.
+//!
+//! While the namenode is in safe mode, it rejects all mutations (with `SafeModeException`, or with
+//! `RetriableException` during startup). `WriteQueue` wraps a `SyncHdfsClient`; writes rejected this way are
+//! queued, and performed in submission order by subsequent `submit`, `flush` or `wait` calls once the cluster
+//! has left safe mode. Queued data is kept in memory up to `max_memory` bytes; beyond that, it is spilled to
+//! files in `spill_dir` if one is set, or the write is rejected as if the queue were full.
+//!
+//! Writes still queued when the `WriteQueue` is dropped are lost.
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use crate::error::*;
+use crate::async_client::{Data, DResult};
+use crate::sync_client::{SyncHdfsClient, CreateOptions, AppendOptions, MkdirsOptions, DeleteOptions};
+
+/// A write operation
+pub enum WriteOp {
+    Create { path: String, data: Vec<u8>, opts: CreateOptions },
+    Append { path: String, data: Vec<u8>, opts: AppendOptions },
+    Mkdirs { path: String, opts: MkdirsOptions },
+    Rename { path: String, destination: String },
+    Delete { path: String, opts: DeleteOptions }
+}
+
+impl WriteOp {
+    /// Target path
+    pub fn path(&self) -> &str {
+        match self {
+            WriteOp::Create { path, .. } | WriteOp::Append { path, .. } | WriteOp::Mkdirs { path, .. } |
+            WriteOp::Rename { path, .. } | WriteOp::Delete { path, .. } => path
+        }
+    }
+
+    fn data_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            WriteOp::Create { data, .. } | WriteOp::Append { data, .. } => Some(data),
+            _ => None
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        match self {
+            WriteOp::Create { data, .. } | WriteOp::Append { data, .. } => data.len(),
+            _ => 0
+        }
+    }
+}
+
+/// Receives write queue notifications. All methods default to no-op.
+/// Data of create and append operations is not available to `succeeded` (it has been sent).
+pub trait QueueObserver {
+    /// Called when a write is queued because the cluster is in safe mode
+    fn queued(&mut self, _op: &WriteOp) {}
+    /// Called when a write is rejected because the queue is full
+    fn queue_full(&mut self, _op: &WriteOp) {}
+    /// Called when a queued write has been performed
+    fn succeeded(&mut self, _op: &WriteOp) {}
+    /// Called when a queued write has failed for a reason other than safe mode. The write is dropped.
+    fn failed(&mut self, _op: &WriteOp, _error: &Error) {}
+}
+
+struct NoopQueueObserver;
+
+impl QueueObserver for NoopQueueObserver {}
+
+/// Outcome of `WriteQueue::submit`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Submitted {
+    /// The write has been performed
+    Done,
+    /// The write has been queued
+    Queued
+}
+
+struct Entry {
+    op: WriteOp,
+    /// File holding the op's data, if spilled
+    spill: Option<PathBuf>
+}
+
+/// Write queue, see module documentation
+pub struct WriteQueue {
+    cx: SyncHdfsClient,
+    queue: VecDeque<Entry>,
+    memory: usize,
+    max_entries: usize,
+    max_memory: usize,
+    spill_dir: Option<PathBuf>,
+    spill_seq: u64,
+    retry_interval: Duration,
+    last_attempt: Option<Instant>,
+    observer: Box<dyn QueueObserver>
+}
+
+/// Whether the error is a namenode safe mode rejection
+fn is_safe_mode(e: &Error) -> bool {
+    e.remote_exception().is_some_and(|r| r.exception == "SafeModeException" || r.message.contains("safe mode"))
+}
+
+/// Performs `op`, taking its data. If the data is recovered from an error, it is put back, and `true` is returned
+/// along with the error.
+fn perform(cx: &mut SyncHdfsClient, op: &mut WriteOp) -> StdResult<(), (Error, bool)> {
+    fn data_result(r: DResult<()>, data: &mut Vec<u8>) -> StdResult<(), (Error, bool)> {
+        r.map_err(|mut e| match e.take_data() {
+            Ok(Some(d)) => { *data = d.into_owned(); (e.error, true) }
+            _ => (e.error, false)
+        })
+    }
+    fn plain<T>(r: Result<T>) -> StdResult<(), (Error, bool)> { r.map(|_| ()).map_err(|e| (e, true)) }
+    match op {
+        WriteOp::Create { path, data, opts } => {
+            let d = std::mem::take(data);
+            data_result(cx.create(path.as_str(), Data::Owned(d), opts.clone()), data)
+        }
+        WriteOp::Append { path, data, opts } => {
+            let d = std::mem::take(data);
+            data_result(cx.append(path.as_str(), Data::Owned(d), opts.clone()), data)
+        }
+        WriteOp::Mkdirs { path, opts } => plain(cx.mkdirs(path.as_str(), opts.clone())),
+        WriteOp::Rename { path, destination } => plain(cx.rename(path.as_str(), destination.clone())),
+        WriteOp::Delete { path, opts } => plain(cx.delete(path.as_str(), opts.clone()))
+    }
+}
+
+impl WriteQueue {
+    const DEFAULT_MAX_ENTRIES: usize = 10000;
+    const DEFAULT_MAX_MEMORY: usize = 64 << 20;
+    const DEFAULT_RETRY_INTERVAL_S: u64 = 10;
+
+    pub fn new(cx: SyncHdfsClient) -> Self {
+        Self {
+            cx,
+            queue: VecDeque::new(),
+            memory: 0,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            max_memory: Self::DEFAULT_MAX_MEMORY,
+            spill_dir: None,
+            spill_seq: 0,
+            retry_interval: Duration::from_secs(Self::DEFAULT_RETRY_INTERVAL_S),
+            last_attempt: None,
+            observer: Box::new(NoopQueueObserver)
+        }
+    }
+    /// Maximum number of queued writes (default 10000)
+    pub fn max_entries(mut self, max_entries: usize) -> Self { self.max_entries = max_entries; self }
+    /// Maximum total size of queued data kept in memory (default 64MB)
+    pub fn max_memory(mut self, max_memory: usize) -> Self { self.max_memory = max_memory; self }
+    /// Directory to spill queued data to when `max_memory` is exceeded (default none)
+    pub fn spill_dir(mut self, spill_dir: impl Into<PathBuf>) -> Self { self.spill_dir = Some(spill_dir.into()); self }
+    /// Minimum interval between attempts to perform queued writes made by `submit` and `wait` (default 10s)
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self { self.retry_interval = retry_interval; self }
+    /// Sets the observer
+    pub fn observer(mut self, observer: impl QueueObserver + 'static) -> Self { self.observer = Box::new(observer); self }
+
+    /// Number of queued writes
+    pub fn len(&self) -> usize { self.queue.len() }
+    pub fn is_empty(&self) -> bool { self.queue.is_empty() }
+    /// The underlying client
+    pub fn client(&mut self) -> &mut SyncHdfsClient { &mut self.cx }
+
+    /// Performs the write, or queues it if the cluster is in safe mode. If there are queued writes, they are
+    /// attempted first (provided `retry_interval` has elapsed since the last attempt), and the write is queued
+    /// behind those remaining, to keep the order of writes. Fails if the write fails for a reason other than
+    /// safe mode, or if the queue is full.
+    pub fn submit(&mut self, mut op: WriteOp) -> Result<Submitted> {
+        if !self.queue.is_empty() && self.last_attempt.is_none_or(|t| t.elapsed() >= self.retry_interval) {
+            self.flush()?;
+        }
+        if !self.queue.is_empty() {
+            self.enqueue(op)?;
+            return Ok(Submitted::Queued)
+        }
+        match perform(&mut self.cx, &mut op) {
+            Ok(()) => Ok(Submitted::Done),
+            Err((e, true)) if is_safe_mode(&e) => { self.enqueue(op)?; Ok(Submitted::Queued) }
+            Err((e, _)) => Err(e)
+        }
+    }
+
+    fn enqueue(&mut self, mut op: WriteOp) -> Result<()> {
+        let len = op.data_len();
+        let spill = if self.queue.len() >= self.max_entries {
+            return self.reject(op)
+        } else if self.memory + len <= self.max_memory {
+            self.memory += len;
+            None
+        } else if let Some(dir) = &self.spill_dir {
+            let p = dir.join(format!("webhdfs-queue-{}-{}", std::process::id(), self.spill_seq));
+            self.spill_seq += 1;
+            if let Some(data) = op.data_mut() {
+                std::fs::write(&p, &data).aerr_f(|| format!("Cannot spill queued write to {}", p.display()))?;
+                *data = vec![];
+            }
+            Some(p)
+        } else {
+            return self.reject(op)
+        };
+        self.observer.queued(&op);
+        self.queue.push_back(Entry { op, spill });
+        Ok(())
+    }
+
+    fn reject(&mut self, op: WriteOp) -> Result<()> {
+        self.observer.queue_full(&op);
+        Err(app_error!(generic "Write queue is full, write to {} rejected", op.path()))
+    }
+
+    /// Performs queued writes, in order, until the queue is empty (returns `true`) or the cluster is found to be
+    /// still in safe mode (returns `false`). Writes failing for other reasons are reported to the observer
+    /// and dropped.
+    pub fn flush(&mut self) -> Result<bool> {
+        self.last_attempt = Some(Instant::now());
+        while let Some(mut e) = self.queue.pop_front() {
+            if let (Some(p), Some(data)) = (&e.spill, e.op.data_mut()) {
+                match std::fs::read(p) {
+                    Ok(d) => *data = d,
+                    Err(err) => { self.queue.push_front(e); return Err(err.into()) }
+                }
+            }
+            let len = if e.spill.is_none() { e.op.data_len() } else { 0 };
+            match perform(&mut self.cx, &mut e.op) {
+                Err((err, true)) if is_safe_mode(&err) => {
+                    if let (Some(_), Some(data)) = (&e.spill, e.op.data_mut()) { *data = vec![] }
+                    self.queue.push_front(e);
+                    return Ok(false)
+                }
+                r => {
+                    self.memory -= len;
+                    if let Some(p) = &e.spill { let _ = std::fs::remove_file(p); }
+                    match r {
+                        Ok(()) => self.observer.succeeded(&e.op),
+                        Err((err, _)) => self.observer.failed(&e.op, &err)
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Flushes the queue every `retry_interval` until it is empty (returns `true`) or `timeout` elapses
+    /// (returns `false`)
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.flush()? { return Ok(true) }
+            let now = Instant::now();
+            if now >= deadline { return Ok(false) }
+            std::thread::sleep(self.retry_interval.min(deadline - now));
+        }
+    }
+}
+
+impl Drop for WriteQueue {
+    fn drop(&mut self) {
+        for p in self.queue.iter().filter_map(|e| e.spill.as_ref()) {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+}
+
+#[test]
+fn test_write_queue() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::mock_server::*;
+
+    #[derive(Clone, Default)]
+    struct Log(Rc<RefCell<Vec<String>>>);
+    impl QueueObserver for Log {
+        fn queued(&mut self, op: &WriteOp) { self.0.borrow_mut().push(format!("queued {}", op.path())) }
+        fn queue_full(&mut self, op: &WriteOp) { self.0.borrow_mut().push(format!("full {}", op.path())) }
+        fn succeeded(&mut self, op: &WriteOp) { self.0.borrow_mut().push(format!("ok {}", op.path())) }
+        fn failed(&mut self, op: &WriteOp, _error: &Error) { self.0.borrow_mut().push(format!("failed {}", op.path())) }
+    }
+
+    let fs = FakeHdfs::new();
+    let safe_mode = Arc::new(AtomicBool::new(false));
+    let (fs1, safe_mode1) = (fs.clone(), safe_mode.clone());
+    let cl = FakeCluster::with_server(MockServer::start(move |r|
+        if safe_mode1.load(Ordering::SeqCst) && r.param("datanode").is_none() && r.method != "GET" {
+            MockResponse::remote_exception(403, "SafeModeException", "org.apache.hadoop.hdfs.server.namenode.SafeModeException",
+                "Cannot create file. Name node is in safe mode.")
+        } else {
+            fs1.handle(r)
+        }
+    ));
+    let spill_dir = std::env::temp_dir().join(format!("webhdfs-test-queue-{}", std::process::id()));
+    std::fs::create_dir_all(&spill_dir).unwrap();
+    let log = Log::default();
+    let cx = cl.sync_builder().build().unwrap();
+    let mut q = WriteQueue::new(cx).max_entries(4).max_memory(4).spill_dir(&spill_dir)
+        .retry_interval(Duration::from_millis(10)).observer(log.clone());
+    let create = |path: &str, data: &[u8]| WriteOp::Create { path: path.to_owned(), data: data.to_vec(), opts: CreateOptions::new() };
+    let append = |path: &str, data: &[u8]| WriteOp::Append { path: path.to_owned(), data: data.to_vec(), opts: AppendOptions::new() };
+
+    assert_eq!(q.submit(create("/f", b"0")).unwrap(), Submitted::Done);
+    safe_mode.store(true, Ordering::SeqCst);
+    assert_eq!(q.submit(create("/a", b"aaaa")).unwrap(), Submitted::Queued);
+    assert_eq!(q.submit(WriteOp::Mkdirs { path: "/d".to_owned(), opts: MkdirsOptions::new() }).unwrap(), Submitted::Queued);
+    assert_eq!(q.submit(append("/a", b"bb")).unwrap(), Submitted::Queued);
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 1);
+    assert_eq!(q.submit(append("/missing", b"c")).unwrap(), Submitted::Queued);
+    assert!(q.submit(create("/b", b"")).is_err());
+    assert!(!q.flush().unwrap());
+    assert_eq!(q.len(), 4);
+
+    safe_mode.store(false, Ordering::SeqCst);
+    assert!(q.wait(Duration::from_secs(5)).unwrap());
+    assert!(q.is_empty());
+    assert_eq!(fs.get("/a").unwrap().data, b"aaaabb");
+    assert!(fs.get("/d").is_some());
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    assert_eq!(*log.0.borrow(), vec![
+        "queued /a", "queued /d", "queued /a", "queued /missing", "full /b", "ok /a", "ok /d", "ok /a", "failed /missing"
+    ]);
+    std::fs::remove_dir_all(&spill_dir).unwrap();
+}